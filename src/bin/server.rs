@@ -5,6 +5,8 @@
  * @Last Modified by: idzeir
  * @Last Modified time: 2023-10-23 14:35:36
  */
+use std::{path::PathBuf, time::Duration};
+
 use clap::Parser;
 use mini_redis::{server, DEFAULT_PORT};
 use tokio::{net::TcpListener, signal};
@@ -33,15 +35,212 @@ pub async fn main() -> mini_redis::Result<()> {
     let port = cli.port.unwrap_or(DEFAULT_PORT);
 
     let listener = TcpListener::bind(&format!("127.0.0.1:{}", port)).await?;
-    server::run(listener, signal::ctrl_c()).await;
+    let mut config = server::Config::new();
+    if let Some(databases) = cli.databases {
+        config = config.databases(databases);
+    }
+    if let Some(capacity) = cli.pubsub_capacity {
+        config = config.pubsub_capacity(capacity);
+    }
+    if cli.disconnect_slow_subscribers {
+        config = config.slow_subscribers(server::SlowSubscribers::Disconnect);
+    }
+    let dir = cli.dir.clone().unwrap_or_default();
+    // Snapshots are only saved to disk when asked to, so that running the
+    // server leaves no files behind.
+    if cli.dir.is_some() || cli.dbfilename.is_some() || cli.save.is_some() {
+        let dbfilename = cli
+            .dbfilename
+            .as_deref()
+            .unwrap_or(DEFAULT_DBFILENAME.as_ref());
+        config = config.snapshot_path(dir.join(dbfilename));
+        let save = cli.save.as_deref().unwrap_or(DEFAULT_SAVE);
+        for (after, changes) in parse_save_rules(save)? {
+            config = config.save_rule(after, changes);
+        }
+    }
+    if cli.appendonly {
+        config = config
+            .aof_path(dir.join(cli.appendfilename))
+            .appendfsync(parse_appendfsync(&cli.appendfsync)?);
+    }
+    if let Some(master) = cli.replicaof {
+        let (host, port) = parse_master(&master)?;
+        config = config.replicaof(host, port);
+    }
+    if let Some(size) = cli.repl_backlog_size {
+        config = config.repl_backlog_size(size);
+    }
+    if let Some(limit) = cli.lua_time_limit {
+        config = config.lua_time_limit(Duration::from_millis(limit));
+    }
+    if !cli.cluster_node.is_empty() {
+        let nodes = cli
+            .cluster_node
+            .iter()
+            .map(|node| parse_cluster_node(node))
+            .collect::<mini_redis::Result<Vec<_>>>()?;
+        let find = |host: &str, port: u16| {
+            nodes
+                .iter()
+                .position(|node| node.port() == port && (host.is_empty() || node.host() == host))
+                .ok_or_else(|| format!("{}:{} is not a node of the cluster", host, port))
+        };
+        let myself = find("", port)?;
+        let mut migrating = vec![];
+        for migration in &cli.cluster_migrating {
+            let (slot, host, port) = parse_migration(migration)?;
+            migrating.push((slot, find(&host, port)?));
+        }
+        config = config.cluster(nodes, myself)?;
+        for (slot, to) in migrating {
+            config = config.cluster_migrating(slot, to)?;
+        }
+    }
+    server::run_with_config(listener, signal::ctrl_c(), config).await;
     Ok(())
 }
 
+/// Where snapshots are saved when enabled, like in Redis.
+const DEFAULT_DBFILENAME: &str = "dump.rdb";
+
+/// Redis' default save rules.
+const DEFAULT_SAVE: &str = "3600 1 300 100 60 10000";
+
 #[derive(Debug, Parser)]
 #[clap(name = "mini-redis-server", version, author, about = "A Redis server")]
 struct Cli {
     #[clap(long)]
     port: Option<u16>,
+
+    /// Number of databases clients can `SELECT`, 16 by default.
+    #[clap(long)]
+    databases: Option<usize>,
+
+    /// Number of messages that may wait for a subscriber, 1024 by default.
+    #[clap(long)]
+    pubsub_capacity: Option<usize>,
+
+    /// Disconnect subscribers that fall behind instead of notifying them of
+    /// the messages they missed.
+    #[clap(long)]
+    disconnect_slow_subscribers: bool,
+
+    /// Directory snapshots and the append only file are written to, the
+    /// current one by default. Enables snapshots.
+    #[clap(long)]
+    dir: Option<PathBuf>,
+
+    /// File snapshots are saved to and loaded from on startup, `dump.rdb`
+    /// by default. Enables snapshots, which are off unless `--dir`,
+    /// `--dbfilename` or `--save` is given, so that data only lives in
+    /// memory.
+    #[clap(long)]
+    dbfilename: Option<PathBuf>,
+
+    /// Snapshot rules as `<seconds> <changes>` pairs, like Redis' `save`
+    /// directive, `3600 1 300 100 60 10000` by default. Enables snapshots,
+    /// an empty string only saves them with `SAVE` and `BGSAVE`.
+    #[clap(long)]
+    save: Option<String>,
+
+    /// Log every change to an append only file, replayed on startup instead
+    /// of loading the snapshot.
+    #[clap(long)]
+    appendonly: bool,
+
+    /// File changes are logged to with `--appendonly`, in `--dir`.
+    #[clap(long, default_value = "appendonly.aof")]
+    appendfilename: PathBuf,
+
+    /// How often the append only file is flushed to disk: `always`,
+    /// `everysec` or `no`.
+    #[clap(long, default_value = "everysec")]
+    appendfsync: String,
+
+    /// Start as a replica of the master at `<host> <port>`.
+    #[clap(long)]
+    replicaof: Option<String>,
+
+    /// Bytes of the latest changes kept for replicas to catch up on, 1MB by
+    /// default.
+    #[clap(long)]
+    repl_backlog_size: Option<usize>,
+
+    /// Milliseconds a script runs before other clients are told the server
+    /// is busy, and may `SCRIPT KILL` it, 5000 by default.
+    #[clap(long)]
+    lua_time_limit: Option<u64>,
+
+    /// A node of the cluster as `<host> <port> <slots>`, where slots are
+    /// ranges such as `0-8191,9000`. Every node of a cluster is started
+    /// with all of them, this server being the one on `--port`.
+    #[clap(long)]
+    cluster_node: Vec<String>,
+
+    /// A slot being moved from its owner to another node of the cluster, as
+    /// `<slot> <host> <port>`.
+    #[clap(long)]
+    cluster_migrating: Vec<String>,
+}
+
+/// Parses save rules such as `900 1 300 10`.
+fn parse_save_rules(src: &str) -> mini_redis::Result<Vec<(Duration, u64)>> {
+    let numbers = src
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<u64>, _>>()?;
+    if numbers.len() % 2 != 0 {
+        return Err("save rules must be pairs of seconds and changes".into());
+    }
+    Ok(numbers
+        .chunks(2)
+        .map(|rule| (Duration::from_secs(rule[0]), rule[1]))
+        .collect())
+}
+
+/// Parses the address of a master, such as `127.0.0.1 6379`.
+fn parse_master(src: &str) -> mini_redis::Result<(String, u16)> {
+    match src.split_whitespace().collect::<Vec<_>>()[..] {
+        [host, port] => Ok((host.to_string(), port.parse()?)),
+        _ => Err("the master must be given as `<host> <port>`".into()),
+    }
+}
+
+/// Parses a node of a cluster, such as `127.0.0.1 7000 0-5460,6000`.
+fn parse_cluster_node(src: &str) -> mini_redis::Result<server::ClusterNode> {
+    let [host, port, slots] = src.split_whitespace().collect::<Vec<_>>()[..] else {
+        return Err("cluster nodes must be given as `<host> <port> <slots>`".into());
+    };
+    let port = port.parse()?;
+    let mut node = server::ClusterNode::new(host, port);
+    for range in slots.split(',') {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let (start, end): (u16, u16) = (start.parse()?, end.parse()?);
+        if start > end {
+            return Err(format!("invalid slot range `{}`", range).into());
+        }
+        node = node.slots(start..=end);
+    }
+    Ok(node)
+}
+
+/// Parses a slot being migrated, such as `42 127.0.0.1 7001`.
+fn parse_migration(src: &str) -> mini_redis::Result<(u16, String, u16)> {
+    match src.split_whitespace().collect::<Vec<_>>()[..] {
+        [slot, host, port] => Ok((slot.parse()?, host.to_string(), port.parse()?)),
+        _ => Err("migrating slots must be given as `<slot> <host> <port>`".into()),
+    }
+}
+
+/// Parses an `appendfsync` policy.
+fn parse_appendfsync(src: &str) -> mini_redis::Result<server::AppendFsync> {
+    match &src.to_lowercase()[..] {
+        "always" => Ok(server::AppendFsync::Always),
+        "everysec" => Ok(server::AppendFsync::Everysec),
+        "no" => Ok(server::AppendFsync::No),
+        _ => Err(format!("unknown appendfsync policy '{}'", src).into()),
+    }
 }
 
 #[cfg(not(feature = "otel"))]
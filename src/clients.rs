@@ -1,5 +1,8 @@
 mod client;
-pub use client::{Client, Message, Subscriber};
+pub use client::{
+    Client, ClusterSlots, Message, PendingEntry, ReplicationRole, StreamEntry, Subscriber,
+    Transaction,
+};
 
 mod blocking_client;
 pub use blocking_client::BlockingClient;
@@ -47,6 +47,42 @@ impl BlockingClient {
             .block_on(self.inner.set_expirse(key, value, expiration))
     }
 
+    pub fn incr(&mut self, key: &str) -> crate::Result<i64> {
+        self.rt.block_on(self.inner.incr(key))
+    }
+
+    pub fn decr(&mut self, key: &str) -> crate::Result<i64> {
+        self.rt.block_on(self.inner.decr(key))
+    }
+
+    pub fn incrby(&mut self, key: &str, delta: i64) -> crate::Result<i64> {
+        self.rt.block_on(self.inner.incrby(key, delta))
+    }
+
+    pub fn decrby(&mut self, key: &str, delta: i64) -> crate::Result<i64> {
+        self.rt.block_on(self.inner.decrby(key, delta))
+    }
+
+    pub fn incrbyfloat(&mut self, key: &str, delta: f64) -> crate::Result<f64> {
+        self.rt.block_on(self.inner.incrbyfloat(key, delta))
+    }
+
+    pub fn append(&mut self, key: &str, value: Bytes) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.append(key, value))
+    }
+
+    pub fn strlen(&mut self, key: &str) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.strlen(key))
+    }
+
+    pub fn getrange(&mut self, key: &str, start: i64, end: i64) -> crate::Result<Bytes> {
+        self.rt.block_on(self.inner.getrange(key, start, end))
+    }
+
+    pub fn setrange(&mut self, key: &str, offset: u64, value: Bytes) -> crate::Result<u64> {
+        self.rt.block_on(self.inner.setrange(key, offset, value))
+    }
+
     pub fn publish(&mut self, channel: &str, message: Bytes) -> crate::Result<u64> {
         self.rt.block_on(self.inner.publish(channel, message))
     }
@@ -65,6 +101,10 @@ impl BlockingSubscriber {
         self.inner.get_subscribed()
     }
 
+    pub fn dropped_messages(&self) -> u64 {
+        self.inner.dropped_messages()
+    }
+
     pub fn next_message(&mut self) -> crate::Result<Option<Message>> {
         self.rt.block_on(self.inner.next_message())
     }
@@ -83,6 +123,14 @@ impl BlockingSubscriber {
     pub fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
         self.rt.block_on(self.inner.unsubscribe(channels))
     }
+
+    pub fn psubscribe(&mut self, patterns: &[String]) -> crate::Result<()> {
+        self.rt.block_on(self.inner.psubscribe(patterns))
+    }
+
+    pub fn punsubscribe(&mut self, patterns: &[String]) -> crate::Result<()> {
+        self.rt.block_on(self.inner.punsubscribe(patterns))
+    }
 }
 
 impl Iterator for SubscriberIterator {
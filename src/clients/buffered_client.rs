@@ -6,7 +6,7 @@ use tokio::sync::{
 
 use crate::Client;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct BufferedClient {
     tx: Sender<Message>,
 }
@@ -15,15 +15,44 @@ pub struct BufferedClient {
 enum Command {
     Get(String),
     Set(String, Bytes),
+    Incrby(String, i64),
+    Incrbyfloat(String, f64),
+    Append(String, Bytes),
+    Strlen(String),
+    Getrange(String, i64, i64),
+    Setrange(String, u64, Bytes),
 }
 
-type Message = (Command, oneshot::Sender<crate::Result<Option<Bytes>>>);
+/// The reply to a buffered command, shaped after the `Client` method that
+/// produced it.
+#[derive(Debug)]
+enum Reply {
+    Value(Option<Bytes>),
+    Integer(i64),
+    Count(u64),
+    Float(f64),
+}
+
+type Message = (Command, oneshot::Sender<crate::Result<Reply>>);
 
 async fn run(mut client: Client, mut rx: Receiver<Message>) {
     while let Some((cmd, tx)) = rx.recv().await {
         let response = match cmd {
-            Command::Get(key) => client.get(&key).await,
-            Command::Set(key, value) => client.set(&key, value).await.map(|_| None),
+            Command::Get(key) => client.get(&key).await.map(Reply::Value),
+            Command::Set(key, value) => client.set(&key, value).await.map(|_| Reply::Value(None)),
+            Command::Incrby(key, delta) => client.incrby(&key, delta).await.map(Reply::Integer),
+            Command::Incrbyfloat(key, delta) => {
+                client.incrbyfloat(&key, delta).await.map(Reply::Float)
+            }
+            Command::Append(key, value) => client.append(&key, value).await.map(Reply::Count),
+            Command::Strlen(key) => client.strlen(&key).await.map(Reply::Count),
+            Command::Getrange(key, start, end) => client
+                .getrange(&key, start, end)
+                .await
+                .map(|value| Reply::Value(Some(value))),
+            Command::Setrange(key, offset, value) => {
+                client.setrange(&key, offset, value).await.map(Reply::Count)
+            }
         };
 
         let _ = tx.send(response);
@@ -39,25 +68,89 @@ impl BufferedClient {
     }
 
     pub async fn get(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
-        let get = Command::Get(key.into());
-        let (tx, rx) = oneshot::channel();
-        self.tx.send((get, tx)).await?;
-
-        match rx.await {
-            Ok(res) => res,
-            Err(err) => Err(err.into()),
+        match self.request(Command::Get(key.into())).await? {
+            Reply::Value(value) => Ok(value),
+            reply => Err(unexpected(reply)),
         }
     }
 
     pub async fn set(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
-        let set = Command::Set(key.into(), value);
+        self.request(Command::Set(key.into(), value))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn incr(&mut self, key: &str) -> crate::Result<i64> {
+        self.incrby(key, 1).await
+    }
+
+    pub async fn decr(&mut self, key: &str) -> crate::Result<i64> {
+        self.incrby(key, -1).await
+    }
+
+    pub async fn incrby(&mut self, key: &str, delta: i64) -> crate::Result<i64> {
+        match self.request(Command::Incrby(key.into(), delta)).await? {
+            Reply::Integer(value) => Ok(value),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    pub async fn decrby(&mut self, key: &str, delta: i64) -> crate::Result<i64> {
+        let delta = delta.checked_neg().ok_or("decrement would overflow")?;
+        self.incrby(key, delta).await
+    }
 
+    pub async fn incrbyfloat(&mut self, key: &str, delta: f64) -> crate::Result<f64> {
+        match self
+            .request(Command::Incrbyfloat(key.into(), delta))
+            .await?
+        {
+            Reply::Float(value) => Ok(value),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    pub async fn append(&mut self, key: &str, value: Bytes) -> crate::Result<u64> {
+        self.count_request(Command::Append(key.into(), value)).await
+    }
+
+    pub async fn strlen(&mut self, key: &str) -> crate::Result<u64> {
+        self.count_request(Command::Strlen(key.into())).await
+    }
+
+    pub async fn getrange(&mut self, key: &str, start: i64, end: i64) -> crate::Result<Bytes> {
+        match self
+            .request(Command::Getrange(key.into(), start, end))
+            .await?
+        {
+            Reply::Value(value) => Ok(value.unwrap_or_default()),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    pub async fn setrange(&mut self, key: &str, offset: u64, value: Bytes) -> crate::Result<u64> {
+        self.count_request(Command::Setrange(key.into(), offset, value))
+            .await
+    }
+
+    async fn request(&mut self, cmd: Command) -> crate::Result<Reply> {
         let (tx, rx) = oneshot::channel();
-        self.tx.send((set, tx)).await?;
+        self.tx.send((cmd, tx)).await?;
 
         match rx.await {
-            Ok(res) => res.map(|_| ()),
+            Ok(res) => res,
             Err(err) => Err(err.into()),
         }
     }
+
+    async fn count_request(&mut self, cmd: Command) -> crate::Result<u64> {
+        match self.request(cmd).await? {
+            Reply::Count(count) => Ok(count),
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+fn unexpected(reply: Reply) -> crate::Error {
+    format!("unexpected reply {:?}", reply).into()
 }
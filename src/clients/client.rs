@@ -6,7 +6,12 @@
  * @Last Modified time: 2023-10-23 16:22:03
  */
 
-use std::io::{Error, ErrorKind};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Error, ErrorKind},
+    ops::RangeInclusive,
+    time::Duration,
+};
 
 use async_stream::try_stream;
 use bytes::Bytes;
@@ -15,23 +20,93 @@ use tokio_stream::Stream;
 use tracing::{debug, instrument};
 
 use crate::{
-    cmd::{Get, Ping, Publish, Set, Subscribe, Unsubscribe},
-    Connection, Frame,
+    cmd::{
+        Append, Asking, Bgrewriteaof, Blmove, Bpop, Cluster, Combine, Copy, Dbsize, Del, Discard,
+        Eval, Exec, Exists, Expire, Flush, Get, Getdel, Getex, Getrange, Hdel, Hello, Hexists,
+        Hget, Hgetall, Hincrby, Hkeys, Hlen, Hmget, Hset, Hvals, Incr, Incrbyfloat, Info, Keys,
+        Lastsave, Lindex, Llen, Lrange, Lrem, Lset, Ltrim, Mget, Move, Mset, Multi, Persist, Ping,
+        Pop, Psubscribe, Publish, Pubsub, Punsubscribe, Push, RangeKind, Rename, Replicaof, Role,
+        Sadd, Save, Scan, ScanKind, Scard, Script, Select, Set, Setex, Setnx, Setrange, Sismember,
+        Smembers, Srem, Strlen, Subscribe, Swapdb, Ttl, TtlFormat, Type, Unsubscribe, Unwatch,
+        Wait, Watch, Xack, Xadd, Xautoclaim, Xclaim, Xgroup, Xlen, Xpending, Xrange, Xreadgroup,
+        Xtrim, Zadd, Zincrby, Zpopmin, Zrange, Zrank, Zrem, Zscore,
+    },
+    db::{parse_score, Expiry, SetOp, Side},
+    Connection, ExpireOptions, Frame, ScanOptions, SetOptions, ZaddOptions,
 };
 
 pub struct Client {
     connection: Connection,
 }
 
+/// Commands to run atomically with `MULTI`/`EXEC`, built with
+/// `Client::transaction`.
+pub struct Transaction<'a> {
+    client: &'a mut Client,
+    commands: Vec<Frame>,
+}
+
 pub struct Subscriber {
     client: Client,
     subscribed_channels: Vec<String>,
+    subscribed_patterns: Vec<String>,
+    dropped_messages: u64,
 }
 
 #[derive(Debug, Clone)]
 pub struct Message {
     pub channel: String,
     pub content: Bytes,
+    /// The pattern the message was received through, if it was not sent to
+    /// a channel subscribed to by name.
+    pub pattern: Option<String>,
+}
+
+/// A record read from a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamEntry {
+    pub id: String,
+    pub fields: Vec<(Bytes, Bytes)>,
+}
+
+/// An entry of a consumer group's pending entries list, as reported by the
+/// extended form of `XPENDING`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEntry {
+    pub id: String,
+    pub consumer: String,
+    pub idle_ms: u64,
+    pub deliveries: u64,
+}
+
+/// What `ROLE` reports about a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationRole {
+    /// A master, at `offset` of the stream of changes it sends its replicas,
+    /// with the IP, port and offset of each of them.
+    Master {
+        offset: u64,
+        replicas: Vec<(String, u16, u64)>,
+    },
+    /// A replica of the master at `host` and `port`, whose link is in
+    /// `state`: `connecting`, `sync` or `connected`. It received `offset` of
+    /// the master's stream.
+    Replica {
+        host: String,
+        port: u16,
+        state: String,
+        offset: u64,
+    },
+}
+
+/// A range of hash slots reported by `CLUSTER SLOTS`, with the node owning
+/// it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterSlots {
+    pub slots: RangeInclusive<u16>,
+    pub host: String,
+    pub port: u16,
+    pub id: String,
 }
 
 impl Client {
@@ -59,174 +134,1979 @@ impl Client {
         }
     }
 
+    /// Removes `keys`, returning how many of them existed.
     #[instrument(skip(self))]
-    pub async fn get(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
-        let frame = Get::new(key).into_frame();
-        debug!(request = ?frame);
+    pub async fn del(&mut self, keys: &[&str]) -> crate::Result<u64> {
+        let frame = Del::new(owned_keys(keys), false).into_frame();
+        self.count_cmd(frame).await
+    }
 
-        self.connection.write_frame(&frame).await?;
+    /// Like `del`, but the server frees large values in the background.
+    #[instrument(skip(self))]
+    pub async fn unlink(&mut self, keys: &[&str]) -> crate::Result<u64> {
+        let frame = Del::new(owned_keys(keys), true).into_frame();
+        self.count_cmd(frame).await
+    }
 
-        match self.read_response().await? {
-            Frame::Simple(value) => Ok(Some(value.into())),
-            Frame::Bulk(value) => Ok(Some(value)),
-            Frame::Null => Ok(None),
-            frame => Err(frame.to_error()),
+    /// Counts how many of `keys` exist, counting repeated keys every time.
+    #[instrument(skip(self))]
+    pub async fn exists(&mut self, keys: &[&str]) -> crate::Result<u64> {
+        let frame = Exists::new(owned_keys(keys), false).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn touch(&mut self, keys: &[&str]) -> crate::Result<u64> {
+        let frame = Exists::new(owned_keys(keys), true).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    /// Returns the name of the type stored at `key`, or `none`.
+    #[instrument(skip(self))]
+    pub async fn key_type(&mut self, key: &str) -> crate::Result<String> {
+        let frame = Type::new(key).into_frame();
+        match self.bulk_cmd(frame).await? {
+            Some(name) => string_from_bytes(name),
+            None => Err("unexpected nil reply to `TYPE`".into()),
         }
     }
+
     #[instrument(skip(self))]
-    pub async fn set(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
-        self.set_cmd(Set::new(key, value, None)).await
+    pub async fn rename(&mut self, key: &str, newkey: &str) -> crate::Result<()> {
+        let frame = Rename::new(key, newkey, false).into_frame();
+        self.ok_cmd(frame).await
     }
 
+    /// Renames `key` unless `newkey` exists, returning whether it was renamed.
     #[instrument(skip(self))]
-    pub async fn set_expirse(
+    pub async fn renamenx(&mut self, key: &str, newkey: &str) -> crate::Result<bool> {
+        let frame = Rename::new(key, newkey, true).into_frame();
+        Ok(self.count_cmd(frame).await? == 1)
+    }
+
+    /// Copies `source` to `destination`, returning whether it was copied.
+    /// An existing `destination` is only overwritten with `replace`.
+    #[instrument(skip(self))]
+    pub async fn copy(
+        &mut self,
+        source: &str,
+        destination: &str,
+        replace: bool,
+    ) -> crate::Result<bool> {
+        let frame = Copy::new(source, destination, replace).into_frame();
+        Ok(self.count_cmd(frame).await? == 1)
+    }
+
+    /// Makes `key` expire after `seconds`, returning whether the expiration
+    /// was set. A key that does not exist is never updated.
+    #[instrument(skip(self))]
+    pub async fn expire(&mut self, key: &str, seconds: u64) -> crate::Result<bool> {
+        self.expire_options(key, seconds, ExpireOptions::new())
+            .await
+    }
+
+    /// Like `expire`, but only updates the expiration as allowed by `options`.
+    #[instrument(skip(self))]
+    pub async fn expire_options(
         &mut self,
         key: &str,
-        value: Bytes,
-        expiration: tokio::time::Duration,
-    ) -> crate::Result<()> {
-        self.set_cmd(Set::new(key, value, Some(expiration))).await
+        seconds: u64,
+        options: ExpireOptions,
+    ) -> crate::Result<bool> {
+        self.expire_cmd(key, Expiry::Ex(seconds), options).await
     }
 
     #[instrument(skip(self))]
-    pub async fn publish(&mut self, channel: &str, message: Bytes) -> crate::Result<u64> {
-        let frame = Publish::new(channel, message).into_frame();
-        debug!(request = ?frame);
+    pub async fn pexpire(&mut self, key: &str, ms: u64) -> crate::Result<bool> {
+        self.expire_cmd(key, Expiry::Px(ms), ExpireOptions::new())
+            .await
+    }
 
-        self.connection.write_frame(&frame).await?;
+    /// Makes `key` expire at the Unix time `secs`, in seconds.
+    #[instrument(skip(self))]
+    pub async fn expireat(&mut self, key: &str, secs: u64) -> crate::Result<bool> {
+        self.expire_cmd(key, Expiry::ExAt(secs), ExpireOptions::new())
+            .await
+    }
 
-        match self.read_response().await? {
-            Frame::Integer(response) => Ok(response),
-            frame => Err(frame.to_error()),
-        }
+    /// Makes `key` expire at the Unix time `ms`, in milliseconds.
+    #[instrument(skip(self))]
+    pub async fn pexpireat(&mut self, key: &str, ms: u64) -> crate::Result<bool> {
+        self.expire_cmd(key, Expiry::PxAt(ms), ExpireOptions::new())
+            .await
     }
 
+    /// The seconds left before `key` expires, `-1` if it never expires and
+    /// `-2` if it does not exist.
     #[instrument(skip(self))]
-    pub async fn subscribe(mut self, channels: Vec<String>) -> crate::Result<Subscriber> {
-        self.subscribe_cmd(&channels).await?;
-        Ok(Subscriber {
-            client: self,
-            subscribed_channels: channels,
-        })
+    pub async fn ttl(&mut self, key: &str) -> crate::Result<i64> {
+        self.int_cmd(Ttl::new(key, TtlFormat::Seconds).into_frame())
+            .await
     }
 
-    async fn subscribe_cmd(&mut self, channels: &[String]) -> crate::Result<()> {
-        let frame = Subscribe::new(channels.to_vec()).into_frame();
-        debug!(request = ?frame);
+    /// Like `ttl`, in milliseconds.
+    #[instrument(skip(self))]
+    pub async fn pttl(&mut self, key: &str) -> crate::Result<i64> {
+        self.int_cmd(Ttl::new(key, TtlFormat::Millis).into_frame())
+            .await
+    }
 
-        self.connection.write_frame(&frame).await?;
-        for channel in channels {
-            let response = self.read_response().await?;
-            match response {
-                Frame::Array(ref frame) => match frame.as_slice() {
-                    [subscribe, schannel, ..]
-                        if *subscribe == "subscribe" && *schannel == channel => {}
-                    _ => return Err(response.to_error()),
-                },
-                frame => return Err(frame.to_error()),
+    /// The Unix time in seconds at which `key` expires, `-1` if it never
+    /// expires and `-2` if it does not exist.
+    #[instrument(skip(self))]
+    pub async fn expiretime(&mut self, key: &str) -> crate::Result<i64> {
+        self.int_cmd(Ttl::new(key, TtlFormat::UnixSeconds).into_frame())
+            .await
+    }
+
+    /// Like `expiretime`, in milliseconds.
+    #[instrument(skip(self))]
+    pub async fn pexpiretime(&mut self, key: &str) -> crate::Result<i64> {
+        self.int_cmd(Ttl::new(key, TtlFormat::UnixMillis).into_frame())
+            .await
+    }
+
+    /// Removes the expiration of `key`, returning whether it had one.
+    #[instrument(skip(self))]
+    pub async fn persist(&mut self, key: &str) -> crate::Result<bool> {
+        let frame = Persist::new(key).into_frame();
+        Ok(self.count_cmd(frame).await? == 1)
+    }
+
+    /// Lists all keys matching the glob `pattern` at once. Prefer `scan_iter`
+    /// on large databases.
+    #[instrument(skip(self))]
+    pub async fn keys(&mut self, pattern: &str) -> crate::Result<Vec<String>> {
+        let frame = Keys::new(pattern.to_string()).into_frame();
+        self.array_cmd(frame)
+            .await?
+            .into_iter()
+            .map(string_from_bytes)
+            .collect()
+    }
+
+    /// Returns a page of keys along with the cursor of the next page. Start
+    /// with cursor `0` and stop once `0` is returned again.
+    #[instrument(skip(self))]
+    pub async fn scan(
+        &mut self,
+        cursor: u64,
+        options: ScanOptions,
+    ) -> crate::Result<(u64, Vec<String>)> {
+        let frame = Scan::new(ScanKind::Keys, None, cursor, options).into_frame();
+        let (cursor, keys) = self.scan_cmd(frame).await?;
+        let keys = keys
+            .into_iter()
+            .map(string_from_bytes)
+            .collect::<crate::Result<_>>()?;
+        Ok((cursor, keys))
+    }
+
+    /// Iterates over all keys matching `options`, issuing `SCAN` for each
+    /// page. Every key present for the whole iteration is yielded once.
+    pub fn scan_iter(
+        &mut self,
+        options: ScanOptions,
+    ) -> impl Stream<Item = crate::Result<String>> + '_ {
+        try_stream! {
+            let mut cursor = 0;
+            loop {
+                let (next, keys) = self.scan(cursor, options.clone()).await?;
+                for key in keys {
+                    yield key;
+                }
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
             }
         }
-        Ok(())
     }
 
-    async fn set_cmd(&mut self, cmd: Set) -> crate::Result<()> {
-        let frame = cmd.into_frame();
-        debug!(request = ?frame);
-        self.connection.write_frame(&frame).await?;
-        match self.read_response().await? {
-            Frame::Simple(response) if response == "OK" => Ok(()),
-            frame => Err(frame.to_error()),
+    #[instrument(skip(self))]
+    pub async fn hscan(
+        &mut self,
+        key: &str,
+        cursor: u64,
+        options: ScanOptions,
+    ) -> crate::Result<(u64, Vec<(String, Bytes)>)> {
+        let frame = Scan::new(ScanKind::Hash, Some(key.to_string()), cursor, options).into_frame();
+        let (cursor, values) = self.scan_cmd(frame).await?;
+
+        let mut values = values.into_iter();
+        let mut fields = vec![];
+        while let (Some(field), Some(value)) = (values.next(), values.next()) {
+            fields.push((string_from_bytes(field)?, value));
         }
+        Ok((cursor, fields))
     }
 
-    async fn read_response(&mut self) -> crate::Result<Frame> {
-        let response = self.connection.read_frame().await?;
-        debug!(?response);
+    #[instrument(skip(self))]
+    pub async fn sscan(
+        &mut self,
+        key: &str,
+        cursor: u64,
+        options: ScanOptions,
+    ) -> crate::Result<(u64, Vec<Bytes>)> {
+        let frame = Scan::new(ScanKind::Set, Some(key.to_string()), cursor, options).into_frame();
+        self.scan_cmd(frame).await
+    }
 
-        match response {
-            Some(Frame::Error(msg)) => Err(msg.into()),
-            Some(frame) => Ok(frame),
-            None => {
-                let err = Error::new(ErrorKind::ConnectionReset, "connection reset by server");
-                Err(err.into())
-            }
+    #[instrument(skip(self))]
+    pub async fn zscan(
+        &mut self,
+        key: &str,
+        cursor: u64,
+        options: ScanOptions,
+    ) -> crate::Result<(u64, Vec<(Bytes, f64)>)> {
+        let frame = Scan::new(ScanKind::ZSet, Some(key.to_string()), cursor, options).into_frame();
+        let (cursor, values) = self.scan_cmd(frame).await?;
+
+        let mut values = values.into_iter();
+        let mut members = vec![];
+        while let (Some(member), Some(score)) = (values.next(), values.next()) {
+            members.push((member, score_from_bytes(&score)?));
         }
+        Ok((cursor, members))
     }
-}
 
-impl Subscriber {
-    pub fn get_subscribed(&self) -> &[String] {
-        &self.subscribed_channels
+    /// Switches this connection to the database at `index`.
+    #[instrument(skip(self))]
+    pub async fn select(&mut self, index: usize) -> crate::Result<()> {
+        let frame = Select::new(index as i64).into_frame();
+        self.ok_cmd(frame).await
     }
 
-    pub async fn next_message(&mut self) -> crate::Result<Option<Message>> {
-        match self.client.connection.read_frame().await? {
-            Some(mframe) => {
-                debug!(?mframe);
-                match mframe {
-                    Frame::Array(ref frame) => match frame.as_slice() {
-                        [message, channel, content] if *message == "message" => Ok(Some(Message {
-                            channel: channel.to_string(),
-                            content: Bytes::from(content.to_string()),
-                        })),
-                        _ => Err(mframe.to_error()),
-                    },
-                    frame => Err(frame.to_error()),
-                }
-            }
-            None => Ok(None),
+    /// Exchanges the contents of two databases, for every connection.
+    #[instrument(skip(self))]
+    pub async fn swapdb(&mut self, index1: usize, index2: usize) -> crate::Result<()> {
+        let frame = Swapdb::new(index1 as i64, index2 as i64).into_frame();
+        self.ok_cmd(frame).await
+    }
+
+    /// Moves `key` from the selected database to the one at `index`,
+    /// returning whether it was moved.
+    #[instrument(skip(self))]
+    pub async fn move_key(&mut self, key: &str, index: usize) -> crate::Result<bool> {
+        let frame = Move::new(key, index as i64).into_frame();
+        Ok(self.count_cmd(frame).await? == 1)
+    }
+
+    /// Removes every key of the selected database.
+    #[instrument(skip(self))]
+    pub async fn flushdb(&mut self) -> crate::Result<()> {
+        self.ok_cmd(Flush::new(false, false).into_frame()).await
+    }
+
+    /// Removes every key of every database.
+    #[instrument(skip(self))]
+    pub async fn flushall(&mut self) -> crate::Result<()> {
+        self.ok_cmd(Flush::new(true, false).into_frame()).await
+    }
+
+    /// Writes a snapshot of every database to the server's snapshot file,
+    /// returning once it is on disk.
+    #[instrument(skip(self))]
+    pub async fn save(&mut self) -> crate::Result<()> {
+        self.ok_cmd(Save::new(false).into_frame()).await
+    }
+
+    /// Starts writing a snapshot in the background, see `lastsave` to tell
+    /// when it is done.
+    #[instrument(skip(self))]
+    pub async fn bgsave(&mut self) -> crate::Result<()> {
+        let frame = Save::new(true).into_frame();
+        match self.request(frame).await? {
+            Frame::Simple(_) => Ok(()),
+            frame => Err(frame.to_error()),
         }
     }
 
-    pub fn into_stream(mut self) -> impl Stream<Item = crate::Result<Message>> {
-        try_stream! {
-            while let Some(message) = self.next_message().await? {
-                yield message;
-            }
+    /// Starts rewriting the server's append only file in the background,
+    /// compacted from the current data.
+    #[instrument(skip(self))]
+    pub async fn bgrewriteaof(&mut self) -> crate::Result<()> {
+        let frame = Bgrewriteaof::new().into_frame();
+        match self.request(frame).await? {
+            Frame::Simple(_) => Ok(()),
+            frame => Err(frame.to_error()),
         }
     }
 
+    /// Makes the server a replica of the server at `host` and `port`.
     #[instrument(skip(self))]
-    pub async fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
-        self.client.subscribe_cmd(channels).await?;
-        self.subscribed_channels
-            .extend(channels.iter().map(Clone::clone));
-        Ok(())
+    pub async fn replicaof(&mut self, host: &str, port: u16) -> crate::Result<()> {
+        let frame = Replicaof::new(Some((host.to_string(), port))).into_frame();
+        match self.request(frame).await? {
+            Frame::Simple(_) => Ok(()),
+            frame => Err(frame.to_error()),
+        }
     }
 
+    /// Makes a replica a master again, keeping its data.
     #[instrument(skip(self))]
-    pub async fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
-        let frame = Unsubscribe::new(channels).into_frame();
-        debug!(request = ?frame);
-        self.client.connection.write_frame(&frame).await?;
+    pub async fn replicaof_no_one(&mut self) -> crate::Result<()> {
+        self.ok_cmd(Replicaof::new(None).into_frame()).await
+    }
 
-        let num = if channels.is_empty() {
-            self.subscribed_channels.len()
-        } else {
-            channels.len()
+    #[instrument(skip(self))]
+    pub async fn role(&mut self) -> crate::Result<ReplicationRole> {
+        role_from_frame(self.request(Role::new().into_frame()).await?)
+    }
+
+    /// Waits until the writes of this client reached `numreplicas` replicas,
+    /// or `timeout` elapsed if given, returning how many replicas they
+    /// reached.
+    #[instrument(skip(self))]
+    pub async fn wait(
+        &mut self,
+        numreplicas: u64,
+        timeout: Option<Duration>,
+    ) -> crate::Result<u64> {
+        self.count_cmd(Wait::new(numreplicas, timeout).into_frame())
+            .await
+    }
+
+    /// Reports on the server, as `field:value` lines. Only the given
+    /// `sections` are reported, or all of them when empty.
+    #[instrument(skip(self))]
+    pub async fn info(&mut self, sections: &[&str]) -> crate::Result<String> {
+        let sections = sections.iter().map(|section| section.to_string()).collect();
+        match self.bulk_cmd(Info::new(sections).into_frame()).await? {
+            Some(info) => string_from_bytes(info),
+            None => Err("unexpected nil reply to `INFO`".into()),
+        }
+    }
+
+    /// Lets the next command use a slot the node is importing, following an
+    /// `ASK` redirection.
+    #[instrument(skip(self))]
+    pub async fn asking(&mut self) -> crate::Result<()> {
+        self.ok_cmd(Asking::new().into_frame()).await
+    }
+
+    /// Reports on the state of the cluster, as `field:value` lines.
+    #[instrument(skip(self))]
+    pub async fn cluster_info(&mut self) -> crate::Result<String> {
+        self.text_cmd(Cluster::info().into_frame()).await
+    }
+
+    /// The ID of the node.
+    #[instrument(skip(self))]
+    pub async fn cluster_myid(&mut self) -> crate::Result<String> {
+        self.text_cmd(Cluster::myid().into_frame()).await
+    }
+
+    /// Describes the nodes of the cluster, one per line, in the format of
+    /// Redis' `nodes.conf`.
+    #[instrument(skip(self))]
+    pub async fn cluster_nodes(&mut self) -> crate::Result<String> {
+        self.text_cmd(Cluster::nodes().into_frame()).await
+    }
+
+    /// The hash slot of `key`.
+    #[instrument(skip(self))]
+    pub async fn cluster_keyslot(&mut self, key: &str) -> crate::Result<u16> {
+        let slot = self.count_cmd(Cluster::keyslot(key).into_frame()).await?;
+        Ok(slot as u16)
+    }
+
+    /// The ranges of slots of the cluster and the node owning each of them.
+    #[instrument(skip(self))]
+    pub async fn cluster_slots(&mut self) -> crate::Result<Vec<ClusterSlots>> {
+        let ranges = match self.request(Cluster::slots().into_frame()).await? {
+            Frame::Array(ranges) => ranges,
+            frame => return Err(frame.to_error()),
         };
-        for _ in 0..num {
-            let response = self.client.read_response().await?;
+        ranges.into_iter().map(cluster_slots_from_frame).collect()
+    }
 
-            match response {
-                Frame::Array(ref frame) => match frame.as_slice() {
-                    [unsubscribe, channel, ..] if *unsubscribe == "unsubscribe" => {
-                        let len = self.subscribed_channels.len();
+    /// Describes each shard of the cluster, with its `slots` and `nodes`.
+    #[instrument(skip(self))]
+    pub async fn cluster_shards(&mut self) -> crate::Result<Vec<HashMap<String, Frame>>> {
+        let shards = match self.request(Cluster::shards().into_frame()).await? {
+            Frame::Array(shards) => shards,
+            frame => return Err(frame.to_error()),
+        };
+        shards
+            .into_iter()
+            .map(|shard| {
+                pairs_from_frame(shard)?
+                    .into_iter()
+                    .map(|(field, value)| match field {
+                        Frame::Bulk(field) => Ok((string_from_bytes(field)?, value)),
+                        field => Err(field.to_error()),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
 
-                        if len == 0 {
-                            return Err(response.to_error());
-                        }
+    /// The Unix time in seconds of the last successful save.
+    #[instrument(skip(self))]
+    pub async fn lastsave(&mut self) -> crate::Result<u64> {
+        self.count_cmd(Lastsave::new().into_frame()).await
+    }
 
-                        self.subscribed_channels.retain(|c| *channel != &c[..]);
-                        if self.subscribed_channels.len() != len - 1 {
-                            return Err(response.to_error());
-                        }
-                    }
-                    _ => return Err(response.to_error()),
-                },
-                frame => return Err(frame.to_error()),
-            }
+    /// Counts the keys of the selected database.
+    #[instrument(skip(self))]
+    pub async fn dbsize(&mut self) -> crate::Result<u64> {
+        self.count_cmd(Dbsize::new().into_frame()).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        let frame = Get::new(key).into_frame();
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Simple(value) => Ok(Some(value.into())),
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
         }
-        Ok(())
+    }
+    #[instrument(skip(self))]
+    pub async fn set(&mut self, key: &str, value: Bytes) -> crate::Result<()> {
+        self.set_cmd(Set::new(key, value, None)).await
+    }
+
+    /// Sets `key` as allowed by `options`, returning whether the value was
+    /// written. `SetOptions::get` is ignored, use `set_get` instead.
+    #[instrument(skip(self))]
+    pub async fn set_options(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        options: SetOptions,
+    ) -> crate::Result<bool> {
+        let options = SetOptions {
+            get: false,
+            ..options
+        };
+        let frame = Set::with_options(key, value, options).into_frame();
+        match self.request(frame).await? {
+            Frame::Simple(response) if response == "OK" => Ok(true),
+            Frame::Null => Ok(false),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Like `set_options`, but replies with the previous value of the key.
+    #[instrument(skip(self))]
+    pub async fn set_get(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        options: SetOptions,
+    ) -> crate::Result<Option<Bytes>> {
+        let frame = Set::with_options(key, value, options.get()).into_frame();
+        self.bulk_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn set_expirse(
+        &mut self,
+        key: &str,
+        value: Bytes,
+        expiration: tokio::time::Duration,
+    ) -> crate::Result<()> {
+        self.set_cmd(Set::new(key, value, Some(expiration))).await
+    }
+
+    /// Reads several keys in a single round trip. Missing keys and keys
+    /// holding other types read as `None`.
+    #[instrument(skip(self))]
+    pub async fn mget(&mut self, keys: &[&str]) -> crate::Result<Vec<Option<Bytes>>> {
+        let keys = owned_keys(keys);
+        let frame = Mget::new(keys).into_frame();
+        self.optional_array_cmd(frame).await
+    }
+
+    /// Atomically writes all of `pairs`.
+    #[instrument(skip(self, pairs))]
+    pub async fn mset(&mut self, pairs: &[(&str, Bytes)]) -> crate::Result<()> {
+        let frame = Mset::new(owned_pairs(pairs), false).into_frame();
+        self.ok_cmd(frame).await
+    }
+
+    /// Atomically writes all of `pairs`, unless any of the keys exists.
+    /// Returns whether the keys were written.
+    #[instrument(skip(self, pairs))]
+    pub async fn msetnx(&mut self, pairs: &[(&str, Bytes)]) -> crate::Result<bool> {
+        let frame = Mset::new(owned_pairs(pairs), true).into_frame();
+        Ok(self.count_cmd(frame).await? == 1)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn getdel(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        let frame = Getdel::new(key).into_frame();
+        self.bulk_cmd(frame).await
+    }
+
+    /// Reads `key` and makes it expire after `expiration`.
+    #[instrument(skip(self))]
+    pub async fn getex(&mut self, key: &str, expiration: Duration) -> crate::Result<Option<Bytes>> {
+        let expire = Expiry::Px(expiration.as_millis() as u64);
+        let frame = Getex::new(key, Some(expire)).into_frame();
+        self.bulk_cmd(frame).await
+    }
+
+    /// Reads `key` and removes its expiration.
+    #[instrument(skip(self))]
+    pub async fn getex_persist(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        let frame = Getex::new(key, Some(Expiry::Persist)).into_frame();
+        self.bulk_cmd(frame).await
+    }
+
+    /// Sets `key` only if it does not exist, returning whether it was set.
+    #[instrument(skip(self))]
+    pub async fn setnx(&mut self, key: &str, value: Bytes) -> crate::Result<bool> {
+        let frame = Setnx::new(key, value).into_frame();
+        Ok(self.count_cmd(frame).await? == 1)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn setex(&mut self, key: &str, seconds: u64, value: Bytes) -> crate::Result<()> {
+        let frame = Setex::new(key, seconds, value).into_frame();
+        self.ok_cmd(frame).await
+    }
+
+    /// Increments the integer stored at `key` by one, treating a missing key
+    /// as `0`, and returns the new value.
+    #[instrument(skip(self))]
+    pub async fn incr(&mut self, key: &str) -> crate::Result<i64> {
+        self.incrby(key, 1).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn decr(&mut self, key: &str) -> crate::Result<i64> {
+        self.incrby(key, -1).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn incrby(&mut self, key: &str, delta: i64) -> crate::Result<i64> {
+        let frame = Incr::new(key, delta).into_frame();
+        self.int_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn decrby(&mut self, key: &str, delta: i64) -> crate::Result<i64> {
+        let delta = delta.checked_neg().ok_or("decrement would overflow")?;
+        self.incrby(key, delta).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn incrbyfloat(&mut self, key: &str, delta: f64) -> crate::Result<f64> {
+        let frame = Incrbyfloat::new(key, delta).into_frame();
+        match self.bulk_cmd(frame).await? {
+            Some(value) => score_from_bytes(&value),
+            None => Err("unexpected nil reply to `INCRBYFLOAT`".into()),
+        }
+    }
+
+    /// Appends `value` to the string at `key` and returns its new length.
+    #[instrument(skip(self))]
+    pub async fn append(&mut self, key: &str, value: Bytes) -> crate::Result<u64> {
+        let frame = Append::new(key, value).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn strlen(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = Strlen::new(key).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    /// Returns the bytes `start..=end` of the string at `key`. Negative
+    /// offsets count from the end of the string.
+    #[instrument(skip(self))]
+    pub async fn getrange(&mut self, key: &str, start: i64, end: i64) -> crate::Result<Bytes> {
+        let frame = Getrange::new(key, start, end).into_frame();
+        Ok(self.bulk_cmd(frame).await?.unwrap_or_default())
+    }
+
+    /// Overwrites the string at `key` from `offset` on, zero padding it when
+    /// needed, and returns its new length.
+    #[instrument(skip(self))]
+    pub async fn setrange(&mut self, key: &str, offset: u64, value: Bytes) -> crate::Result<u64> {
+        let frame = Setrange::new(key, offset, value).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn publish(&mut self, channel: &str, message: Bytes) -> crate::Result<u64> {
+        let frame = Publish::new(channel, message).into_frame();
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+
+        match self.read_response().await? {
+            Frame::Integer(response) => Ok(response as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// The channels with subscribers, optionally only those matching the
+    /// glob `pattern`.
+    #[instrument(skip(self))]
+    pub async fn pubsub_channels(&mut self, pattern: Option<&str>) -> crate::Result<Vec<String>> {
+        let frame = Pubsub::channels(pattern.map(str::to_string)).into_frame();
+        self.array_cmd(frame)
+            .await?
+            .into_iter()
+            .map(string_from_bytes)
+            .collect()
+    }
+
+    /// The number of subscribers of each of `channels`, not counting those
+    /// subscribed through a pattern.
+    #[instrument(skip(self))]
+    pub async fn pubsub_numsub(&mut self, channels: &[&str]) -> crate::Result<Vec<(String, u64)>> {
+        let frame = Pubsub::numsub(owned_keys(channels)).into_frame();
+        self.counts_cmd(frame).await
+    }
+
+    /// The number of distinct patterns subscribed to.
+    #[instrument(skip(self))]
+    pub async fn pubsub_numpat(&mut self) -> crate::Result<u64> {
+        self.count_cmd(Pubsub::numpat().into_frame()).await
+    }
+
+    /// The number of messages dropped by slow subscribers of each of
+    /// `names`, channels or patterns, for as long as they have subscribers.
+    #[instrument(skip(self))]
+    pub async fn pubsub_dropped(&mut self, names: &[&str]) -> crate::Result<Vec<(String, u64)>> {
+        let frame = Pubsub::dropped(owned_keys(names)).into_frame();
+        self.counts_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn lpush(&mut self, key: &str, values: Vec<Bytes>) -> crate::Result<u64> {
+        let frame = Push::new(key, values, Side::Left).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn rpush(&mut self, key: &str, values: Vec<Bytes>) -> crate::Result<u64> {
+        let frame = Push::new(key, values, Side::Right).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn lpop(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        let frame = Pop::new(key, None, Side::Left).into_frame();
+        self.bulk_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn rpop(&mut self, key: &str) -> crate::Result<Option<Bytes>> {
+        let frame = Pop::new(key, None, Side::Right).into_frame();
+        self.bulk_cmd(frame).await
+    }
+
+    /// Pops from the head of the first non-empty list of `keys`, waiting up
+    /// to `timeout` (forever when `None`) for an element to be pushed.
+    /// Returns the key the element was popped from along with the element.
+    #[instrument(skip(self))]
+    pub async fn blpop(
+        &mut self,
+        keys: &[&str],
+        timeout: Option<Duration>,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        self.bpop_cmd(keys, timeout, Side::Left).await
+    }
+
+    /// Like `blpop`, but pops from the tail.
+    #[instrument(skip(self))]
+    pub async fn brpop(
+        &mut self,
+        keys: &[&str],
+        timeout: Option<Duration>,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        self.bpop_cmd(keys, timeout, Side::Right).await
+    }
+
+    /// Atomically moves an element from the `from` end of `source` to the
+    /// `to` end of `destination`, waiting up to `timeout` (forever when
+    /// `None`) for `source` to be pushed to.
+    #[instrument(skip(self))]
+    pub async fn blmove(
+        &mut self,
+        source: &str,
+        destination: &str,
+        from: Side,
+        to: Side,
+        timeout: Option<Duration>,
+    ) -> crate::Result<Option<Bytes>> {
+        let frame = Blmove::new(source, destination, from, to, timeout).into_frame();
+        self.bulk_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn lrange(&mut self, key: &str, start: i64, stop: i64) -> crate::Result<Vec<Bytes>> {
+        let frame = Lrange::new(key, start, stop).into_frame();
+        self.array_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn llen(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = Llen::new(key).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn lindex(&mut self, key: &str, index: i64) -> crate::Result<Option<Bytes>> {
+        let frame = Lindex::new(key, index).into_frame();
+        self.bulk_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn lset(&mut self, key: &str, index: i64, value: Bytes) -> crate::Result<()> {
+        let frame = Lset::new(key, index, value).into_frame();
+        self.ok_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn ltrim(&mut self, key: &str, start: i64, stop: i64) -> crate::Result<()> {
+        let frame = Ltrim::new(key, start, stop).into_frame();
+        self.ok_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn lrem(&mut self, key: &str, count: i64, value: Bytes) -> crate::Result<u64> {
+        let frame = Lrem::new(key, count, value).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hset(&mut self, key: &str, fields: Vec<(String, Bytes)>) -> crate::Result<u64> {
+        let frame = Hset::new(key, fields).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hget(&mut self, key: &str, field: &str) -> crate::Result<Option<Bytes>> {
+        let frame = Hget::new(key, field).into_frame();
+        self.bulk_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hmget(&mut self, key: &str, fields: &[&str]) -> crate::Result<Vec<Option<Bytes>>> {
+        let fields = fields.iter().map(|field| field.to_string()).collect();
+        let frame = Hmget::new(key, fields).into_frame();
+        self.optional_array_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hdel(&mut self, key: &str, fields: &[&str]) -> crate::Result<u64> {
+        let fields = fields.iter().map(|field| field.to_string()).collect();
+        let frame = Hdel::new(key, fields).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hgetall(&mut self, key: &str) -> crate::Result<HashMap<String, Bytes>> {
+        let frame = Hgetall::new(key).into_frame();
+        let pairs = pairs_from_frame(self.request(frame).await?)?;
+
+        let mut fields = HashMap::new();
+        for pair in pairs {
+            match pair {
+                (Frame::Bulk(field), Frame::Bulk(value)) => {
+                    fields.insert(string_from_bytes(field)?, value);
+                }
+                (field, _) => return Err(field.to_error()),
+            }
+        }
+        Ok(fields)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hkeys(&mut self, key: &str) -> crate::Result<Vec<String>> {
+        let frame = Hkeys::new(key).into_frame();
+        self.array_cmd(frame)
+            .await?
+            .into_iter()
+            .map(|field| Ok(String::from_utf8(field.to_vec())?))
+            .collect()
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hvals(&mut self, key: &str) -> crate::Result<Vec<Bytes>> {
+        let frame = Hvals::new(key).into_frame();
+        self.array_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hlen(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = Hlen::new(key).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hexists(&mut self, key: &str, field: &str) -> crate::Result<bool> {
+        let frame = Hexists::new(key, field).into_frame();
+        self.count_cmd(frame).await.map(|exists| exists == 1)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn hincrby(&mut self, key: &str, field: &str, delta: i64) -> crate::Result<i64> {
+        let frame = Hincrby::new(key, field, delta).into_frame();
+        self.int_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn sadd(&mut self, key: &str, members: Vec<Bytes>) -> crate::Result<u64> {
+        let frame = Sadd::new(key, members).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn srem(&mut self, key: &str, members: Vec<Bytes>) -> crate::Result<u64> {
+        let frame = Srem::new(key, members).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn smembers(&mut self, key: &str) -> crate::Result<HashSet<Bytes>> {
+        let frame = Smembers::new(key).into_frame();
+        Ok(self.array_cmd(frame).await?.into_iter().collect())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn sismember(&mut self, key: &str, member: Bytes) -> crate::Result<bool> {
+        let frame = Sismember::new(key, member).into_frame();
+        self.count_cmd(frame).await.map(|member| member == 1)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn scard(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = Scard::new(key).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn sinter(&mut self, keys: &[&str]) -> crate::Result<HashSet<Bytes>> {
+        self.combine_cmd(SetOp::Inter, keys).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn sunion(&mut self, keys: &[&str]) -> crate::Result<HashSet<Bytes>> {
+        self.combine_cmd(SetOp::Union, keys).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn sdiff(&mut self, keys: &[&str]) -> crate::Result<HashSet<Bytes>> {
+        self.combine_cmd(SetOp::Diff, keys).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn sinterstore(&mut self, destination: &str, keys: &[&str]) -> crate::Result<u64> {
+        self.combine_store_cmd(SetOp::Inter, destination, keys)
+            .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn sunionstore(&mut self, destination: &str, keys: &[&str]) -> crate::Result<u64> {
+        self.combine_store_cmd(SetOp::Union, destination, keys)
+            .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn sdiffstore(&mut self, destination: &str, keys: &[&str]) -> crate::Result<u64> {
+        self.combine_store_cmd(SetOp::Diff, destination, keys).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zadd(&mut self, key: &str, members: Vec<(f64, Bytes)>) -> crate::Result<u64> {
+        self.zadd_options(key, ZaddOptions::new(), members).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zadd_options(
+        &mut self,
+        key: &str,
+        options: ZaddOptions,
+        members: Vec<(f64, Bytes)>,
+    ) -> crate::Result<u64> {
+        let frame = Zadd::new(key, options, false, members).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    /// `ZADD ... INCR`, returns `None` when the options prevented the update.
+    #[instrument(skip(self))]
+    pub async fn zadd_incr(
+        &mut self,
+        key: &str,
+        options: ZaddOptions,
+        delta: f64,
+        member: Bytes,
+    ) -> crate::Result<Option<f64>> {
+        let frame = Zadd::new(key, options, true, vec![(delta, member)]).into_frame();
+        self.bulk_cmd(frame)
+            .await?
+            .map(|score| score_from_bytes(&score))
+            .transpose()
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zrange(&mut self, key: &str, start: i64, stop: i64) -> crate::Result<Vec<Bytes>> {
+        let frame = Zrange::new(key, start, stop, RangeKind::Rank).into_frame();
+        self.array_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zrevrange(
+        &mut self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> crate::Result<Vec<Bytes>> {
+        let frame = Zrange::new(key, start, stop, RangeKind::Rank)
+            .rev()
+            .into_frame();
+        self.array_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zrange_withscores(
+        &mut self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> crate::Result<Vec<(Bytes, f64)>> {
+        let frame = Zrange::new(key, start, stop, RangeKind::Rank)
+            .withscores()
+            .into_frame();
+        self.scored_array_cmd(frame).await
+    }
+
+    /// Members with scores between `min` and `max`, which accept the Redis
+    /// syntax such as `(1.5` or `-inf`.
+    #[instrument(skip(self))]
+    pub async fn zrangebyscore(
+        &mut self,
+        key: &str,
+        min: &str,
+        max: &str,
+        limit: Option<(i64, i64)>,
+    ) -> crate::Result<Vec<(Bytes, f64)>> {
+        let mut zrange = Zrange::new(key, min, max, RangeKind::Score).withscores();
+        if let Some((offset, count)) = limit {
+            zrange = zrange.limit(offset, count);
+        }
+        self.scored_array_cmd(zrange.into_frame()).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zrangebylex(
+        &mut self,
+        key: &str,
+        min: &str,
+        max: &str,
+    ) -> crate::Result<Vec<Bytes>> {
+        let frame = Zrange::new(key, min, max, RangeKind::Lex).into_frame();
+        self.array_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zrank(&mut self, key: &str, member: Bytes) -> crate::Result<Option<u64>> {
+        let frame = Zrank::new(key, member).into_frame();
+        match self.request(frame).await? {
+            Frame::Integer(rank) if rank >= 0 => Ok(Some(rank as u64)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zscore(&mut self, key: &str, member: Bytes) -> crate::Result<Option<f64>> {
+        let frame = Zscore::new(key, member).into_frame();
+        self.score_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zincrby(&mut self, key: &str, delta: f64, member: Bytes) -> crate::Result<f64> {
+        let frame = Zincrby::new(key, delta, member).into_frame();
+        match self.score_cmd(frame).await? {
+            Some(score) => Ok(score),
+            None => Err("unexpected nil reply to `ZINCRBY`".into()),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zrem(&mut self, key: &str, members: Vec<Bytes>) -> crate::Result<u64> {
+        let frame = Zrem::new(key, members).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn zpopmin(&mut self, key: &str, count: u64) -> crate::Result<Vec<(Bytes, f64)>> {
+        let frame = Zpopmin::new(key, Some(count)).into_frame();
+        self.scored_array_cmd(frame).await
+    }
+
+    /// Appends a record to the stream at `key` and returns its ID. Pass `*`
+    /// as `id` to let the server generate one.
+    #[instrument(skip(self, fields))]
+    pub async fn xadd(
+        &mut self,
+        key: &str,
+        id: &str,
+        fields: Vec<(Bytes, Bytes)>,
+        maxlen: Option<u64>,
+    ) -> crate::Result<String> {
+        let frame = Xadd::new(key, id, fields, maxlen).into_frame();
+        match self.bulk_cmd(frame).await? {
+            Some(id) => string_from_bytes(id),
+            None => Err("unexpected nil reply to `XADD`".into()),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn xrange(
+        &mut self,
+        key: &str,
+        start: &str,
+        end: &str,
+        count: Option<u64>,
+    ) -> crate::Result<Vec<StreamEntry>> {
+        let frame = Xrange::new(key, start, end, count, false).into_frame();
+        entries_from_frame(self.request(frame).await?)
+    }
+
+    /// Like `xrange`, but returns the records from `end` down to `start`.
+    #[instrument(skip(self))]
+    pub async fn xrevrange(
+        &mut self,
+        key: &str,
+        end: &str,
+        start: &str,
+        count: Option<u64>,
+    ) -> crate::Result<Vec<StreamEntry>> {
+        let frame = Xrange::new(key, start, end, count, true).into_frame();
+        entries_from_frame(self.request(frame).await?)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn xlen(&mut self, key: &str) -> crate::Result<u64> {
+        let frame = Xlen::new(key).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn xtrim(&mut self, key: &str, maxlen: u64) -> crate::Result<u64> {
+        let frame = Xtrim::new(key, maxlen).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    /// Creates a consumer group starting after `id`, or after the current
+    /// last record when `id` is `$`.
+    #[instrument(skip(self))]
+    pub async fn xgroup_create(
+        &mut self,
+        key: &str,
+        group: &str,
+        id: &str,
+        mkstream: bool,
+    ) -> crate::Result<()> {
+        let frame = Xgroup::create(key, group, id, mkstream).into_frame();
+        self.ok_cmd(frame).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn xgroup_destroy(&mut self, key: &str, group: &str) -> crate::Result<bool> {
+        let frame = Xgroup::destroy(key, group).into_frame();
+        Ok(self.count_cmd(frame).await? == 1)
+    }
+
+    /// Reads from each `(key, id)` pair on behalf of `consumer`. An ID of `>`
+    /// reads new records, any other ID re-reads the consumer's pending ones.
+    /// Streams with nothing to deliver are left out of the result.
+    #[instrument(skip(self))]
+    pub async fn xreadgroup(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        count: Option<u64>,
+        streams: &[(&str, &str)],
+    ) -> crate::Result<Vec<(String, Vec<StreamEntry>)>> {
+        let streams = streams
+            .iter()
+            .map(|(key, id)| (key.to_string(), id.to_string()))
+            .collect();
+        let frame = Xreadgroup::new(group, consumer, count, streams).into_frame();
+        match self.request(frame).await? {
+            Frame::Array(streams) => streams
+                .into_iter()
+                .map(|stream| match stream {
+                    Frame::Array(stream) => match <[Frame; 2]>::try_from(stream) {
+                        Ok([Frame::Bulk(key), entries]) => {
+                            Ok((string_from_bytes(key)?, entries_from_frame(entries)?))
+                        }
+                        _ => Err("malformed `XREADGROUP` reply".into()),
+                    },
+                    frame => Err(frame.to_error()),
+                })
+                .collect(),
+            Frame::Null => Ok(vec![]),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn xack(&mut self, key: &str, group: &str, ids: &[&str]) -> crate::Result<u64> {
+        let ids = ids.iter().map(|id| id.to_string()).collect();
+        let frame = Xack::new(key, group, ids).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    /// Returns the number of records pending in `group`.
+    #[instrument(skip(self))]
+    pub async fn xpending(&mut self, key: &str, group: &str) -> crate::Result<u64> {
+        let frame = Xpending::new(key, group).into_frame();
+        match self.request(frame).await? {
+            Frame::Array(summary) => match summary.first() {
+                Some(Frame::Integer(count)) if *count >= 0 => Ok(*count as u64),
+                _ => Err("malformed `XPENDING` reply".into()),
+            },
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn xpending_range(
+        &mut self,
+        key: &str,
+        group: &str,
+        start: &str,
+        end: &str,
+        count: u64,
+    ) -> crate::Result<Vec<PendingEntry>> {
+        let frame = Xpending::range(key, group, start, end, count).into_frame();
+        match self.request(frame).await? {
+            Frame::Array(entries) => entries
+                .into_iter()
+                .map(|entry| match entry {
+                    Frame::Array(entry) => match <[Frame; 4]>::try_from(entry) {
+                        Ok([
+                            Frame::Bulk(id),
+                            Frame::Bulk(consumer),
+                            Frame::Integer(idle_ms),
+                            Frame::Integer(deliveries),
+                        ]) => Ok(PendingEntry {
+                            id: string_from_bytes(id)?,
+                            consumer: string_from_bytes(consumer)?,
+                            idle_ms: idle_ms as u64,
+                            deliveries: deliveries as u64,
+                        }),
+                        _ => Err("malformed `XPENDING` reply".into()),
+                    },
+                    frame => Err(frame.to_error()),
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Transfers the pending records `ids` idle for at least `min_idle_ms`
+    /// to `consumer`, returning the records that were claimed.
+    #[instrument(skip(self))]
+    pub async fn xclaim(
+        &mut self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle_ms: u64,
+        ids: &[&str],
+    ) -> crate::Result<Vec<StreamEntry>> {
+        let ids = ids.iter().map(|id| id.to_string()).collect();
+        let frame = Xclaim::new(key, group, consumer, min_idle_ms, ids).into_frame();
+        entries_from_frame(self.request(frame).await?)
+    }
+
+    /// Claims up to `count` pending records idle for at least `min_idle_ms`,
+    /// scanning from `start`. Returns the ID to continue the scan from, which
+    /// is `0-0` once the whole pending entries list was visited, and the
+    /// claimed records.
+    #[instrument(skip(self))]
+    pub async fn xautoclaim(
+        &mut self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle_ms: u64,
+        start: &str,
+        count: Option<u64>,
+    ) -> crate::Result<(String, Vec<StreamEntry>)> {
+        let frame = Xautoclaim::new(key, group, consumer, min_idle_ms, start, count).into_frame();
+        match self.request(frame).await? {
+            Frame::Array(reply) => match <[Frame; 3]>::try_from(reply) {
+                Ok([Frame::Bulk(next), entries, _]) => {
+                    Ok((string_from_bytes(next)?, entries_from_frame(entries)?))
+                }
+                _ => Err("malformed `XAUTOCLAIM` reply".into()),
+            },
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Runs the Lua `script` atomically, with `keys` as `KEYS` and `args` as
+    /// `ARGV`, returning its reply.
+    #[instrument(skip(self))]
+    pub async fn eval(
+        &mut self,
+        script: &str,
+        keys: &[&str],
+        args: Vec<Bytes>,
+    ) -> crate::Result<Frame> {
+        let frame = Eval::new(script.to_string().into(), owned_keys(keys), args, false);
+        self.request(frame.into_frame()).await
+    }
+
+    /// Like `eval`, but runs a script cached by `script_load` or an earlier
+    /// `eval`, given by its SHA1 digest.
+    #[instrument(skip(self))]
+    pub async fn evalsha(
+        &mut self,
+        sha: &str,
+        keys: &[&str],
+        args: Vec<Bytes>,
+    ) -> crate::Result<Frame> {
+        let frame = Eval::new(sha.to_string().into(), owned_keys(keys), args, true);
+        self.request(frame.into_frame()).await
+    }
+
+    /// Caches `script` without running it, returning its SHA1 digest.
+    #[instrument(skip(self))]
+    pub async fn script_load(&mut self, script: &str) -> crate::Result<String> {
+        let frame = Script::load(script.to_string().into()).into_frame();
+        match self.request(frame).await? {
+            Frame::Bulk(sha) => string_from_bytes(sha),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Reports which of the scripts named by `shas` are cached.
+    #[instrument(skip(self))]
+    pub async fn script_exists(&mut self, shas: &[&str]) -> crate::Result<Vec<bool>> {
+        let frame = Script::exists(owned_keys(shas)).into_frame();
+        match self.request(frame).await? {
+            Frame::Array(frames) => frames
+                .into_iter()
+                .map(|frame| match frame {
+                    Frame::Integer(exists) => Ok(exists == 1),
+                    frame => Err(frame.to_error()),
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn script_flush(&mut self) -> crate::Result<()> {
+        self.ok_cmd(Script::flush().into_frame()).await
+    }
+
+    /// Kills the script running on the server, which fails unless it did
+    /// not change keys yet.
+    #[instrument(skip(self))]
+    pub async fn script_kill(&mut self) -> crate::Result<()> {
+        self.ok_cmd(Script::kill().into_frame()).await
+    }
+
+    /// Switches the connection to the RESP `protover`, `2` or `3`,
+    /// returning what the server says about itself.
+    ///
+    /// With RESP3, replies such as the one of `HGETALL` are sent with the
+    /// native map, set and double types. The methods of `Client` handle both
+    /// versions, while `Frame`s returned as is, such as by `eval`, reflect
+    /// the version in use.
+    #[instrument(skip(self))]
+    pub async fn hello(&mut self, protover: u8) -> crate::Result<HashMap<String, Frame>> {
+        let frame = Hello::new(Some(protover)).into_frame();
+        let pairs = pairs_from_frame(self.request(frame).await?)?;
+        self.connection.set_protocol(protover);
+
+        pairs
+            .into_iter()
+            .map(|(field, value)| match field {
+                Frame::Bulk(field) => Ok((string_from_bytes(field)?, value)),
+                field => Err(field.to_error()),
+            })
+            .collect()
+    }
+
+    /// Starts building a transaction, sent to the server by
+    /// `Transaction::exec`.
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        Transaction {
+            client: self,
+            commands: vec![],
+        }
+    }
+
+    /// Watches `keys`, making the next transaction fail if any of them is
+    /// modified before it runs.
+    #[instrument(skip(self))]
+    pub async fn watch(&mut self, keys: &[&str]) -> crate::Result<()> {
+        self.ok_cmd(Watch::new(owned_keys(keys)).into_frame()).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn unwatch(&mut self) -> crate::Result<()> {
+        self.ok_cmd(Unwatch::new().into_frame()).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn subscribe(mut self, channels: Vec<String>) -> crate::Result<Subscriber> {
+        let frame = Subscribe::new(channels.clone()).into_frame();
+        self.subscribe_cmd(frame, "subscribe", &channels).await?;
+        Ok(Subscriber {
+            client: self,
+            subscribed_channels: channels,
+            subscribed_patterns: vec![],
+            dropped_messages: 0,
+        })
+    }
+
+    /// Subscribes to the channels matching the glob `patterns`, like
+    /// `orders.*`.
+    #[instrument(skip(self))]
+    pub async fn psubscribe(mut self, patterns: Vec<String>) -> crate::Result<Subscriber> {
+        let frame = Psubscribe::new(patterns.clone()).into_frame();
+        self.subscribe_cmd(frame, "psubscribe", &patterns).await?;
+        Ok(Subscriber {
+            client: self,
+            subscribed_channels: vec![],
+            subscribed_patterns: patterns,
+            dropped_messages: 0,
+        })
+    }
+
+    /// Sends a `kind` of subscription command, which is confirmed once for
+    /// each of `names`.
+    async fn subscribe_cmd(
+        &mut self,
+        frame: Frame,
+        kind: &str,
+        names: &[String],
+    ) -> crate::Result<()> {
+        debug!(request = ?frame);
+
+        self.connection.write_frame(&frame).await?;
+        for name in names {
+            let response = self.read_response().await?;
+            match response {
+                Frame::Array(ref frame) | Frame::Push(ref frame) => match frame.as_slice() {
+                    [subscribe, sname, ..] if *subscribe == kind && *sname == name.as_str() => {}
+                    _ => return Err(response.to_error()),
+                },
+                frame => return Err(frame.to_error()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a `kind` of unsubscription command, removing the confirmed
+    /// names from `subscribed`. All of them are unsubscribed from when
+    /// `names` is empty.
+    async fn unsubscribe_cmd(
+        &mut self,
+        frame: Frame,
+        kind: &str,
+        names: &[String],
+        subscribed: &mut Vec<String>,
+    ) -> crate::Result<()> {
+        debug!(request = ?frame);
+        self.connection.write_frame(&frame).await?;
+
+        let num = if names.is_empty() {
+            subscribed.len()
+        } else {
+            names.len()
+        };
+        for _ in 0..num {
+            let response = self.read_response().await?;
+
+            match response {
+                Frame::Array(ref frame) | Frame::Push(ref frame) => match frame.as_slice() {
+                    [unsubscribe, name, ..] if *unsubscribe == kind => {
+                        let len = subscribed.len();
+
+                        if len == 0 {
+                            return Err(response.to_error());
+                        }
+
+                        subscribed.retain(|c| *name != &c[..]);
+                        if subscribed.len() != len - 1 {
+                            return Err(response.to_error());
+                        }
+                    }
+                    _ => return Err(response.to_error()),
+                },
+                frame => return Err(frame.to_error()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn bpop_cmd(
+        &mut self,
+        keys: &[&str],
+        timeout: Option<Duration>,
+        side: Side,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        let keys = owned_keys(keys);
+        let frame = Bpop::new(keys, timeout, side).into_frame();
+        match self.request(frame).await? {
+            Frame::Array(popped) => match <[Frame; 2]>::try_from(popped) {
+                Ok([Frame::Bulk(key), Frame::Bulk(value)]) => {
+                    Ok(Some((string_from_bytes(key)?, value)))
+                }
+                _ => Err("malformed blocking pop reply".into()),
+            },
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    async fn combine_cmd(&mut self, op: SetOp, keys: &[&str]) -> crate::Result<HashSet<Bytes>> {
+        let keys = owned_keys(keys);
+        let frame = Combine::new(op, None, keys).into_frame();
+        Ok(self.array_cmd(frame).await?.into_iter().collect())
+    }
+
+    async fn combine_store_cmd(
+        &mut self,
+        op: SetOp,
+        destination: &str,
+        keys: &[&str],
+    ) -> crate::Result<u64> {
+        let keys = owned_keys(keys);
+        let frame = Combine::new(op, Some(destination.to_string()), keys).into_frame();
+        self.count_cmd(frame).await
+    }
+
+    async fn set_cmd(&mut self, cmd: Set) -> crate::Result<()> {
+        let frame = cmd.into_frame();
+        debug!(request = ?frame);
+        self.connection.write_frame(&frame).await?;
+        match self.read_response().await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    async fn request(&mut self, frame: Frame) -> crate::Result<Frame> {
+        debug!(request = ?frame);
+        self.connection.write_frame(&frame).await?;
+        self.read_response().await
+    }
+
+    async fn ok_cmd(&mut self, frame: Frame) -> crate::Result<()> {
+        match self.request(frame).await? {
+            Frame::Simple(response) if response == "OK" => Ok(()),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    async fn expire_cmd(
+        &mut self,
+        key: &str,
+        expire: Expiry,
+        options: ExpireOptions,
+    ) -> crate::Result<bool> {
+        let frame = Expire::new(key, expire, options).into_frame();
+        Ok(self.count_cmd(frame).await? == 1)
+    }
+
+    async fn int_cmd(&mut self, frame: Frame) -> crate::Result<i64> {
+        match self.request(frame).await? {
+            Frame::Integer(response) => Ok(response),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    async fn count_cmd(&mut self, frame: Frame) -> crate::Result<u64> {
+        match self.request(frame).await? {
+            Frame::Integer(response) if response >= 0 => Ok(response as u64),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Sends a command replying with names paired with counts.
+    async fn counts_cmd(&mut self, frame: Frame) -> crate::Result<Vec<(String, u64)>> {
+        pairs_from_frame(self.request(frame).await?)?
+            .into_iter()
+            .map(|pair| match pair {
+                (Frame::Bulk(name), Frame::Integer(count)) if count >= 0 => {
+                    Ok((string_from_bytes(name)?, count as u64))
+                }
+                (_, frame) => Err(frame.to_error()),
+            })
+            .collect()
+    }
+
+    async fn bulk_cmd(&mut self, frame: Frame) -> crate::Result<Option<Bytes>> {
+        match self.request(frame).await? {
+            Frame::Simple(value) => Ok(Some(value.into())),
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Sends a command replying with text, such as `INFO`.
+    async fn text_cmd(&mut self, frame: Frame) -> crate::Result<String> {
+        match self.request(frame).await? {
+            Frame::Simple(text) => Ok(text),
+            Frame::Bulk(text) => string_from_bytes(text),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    async fn array_cmd(&mut self, frame: Frame) -> crate::Result<Vec<Bytes>> {
+        match self.request(frame).await? {
+            Frame::Array(values) | Frame::Set(values) => values
+                .into_iter()
+                .map(|value| match value {
+                    Frame::Simple(value) => Ok(value.into()),
+                    Frame::Bulk(value) => Ok(value),
+                    frame => Err(frame.to_error()),
+                })
+                .collect(),
+            Frame::Null => Ok(vec![]),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    async fn optional_array_cmd(&mut self, frame: Frame) -> crate::Result<Vec<Option<Bytes>>> {
+        match self.request(frame).await? {
+            Frame::Array(values) => values
+                .into_iter()
+                .map(|value| match value {
+                    Frame::Simple(value) => Ok(Some(value.into())),
+                    Frame::Bulk(value) => Ok(Some(value)),
+                    Frame::Null => Ok(None),
+                    frame => Err(frame.to_error()),
+                })
+                .collect(),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    /// Reads a flat `member score member score ...` array reply.
+    /// Reads a `[cursor, [element, ...]]` scan reply.
+    async fn scan_cmd(&mut self, frame: Frame) -> crate::Result<(u64, Vec<Bytes>)> {
+        let (cursor, elements) = match self.request(frame).await? {
+            Frame::Array(reply) if reply.len() == 2 => {
+                let mut reply = reply.into_iter();
+                (reply.next().unwrap(), reply.next().unwrap())
+            }
+            frame => return Err(frame.to_error()),
+        };
+
+        let cursor = match cursor {
+            Frame::Bulk(cursor) => std::str::from_utf8(&cursor)?.parse()?,
+            frame => return Err(frame.to_error()),
+        };
+        let elements = match elements {
+            Frame::Array(elements) => elements
+                .into_iter()
+                .map(|element| match element {
+                    Frame::Bulk(element) => Ok(element),
+                    frame => Err(frame.to_error()),
+                })
+                .collect::<crate::Result<_>>()?,
+            frame => return Err(frame.to_error()),
+        };
+        Ok((cursor, elements))
+    }
+
+    /// Members followed by their scores, sent as doubles with RESP3 and as
+    /// bulk strings before.
+    async fn scored_array_cmd(&mut self, frame: Frame) -> crate::Result<Vec<(Bytes, f64)>> {
+        let mut values = match self.request(frame).await? {
+            Frame::Array(values) => values.into_iter(),
+            frame => return Err(frame.to_error()),
+        };
+
+        let mut members = vec![];
+        while let (Some(member), Some(score)) = (values.next(), values.next()) {
+            let member = match member {
+                Frame::Bulk(member) => member,
+                frame => return Err(frame.to_error()),
+            };
+            let score = match score {
+                Frame::Double(score) => score,
+                Frame::Bulk(score) => score_from_bytes(&score)?,
+                frame => return Err(frame.to_error()),
+            };
+            members.push((member, score));
+        }
+        Ok(members)
+    }
+
+    /// A score, sent as a double with RESP3 and as a bulk string before.
+    async fn score_cmd(&mut self, frame: Frame) -> crate::Result<Option<f64>> {
+        match self.request(frame).await? {
+            Frame::Double(score) => Ok(Some(score)),
+            Frame::Bulk(score) => score_from_bytes(&score).map(Some),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    async fn read_response(&mut self) -> crate::Result<Frame> {
+        let response = self.connection.read_frame().await?;
+        debug!(?response);
+
+        match response {
+            Some(Frame::Error(msg)) => Err(msg.into()),
+            Some(frame) => Ok(frame),
+            None => {
+                let err = Error::new(ErrorKind::ConnectionReset, "connection reset by server");
+                Err(err.into())
+            }
+        }
+    }
+}
+
+fn score_from_bytes(score: &[u8]) -> crate::Result<f64> {
+    std::str::from_utf8(score)
+        .ok()
+        .and_then(parse_score)
+        .ok_or_else(|| format!("invalid score {:?}", score).into())
+}
+
+fn owned_keys(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(|key| key.to_string()).collect()
+}
+
+fn owned_pairs(pairs: &[(&str, Bytes)]) -> Vec<(String, Bytes)> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect()
+}
+
+/// The entries of a RESP3 map, or of a RESP2 array alternating keys and
+/// values.
+fn pairs_from_frame(frame: Frame) -> crate::Result<Vec<(Frame, Frame)>> {
+    match frame {
+        Frame::Map(pairs) => Ok(pairs),
+        Frame::Array(values) => {
+            let mut values = values.into_iter();
+            let mut pairs = vec![];
+            while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                pairs.push((key, value));
+            }
+            Ok(pairs)
+        }
+        frame => Err(frame.to_error()),
+    }
+}
+
+fn string_from_bytes(bytes: Bytes) -> crate::Result<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|err| err.into())
+}
+
+/// Reads the reply to `ROLE`.
+fn cluster_slots_from_frame(frame: Frame) -> crate::Result<ClusterSlots> {
+    let invalid = || -> crate::Error { "invalid `CLUSTER SLOTS` reply".into() };
+    let Frame::Array(fields) = frame else {
+        return Err(invalid());
+    };
+    let [Frame::Integer(start), Frame::Integer(end), Frame::Array(node), ..] = &fields[..] else {
+        return Err(invalid());
+    };
+    let [Frame::Bulk(host), Frame::Integer(port), Frame::Bulk(id), ..] = &node[..] else {
+        return Err(invalid());
+    };
+    Ok(ClusterSlots {
+        slots: *start as u16..=*end as u16,
+        host: string_from_bytes(host.clone())?,
+        port: *port as u16,
+        id: string_from_bytes(id.clone())?,
+    })
+}
+
+fn role_from_frame(frame: Frame) -> crate::Result<ReplicationRole> {
+    fn number<T: std::str::FromStr>(frame: &Frame) -> crate::Result<T> {
+        let parsed = match frame {
+            Frame::Integer(value) => value.to_string().parse().ok(),
+            Frame::Bulk(value) => std::str::from_utf8(value).ok().and_then(|v| v.parse().ok()),
+            _ => None,
+        };
+        parsed.ok_or_else(|| "invalid number in `ROLE` reply".into())
+    }
+
+    let invalid = || -> crate::Error { "invalid `ROLE` reply".into() };
+    let Frame::Array(fields) = frame else {
+        return Err(frame.to_error());
+    };
+    match &fields[..] {
+        [Frame::Bulk(role), offset, Frame::Array(replicas)] if &role[..] == b"master" => {
+            let replicas = replicas
+                .iter()
+                .map(|replica| match replica {
+                    Frame::Array(fields) => match &fields[..] {
+                        [Frame::Bulk(ip), port, offset] => Ok((
+                            string_from_bytes(ip.clone())?,
+                            number(port)?,
+                            number(offset)?,
+                        )),
+                        _ => Err(invalid()),
+                    },
+                    _ => Err(invalid()),
+                })
+                .collect::<crate::Result<_>>()?;
+            Ok(ReplicationRole::Master {
+                offset: number(offset)?,
+                replicas,
+            })
+        }
+        [Frame::Bulk(role), Frame::Bulk(host), port, Frame::Bulk(state), offset]
+            if &role[..] == b"slave" =>
+        {
+            Ok(ReplicationRole::Replica {
+                host: string_from_bytes(host.clone())?,
+                port: number(port)?,
+                state: string_from_bytes(state.clone())?,
+                offset: number(offset)?,
+            })
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Reads an array of `[id, [field, value, ...]]` stream records.
+fn entries_from_frame(frame: Frame) -> crate::Result<Vec<StreamEntry>> {
+    let entries = match frame {
+        Frame::Array(entries) => entries,
+        Frame::Null => return Ok(vec![]),
+        frame => return Err(frame.to_error()),
+    };
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let [id, fields] = match entry {
+                Frame::Array(entry) => {
+                    <[Frame; 2]>::try_from(entry).map_err(|_| "malformed stream record")?
+                }
+                frame => return Err(frame.to_error()),
+            };
+            let id = match id {
+                Frame::Bulk(id) => string_from_bytes(id)?,
+                frame => return Err(frame.to_error()),
+            };
+            let mut values = match fields {
+                Frame::Array(values) => values.into_iter(),
+                frame => return Err(frame.to_error()),
+            };
+
+            let mut fields = vec![];
+            while let (Some(Frame::Bulk(field)), Some(Frame::Bulk(value))) =
+                (values.next(), values.next())
+            {
+                fields.push((field, value));
+            }
+            Ok(StreamEntry { id, fields })
+        })
+        .collect()
+}
+
+impl Transaction<'_> {
+    pub fn get(self, key: &str) -> Self {
+        self.queue(Get::new(key).into_frame())
+    }
+
+    pub fn set(self, key: &str, value: Bytes) -> Self {
+        self.queue(Set::new(key, value, None).into_frame())
+    }
+
+    pub fn del(self, keys: &[&str]) -> Self {
+        self.queue(Del::new(owned_keys(keys), false).into_frame())
+    }
+
+    pub fn incr(self, key: &str) -> Self {
+        self.incrby(key, 1)
+    }
+
+    pub fn incrby(self, key: &str, delta: i64) -> Self {
+        self.queue(Incr::new(key, delta).into_frame())
+    }
+
+    pub fn expire(self, key: &str, seconds: u64) -> Self {
+        let frame = Expire::new(key, Expiry::Ex(seconds), ExpireOptions::new()).into_frame();
+        self.queue(frame)
+    }
+
+    pub fn lpush(self, key: &str, values: Vec<Bytes>) -> Self {
+        self.queue(Push::new(key, values, Side::Left).into_frame())
+    }
+
+    pub fn rpush(self, key: &str, values: Vec<Bytes>) -> Self {
+        self.queue(Push::new(key, values, Side::Right).into_frame())
+    }
+
+    pub fn hset(self, key: &str, fields: Vec<(String, Bytes)>) -> Self {
+        self.queue(Hset::new(key, fields).into_frame())
+    }
+
+    pub fn sadd(self, key: &str, members: Vec<Bytes>) -> Self {
+        self.queue(Sadd::new(key, members).into_frame())
+    }
+
+    /// Queues a command the builder has no method for.
+    pub fn command(self, frame: Frame) -> Self {
+        self.queue(frame)
+    }
+
+    /// Runs the queued commands, returning their replies in order, or
+    /// `None` if a watched key was modified and nothing ran.
+    ///
+    /// A command the server refuses to queue discards the whole transaction
+    /// and its error is returned.
+    #[instrument(skip(self))]
+    pub async fn exec(self) -> crate::Result<Option<Vec<Frame>>> {
+        let client = self.client;
+        client.ok_cmd(Multi::new().into_frame()).await?;
+
+        for frame in self.commands {
+            match client.request(frame).await {
+                Ok(Frame::Simple(response)) if response == "QUEUED" => {}
+                Ok(frame) => {
+                    client.ok_cmd(Discard::new().into_frame()).await?;
+                    return Err(frame.to_error());
+                }
+                Err(err) => {
+                    client.ok_cmd(Discard::new().into_frame()).await?;
+                    return Err(err);
+                }
+            }
+        }
+
+        match client.request(Exec::new().into_frame()).await? {
+            Frame::Array(replies) => Ok(Some(replies)),
+            Frame::Null => Ok(None),
+            frame => Err(frame.to_error()),
+        }
+    }
+
+    fn queue(mut self, frame: Frame) -> Self {
+        self.commands.push(frame);
+        self
+    }
+}
+
+impl Subscriber {
+    pub fn get_subscribed(&self) -> &[String] {
+        &self.subscribed_channels
+    }
+
+    pub fn get_subscribed_patterns(&self) -> &[String] {
+        &self.subscribed_patterns
+    }
+
+    /// The number of messages the server dropped because this subscriber
+    /// did not keep up, as notified so far.
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped_messages
+    }
+
+    /// Waits for the next message. Notifications of dropped messages are
+    /// counted by `dropped_messages` rather than returned.
+    pub async fn next_message(&mut self) -> crate::Result<Option<Message>> {
+        while let Some(mframe) = self.client.connection.read_frame().await? {
+            debug!(?mframe);
+            match mframe {
+                Frame::Array(ref frame) | Frame::Push(ref frame) => match frame.as_slice() {
+                    [message, channel, content] if *message == "message" => {
+                        return Ok(Some(Message {
+                            channel: channel.to_string(),
+                            content: Bytes::from(content.to_string()),
+                            pattern: None,
+                        }))
+                    }
+                    [message, pattern, channel, content] if *message == "pmessage" => {
+                        return Ok(Some(Message {
+                            channel: channel.to_string(),
+                            content: Bytes::from(content.to_string()),
+                            pattern: Some(pattern.to_string()),
+                        }))
+                    }
+                    [lagged, _, Frame::Integer(dropped)]
+                        if *lagged == "lagged" || *lagged == "plagged" =>
+                    {
+                        self.dropped_messages += *dropped as u64;
+                    }
+                    _ => return Err(mframe.to_error()),
+                },
+                frame => return Err(frame.to_error()),
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn into_stream(mut self) -> impl Stream<Item = crate::Result<Message>> {
+        try_stream! {
+            while let Some(message) = self.next_message().await? {
+                yield message;
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn subscribe(&mut self, channels: &[String]) -> crate::Result<()> {
+        let frame = Subscribe::new(channels.to_vec()).into_frame();
+        self.client
+            .subscribe_cmd(frame, "subscribe", channels)
+            .await?;
+        self.subscribed_channels
+            .extend(channels.iter().map(Clone::clone));
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn unsubscribe(&mut self, channels: &[String]) -> crate::Result<()> {
+        let frame = Unsubscribe::new(channels).into_frame();
+        self.client
+            .unsubscribe_cmd(
+                frame,
+                "unsubscribe",
+                channels,
+                &mut self.subscribed_channels,
+            )
+            .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn psubscribe(&mut self, patterns: &[String]) -> crate::Result<()> {
+        let frame = Psubscribe::new(patterns.to_vec()).into_frame();
+        self.client
+            .subscribe_cmd(frame, "psubscribe", patterns)
+            .await?;
+        self.subscribed_patterns
+            .extend(patterns.iter().map(Clone::clone));
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn punsubscribe(&mut self, patterns: &[String]) -> crate::Result<()> {
+        let frame = Punsubscribe::new(patterns).into_frame();
+        self.client
+            .unsubscribe_cmd(
+                frame,
+                "punsubscribe",
+                patterns,
+                &mut self.subscribed_patterns,
+            )
+            .await
     }
 }
@@ -6,26 +6,310 @@
  * @Last Modified time: 2023-10-20 17:58:35
  */
 
-use crate::{db::Db, parse::Parse, shutdown::Shutdown, Connection, Frame};
+use crate::{
+    db::{Databases, Expiry, SetOp, Side},
+    parse::{Parse, ParseError},
+    shutdown::Shutdown,
+    Connection, Frame,
+};
+use tokio::{select, time::Instant};
+use tracing::debug;
+
+/// The error replicas reply to the commands that change keys.
+pub(crate) const READONLY: &str = "READONLY You can't write against a read only replica.";
+
+mod append;
+pub use append::Append;
+
+mod asking;
+pub use asking::Asking;
+
+mod bgrewriteaof;
+pub use bgrewriteaof::Bgrewriteaof;
+
+mod blmove;
+pub use blmove::Blmove;
+
+mod bpop;
+pub use bpop::Bpop;
+
+mod cluster;
+pub use cluster::Cluster;
+
+mod combine;
+pub use combine::Combine;
+
+mod copy;
+pub use copy::Copy;
+
+mod dbsize;
+pub use dbsize::Dbsize;
+
+mod del;
+pub use del::Del;
+
+mod discard;
+pub use discard::Discard;
+
+mod eval;
+pub use eval::Eval;
+
+mod exec;
+pub use exec::Exec;
+
+mod exists;
+pub use exists::Exists;
+
+mod expire;
+pub use expire::Expire;
+
+mod flush;
+pub use flush::Flush;
 
 mod get;
 pub use get::Get;
 
+mod getdel;
+pub use getdel::Getdel;
+
+mod getex;
+pub use getex::Getex;
+
+mod getrange;
+pub use getrange::Getrange;
+
+mod hdel;
+pub use hdel::Hdel;
+
+mod hello;
+pub use hello::Hello;
+
+mod hexists;
+pub use hexists::Hexists;
+
+mod hget;
+pub use hget::Hget;
+
+mod hgetall;
+pub use hgetall::Hgetall;
+
+mod hincrby;
+pub use hincrby::Hincrby;
+
+mod hkeys;
+pub use hkeys::Hkeys;
+
+mod hlen;
+pub use hlen::Hlen;
+
+mod hmget;
+pub use hmget::Hmget;
+
+mod hset;
+pub use hset::Hset;
+
+mod hvals;
+pub use hvals::Hvals;
+
+mod incr;
+pub use incr::Incr;
+
+mod incrbyfloat;
+pub use incrbyfloat::Incrbyfloat;
+
+mod info;
+pub use info::Info;
+
+mod key_type;
+pub use key_type::Type;
+
+mod keys;
+pub use keys::Keys;
+
+mod lastsave;
+pub use lastsave::Lastsave;
+
+mod lindex;
+pub use lindex::Lindex;
+
+mod llen;
+pub use llen::Llen;
+
+mod lrange;
+pub use lrange::Lrange;
+
+mod lrem;
+pub use lrem::Lrem;
+
+mod lset;
+pub use lset::Lset;
+
+mod ltrim;
+pub use ltrim::Ltrim;
+
+mod mget;
+pub use mget::Mget;
+
+mod move_key;
+pub use move_key::Move;
+
+mod mset;
+pub use mset::Mset;
+
+mod multi;
+pub use multi::Multi;
+pub(crate) use multi::MultiState;
+
+mod persist;
+pub use persist::Persist;
+
 mod ping;
 pub use ping::Ping;
 
+mod pop;
+pub use pop::Pop;
+
+mod psync;
+pub use psync::Psync;
+
 mod publish;
 pub use publish::Publish;
 
+mod pubsub;
+pub use pubsub::Pubsub;
+
+mod push;
+pub use push::Push;
+
+mod rename;
+pub use rename::Rename;
+
+mod replconf;
+pub use replconf::Replconf;
+
+mod replicaof;
+pub use replicaof::Replicaof;
+
+mod role;
+pub use role::Role;
+
+mod sadd;
+pub use sadd::Sadd;
+
+mod save;
+pub use save::Save;
+
+mod scan;
+pub use scan::Scan;
+pub(crate) use scan::ScanKind;
+
+mod scard;
+pub use scard::Scard;
+
+mod script;
+pub use script::Script;
+
+mod select;
+pub use select::Select;
+
 mod set;
 pub use set::Set;
 
+mod setex;
+pub use setex::Setex;
+
+mod setnx;
+pub use setnx::Setnx;
+
+mod setrange;
+pub use setrange::Setrange;
+
+mod sismember;
+pub use sismember::Sismember;
+
+mod smembers;
+pub use smembers::Smembers;
+
+mod srem;
+pub use srem::Srem;
+
+mod strlen;
+pub use strlen::Strlen;
+
 mod subscribe;
-pub use subscribe::{Subscribe, Unsubscribe};
+pub use subscribe::{Psubscribe, Punsubscribe, Subscribe, Unsubscribe};
+
+mod swapdb;
+pub use swapdb::Swapdb;
+
+mod ttl;
+pub use ttl::Ttl;
+pub(crate) use ttl::TtlFormat;
 
 mod unknown;
 pub use unknown::Unknown;
 
+mod unwatch;
+pub use unwatch::Unwatch;
+
+mod wait;
+pub use wait::Wait;
+
+mod watch;
+pub use watch::Watch;
+
+mod xack;
+pub use xack::Xack;
+
+mod xadd;
+pub use xadd::Xadd;
+
+mod xautoclaim;
+pub use xautoclaim::Xautoclaim;
+
+mod xclaim;
+pub use xclaim::Xclaim;
+
+mod xgroup;
+pub use xgroup::Xgroup;
+
+mod xlen;
+pub use xlen::Xlen;
+
+mod xpending;
+pub use xpending::Xpending;
+
+mod xrange;
+pub use xrange::Xrange;
+
+mod xreadgroup;
+pub use xreadgroup::Xreadgroup;
+
+mod xtrim;
+pub use xtrim::Xtrim;
+
+mod zadd;
+pub use zadd::Zadd;
+
+mod zincrby;
+pub use zincrby::Zincrby;
+
+mod zpopmin;
+pub use zpopmin::Zpopmin;
+
+mod zrange;
+pub(crate) use zrange::RangeKind;
+pub use zrange::Zrange;
+
+mod zrank;
+pub use zrank::Zrank;
+
+mod zrem;
+pub use zrem::Zrem;
+
+mod zscore;
+pub use zscore::Zscore;
+
 #[derive(Debug)]
 pub enum Command {
     Get(Get),
@@ -34,6 +318,128 @@ pub enum Command {
     Subscribe(Subscribe),
     Unsubscribe(Unsubscribe),
     Ping(Ping),
+    Lpush(Push),
+    Rpush(Push),
+    Lpop(Pop),
+    Rpop(Pop),
+    Lrange(Lrange),
+    Llen(Llen),
+    Lindex(Lindex),
+    Lset(Lset),
+    Ltrim(Ltrim),
+    Lrem(Lrem),
+    Hset(Hset),
+    Hget(Hget),
+    Hmget(Hmget),
+    Hdel(Hdel),
+    Hgetall(Hgetall),
+    Hkeys(Hkeys),
+    Hvals(Hvals),
+    Hlen(Hlen),
+    Hexists(Hexists),
+    Hincrby(Hincrby),
+    Sadd(Sadd),
+    Srem(Srem),
+    Smembers(Smembers),
+    Sismember(Sismember),
+    Scard(Scard),
+    Sinter(Combine),
+    Sunion(Combine),
+    Sdiff(Combine),
+    Sinterstore(Combine),
+    Sunionstore(Combine),
+    Sdiffstore(Combine),
+    Zadd(Zadd),
+    Zrange(Zrange),
+    Zrangebyscore(Zrange),
+    Zrank(Zrank),
+    Zscore(Zscore),
+    Zincrby(Zincrby),
+    Zrem(Zrem),
+    Zpopmin(Zpopmin),
+    Xadd(Xadd),
+    Xrange(Xrange),
+    Xrevrange(Xrange),
+    Xlen(Xlen),
+    Xtrim(Xtrim),
+    Xgroup(Xgroup),
+    Xreadgroup(Xreadgroup),
+    Xack(Xack),
+    Xpending(Xpending),
+    Xclaim(Xclaim),
+    Xautoclaim(Xautoclaim),
+    Blpop(Bpop),
+    Brpop(Bpop),
+    Blmove(Blmove),
+    Incr(Incr),
+    Decr(Incr),
+    Incrby(Incr),
+    Decrby(Incr),
+    Incrbyfloat(Incrbyfloat),
+    Append(Append),
+    Strlen(Strlen),
+    Getrange(Getrange),
+    Setrange(Setrange),
+    Mget(Mget),
+    Mset(Mset),
+    Msetnx(Mset),
+    Getdel(Getdel),
+    Getex(Getex),
+    Setnx(Setnx),
+    Setex(Setex),
+    Del(Del),
+    Unlink(Del),
+    Exists(Exists),
+    Touch(Exists),
+    Type(Type),
+    Rename(Rename),
+    Renamenx(Rename),
+    Copy(Copy),
+    Expire(Expire),
+    Pexpire(Expire),
+    Expireat(Expire),
+    Pexpireat(Expire),
+    Ttl(Ttl),
+    Pttl(Ttl),
+    Expiretime(Ttl),
+    Pexpiretime(Ttl),
+    Persist(Persist),
+    Keys(Keys),
+    Scan(Scan),
+    Hscan(Scan),
+    Sscan(Scan),
+    Zscan(Scan),
+    Select(Select),
+    Swapdb(Swapdb),
+    Move(Move),
+    Flushdb(Flush),
+    Flushall(Flush),
+    Dbsize(Dbsize),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Watch(Watch),
+    Unwatch(Unwatch),
+    Eval(Eval),
+    Evalsha(Eval),
+    Script(Script),
+    Hello(Hello),
+    Psubscribe(Psubscribe),
+    Punsubscribe(Punsubscribe),
+    Pubsub(Pubsub),
+    Save(Save),
+    Bgsave(Save),
+    Lastsave(Lastsave),
+    Bgrewriteaof(Bgrewriteaof),
+    Replicaof(Replicaof),
+    Slaveof(Replicaof),
+    Psync(Psync),
+    Replconf(Replconf),
+    Role(Role),
+    Info(Info),
+    Wait(Wait),
+    Asking(Asking),
+    Cluster(Cluster),
     Unknown(Unknown),
 }
 
@@ -42,15 +448,155 @@ impl Command {
         let mut parse = Parse::new(frame)?;
         let command_name = parse.next_string()?.to_lowercase();
 
-        let command = match &command_name[..] {
-            "get" => Command::Get(Get::parse_frames(&mut parse)?),
-            "publish" => Command::Publish(Publish::parse_frames(&mut parse)?),
-            "set" => Command::Set(Set::parse_frames(&mut parse)?),
-            "subscribe" => Command::Subscribe(Subscribe::parse_frames(&mut parse)?),
-            "unsubscribe" => Command::Unsubscribe(Unsubscribe::parse_frames(&mut parse)?),
-            "ping" => Command::Ping(Ping::parse_frames(&mut parse)?),
+        // Missing or extra arguments are reported like Redis does.
+        Command::parse_args(&command_name, &mut parse).map_err(|err| {
+            match err.downcast_ref::<ParseError>() {
+                Some(ParseError::EndOfStream | ParseError::Extra) => format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    command_name
+                )
+                .into(),
+                _ => err,
+            }
+        })
+    }
+
+    fn parse_args(command_name: &str, parse: &mut Parse) -> crate::Result<Command> {
+        let command = match command_name {
+            "get" => Command::Get(Get::parse_frames(parse)?),
+            "publish" => Command::Publish(Publish::parse_frames(parse)?),
+            "set" => Command::Set(Set::parse_frames(parse)?),
+            "subscribe" => Command::Subscribe(Subscribe::parse_frames(parse)?),
+            "unsubscribe" => Command::Unsubscribe(Unsubscribe::parse_frames(parse)?),
+            "ping" => Command::Ping(Ping::parse_frames(parse)?),
+            "lpush" => Command::Lpush(Push::parse_frames(parse, Side::Left)?),
+            "rpush" => Command::Rpush(Push::parse_frames(parse, Side::Right)?),
+            "lpop" => Command::Lpop(Pop::parse_frames(parse, Side::Left)?),
+            "rpop" => Command::Rpop(Pop::parse_frames(parse, Side::Right)?),
+            "lrange" => Command::Lrange(Lrange::parse_frames(parse)?),
+            "llen" => Command::Llen(Llen::parse_frames(parse)?),
+            "lindex" => Command::Lindex(Lindex::parse_frames(parse)?),
+            "lset" => Command::Lset(Lset::parse_frames(parse)?),
+            "ltrim" => Command::Ltrim(Ltrim::parse_frames(parse)?),
+            "lrem" => Command::Lrem(Lrem::parse_frames(parse)?),
+            "hset" => Command::Hset(Hset::parse_frames(parse)?),
+            "hget" => Command::Hget(Hget::parse_frames(parse)?),
+            "hmget" => Command::Hmget(Hmget::parse_frames(parse)?),
+            "hdel" => Command::Hdel(Hdel::parse_frames(parse)?),
+            "hgetall" => Command::Hgetall(Hgetall::parse_frames(parse)?),
+            "hkeys" => Command::Hkeys(Hkeys::parse_frames(parse)?),
+            "hvals" => Command::Hvals(Hvals::parse_frames(parse)?),
+            "hlen" => Command::Hlen(Hlen::parse_frames(parse)?),
+            "hexists" => Command::Hexists(Hexists::parse_frames(parse)?),
+            "hincrby" => Command::Hincrby(Hincrby::parse_frames(parse)?),
+            "sadd" => Command::Sadd(Sadd::parse_frames(parse)?),
+            "srem" => Command::Srem(Srem::parse_frames(parse)?),
+            "smembers" => Command::Smembers(Smembers::parse_frames(parse)?),
+            "sismember" => Command::Sismember(Sismember::parse_frames(parse)?),
+            "scard" => Command::Scard(Scard::parse_frames(parse)?),
+            "sinter" => Command::Sinter(Combine::parse_frames(parse, SetOp::Inter, false)?),
+            "sunion" => Command::Sunion(Combine::parse_frames(parse, SetOp::Union, false)?),
+            "sdiff" => Command::Sdiff(Combine::parse_frames(parse, SetOp::Diff, false)?),
+            "sinterstore" => {
+                Command::Sinterstore(Combine::parse_frames(parse, SetOp::Inter, true)?)
+            }
+            "sunionstore" => {
+                Command::Sunionstore(Combine::parse_frames(parse, SetOp::Union, true)?)
+            }
+            "sdiffstore" => Command::Sdiffstore(Combine::parse_frames(parse, SetOp::Diff, true)?),
+            "zadd" => Command::Zadd(Zadd::parse_frames(parse)?),
+            "zrange" => Command::Zrange(Zrange::parse_frames(parse, false)?),
+            "zrangebyscore" => Command::Zrangebyscore(Zrange::parse_frames(parse, true)?),
+            "zrank" => Command::Zrank(Zrank::parse_frames(parse)?),
+            "zscore" => Command::Zscore(Zscore::parse_frames(parse)?),
+            "zincrby" => Command::Zincrby(Zincrby::parse_frames(parse)?),
+            "zrem" => Command::Zrem(Zrem::parse_frames(parse)?),
+            "zpopmin" => Command::Zpopmin(Zpopmin::parse_frames(parse)?),
+            "xadd" => Command::Xadd(Xadd::parse_frames(parse)?),
+            "xrange" => Command::Xrange(Xrange::parse_frames(parse, false)?),
+            "xrevrange" => Command::Xrevrange(Xrange::parse_frames(parse, true)?),
+            "xlen" => Command::Xlen(Xlen::parse_frames(parse)?),
+            "xtrim" => Command::Xtrim(Xtrim::parse_frames(parse)?),
+            "xgroup" => Command::Xgroup(Xgroup::parse_frames(parse)?),
+            "xreadgroup" => Command::Xreadgroup(Xreadgroup::parse_frames(parse)?),
+            "xack" => Command::Xack(Xack::parse_frames(parse)?),
+            "xpending" => Command::Xpending(Xpending::parse_frames(parse)?),
+            "xclaim" => Command::Xclaim(Xclaim::parse_frames(parse)?),
+            "xautoclaim" => Command::Xautoclaim(Xautoclaim::parse_frames(parse)?),
+            "blpop" => Command::Blpop(Bpop::parse_frames(parse, Side::Left)?),
+            "brpop" => Command::Brpop(Bpop::parse_frames(parse, Side::Right)?),
+            "blmove" => Command::Blmove(Blmove::parse_frames(parse)?),
+            "incr" => Command::Incr(Incr::parse_frames(parse, false, false)?),
+            "decr" => Command::Decr(Incr::parse_frames(parse, false, true)?),
+            "incrby" => Command::Incrby(Incr::parse_frames(parse, true, false)?),
+            "decrby" => Command::Decrby(Incr::parse_frames(parse, true, true)?),
+            "incrbyfloat" => Command::Incrbyfloat(Incrbyfloat::parse_frames(parse)?),
+            "append" => Command::Append(Append::parse_frames(parse)?),
+            "strlen" => Command::Strlen(Strlen::parse_frames(parse)?),
+            "getrange" => Command::Getrange(Getrange::parse_frames(parse)?),
+            "setrange" => Command::Setrange(Setrange::parse_frames(parse)?),
+            "mget" => Command::Mget(Mget::parse_frames(parse)?),
+            "mset" => Command::Mset(Mset::parse_frames(parse, false)?),
+            "msetnx" => Command::Msetnx(Mset::parse_frames(parse, true)?),
+            "getdel" => Command::Getdel(Getdel::parse_frames(parse)?),
+            "getex" => Command::Getex(Getex::parse_frames(parse)?),
+            "setnx" => Command::Setnx(Setnx::parse_frames(parse)?),
+            "setex" => Command::Setex(Setex::parse_frames(parse)?),
+            "del" => Command::Del(Del::parse_frames(parse, false)?),
+            "unlink" => Command::Unlink(Del::parse_frames(parse, true)?),
+            "exists" => Command::Exists(Exists::parse_frames(parse, false)?),
+            "touch" => Command::Touch(Exists::parse_frames(parse, true)?),
+            "type" => Command::Type(Type::parse_frames(parse)?),
+            "rename" => Command::Rename(Rename::parse_frames(parse, false)?),
+            "renamenx" => Command::Renamenx(Rename::parse_frames(parse, true)?),
+            "copy" => Command::Copy(Copy::parse_frames(parse)?),
+            "expire" => Command::Expire(Expire::parse_frames(parse, Expiry::Ex)?),
+            "pexpire" => Command::Pexpire(Expire::parse_frames(parse, Expiry::Px)?),
+            "expireat" => Command::Expireat(Expire::parse_frames(parse, Expiry::ExAt)?),
+            "pexpireat" => Command::Pexpireat(Expire::parse_frames(parse, Expiry::PxAt)?),
+            "ttl" => Command::Ttl(Ttl::parse_frames(parse, TtlFormat::Seconds)?),
+            "pttl" => Command::Pttl(Ttl::parse_frames(parse, TtlFormat::Millis)?),
+            "expiretime" => Command::Expiretime(Ttl::parse_frames(parse, TtlFormat::UnixSeconds)?),
+            "pexpiretime" => Command::Pexpiretime(Ttl::parse_frames(parse, TtlFormat::UnixMillis)?),
+            "persist" => Command::Persist(Persist::parse_frames(parse)?),
+            "keys" => Command::Keys(Keys::parse_frames(parse)?),
+            "scan" => Command::Scan(Scan::parse_frames(parse, ScanKind::Keys)?),
+            "hscan" => Command::Hscan(Scan::parse_frames(parse, ScanKind::Hash)?),
+            "sscan" => Command::Sscan(Scan::parse_frames(parse, ScanKind::Set)?),
+            "zscan" => Command::Zscan(Scan::parse_frames(parse, ScanKind::ZSet)?),
+            "select" => Command::Select(Select::parse_frames(parse)?),
+            "swapdb" => Command::Swapdb(Swapdb::parse_frames(parse)?),
+            "move" => Command::Move(Move::parse_frames(parse)?),
+            "flushdb" => Command::Flushdb(Flush::parse_frames(parse, false)?),
+            "flushall" => Command::Flushall(Flush::parse_frames(parse, true)?),
+            "dbsize" => Command::Dbsize(Dbsize::parse_frames(parse)?),
+            "multi" => Command::Multi(Multi::parse_frames(parse)?),
+            "exec" => Command::Exec(Exec::parse_frames(parse)?),
+            "discard" => Command::Discard(Discard::parse_frames(parse)?),
+            "watch" => Command::Watch(Watch::parse_frames(parse)?),
+            "unwatch" => Command::Unwatch(Unwatch::parse_frames(parse)?),
+            "eval" => Command::Eval(Eval::parse_frames(parse, false)?),
+            "evalsha" => Command::Evalsha(Eval::parse_frames(parse, true)?),
+            "script" => Command::Script(Script::parse_frames(parse)?),
+            "hello" => Command::Hello(Hello::parse_frames(parse)?),
+            "psubscribe" => Command::Psubscribe(Psubscribe::parse_frames(parse)?),
+            "punsubscribe" => Command::Punsubscribe(Punsubscribe::parse_frames(parse)?),
+            "pubsub" => Command::Pubsub(Pubsub::parse_frames(parse)?),
+            "save" => Command::Save(Save::parse_frames(parse, false)?),
+            "bgsave" => Command::Bgsave(Save::parse_frames(parse, true)?),
+            "lastsave" => Command::Lastsave(Lastsave::parse_frames(parse)?),
+            "bgrewriteaof" => Command::Bgrewriteaof(Bgrewriteaof::parse_frames(parse)?),
+            "replicaof" => Command::Replicaof(Replicaof::parse_frames(parse)?),
+            "slaveof" => Command::Slaveof(Replicaof::parse_frames(parse)?),
+            "psync" => Command::Psync(Psync::parse_frames(parse)?),
+            "replconf" => Command::Replconf(Replconf::parse_frames(parse)?),
+            "role" => Command::Role(Role::parse_frames(parse)?),
+            "info" => Command::Info(Info::parse_frames(parse)?),
+            "wait" => Command::Wait(Wait::parse_frames(parse)?),
+            "asking" => Command::Asking(Asking::parse_frames(parse)?),
+            "cluster" => Command::Cluster(Cluster::parse_frames(parse)?),
             _ => {
-                return Ok(Command::Unknown(Unknown::new(command_name)));
+                return Ok(Command::Unknown(Unknown::new(command_name.to_string())));
             }
         };
 
@@ -59,22 +605,579 @@ impl Command {
         Ok(command)
     }
 
+    /// The error reply to a frame `from_frame` failed to parse, which is
+    /// refused without closing the connection, like Redis does. Errors
+    /// without a code, like the protocol errors of `Parse`, get the generic
+    /// `ERR` one.
+    pub(crate) fn parse_error_reply(err: crate::Error) -> Frame {
+        let msg = err.to_string();
+        let coded = msg
+            .split(' ')
+            .next()
+            .is_some_and(|code| !code.is_empty() && code.bytes().all(|b| b.is_ascii_uppercase()));
+        if coded {
+            Frame::Error(msg)
+        } else {
+            Frame::Error(format!("ERR {}", msg))
+        }
+    }
+
+    /// Runs the command against the database `selected` by the connection,
+    /// which `SELECT` changes.
     pub(crate) async fn apply(
         self,
-        db: &Db,
+        databases: &Databases,
+        selected: &mut usize,
+        transaction: &mut MultiState,
         dst: &mut Connection,
         shutdown: &mut Shutdown,
     ) -> crate::Result<()> {
         use Command::*;
 
+        // Commands wait for a running script, which holds every database,
+        // except for `SCRIPT KILL` which stops it.
+        if !matches!(&self, Script(cmd) if cmd.is_kill()) {
+            if let Err(err) = databases.scripts().wait_for_script().await {
+                return transaction.refuse(Frame::Error(err.to_string()), dst).await;
+            }
+        }
+
+        // In cluster mode, keys are only served by the node owning their
+        // slot.
+        let asking = dst.take_asking();
+        if let Some(redirect) = databases.redirect(&self, *selected, asking) {
+            return transaction.refuse(redirect, dst).await;
+        }
+
+        // Replicas only change keys as their master tells them to.
+        if self.is_write() && databases.read_only() {
+            return transaction
+                .refuse(Frame::Error(READONLY.to_string()), dst)
+                .await;
+        }
+
+        if transaction.is_queuing() && !matches!(self, Multi(_) | Exec(_) | Discard(_) | Watch(_)) {
+            return transaction.queue(self, dst).await;
+        }
+
+        // Changes are propagated in the order they are made, so the commands
+        // making them run one at a time.
+        if self.is_write() {
+            return self
+                .apply_propagated(databases, selected, dst, shutdown)
+                .await;
+        }
+
+        let db = databases.db(*selected);
         match self {
             Get(cmd) => cmd.apply(db, dst).await,
-            Publish(cmd) => cmd.apply(db, dst).await,
+            Publish(cmd) => cmd.apply(databases.pub_sub(), dst).await,
             Set(cmd) => cmd.apply(db, dst).await,
-            Subscribe(cmd) => cmd.apply(db, dst, shutdown).await,
+            Subscribe(cmd) => cmd.apply(databases, dst, shutdown).await,
             Ping(cmd) => cmd.apply(dst).await,
+            Lpush(cmd) | Rpush(cmd) => cmd.apply(db, dst).await,
+            Lpop(cmd) | Rpop(cmd) => cmd.apply(db, dst).await,
+            Lrange(cmd) => cmd.apply(db, dst).await,
+            Llen(cmd) => cmd.apply(db, dst).await,
+            Lindex(cmd) => cmd.apply(db, dst).await,
+            Lset(cmd) => cmd.apply(db, dst).await,
+            Ltrim(cmd) => cmd.apply(db, dst).await,
+            Lrem(cmd) => cmd.apply(db, dst).await,
+            Hset(cmd) => cmd.apply(db, dst).await,
+            Hget(cmd) => cmd.apply(db, dst).await,
+            Hmget(cmd) => cmd.apply(db, dst).await,
+            Hdel(cmd) => cmd.apply(db, dst).await,
+            Hgetall(cmd) => cmd.apply(db, dst).await,
+            Hkeys(cmd) => cmd.apply(db, dst).await,
+            Hvals(cmd) => cmd.apply(db, dst).await,
+            Hlen(cmd) => cmd.apply(db, dst).await,
+            Hexists(cmd) => cmd.apply(db, dst).await,
+            Hincrby(cmd) => cmd.apply(db, dst).await,
+            Sadd(cmd) => cmd.apply(db, dst).await,
+            Srem(cmd) => cmd.apply(db, dst).await,
+            Smembers(cmd) => cmd.apply(db, dst).await,
+            Sismember(cmd) => cmd.apply(db, dst).await,
+            Scard(cmd) => cmd.apply(db, dst).await,
+            Sinter(cmd) | Sunion(cmd) | Sdiff(cmd) | Sinterstore(cmd) | Sunionstore(cmd)
+            | Sdiffstore(cmd) => cmd.apply(db, dst).await,
+            Zadd(cmd) => cmd.apply(db, dst).await,
+            Zrange(cmd) | Zrangebyscore(cmd) => cmd.apply(db, dst).await,
+            Zrank(cmd) => cmd.apply(db, dst).await,
+            Zscore(cmd) => cmd.apply(db, dst).await,
+            Zincrby(cmd) => cmd.apply(db, dst).await,
+            Zrem(cmd) => cmd.apply(db, dst).await,
+            Zpopmin(cmd) => cmd.apply(db, dst).await,
+            Xadd(cmd) => cmd.apply(db, dst).await,
+            Xrange(cmd) | Xrevrange(cmd) => cmd.apply(db, dst).await,
+            Xlen(cmd) => cmd.apply(db, dst).await,
+            Xtrim(cmd) => cmd.apply(db, dst).await,
+            Xgroup(cmd) => cmd.apply(db, dst).await,
+            Xreadgroup(cmd) => cmd.apply(db, dst).await,
+            Xack(cmd) => cmd.apply(db, dst).await,
+            Xpending(cmd) => cmd.apply(db, dst).await,
+            Xclaim(cmd) => cmd.apply(db, dst).await,
+            Xautoclaim(cmd) => cmd.apply(db, dst).await,
+            Blpop(cmd) | Brpop(cmd) => cmd.apply(db, dst, shutdown).await,
+            Blmove(cmd) => cmd.apply(db, dst, shutdown).await,
+            Incr(cmd) | Decr(cmd) | Incrby(cmd) | Decrby(cmd) => cmd.apply(db, dst).await,
+            Incrbyfloat(cmd) => cmd.apply(db, dst).await,
+            Append(cmd) => cmd.apply(db, dst).await,
+            Strlen(cmd) => cmd.apply(db, dst).await,
+            Getrange(cmd) => cmd.apply(db, dst).await,
+            Setrange(cmd) => cmd.apply(db, dst).await,
+            Mget(cmd) => cmd.apply(db, dst).await,
+            Mset(cmd) | Msetnx(cmd) => cmd.apply(db, dst).await,
+            Getdel(cmd) => cmd.apply(db, dst).await,
+            Getex(cmd) => cmd.apply(db, dst).await,
+            Setnx(cmd) => cmd.apply(db, dst).await,
+            Setex(cmd) => cmd.apply(db, dst).await,
+            Del(cmd) | Unlink(cmd) => cmd.apply(db, dst).await,
+            Exists(cmd) | Touch(cmd) => cmd.apply(db, dst).await,
+            Type(cmd) => cmd.apply(db, dst).await,
+            Rename(cmd) | Renamenx(cmd) => cmd.apply(db, dst).await,
+            Copy(cmd) => cmd.apply(db, dst).await,
+            Expire(cmd) | Pexpire(cmd) | Expireat(cmd) | Pexpireat(cmd) => cmd.apply(db, dst).await,
+            Ttl(cmd) | Pttl(cmd) | Expiretime(cmd) | Pexpiretime(cmd) => cmd.apply(db, dst).await,
+            Persist(cmd) => cmd.apply(db, dst).await,
+            Keys(cmd) => cmd.apply(db, dst).await,
+            Scan(cmd) | Hscan(cmd) | Sscan(cmd) | Zscan(cmd) => cmd.apply(db, dst).await,
+            Select(cmd) => cmd.apply(databases, selected, dst).await,
+            Swapdb(cmd) => cmd.apply(databases, dst).await,
+            Move(cmd) => cmd.apply(databases, *selected, dst).await,
+            Flushdb(cmd) | Flushall(cmd) => cmd.apply(databases, *selected, dst).await,
+            Dbsize(cmd) => cmd.apply(db, dst).await,
+            Multi(cmd) => cmd.apply(transaction, dst).await,
+            Exec(cmd) => cmd.apply(databases, selected, transaction, dst).await,
+            Discard(cmd) => cmd.apply(transaction, dst).await,
+            Watch(cmd) => cmd.apply(databases, *selected, transaction, dst).await,
+            Unwatch(cmd) => cmd.apply(transaction, dst).await,
+            Eval(cmd) | Evalsha(cmd) => cmd.apply(databases, *selected, dst).await,
+            Script(cmd) => cmd.apply(databases, dst).await,
+            Hello(cmd) => cmd.apply(databases, dst).await,
+            Psubscribe(cmd) => cmd.apply(databases, dst, shutdown).await,
+            Pubsub(cmd) => cmd.apply(databases.pub_sub(), dst).await,
+            Save(cmd) | Bgsave(cmd) => cmd.apply(databases, dst).await,
+            Lastsave(cmd) => cmd.apply(databases, dst).await,
+            Bgrewriteaof(cmd) => cmd.apply(databases, dst).await,
+            Replicaof(cmd) | Slaveof(cmd) => cmd.apply(databases, dst).await,
+            Psync(cmd) => cmd.apply(databases, dst, shutdown).await,
+            Replconf(cmd) => cmd.apply(dst).await,
+            Role(cmd) => cmd.apply(databases, dst).await,
+            Info(cmd) => cmd.apply(databases, dst).await,
+            Asking(cmd) => cmd.apply(dst).await,
+            Cluster(cmd) => cmd.apply(databases, dst).await,
             Unknown(cmd) => cmd.apply(dst).await,
             Unsubscribe(_) => Err("`Unsubscribe` is unsupported in this context".into()),
+            Punsubscribe(_) => Err("`Punsubscribe` is unsupported in this context".into()),
+            Wait(_) => Err("`Wait` is unsupported in this context".into()),
+        }
+    }
+
+    /// Applies a command that changes keys, propagating the changes.
+    /// Blocking commands wait for one of their keys to exist, then run
+    /// without blocking, waiting again if another client emptied it first.
+    async fn apply_propagated(
+        self,
+        databases: &Databases,
+        selected: &mut usize,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        use Command::*;
+
+        let (keys, timeout) = match &self {
+            Blpop(cmd) | Brpop(cmd) => (cmd.keys().to_vec(), cmd.timeout()),
+            Blmove(cmd) => (vec![cmd.source().to_string()], cmd.timeout()),
+            _ => {
+                let response = databases.propagating(|| self.execute(databases, selected));
+                databases.fsync_aof_if_always().await;
+                debug!(?response);
+                dst.write_frame(&response).await?;
+                return Ok(());
+            }
+        };
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let db = databases.db(*selected);
+        let frame = self.into_propagated_frame();
+        let response = loop {
+            let exists = select! {
+                exists = db.wait_for_keys(&keys, deadline) => exists,
+                _ = shutdown.recv() => return Ok(()),
+            };
+            if !exists {
+                break Frame::Null;
+            }
+
+            let cmd = Command::from_frame(frame.clone())?;
+            let response = databases.propagating(|| cmd.execute(databases, selected));
+            if response != Frame::Null {
+                break response;
+            }
+        };
+        databases.fsync_aof_if_always().await;
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Runs the command without a connection to write to, returning its
+    /// reply. This is how `EXEC` runs the commands it queued.
+    ///
+    /// A command that changes keys runs as the frame it is propagated as, so
+    /// that replaying it has the same effect, unless nothing is propagated.
+    pub(crate) fn execute(self, databases: &Databases, selected: &mut usize) -> Frame {
+        if !self.is_write() || !databases.is_propagating() {
+            return self.run(databases, selected);
+        }
+
+        let frame = self.into_propagated_frame();
+        let cmd = match Command::from_frame(frame.clone()) {
+            Ok(cmd) => cmd,
+            Err(err) => return Frame::Error(err.to_string()),
+        };
+        let xadd = matches!(cmd, Command::Xadd(_));
+        let db = *selected;
+        // Only `SWAPDB` and `FLUSHALL` change other databases than the
+        // selected one.
+        let all = matches!(cmd, Command::Swapdb(_) | Command::Flushall(_));
+        let dirty = || {
+            if all {
+                databases.dirty()
+            } else {
+                databases.db(db).dirty()
+            }
+        };
+        let before = dirty();
+
+        // Claims are propagated as the changes they made to the PEL.
+        let (response, claims) = match cmd {
+            Command::Xclaim(cmd) => cmd.execute_with_claims(databases.db(db)),
+            Command::Xautoclaim(cmd) => cmd.execute_with_claims(databases.db(db)),
+            cmd => (cmd.run(databases, selected), vec![]),
+        };
+        if dirty() != before {
+            let frames = match &response {
+                _ if !claims.is_empty() => claims,
+                // The entry has to be added with the same ID when replayed.
+                Frame::Bulk(id) if xadd => match Command::from_frame(frame) {
+                    Ok(Command::Xadd(cmd)) => {
+                        vec![cmd.with_id(String::from_utf8_lossy(id)).into_frame()]
+                    }
+                    _ => unreachable!("`XADD` is propagated as itself"),
+                },
+                _ => vec![frame],
+            };
+            for frame in frames {
+                databases.propagate(db, frame);
+            }
+        }
+        response
+    }
+
+    /// Runs the command like `execute`, without propagating the changes it
+    /// makes. This is how the append only file is replayed.
+    pub(crate) fn run(self, databases: &Databases, selected: &mut usize) -> Frame {
+        use Command::*;
+
+        let db = databases.db(*selected);
+        match self {
+            Get(cmd) => cmd.execute(db),
+            Publish(cmd) => cmd.execute(databases.pub_sub()),
+            Set(cmd) => cmd.execute(db),
+            Ping(cmd) => cmd.execute(),
+            Lpush(cmd) | Rpush(cmd) => cmd.execute(db),
+            Lpop(cmd) | Rpop(cmd) => cmd.execute(db),
+            Lrange(cmd) => cmd.execute(db),
+            Llen(cmd) => cmd.execute(db),
+            Lindex(cmd) => cmd.execute(db),
+            Lset(cmd) => cmd.execute(db),
+            Ltrim(cmd) => cmd.execute(db),
+            Lrem(cmd) => cmd.execute(db),
+            Hset(cmd) => cmd.execute(db),
+            Hget(cmd) => cmd.execute(db),
+            Hmget(cmd) => cmd.execute(db),
+            Hdel(cmd) => cmd.execute(db),
+            Hgetall(cmd) => cmd.execute(db),
+            Hkeys(cmd) => cmd.execute(db),
+            Hvals(cmd) => cmd.execute(db),
+            Hlen(cmd) => cmd.execute(db),
+            Hexists(cmd) => cmd.execute(db),
+            Hincrby(cmd) => cmd.execute(db),
+            Sadd(cmd) => cmd.execute(db),
+            Srem(cmd) => cmd.execute(db),
+            Smembers(cmd) => cmd.execute(db),
+            Sismember(cmd) => cmd.execute(db),
+            Scard(cmd) => cmd.execute(db),
+            Sinter(cmd) | Sunion(cmd) | Sdiff(cmd) | Sinterstore(cmd) | Sunionstore(cmd)
+            | Sdiffstore(cmd) => cmd.execute(db),
+            Zadd(cmd) => cmd.execute(db),
+            Zrange(cmd) | Zrangebyscore(cmd) => cmd.execute(db),
+            Zrank(cmd) => cmd.execute(db),
+            Zscore(cmd) => cmd.execute(db),
+            Zincrby(cmd) => cmd.execute(db),
+            Zrem(cmd) => cmd.execute(db),
+            Zpopmin(cmd) => cmd.execute(db),
+            Xadd(cmd) => cmd.execute(db),
+            Xrange(cmd) | Xrevrange(cmd) => cmd.execute(db),
+            Xlen(cmd) => cmd.execute(db),
+            Xtrim(cmd) => cmd.execute(db),
+            Xgroup(cmd) => cmd.execute(db),
+            Xreadgroup(cmd) => cmd.execute(db),
+            Xack(cmd) => cmd.execute(db),
+            Xpending(cmd) => cmd.execute(db),
+            Xclaim(cmd) => cmd.execute(db),
+            Xautoclaim(cmd) => cmd.execute(db),
+            Blpop(cmd) | Brpop(cmd) => cmd.execute(db),
+            Blmove(cmd) => cmd.execute(db),
+            Incr(cmd) | Decr(cmd) | Incrby(cmd) | Decrby(cmd) => cmd.execute(db),
+            Incrbyfloat(cmd) => cmd.execute(db),
+            Append(cmd) => cmd.execute(db),
+            Strlen(cmd) => cmd.execute(db),
+            Getrange(cmd) => cmd.execute(db),
+            Setrange(cmd) => cmd.execute(db),
+            Mget(cmd) => cmd.execute(db),
+            Mset(cmd) | Msetnx(cmd) => cmd.execute(db),
+            Getdel(cmd) => cmd.execute(db),
+            Getex(cmd) => cmd.execute(db),
+            Setnx(cmd) => cmd.execute(db),
+            Setex(cmd) => cmd.execute(db),
+            Del(cmd) | Unlink(cmd) => cmd.execute(db),
+            Exists(cmd) | Touch(cmd) => cmd.execute(db),
+            Type(cmd) => cmd.execute(db),
+            Rename(cmd) | Renamenx(cmd) => cmd.execute(db),
+            Copy(cmd) => cmd.execute(db),
+            Expire(cmd) | Pexpire(cmd) | Expireat(cmd) | Pexpireat(cmd) => cmd.execute(db),
+            Ttl(cmd) | Pttl(cmd) | Expiretime(cmd) | Pexpiretime(cmd) => cmd.execute(db),
+            Persist(cmd) => cmd.execute(db),
+            Keys(cmd) => cmd.execute(db),
+            Scan(cmd) | Hscan(cmd) | Sscan(cmd) | Zscan(cmd) => cmd.execute(db),
+            Select(cmd) => cmd.execute(databases, selected),
+            Swapdb(cmd) => cmd.execute(databases),
+            Move(cmd) => cmd.execute(databases, *selected),
+            Flushdb(cmd) | Flushall(cmd) => cmd.execute(databases, *selected),
+            Dbsize(cmd) => cmd.execute(db),
+            Unwatch(cmd) => cmd.execute(),
+            Eval(cmd) | Evalsha(cmd) => cmd.execute(databases, *selected),
+            Script(cmd) => cmd.execute(databases),
+            Pubsub(cmd) => cmd.execute(databases.pub_sub()),
+            Save(cmd) | Bgsave(cmd) => cmd.execute(databases),
+            Lastsave(cmd) => cmd.execute(databases),
+            Bgrewriteaof(cmd) => cmd.execute(databases),
+            Replicaof(cmd) | Slaveof(cmd) => cmd.execute(databases),
+            Role(cmd) => cmd.execute(databases),
+            Info(cmd) => cmd.execute(databases),
+            Cluster(cmd) => cmd.execute(databases),
+            Unknown(cmd) => cmd.execute(),
+            Multi(_) | Exec(_) | Discard(_) | Watch(_) | Subscribe(_) | Unsubscribe(_)
+            | Psubscribe(_) | Punsubscribe(_) | Hello(_) | Psync(_) | Replconf(_) | Wait(_)
+            | Asking(_) => Frame::Error(format!("ERR {} is not allowed here", self.get_name())),
+        }
+    }
+
+    /// Whether the command may change keys, in which case the change is
+    /// propagated.
+    pub(crate) fn is_write(&self) -> bool {
+        use Command::*;
+
+        matches!(
+            self,
+            Set(_)
+                | Lpush(_)
+                | Rpush(_)
+                | Lpop(_)
+                | Rpop(_)
+                | Lset(_)
+                | Ltrim(_)
+                | Lrem(_)
+                | Hset(_)
+                | Hdel(_)
+                | Hincrby(_)
+                | Sadd(_)
+                | Srem(_)
+                | Sinterstore(_)
+                | Sunionstore(_)
+                | Sdiffstore(_)
+                | Zadd(_)
+                | Zincrby(_)
+                | Zrem(_)
+                | Zpopmin(_)
+                | Xadd(_)
+                | Xtrim(_)
+                | Xgroup(_)
+                | Xreadgroup(_)
+                | Xack(_)
+                | Xclaim(_)
+                | Xautoclaim(_)
+                | Blpop(_)
+                | Brpop(_)
+                | Blmove(_)
+                | Incr(_)
+                | Decr(_)
+                | Incrby(_)
+                | Decrby(_)
+                | Incrbyfloat(_)
+                | Append(_)
+                | Setrange(_)
+                | Mset(_)
+                | Msetnx(_)
+                | Getdel(_)
+                | Getex(_)
+                | Setnx(_)
+                | Setex(_)
+                | Del(_)
+                | Unlink(_)
+                | Rename(_)
+                | Renamenx(_)
+                | Copy(_)
+                | Expire(_)
+                | Pexpire(_)
+                | Expireat(_)
+                | Pexpireat(_)
+                | Persist(_)
+                | Swapdb(_)
+                | Move(_)
+                | Flushdb(_)
+                | Flushall(_)
+        )
+    }
+
+    /// The keys the command reads or changes, which a cluster node has to
+    /// own, see `Databases::redirect`.
+    pub(crate) fn keys(&self) -> Vec<&str> {
+        use Command::*;
+
+        match self {
+            Get(cmd) => vec![cmd.key()],
+            Set(cmd) => vec![cmd.key()],
+            Lpush(cmd) | Rpush(cmd) => vec![cmd.key()],
+            Lpop(cmd) | Rpop(cmd) => vec![cmd.key()],
+            Lrange(cmd) => vec![cmd.key()],
+            Llen(cmd) => vec![cmd.key()],
+            Lindex(cmd) => vec![cmd.key()],
+            Lset(cmd) => vec![cmd.key()],
+            Ltrim(cmd) => vec![cmd.key()],
+            Lrem(cmd) => vec![cmd.key()],
+            Hset(cmd) => vec![cmd.key()],
+            Hget(cmd) => vec![cmd.key()],
+            Hmget(cmd) => vec![cmd.key()],
+            Hdel(cmd) => vec![cmd.key()],
+            Hgetall(cmd) => vec![cmd.key()],
+            Hkeys(cmd) => vec![cmd.key()],
+            Hvals(cmd) => vec![cmd.key()],
+            Hlen(cmd) => vec![cmd.key()],
+            Hexists(cmd) => vec![cmd.key()],
+            Hincrby(cmd) => vec![cmd.key()],
+            Sadd(cmd) => vec![cmd.key()],
+            Srem(cmd) => vec![cmd.key()],
+            Smembers(cmd) => vec![cmd.key()],
+            Sismember(cmd) => vec![cmd.key()],
+            Scard(cmd) => vec![cmd.key()],
+            Sinter(cmd) | Sunion(cmd) | Sdiff(cmd) | Sinterstore(cmd) | Sunionstore(cmd)
+            | Sdiffstore(cmd) => cmd
+                .destination()
+                .into_iter()
+                .chain(cmd.keys().iter().map(String::as_str))
+                .collect(),
+            Zadd(cmd) => vec![cmd.key()],
+            Zrange(cmd) | Zrangebyscore(cmd) => vec![cmd.key()],
+            Zrank(cmd) => vec![cmd.key()],
+            Zscore(cmd) => vec![cmd.key()],
+            Zincrby(cmd) => vec![cmd.key()],
+            Zrem(cmd) => vec![cmd.key()],
+            Zpopmin(cmd) => vec![cmd.key()],
+            Xadd(cmd) => vec![cmd.key()],
+            Xrange(cmd) | Xrevrange(cmd) => vec![cmd.key()],
+            Xlen(cmd) => vec![cmd.key()],
+            Xtrim(cmd) => vec![cmd.key()],
+            Xgroup(cmd) => vec![cmd.key()],
+            Xreadgroup(cmd) => cmd.keys().collect(),
+            Xack(cmd) => vec![cmd.key()],
+            Xpending(cmd) => vec![cmd.key()],
+            Xclaim(cmd) => vec![cmd.key()],
+            Xautoclaim(cmd) => vec![cmd.key()],
+            Blpop(cmd) | Brpop(cmd) => cmd.keys().iter().map(String::as_str).collect(),
+            Blmove(cmd) => vec![cmd.source(), cmd.destination()],
+            Incr(cmd) | Decr(cmd) | Incrby(cmd) | Decrby(cmd) => vec![cmd.key()],
+            Incrbyfloat(cmd) => vec![cmd.key()],
+            Append(cmd) => vec![cmd.key()],
+            Strlen(cmd) => vec![cmd.key()],
+            Getrange(cmd) => vec![cmd.key()],
+            Setrange(cmd) => vec![cmd.key()],
+            Mget(cmd) => cmd.keys().iter().map(String::as_str).collect(),
+            Mset(cmd) | Msetnx(cmd) => cmd.keys().collect(),
+            Getdel(cmd) => vec![cmd.key()],
+            Getex(cmd) => vec![cmd.key()],
+            Setnx(cmd) => vec![cmd.key()],
+            Setex(cmd) => vec![cmd.key()],
+            Del(cmd) | Unlink(cmd) => cmd.keys().iter().map(String::as_str).collect(),
+            Exists(cmd) | Touch(cmd) => cmd.keys().iter().map(String::as_str).collect(),
+            Type(cmd) => vec![cmd.key()],
+            Rename(cmd) | Renamenx(cmd) => vec![cmd.key(), cmd.newkey()],
+            Copy(cmd) => vec![cmd.source(), cmd.destination()],
+            Expire(cmd) | Pexpire(cmd) | Expireat(cmd) | Pexpireat(cmd) => vec![cmd.key()],
+            Ttl(cmd) | Pttl(cmd) | Expiretime(cmd) | Pexpiretime(cmd) => vec![cmd.key()],
+            Persist(cmd) => vec![cmd.key()],
+            Hscan(cmd) | Sscan(cmd) | Zscan(cmd) => cmd.key().into_iter().collect(),
+            Move(cmd) => vec![cmd.key()],
+            Watch(cmd) => cmd.keys().iter().map(String::as_str).collect(),
+            Eval(cmd) | Evalsha(cmd) => cmd.keys().iter().map(String::as_str).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The frame a command that changes keys is propagated as, with relative
+    /// expirations turned into Unix times.
+    fn into_propagated_frame(self) -> Frame {
+        use Command::*;
+
+        match self {
+            Set(cmd) => cmd.absolute().into_frame(),
+            Setex(cmd) => cmd.into_set().absolute().into_frame(),
+            Getex(cmd) => cmd.absolute().into_frame(),
+            Expire(cmd) | Pexpire(cmd) | Expireat(cmd) | Pexpireat(cmd) => {
+                cmd.absolute().into_frame()
+            }
+            Lpush(cmd) | Rpush(cmd) => cmd.into_frame(),
+            Lpop(cmd) | Rpop(cmd) => cmd.into_frame(),
+            Lset(cmd) => cmd.into_frame(),
+            Ltrim(cmd) => cmd.into_frame(),
+            Lrem(cmd) => cmd.into_frame(),
+            Hset(cmd) => cmd.into_frame(),
+            Hdel(cmd) => cmd.into_frame(),
+            Hincrby(cmd) => cmd.into_frame(),
+            Sadd(cmd) => cmd.into_frame(),
+            Srem(cmd) => cmd.into_frame(),
+            Sinterstore(cmd) | Sunionstore(cmd) | Sdiffstore(cmd) => cmd.into_frame(),
+            Zadd(cmd) => cmd.into_frame(),
+            Zincrby(cmd) => cmd.into_frame(),
+            Zrem(cmd) => cmd.into_frame(),
+            Zpopmin(cmd) => cmd.into_frame(),
+            Xadd(cmd) => cmd.into_frame(),
+            Xtrim(cmd) => cmd.into_frame(),
+            Xgroup(cmd) => cmd.into_frame(),
+            Xreadgroup(cmd) => cmd.into_frame(),
+            Xack(cmd) => cmd.into_frame(),
+            Xclaim(cmd) => cmd.into_frame(),
+            Xautoclaim(cmd) => cmd.into_frame(),
+            Blpop(cmd) | Brpop(cmd) => cmd.into_frame(),
+            Blmove(cmd) => cmd.into_frame(),
+            Incr(cmd) | Decr(cmd) | Incrby(cmd) | Decrby(cmd) => cmd.into_frame(),
+            Incrbyfloat(cmd) => cmd.into_frame(),
+            Append(cmd) => cmd.into_frame(),
+            Setrange(cmd) => cmd.into_frame(),
+            Mset(cmd) | Msetnx(cmd) => cmd.into_frame(),
+            Getdel(cmd) => cmd.into_frame(),
+            Setnx(cmd) => cmd.into_frame(),
+            Del(cmd) | Unlink(cmd) => cmd.into_frame(),
+            Rename(cmd) | Renamenx(cmd) => cmd.into_frame(),
+            Copy(cmd) => cmd.into_frame(),
+            Persist(cmd) => cmd.into_frame(),
+            Swapdb(cmd) => cmd.into_frame(),
+            Move(cmd) => cmd.into_frame(),
+            Flushdb(cmd) | Flushall(cmd) => cmd.into_frame(),
+            cmd => unreachable!("`{}` doesn't change keys", cmd.get_name()),
         }
     }
 
@@ -86,6 +1189,128 @@ impl Command {
             Command::Subscribe(_) => "subscribe",
             Command::Unsubscribe(_) => "unsubscribe",
             Command::Ping(_) => "ping",
+            Command::Lpush(_) => "lpush",
+            Command::Rpush(_) => "rpush",
+            Command::Lpop(_) => "lpop",
+            Command::Rpop(_) => "rpop",
+            Command::Lrange(_) => "lrange",
+            Command::Llen(_) => "llen",
+            Command::Lindex(_) => "lindex",
+            Command::Lset(_) => "lset",
+            Command::Ltrim(_) => "ltrim",
+            Command::Lrem(_) => "lrem",
+            Command::Hset(_) => "hset",
+            Command::Hget(_) => "hget",
+            Command::Hmget(_) => "hmget",
+            Command::Hdel(_) => "hdel",
+            Command::Hgetall(_) => "hgetall",
+            Command::Hkeys(_) => "hkeys",
+            Command::Hvals(_) => "hvals",
+            Command::Hlen(_) => "hlen",
+            Command::Hexists(_) => "hexists",
+            Command::Hincrby(_) => "hincrby",
+            Command::Sadd(_) => "sadd",
+            Command::Srem(_) => "srem",
+            Command::Smembers(_) => "smembers",
+            Command::Sismember(_) => "sismember",
+            Command::Scard(_) => "scard",
+            Command::Sinter(_) => "sinter",
+            Command::Sunion(_) => "sunion",
+            Command::Sdiff(_) => "sdiff",
+            Command::Sinterstore(_) => "sinterstore",
+            Command::Sunionstore(_) => "sunionstore",
+            Command::Sdiffstore(_) => "sdiffstore",
+            Command::Zadd(_) => "zadd",
+            Command::Zrange(_) => "zrange",
+            Command::Zrangebyscore(_) => "zrangebyscore",
+            Command::Zrank(_) => "zrank",
+            Command::Zscore(_) => "zscore",
+            Command::Zincrby(_) => "zincrby",
+            Command::Zrem(_) => "zrem",
+            Command::Zpopmin(_) => "zpopmin",
+            Command::Xadd(_) => "xadd",
+            Command::Xrange(_) => "xrange",
+            Command::Xrevrange(_) => "xrevrange",
+            Command::Xlen(_) => "xlen",
+            Command::Xtrim(_) => "xtrim",
+            Command::Xgroup(_) => "xgroup",
+            Command::Xreadgroup(_) => "xreadgroup",
+            Command::Xack(_) => "xack",
+            Command::Xpending(_) => "xpending",
+            Command::Xclaim(_) => "xclaim",
+            Command::Xautoclaim(_) => "xautoclaim",
+            Command::Blpop(_) => "blpop",
+            Command::Brpop(_) => "brpop",
+            Command::Blmove(_) => "blmove",
+            Command::Incr(_) => "incr",
+            Command::Decr(_) => "decr",
+            Command::Incrby(_) => "incrby",
+            Command::Decrby(_) => "decrby",
+            Command::Incrbyfloat(_) => "incrbyfloat",
+            Command::Append(_) => "append",
+            Command::Strlen(_) => "strlen",
+            Command::Getrange(_) => "getrange",
+            Command::Setrange(_) => "setrange",
+            Command::Mget(_) => "mget",
+            Command::Mset(_) => "mset",
+            Command::Msetnx(_) => "msetnx",
+            Command::Getdel(_) => "getdel",
+            Command::Getex(_) => "getex",
+            Command::Setnx(_) => "setnx",
+            Command::Setex(_) => "setex",
+            Command::Del(_) => "del",
+            Command::Unlink(_) => "unlink",
+            Command::Exists(_) => "exists",
+            Command::Touch(_) => "touch",
+            Command::Type(_) => "type",
+            Command::Rename(_) => "rename",
+            Command::Renamenx(_) => "renamenx",
+            Command::Copy(_) => "copy",
+            Command::Expire(_) => "expire",
+            Command::Pexpire(_) => "pexpire",
+            Command::Expireat(_) => "expireat",
+            Command::Pexpireat(_) => "pexpireat",
+            Command::Ttl(_) => "ttl",
+            Command::Pttl(_) => "pttl",
+            Command::Expiretime(_) => "expiretime",
+            Command::Pexpiretime(_) => "pexpiretime",
+            Command::Persist(_) => "persist",
+            Command::Keys(_) => "keys",
+            Command::Scan(_) => "scan",
+            Command::Hscan(_) => "hscan",
+            Command::Sscan(_) => "sscan",
+            Command::Zscan(_) => "zscan",
+            Command::Select(_) => "select",
+            Command::Swapdb(_) => "swapdb",
+            Command::Move(_) => "move",
+            Command::Flushdb(_) => "flushdb",
+            Command::Flushall(_) => "flushall",
+            Command::Dbsize(_) => "dbsize",
+            Command::Multi(_) => "multi",
+            Command::Exec(_) => "exec",
+            Command::Discard(_) => "discard",
+            Command::Watch(_) => "watch",
+            Command::Unwatch(_) => "unwatch",
+            Command::Eval(_) => "eval",
+            Command::Evalsha(_) => "evalsha",
+            Command::Script(_) => "script",
+            Command::Hello(_) => "hello",
+            Command::Psubscribe(_) => "psubscribe",
+            Command::Punsubscribe(_) => "punsubscribe",
+            Command::Pubsub(_) => "pubsub",
+            Command::Save(_) => "save",
+            Command::Bgsave(_) => "bgsave",
+            Command::Lastsave(_) => "lastsave",
+            Command::Bgrewriteaof(_) => "bgrewriteaof",
+            Command::Replicaof(_) => "replicaof",
+            Command::Slaveof(_) => "slaveof",
+            Command::Psync(_) => "psync",
+            Command::Replconf(_) => "replconf",
+            Command::Role(_) => "role",
+            Command::Info(_) => "info",
+            Command::Wait(_) => "wait",
+            Command::Asking(_) => "asking",
+            Command::Cluster(_) => "cluster",
             Command::Unknown(cmd) => cmd.get_name(),
         }
     }
@@ -0,0 +1,35 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{parse::Parse, Connection, Frame};
+
+/// Sent by clients following an `ASK` redirection, so that the node a slot
+/// is being moved to serves the next command on it.
+#[derive(Debug, Default)]
+pub struct Asking;
+
+impl Asking {
+    pub fn new() -> Asking {
+        Asking
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Asking> {
+        Ok(Asking)
+    }
+
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        dst.set_asking();
+        let response = Frame::Simple("OK".to_string());
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("asking".as_bytes()));
+        frame
+    }
+}
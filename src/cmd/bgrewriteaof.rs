@@ -0,0 +1,45 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, Connection, Frame};
+
+/// Compacts the append only file in the background, replacing the commands
+/// logged so far with the data they produced.
+#[derive(Debug, Default)]
+pub struct Bgrewriteaof;
+
+impl Bgrewriteaof {
+    pub fn new() -> Bgrewriteaof {
+        Bgrewriteaof
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Bgrewriteaof> {
+        Ok(Bgrewriteaof)
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases) -> Frame {
+        match databases.bgrewriteaof() {
+            Ok(()) => Frame::Simple("Background append only file rewriting started".to_string()),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("bgrewriteaof".as_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::select;
+use tracing::{debug, instrument};
+
+use crate::{
+    cmd::bpop::{parse_timeout, timeout_arg},
+    db::{Db, Side},
+    parse::Parse,
+    shutdown::Shutdown,
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Blmove {
+    source: String,
+    destination: String,
+    from: Side,
+    to: Side,
+    timeout: Option<Duration>,
+}
+
+impl Blmove {
+    pub fn new(
+        source: impl ToString,
+        destination: impl ToString,
+        from: Side,
+        to: Side,
+        timeout: Option<Duration>,
+    ) -> Blmove {
+        Blmove {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            from,
+            to,
+            timeout,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Blmove> {
+        let source = parse.next_string()?;
+        let destination = parse.next_string()?;
+        let from = parse_side(&parse.next_string()?)?;
+        let to = parse_side(&parse.next_string()?)?;
+        let timeout = parse_timeout(&parse.next_string()?)?;
+
+        Ok(Blmove {
+            source,
+            destination,
+            from,
+            to,
+            timeout,
+        })
+    }
+
+    #[instrument(skip(self, db, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let moved = select! {
+            moved = db.blocking_move(&self.source, &self.destination, self.from, self.to, self.timeout) => moved,
+            _ = shutdown.recv() => return Ok(()),
+        };
+
+        let response = move_reply(moved);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Moves without waiting, the way blocking commands run inside `MULTI`.
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        move_reply(db.move_element(&self.source, &self.destination, self.from, self.to))
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("blmove".as_bytes()));
+        frame.push_bulk(Bytes::from(self.source.into_bytes()));
+        frame.push_bulk(Bytes::from(self.destination.into_bytes()));
+        frame.push_bulk(Bytes::from(side_arg(self.from).as_bytes()));
+        frame.push_bulk(Bytes::from(side_arg(self.to).as_bytes()));
+        frame.push_bulk(timeout_arg(self.timeout));
+        frame
+    }
+}
+
+fn move_reply(moved: crate::Result<Option<Bytes>>) -> Frame {
+    match moved {
+        Ok(Some(value)) => Frame::Bulk(value),
+        Ok(None) => Frame::Null,
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
+
+fn parse_side(side: &str) -> crate::Result<Side> {
+    match &side.to_uppercase()[..] {
+        "LEFT" => Ok(Side::Left),
+        "RIGHT" => Ok(Side::Right),
+        _ => Err("ERR syntax error".into()),
+    }
+}
+
+fn side_arg(side: Side) -> &'static str {
+    match side {
+        Side::Left => "LEFT",
+        Side::Right => "RIGHT",
+    }
+}
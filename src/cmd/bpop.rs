@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::select;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, Side},
+    parse::{Parse, ParseError},
+    shutdown::Shutdown,
+    Connection, Frame,
+};
+
+/// `BLPOP` / `BRPOP`, depending on `side`.
+#[derive(Debug)]
+pub struct Bpop {
+    keys: Vec<String>,
+    timeout: Option<Duration>,
+    side: Side,
+}
+
+impl Bpop {
+    pub(crate) fn new(keys: Vec<String>, timeout: Option<Duration>, side: Side) -> Bpop {
+        Bpop {
+            keys,
+            timeout,
+            side,
+        }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, side: Side) -> crate::Result<Bpop> {
+        let mut keys = vec![parse.next_string()?];
+        keys.extend(parse.remaining_strings()?);
+
+        // The timeout comes last, after at least one key.
+        let timeout = keys.pop().unwrap();
+        if keys.is_empty() {
+            return Err(ParseError::EndOfStream.into());
+        }
+        let timeout = parse_timeout(&timeout)?;
+
+        Ok(Bpop {
+            keys,
+            timeout,
+            side,
+        })
+    }
+
+    /// Waits for an element to pop, returning early without a reply when the
+    /// server shuts down.
+    #[instrument(skip(self, db, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let popped = select! {
+            popped = db.blocking_pop(&self.keys, self.side, self.timeout) => popped,
+            _ = shutdown.recv() => return Ok(()),
+        };
+
+        let response = pop_reply(popped);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Pops without waiting, the way blocking commands run inside `MULTI`.
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        pop_reply(db.pop_first(&self.keys, self.side))
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        let name = match self.side {
+            Side::Left => "blpop",
+            Side::Right => "brpop",
+        };
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+        frame.push_bulk(timeout_arg(self.timeout));
+        frame
+    }
+}
+
+fn pop_reply(popped: crate::Result<Option<(String, Bytes)>>) -> Frame {
+    match popped {
+        Ok(Some((key, value))) => {
+            Frame::Array(vec![Frame::Bulk(Bytes::from(key)), Frame::Bulk(value)])
+        }
+        Ok(None) => Frame::Null,
+        Err(err) => Frame::Error(err.to_string()),
+    }
+}
+
+/// Parses a blocking timeout in seconds, where `0` blocks forever.
+pub(crate) fn parse_timeout(timeout: &str) -> crate::Result<Option<Duration>> {
+    let secs = timeout
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite())
+        .ok_or("ERR timeout is not a float or out of range")?;
+
+    if secs < 0.0 {
+        return Err("ERR timeout is negative".into());
+    }
+    if secs == 0.0 {
+        return Ok(None);
+    }
+    Ok(Some(Duration::from_secs_f64(secs)))
+}
+
+pub(crate) fn timeout_arg(timeout: Option<Duration>) -> Bytes {
+    let secs = timeout.map(|timeout| timeout.as_secs_f64()).unwrap_or(0.0);
+    Bytes::from(secs.to_string())
+}
@@ -0,0 +1,121 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, Connection, Frame};
+
+/// The `INFO`, `MYID`, `NODES`, `SLOTS`, `SHARDS` and `KEYSLOT` subcommands
+/// of `CLUSTER`, which report on the cluster this server is a node of.
+#[derive(Debug)]
+pub struct Cluster {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Info,
+    Myid,
+    Nodes,
+    Slots,
+    Shards,
+    Keyslot(String),
+}
+
+impl Cluster {
+    pub fn info() -> Cluster {
+        Cluster {
+            subcommand: Subcommand::Info,
+        }
+    }
+
+    pub fn myid() -> Cluster {
+        Cluster {
+            subcommand: Subcommand::Myid,
+        }
+    }
+
+    pub fn nodes() -> Cluster {
+        Cluster {
+            subcommand: Subcommand::Nodes,
+        }
+    }
+
+    pub fn slots() -> Cluster {
+        Cluster {
+            subcommand: Subcommand::Slots,
+        }
+    }
+
+    pub fn shards() -> Cluster {
+        Cluster {
+            subcommand: Subcommand::Shards,
+        }
+    }
+
+    /// Reports the hash slot of `key`.
+    pub fn keyslot(key: impl ToString) -> Cluster {
+        Cluster {
+            subcommand: Subcommand::Keyslot(key.to_string()),
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Cluster> {
+        let subcommand = match &parse.next_string()?.to_uppercase()[..] {
+            "INFO" => Subcommand::Info,
+            "MYID" => Subcommand::Myid,
+            "NODES" => Subcommand::Nodes,
+            "SLOTS" => Subcommand::Slots,
+            "SHARDS" => Subcommand::Shards,
+            "KEYSLOT" => Subcommand::Keyslot(parse.next_string()?),
+            subcommand => return Err(format!("ERR unknown subcommand '{}'", subcommand).into()),
+        };
+
+        Ok(Cluster { subcommand })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases) -> Frame {
+        let bulk = |text: String| Frame::Bulk(Bytes::from(text.into_bytes()));
+        let response = match self.subcommand {
+            Subcommand::Info => databases.cluster_info().map(bulk),
+            Subcommand::Myid => databases.cluster_myid().map(bulk),
+            Subcommand::Nodes => databases.cluster_nodes().map(bulk),
+            Subcommand::Slots => databases.cluster_slots(),
+            Subcommand::Shards => databases.cluster_shards(),
+            Subcommand::Keyslot(key) => databases
+                .cluster_keyslot(&key)
+                .map(|slot| Frame::Integer(slot as i64)),
+        };
+        response.unwrap_or_else(|err| Frame::Error(err.to_string()))
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("cluster".as_bytes()));
+        let subcommand = match self.subcommand {
+            Subcommand::Info => "info",
+            Subcommand::Myid => "myid",
+            Subcommand::Nodes => "nodes",
+            Subcommand::Slots => "slots",
+            Subcommand::Shards => "shards",
+            Subcommand::Keyslot(key) => {
+                frame.push_bulk(Bytes::from("keyslot".as_bytes()));
+                frame.push_bulk(Bytes::from(key.into_bytes()));
+                return frame;
+            }
+        };
+        frame.push_bulk(Bytes::from(subcommand.as_bytes()));
+        frame
+    }
+}
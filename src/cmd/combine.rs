@@ -0,0 +1,100 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, SetOp},
+    parse::Parse,
+    Connection, Frame,
+};
+
+/// `SINTER`, `SUNION` and `SDIFF`, or their `STORE` variants when a
+/// `destination` is given.
+#[derive(Debug)]
+pub struct Combine {
+    op: SetOp,
+    destination: Option<String>,
+    keys: Vec<String>,
+}
+
+impl Combine {
+    pub(crate) fn new(op: SetOp, destination: Option<String>, keys: Vec<String>) -> Combine {
+        Combine {
+            op,
+            destination,
+            keys,
+        }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub fn destination(&self) -> Option<&str> {
+        self.destination.as_deref()
+    }
+
+    pub(crate) fn parse_frames(
+        parse: &mut Parse,
+        op: SetOp,
+        store: bool,
+    ) -> crate::Result<Combine> {
+        let destination = if store {
+            Some(parse.next_string()?)
+        } else {
+            None
+        };
+        let mut keys = vec![parse.next_string()?];
+        keys.extend(parse.remaining_strings()?);
+
+        Ok(Combine {
+            op,
+            destination,
+            keys,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self.destination {
+            Some(destination) => match db.combine_store(self.op, &destination, &self.keys) {
+                Ok(len) => Frame::Integer(len as i64),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+            None => match db.combine(self.op, &self.keys) {
+                Ok(members) => Frame::Set(members.into_iter().map(Frame::Bulk).collect()),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+        }
+    }
+
+    pub(crate) fn get_name(&self) -> &'static str {
+        match (self.op, self.destination.is_some()) {
+            (SetOp::Inter, false) => "sinter",
+            (SetOp::Union, false) => "sunion",
+            (SetOp::Diff, false) => "sdiff",
+            (SetOp::Inter, true) => "sinterstore",
+            (SetOp::Union, true) => "sunionstore",
+            (SetOp::Diff, true) => "sdiffstore",
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(self.get_name().as_bytes()));
+        if let Some(destination) = self.destination {
+            frame.push_bulk(Bytes::from(destination.into_bytes()));
+        }
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+        frame
+    }
+}
@@ -0,0 +1,79 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::Db,
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Copy {
+    source: String,
+    destination: String,
+    replace: bool,
+}
+
+impl Copy {
+    pub fn new(source: impl ToString, destination: impl ToString, replace: bool) -> Copy {
+        Copy {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            replace,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Copy> {
+        use ParseError::EndOfStream;
+
+        let source = parse.next_string()?;
+        let destination = parse.next_string()?;
+        let replace = match parse.next_string() {
+            Ok(s) if s.to_uppercase() == "REPLACE" => true,
+            Ok(_) => return Err("ERR syntax error".into()),
+            Err(EndOfStream) => false,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Copy {
+            source,
+            destination,
+            replace,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.copy(&self.source, &self.destination, self.replace) {
+            Ok(copied) => Frame::Integer(copied as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("copy".as_bytes()));
+        frame.push_bulk(Bytes::from(self.source.into_bytes()));
+        frame.push_bulk(Bytes::from(self.destination.into_bytes()));
+        if self.replace {
+            frame.push_bulk(Bytes::from("REPLACE".as_bytes()));
+        }
+        frame
+    }
+}
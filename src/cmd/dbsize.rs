@@ -0,0 +1,37 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+/// Counts the keys of the selected database.
+#[derive(Debug, Default)]
+pub struct Dbsize;
+
+impl Dbsize {
+    pub fn new() -> Dbsize {
+        Dbsize
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Dbsize> {
+        Ok(Dbsize)
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        Frame::Integer(db.dbsize() as i64)
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("dbsize".as_bytes()));
+        frame
+    }
+}
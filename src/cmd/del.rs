@@ -0,0 +1,55 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+/// `DEL`, or `UNLINK` when `unlink` is set.
+#[derive(Debug)]
+pub struct Del {
+    keys: Vec<String>,
+    unlink: bool,
+}
+
+impl Del {
+    pub fn new(keys: Vec<String>, unlink: bool) -> Del {
+        Del { keys, unlink }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, unlink: bool) -> crate::Result<Del> {
+        let mut keys = vec![parse.next_string()?];
+        keys.extend(parse.remaining_strings()?);
+        Ok(Del { keys, unlink })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let removed = if self.unlink {
+            db.unlink(&self.keys)
+        } else {
+            db.del(&self.keys)
+        };
+        Frame::Integer(removed as i64)
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        let name = if self.unlink { "unlink" } else { "del" };
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+        frame
+    }
+}
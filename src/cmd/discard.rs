@@ -0,0 +1,37 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{cmd::MultiState, parse::Parse, Connection, Frame};
+
+/// Drops the commands queued since `MULTI` and unwatches every key.
+#[derive(Debug, Default)]
+pub struct Discard;
+
+impl Discard {
+    pub fn new() -> Discard {
+        Discard
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Discard> {
+        Ok(Discard)
+    }
+
+    #[instrument(skip(self, transaction, dst))]
+    pub(crate) async fn apply(
+        self,
+        transaction: &mut MultiState,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = transaction.discard();
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("discard".as_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,108 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, scripting, Connection, Frame};
+
+/// Runs a Lua script atomically, given by its body with `EVAL` or by the
+/// digest of a cached one with `EVALSHA`.
+#[derive(Debug)]
+pub struct Eval {
+    /// The body of the script, or its digest for `EVALSHA`.
+    script: Bytes,
+    keys: Vec<String>,
+    args: Vec<Bytes>,
+    sha: bool,
+}
+
+impl Eval {
+    pub fn new(script: Bytes, keys: Vec<String>, args: Vec<Bytes>, sha: bool) -> Eval {
+        Eval {
+            script,
+            keys,
+            args,
+            sha,
+        }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, sha: bool) -> crate::Result<Eval> {
+        let script = parse.next_bytes()?;
+        let numkeys = parse.next_signed_int()?;
+        let numkeys =
+            usize::try_from(numkeys).map_err(|_| "ERR Number of keys can't be negative")?;
+
+        let mut args = parse.remaining_bytes()?;
+        if numkeys > args.len() {
+            return Err("ERR Number of keys can't be greater than number of args".into());
+        }
+        let keys = args
+            .drain(..numkeys)
+            .map(|key| String::from_utf8(key.to_vec()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Eval {
+            script,
+            keys,
+            args,
+            sha,
+        })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        selected: usize,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        // What the script changes is propagated as one transaction, see
+        // `Databases::exclusive`.
+        let response = self.execute(databases, selected);
+        databases.fsync_aof_if_always().await;
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases, selected: usize) -> Frame {
+        let scripts = databases.scripts();
+        let body = if self.sha {
+            let sha = String::from_utf8_lossy(&self.script);
+            match scripts.get(&sha) {
+                Some(body) => body,
+                None => {
+                    return Frame::Error(
+                        "NOSCRIPT No matching script. Please use EVAL.".to_string(),
+                    )
+                }
+            }
+        } else {
+            scripts.load(self.script.clone());
+            self.script
+        };
+
+        let _announced = scripts.announce();
+        databases
+            .exclusive(|databases| scripting::run(databases, selected, &body, self.keys, self.args))
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let name = if self.sha { "evalsha" } else { "eval" };
+
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        frame.push_bulk(self.script);
+        frame.push_int(self.keys.len() as i64);
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+        for arg in self.args {
+            frame.push_bulk(arg);
+        }
+        frame
+    }
+}
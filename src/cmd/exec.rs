@@ -0,0 +1,43 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{cmd::MultiState, db::Databases, parse::Parse, Connection, Frame};
+
+/// Runs the commands queued since `MULTI`, replying with an array of their
+/// replies, or `Null` if a watched key was modified in the meantime.
+#[derive(Debug, Default)]
+pub struct Exec;
+
+impl Exec {
+    pub fn new() -> Exec {
+        Exec
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Exec> {
+        Ok(Exec)
+    }
+
+    #[instrument(skip(self, databases, selected, transaction, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        selected: &mut usize,
+        transaction: &mut MultiState,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        // What the queued commands change is propagated as one transaction,
+        // see `Databases::exclusive`.
+        let response = transaction.exec(databases, selected);
+        databases.fsync_aof_if_always().await;
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("exec".as_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,51 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+/// `EXISTS`, or `TOUCH` when `touch` is set. Keys carry no access time, so
+/// both only count the existing keys.
+#[derive(Debug)]
+pub struct Exists {
+    keys: Vec<String>,
+    touch: bool,
+}
+
+impl Exists {
+    pub fn new(keys: Vec<String>, touch: bool) -> Exists {
+        Exists { keys, touch }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, touch: bool) -> crate::Result<Exists> {
+        let mut keys = vec![parse.next_string()?];
+        keys.extend(parse.remaining_strings()?);
+        Ok(Exists { keys, touch })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        Frame::Integer(db.exists(&self.keys) as i64)
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        let name = if self.touch { "touch" } else { "exists" };
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+        frame
+    }
+}
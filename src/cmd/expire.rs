@@ -0,0 +1,123 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, ExpireOptions, Expiry},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// `EXPIRE`, `PEXPIRE`, `EXPIREAT` or `PEXPIREAT`, depending on the kind of
+/// `expire`.
+#[derive(Debug)]
+pub struct Expire {
+    key: String,
+    expire: Expiry,
+    options: ExpireOptions,
+}
+
+impl Expire {
+    pub(crate) fn new(key: impl ToString, expire: Expiry, options: ExpireOptions) -> Expire {
+        Expire {
+            key: key.to_string(),
+            expire,
+            options,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The same command with a relative expiration turned into a Unix time,
+    /// the way it is propagated.
+    pub(crate) fn absolute(mut self) -> Expire {
+        self.expire = self.expire.absolute();
+        self
+    }
+
+    /// Parses the arguments, building the expiration with `expiry` from the
+    /// given time.
+    pub(crate) fn parse_frames(
+        parse: &mut Parse,
+        expiry: fn(u64) -> Expiry,
+    ) -> crate::Result<Expire> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        // Negative times are in the past and delete the key, just like `0`.
+        let time = parse.next_signed_int()?.max(0) as u64;
+
+        let mut options = ExpireOptions::new();
+        loop {
+            match parse.next_string() {
+                Ok(option) => match &option.to_uppercase()[..] {
+                    "NX" => options.nx = true,
+                    "XX" => options.xx = true,
+                    "GT" => options.gt = true,
+                    "LT" => options.lt = true,
+                    option => return Err(format!("ERR Unsupported option {}", option).into()),
+                },
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        options.validate()?;
+
+        Ok(Expire {
+            key,
+            expire: expiry(time),
+            options,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.expire(&self.key, self.expire, self.options, self.name()) {
+            Ok(changed) => Frame::Integer(changed as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(self.name().as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        let time = match self.expire {
+            Expiry::Ex(time) | Expiry::Px(time) | Expiry::ExAt(time) | Expiry::PxAt(time) => time,
+            Expiry::KeepTtl | Expiry::Persist => unreachable!(),
+        };
+        frame.push_int(time as i64);
+
+        let options = self.options;
+        for (set, name) in [
+            (options.nx, "nx"),
+            (options.xx, "xx"),
+            (options.gt, "gt"),
+            (options.lt, "lt"),
+        ] {
+            if set {
+                frame.push_bulk(Bytes::from(name.as_bytes()));
+            }
+        }
+        frame
+    }
+
+    fn name(&self) -> &'static str {
+        match self.expire {
+            Expiry::Ex(_) => "expire",
+            Expiry::Px(_) => "pexpire",
+            Expiry::ExAt(_) => "expireat",
+            Expiry::PxAt(_) => "pexpireat",
+            Expiry::KeepTtl | Expiry::Persist => unreachable!("{:?} is not an EXPIRE", self.expire),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::Databases,
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// `FLUSHDB`, or `FLUSHALL` when `all` is set. With `ASYNC`, the removed
+/// values are freed in the background.
+#[derive(Debug)]
+pub struct Flush {
+    all: bool,
+    lazy: bool,
+}
+
+impl Flush {
+    pub fn new(all: bool, lazy: bool) -> Flush {
+        Flush { all, lazy }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, all: bool) -> crate::Result<Flush> {
+        use ParseError::EndOfStream;
+
+        let lazy = match parse.next_string() {
+            Ok(mode) => match &mode.to_uppercase()[..] {
+                "ASYNC" => true,
+                "SYNC" => false,
+                _ => return Err("ERR syntax error".into()),
+            },
+            Err(EndOfStream) => false,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Flush { all, lazy })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        selected: usize,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases, selected);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases, selected: usize) -> Frame {
+        if self.all {
+            databases.flush_all(self.lazy);
+        } else {
+            databases.db(selected).flush(self.lazy);
+        }
+        Frame::Simple("OK".to_string())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        let name = if self.all { "flushall" } else { "flushdb" };
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        if self.lazy {
+            frame.push_bulk(Bytes::from("async".as_bytes()));
+        }
+        frame
+    }
+}
@@ -0,0 +1,78 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    cmd::set::{parse_expiry, push_expiry},
+    db::{Db, Expiry},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Getex {
+    key: String,
+    expire: Option<Expiry>,
+}
+
+impl Getex {
+    pub(crate) fn new(key: impl ToString, expire: Option<Expiry>) -> Getex {
+        Getex {
+            key: key.to_string(),
+            expire,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The same command with a relative expiration turned into a Unix time,
+    /// the way it is propagated.
+    pub(crate) fn absolute(mut self) -> Getex {
+        self.expire = self.expire.map(Expiry::absolute);
+        self
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Getex> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let expire = match parse.next_string() {
+            Ok(option) => match &option.to_uppercase()[..] {
+                "PERSIST" => Some(Expiry::Persist),
+                option => Some(parse_expiry("getex", option, parse)?.ok_or("ERR syntax error")?),
+            },
+            Err(EndOfStream) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Getex { key, expire })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.getex(&self.key, self.expire) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("getex".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        if let Some(expire) = self.expire {
+            push_expiry(&mut frame, expire);
+        }
+        frame
+    }
+}
@@ -0,0 +1,115 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::Databases,
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// Switches the connection to RESP2 or RESP3, replying with information
+/// about the server.
+#[derive(Debug, Default)]
+pub struct Hello {
+    protover: Option<u64>,
+}
+
+impl Hello {
+    pub fn new(protover: Option<u8>) -> Hello {
+        Hello {
+            protover: protover.map(u64::from),
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Hello> {
+        use ParseError::EndOfStream;
+
+        let protover = match parse.next_int() {
+            Ok(protover) => Some(protover),
+            Err(EndOfStream) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        // There are neither users nor client names here, so the options are
+        // only checked for their syntax.
+        loop {
+            match parse.next_string() {
+                Ok(option) => match &option.to_uppercase()[..] {
+                    "AUTH" => {
+                        parse.next_bytes()?;
+                        parse.next_bytes()?;
+                    }
+                    "SETNAME" => {
+                        parse.next_bytes()?;
+                    }
+                    _ => return Err("ERR syntax error".into()),
+                },
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Hello { protover })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = match self.protover {
+            Some(protover) if protover != 2 && protover != 3 => {
+                Frame::Error("NOPROTO unsupported protocol version".to_string())
+            }
+            protover => {
+                if let Some(protover) = protover {
+                    dst.set_protocol(protover as u8);
+                }
+                server_info(dst.protocol(), databases)
+            }
+        };
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("hello".as_bytes()));
+        if let Some(protover) = self.protover {
+            frame.push_int(protover as i64);
+        }
+        frame
+    }
+}
+
+fn server_info(protover: u8, databases: &Databases) -> Frame {
+    let field =
+        |name: &'static str, value| (Frame::Bulk(Bytes::from_static(name.as_bytes())), value);
+    let bulk = |value: &'static str| Frame::Bulk(Bytes::from_static(value.as_bytes()));
+
+    Frame::Map(vec![
+        field("server", bulk("redis")),
+        field("version", bulk(env!("CARGO_PKG_VERSION"))),
+        field("proto", Frame::Integer(protover as i64)),
+        field(
+            "mode",
+            bulk(if databases.cluster_enabled() {
+                "cluster"
+            } else {
+                "standalone"
+            }),
+        ),
+        field(
+            "role",
+            bulk(if databases.read_only() {
+                "replica"
+            } else {
+                "master"
+            }),
+        ),
+        field("modules", Frame::Array(vec![])),
+    ])
+}
@@ -0,0 +1,53 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+#[derive(Debug)]
+pub struct Hexists {
+    key: String,
+    field: String,
+}
+
+impl Hexists {
+    pub fn new(key: impl ToString, field: impl ToString) -> Hexists {
+        Hexists {
+            key: key.to_string(),
+            field: field.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Hexists> {
+        let key = parse.next_string()?;
+        let field = parse.next_string()?;
+        Ok(Hexists { key, field })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hexists(&self.key, &self.field) {
+            Ok(exists) => Frame::Integer(exists as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("hexists".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.field.into_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,57 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+#[derive(Debug)]
+pub struct Hincrby {
+    key: String,
+    field: String,
+    delta: i64,
+}
+
+impl Hincrby {
+    pub fn new(key: impl ToString, field: impl ToString, delta: i64) -> Hincrby {
+        Hincrby {
+            key: key.to_string(),
+            field: field.to_string(),
+            delta,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Hincrby> {
+        let key = parse.next_string()?;
+        let field = parse.next_string()?;
+        let delta = parse.next_signed_int()?;
+        Ok(Hincrby { key, field, delta })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hincrby(&self.key, &self.field, self.delta) {
+            Ok(value) => Frame::Integer(value),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("hincrby".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.field.into_bytes()));
+        frame.push_bulk(Bytes::from(self.delta.to_string()));
+        frame
+    }
+}
@@ -0,0 +1,71 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::Db,
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Hset {
+    key: String,
+    fields: Vec<(String, Bytes)>,
+}
+
+impl Hset {
+    pub fn new(key: impl ToString, fields: Vec<(String, Bytes)>) -> Hset {
+        Hset {
+            key: key.to_string(),
+            fields,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Hset> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let mut fields = vec![(parse.next_string()?, parse.next_bytes()?)];
+
+        loop {
+            match parse.next_string() {
+                Ok(field) => fields.push((field, parse.next_bytes()?)),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Hset { key, fields })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.hset(&self.key, self.fields) {
+            Ok(added) => Frame::Integer(added as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("hset".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        for (field, value) in self.fields {
+            frame.push_bulk(Bytes::from(field.into_bytes()));
+            frame.push_bulk(value);
+        }
+        frame
+    }
+}
@@ -0,0 +1,63 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+/// `INCR`, `DECR`, `INCRBY` and `DECRBY`, which all add a signed `delta`.
+#[derive(Debug)]
+pub struct Incr {
+    key: String,
+    delta: i64,
+}
+
+impl Incr {
+    pub fn new(key: impl ToString, delta: i64) -> Incr {
+        Incr {
+            key: key.to_string(),
+            delta,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Parses the command, reading the delta as an argument when `by` is set.
+    /// A `negate`d delta is subtracted instead.
+    pub(crate) fn parse_frames(parse: &mut Parse, by: bool, negate: bool) -> crate::Result<Incr> {
+        let key = parse.next_string()?;
+        let delta = if by { parse.next_signed_int()? } else { 1 };
+        let delta = if negate {
+            delta.checked_neg().ok_or("ERR decrement would overflow")?
+        } else {
+            delta
+        };
+
+        Ok(Incr { key, delta })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.incr_by(&self.key, self.delta) {
+            Ok(value) => Frame::Integer(value),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    /// Always encodes as `INCRBY`, which covers the other three commands.
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("incrby".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.delta);
+        frame
+    }
+}
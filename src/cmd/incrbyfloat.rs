@@ -0,0 +1,53 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+#[derive(Debug)]
+pub struct Incrbyfloat {
+    key: String,
+    delta: f64,
+}
+
+impl Incrbyfloat {
+    pub fn new(key: impl ToString, delta: f64) -> Incrbyfloat {
+        Incrbyfloat {
+            key: key.to_string(),
+            delta,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Incrbyfloat> {
+        let key = parse.next_string()?;
+        let delta = parse.next_double()?;
+        Ok(Incrbyfloat { key, delta })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.incr_by_float(&self.key, self.delta) {
+            Ok(value) => Frame::Bulk(value),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("incrbyfloat".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.delta.to_string()));
+        frame
+    }
+}
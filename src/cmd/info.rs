@@ -0,0 +1,70 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, Connection, Frame};
+
+/// Reports on the server as `field:value` lines grouped in sections. Only
+/// the `replication`, `stats` and `cluster` sections are known.
+#[derive(Debug, Default)]
+pub struct Info {
+    sections: Vec<String>,
+}
+
+impl Info {
+    /// Reports the given `sections`, or all of them when empty.
+    pub fn new(sections: Vec<String>) -> Info {
+        Info { sections }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Info> {
+        let sections = parse
+            .remaining_strings()?
+            .into_iter()
+            .map(|section| section.to_lowercase())
+            .collect();
+        Ok(Info { sections })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases) -> Frame {
+        let all = self.sections.is_empty()
+            || self
+                .sections
+                .iter()
+                .any(|section| matches!(&section[..], "all" | "default" | "everything"));
+        let wants = |name: &str| all || self.sections.iter().any(|section| section == name);
+
+        let mut sections = vec![];
+        if wants("replication") {
+            sections.push(databases.replication_info());
+        }
+        if wants("stats") {
+            sections.push(databases.stats_info());
+        }
+        if wants("cluster") {
+            sections.push(databases.cluster_enabled_info());
+        }
+        Frame::Bulk(Bytes::from(sections.join("\r\n")))
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("info".as_bytes()));
+        for section in self.sections {
+            frame.push_bulk(Bytes::from(section.into_bytes()));
+        }
+        frame
+    }
+}
@@ -0,0 +1,41 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, Connection, Frame};
+
+/// The Unix time of the last successful `SAVE` or `BGSAVE`.
+#[derive(Debug, Default)]
+pub struct Lastsave;
+
+impl Lastsave {
+    pub fn new() -> Lastsave {
+        Lastsave
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Lastsave> {
+        Ok(Lastsave)
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases) -> Frame {
+        Frame::Integer(databases.last_save() as i64)
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("lastsave".as_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,57 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+#[derive(Debug)]
+pub struct Lrem {
+    key: String,
+    count: i64,
+    value: Bytes,
+}
+
+impl Lrem {
+    pub fn new(key: impl ToString, count: i64, value: Bytes) -> Lrem {
+        Lrem {
+            key: key.to_string(),
+            count,
+            value,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Lrem> {
+        let key = parse.next_string()?;
+        let count = parse.next_signed_int()?;
+        let value = parse.next_bytes()?;
+        Ok(Lrem { key, count, value })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.lrem(&self.key, self.count, &self.value) {
+            Ok(removed) => Frame::Integer(removed as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("lrem".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.count.to_string()));
+        frame.push_bulk(self.value);
+        frame
+    }
+}
@@ -0,0 +1,63 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, Connection, Frame};
+
+/// `MOVE`, named so to stay clear of the keyword. Moves a key from the
+/// selected database to another one.
+#[derive(Debug)]
+pub struct Move {
+    key: String,
+    index: i64,
+}
+
+impl Move {
+    pub fn new(key: impl ToString, index: i64) -> Move {
+        Move {
+            key: key.to_string(),
+            index,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Move> {
+        let key = parse.next_string()?;
+        let index = parse.next_signed_int()?;
+        Ok(Move { key, index })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        selected: usize,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases, selected);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases, selected: usize) -> Frame {
+        let moved = databases
+            .index(self.index)
+            .and_then(|index| databases.move_key(&self.key, selected, index));
+        match moved {
+            Ok(moved) => Frame::Integer(moved as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("move".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.index);
+        frame
+    }
+}
@@ -0,0 +1,69 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::Db,
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// `MSET`, or `MSETNX` when `nx` is set.
+#[derive(Debug)]
+pub struct Mset {
+    pairs: Vec<(String, Bytes)>,
+    nx: bool,
+}
+
+impl Mset {
+    pub fn new(pairs: Vec<(String, Bytes)>, nx: bool) -> Mset {
+        Mset { pairs, nx }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.pairs.iter().map(|(key, _)| &key[..])
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, nx: bool) -> crate::Result<Mset> {
+        use ParseError::EndOfStream;
+
+        let mut pairs = vec![(parse.next_string()?, parse.next_bytes()?)];
+        loop {
+            match parse.next_string() {
+                Ok(key) => pairs.push((key, parse.next_bytes()?)),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Mset { pairs, nx })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let written = db.mset(self.pairs, self.nx);
+        if self.nx {
+            Frame::Integer(written as i64)
+        } else {
+            Frame::Simple("OK".to_string())
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        let name = if self.nx { "msetnx" } else { "mset" };
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        for (key, value) in self.pairs {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+            frame.push_bulk(value);
+        }
+        frame
+    }
+}
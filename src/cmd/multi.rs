@@ -0,0 +1,184 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Databases, Db},
+    parse::Parse,
+    Command, Connection, Frame,
+};
+
+/// Starts a transaction: the following commands are queued until `EXEC`
+/// runs them all at once, or `DISCARD` drops them.
+#[derive(Debug, Default)]
+pub struct Multi;
+
+/// The transaction state of a connection, the commands queued since `MULTI`
+/// and the keys under `WATCH`.
+#[derive(Debug, Default)]
+pub(crate) struct MultiState {
+    /// `Some` between `MULTI` and `EXEC` or `DISCARD`.
+    queued: Option<Vec<Command>>,
+    /// Set when a command could not be queued, which fails the `EXEC`.
+    failed: bool,
+    watched: Vec<WatchedKey>,
+}
+
+/// A key under `WATCH`, unwatched when dropped.
+#[derive(Debug)]
+struct WatchedKey {
+    db: Db,
+    index: usize,
+    key: String,
+    version: u64,
+}
+
+impl Multi {
+    pub fn new() -> Multi {
+        Multi
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Multi> {
+        Ok(Multi)
+    }
+
+    #[instrument(skip(self, transaction, dst))]
+    pub(crate) async fn apply(
+        self,
+        transaction: &mut MultiState,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = if transaction.is_queuing() {
+            Frame::Error("ERR MULTI calls can not be nested".to_string())
+        } else {
+            transaction.queued = Some(vec![]);
+            Frame::Simple("OK".to_string())
+        };
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("multi".as_bytes()));
+        frame
+    }
+}
+
+impl MultiState {
+    pub(crate) fn is_queuing(&self) -> bool {
+        self.queued.is_some()
+    }
+
+    /// Queues `cmd` for `EXEC`. Commands that can't run inside a transaction
+    /// are refused instead, failing the whole transaction like Redis does.
+    pub(crate) async fn queue(&mut self, cmd: Command, dst: &mut Connection) -> crate::Result<()> {
+        let response = match cmd {
+            Command::Unknown(cmd) => {
+                self.failed = true;
+                cmd.execute()
+            }
+            Command::Subscribe(_)
+            | Command::Unsubscribe(_)
+            | Command::Psubscribe(_)
+            | Command::Punsubscribe(_)
+            | Command::Hello(_)
+            | Command::Psync(_)
+            | Command::Replconf(_)
+            | Command::Wait(_)
+            | Command::Asking(_) => {
+                self.failed = true;
+                Frame::Error("ERR Command not allowed inside a transaction".to_string())
+            }
+            cmd => {
+                self.queued.get_or_insert_with(Vec::new).push(cmd);
+                Frame::Simple("QUEUED".to_string())
+            }
+        };
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Replies `response` to a command that is refused, failing the
+    /// transaction if one is queuing.
+    pub(crate) async fn refuse(
+        &mut self,
+        response: Frame,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        if self.is_queuing() {
+            self.failed = true;
+        }
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Runs the queued commands while holding every database lock. Replies
+    /// `Null` without running anything if a watched key was modified.
+    pub(crate) fn exec(&mut self, databases: &Databases, selected: &mut usize) -> Frame {
+        let queued = match self.queued.take() {
+            Some(queued) => queued,
+            None => return Frame::Error("ERR EXEC without MULTI".to_string()),
+        };
+        // Dropped, and so unwatched, once the locks are released.
+        let watched = std::mem::take(&mut self.watched);
+        if std::mem::take(&mut self.failed) {
+            return Frame::Error(
+                "EXECABORT Transaction discarded because of previous errors.".to_string(),
+            );
+        }
+
+        databases.exclusive(|databases| {
+            let modified = watched.iter().any(|watched| {
+                databases
+                    .db(watched.index)
+                    .modified_since(&watched.key, watched.version)
+            });
+            if modified {
+                return Frame::Null;
+            }
+
+            let replies = queued
+                .into_iter()
+                .map(|cmd| cmd.execute(databases, selected))
+                .collect();
+            Frame::Array(replies)
+        })
+    }
+
+    pub(crate) fn discard(&mut self) -> Frame {
+        if self.queued.take().is_none() {
+            return Frame::Error("ERR DISCARD without MULTI".to_string());
+        }
+        self.failed = false;
+        self.watched.clear();
+        Frame::Simple("OK".to_string())
+    }
+
+    /// Watches `key` of the database at `index`.
+    pub(crate) fn watch(&mut self, databases: &Databases, index: usize, key: String) {
+        let db = databases.db(index).clone();
+        let version = db.watch(&key);
+        self.watched.push(WatchedKey {
+            db,
+            index,
+            key,
+            version,
+        });
+    }
+
+    pub(crate) fn unwatch(&mut self) {
+        self.watched.clear();
+    }
+}
+
+impl Drop for WatchedKey {
+    fn drop(&mut self) {
+        self.db.unwatch(&self.key);
+    }
+}
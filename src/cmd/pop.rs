@@ -0,0 +1,78 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, Side},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// `LPOP` / `RPOP`, depending on `side`.
+#[derive(Debug)]
+pub struct Pop {
+    key: String,
+    count: Option<u64>,
+    side: Side,
+}
+
+impl Pop {
+    pub(crate) fn new(key: impl ToString, count: Option<u64>, side: Side) -> Pop {
+        Pop {
+            key: key.to_string(),
+            count,
+            side,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, side: Side) -> crate::Result<Pop> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let count = match parse.next_int() {
+            Ok(count) => Some(count),
+            Err(EndOfStream) => None,
+            Err(_) => return Err("ERR value is out of range, must be positive".into()),
+        };
+
+        Ok(Pop { key, count, side })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let count = self.count.unwrap_or(1) as usize;
+        match db.pop(&self.key, self.side, count) {
+            Ok(Some(values)) if self.count.is_some() => {
+                Frame::Array(values.into_iter().map(Frame::Bulk).collect())
+            }
+            Ok(Some(mut values)) => values.pop().map(Frame::Bulk).unwrap_or(Frame::Null),
+            Ok(None) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        let name = match self.side {
+            Side::Left => "lpop",
+            Side::Right => "rpop",
+        };
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        if let Some(count) = self.count {
+            frame.push_int(count as i64);
+        }
+        frame
+    }
+}
@@ -0,0 +1,108 @@
+use bytes::Bytes;
+use tokio::select;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Databases, Resync},
+    parse::Parse,
+    shutdown::Shutdown,
+    Command, Connection, Frame,
+};
+
+/// Sent by replicas to start receiving the replication stream, from the
+/// history and offset they left off at if possible, see `Databases::psync`.
+#[derive(Debug)]
+pub struct Psync {
+    replid: String,
+    /// The offset of the next byte of the stream the replica needs, or `-1`
+    /// to start over.
+    offset: i64,
+}
+
+impl Psync {
+    pub(crate) fn new(replid: String, offset: i64) -> Psync {
+        Psync { replid, offset }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Psync> {
+        let replid = parse.next_string()?;
+        let offset = parse
+            .next_string()?
+            .parse()
+            .map_err(|_| "ERR value is not an integer or out of range")?;
+        Ok(Psync { replid, offset })
+    }
+
+    /// Turns the connection into a replica's: sends it what it misses of the
+    /// stream, then the stream as it grows, until the connection is closed.
+    /// Meanwhile the replica acknowledges what it received.
+    #[instrument(skip(self, databases, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let received = u64::try_from(self.offset - 1).ok();
+        let (id, mut sent) = match databases.psync(&self.replid, received).await? {
+            Resync::Partial { id, offset } => {
+                let response = Frame::Simple(format!("CONTINUE {}", id));
+                debug!(?response);
+                dst.write_frame(&response).await?;
+                (id, offset)
+            }
+            Resync::Full {
+                id,
+                offset,
+                snapshot,
+            } => {
+                let response = Frame::Simple(format!("FULLRESYNC {} {}", id, offset));
+                debug!(?response);
+                dst.write_frame(&response).await?;
+                dst.write_frame(&Frame::Bulk(snapshot)).await?;
+                (id, offset)
+            }
+        };
+
+        let port = dst.listening_port().unwrap_or_default();
+        let replica = databases.add_replica(dst.peer_addr()?.ip(), port);
+        let mut end = databases.stream_offset();
+        loop {
+            let Some(stream) = databases.stream_since(&id, sent) else {
+                return Err("replica fell behind the replication backlog".into());
+            };
+            if !stream.is_empty() {
+                dst.write_raw(&stream).await?;
+                sent += stream.len() as u64;
+            }
+
+            select! {
+                res = end.changed() => {
+                    if res.is_err() {
+                        return Ok(());
+                    }
+                }
+                res = dst.read_frame() => {
+                    let Some(frame) = res? else {
+                        return Ok(());
+                    };
+                    // Replicas only send acknowledgements from here on.
+                    if let Ok(Command::Replconf(cmd)) = Command::from_frame(frame) {
+                        if let Some(offset) = cmd.acked() {
+                            replica.acked(offset);
+                        }
+                    }
+                }
+                _ = shutdown.recv() => return Ok(()),
+            }
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("psync".as_bytes()));
+        frame.push_bulk(Bytes::from(self.replid.into_bytes()));
+        frame.push_bulk(Bytes::from(self.offset.to_string().into_bytes()));
+        frame
+    }
+}
@@ -23,14 +23,18 @@ impl Publish {
     }
 
     pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
-        let num_subscribers = db.publish(&self.channel, self.message);
-        let response = Frame::Integer(num_subscribers as u64);
+        let response = self.execute(db);
 
         dst.write_frame(&response).await?;
 
         Ok(())
     }
 
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let num_subscribers = db.publish(&self.channel, self.message);
+        Frame::Integer(num_subscribers as i64)
+    }
+
     pub(crate) fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
         frame.push_bulk(Bytes::from("publish".as_bytes()));
@@ -0,0 +1,136 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::Db,
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// The `CHANNELS`, `NUMSUB` and `NUMPAT` subcommands of `PUBSUB`, which
+/// report on the active subscriptions, and `DROPPED`, which is specific to
+/// mini-redis and reports the messages slow subscribers missed.
+#[derive(Debug)]
+pub struct Pubsub {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Channels(Option<String>),
+    Numsub(Vec<String>),
+    Numpat,
+    Dropped(Vec<String>),
+}
+
+impl Pubsub {
+    pub fn channels(pattern: Option<String>) -> Pubsub {
+        Pubsub {
+            subcommand: Subcommand::Channels(pattern),
+        }
+    }
+
+    pub fn numsub(channels: Vec<String>) -> Pubsub {
+        Pubsub {
+            subcommand: Subcommand::Numsub(channels),
+        }
+    }
+
+    pub fn numpat() -> Pubsub {
+        Pubsub {
+            subcommand: Subcommand::Numpat,
+        }
+    }
+
+    /// Reports the messages dropped by slow subscribers of each of `names`,
+    /// channels or patterns.
+    pub fn dropped(names: Vec<String>) -> Pubsub {
+        Pubsub {
+            subcommand: Subcommand::Dropped(names),
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Pubsub> {
+        use ParseError::EndOfStream;
+
+        let subcommand = match &parse.next_string()?.to_uppercase()[..] {
+            "CHANNELS" => match parse.next_string() {
+                Ok(pattern) => Subcommand::Channels(Some(pattern)),
+                Err(EndOfStream) => Subcommand::Channels(None),
+                Err(err) => return Err(err.into()),
+            },
+            "NUMSUB" => Subcommand::Numsub(parse.remaining_strings()?),
+            "NUMPAT" => Subcommand::Numpat,
+            "DROPPED" => Subcommand::Dropped(parse.remaining_strings()?),
+            subcommand => return Err(format!("ERR unknown subcommand '{}'", subcommand).into()),
+        };
+
+        Ok(Pubsub { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self.subcommand {
+            Subcommand::Channels(pattern) => Frame::Array(
+                db.channels(pattern.as_deref())
+                    .into_iter()
+                    .map(|channel| Frame::Bulk(Bytes::from(channel.into_bytes())))
+                    .collect(),
+            ),
+            Subcommand::Numsub(channels) => {
+                let mut frame = Frame::array();
+                for channel in channels {
+                    let num_subscribers = db.num_subscribers(&channel);
+                    frame.push_bulk(Bytes::from(channel.into_bytes()));
+                    frame.push_int(num_subscribers as i64);
+                }
+                frame
+            }
+            Subcommand::Numpat => Frame::Integer(db.num_patterns() as i64),
+            Subcommand::Dropped(names) => {
+                let mut frame = Frame::array();
+                for name in names {
+                    let dropped = db.dropped_messages(&name);
+                    frame.push_bulk(Bytes::from(name.into_bytes()));
+                    frame.push_int(dropped as i64);
+                }
+                frame
+            }
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("pubsub".as_bytes()));
+        match self.subcommand {
+            Subcommand::Channels(pattern) => {
+                frame.push_bulk(Bytes::from("channels".as_bytes()));
+                if let Some(pattern) = pattern {
+                    frame.push_bulk(Bytes::from(pattern.into_bytes()));
+                }
+            }
+            Subcommand::Numsub(channels) => {
+                frame.push_bulk(Bytes::from("numsub".as_bytes()));
+                for channel in channels {
+                    frame.push_bulk(Bytes::from(channel.into_bytes()));
+                }
+            }
+            Subcommand::Numpat => frame.push_bulk(Bytes::from("numpat".as_bytes())),
+            Subcommand::Dropped(names) => {
+                frame.push_bulk(Bytes::from("dropped".as_bytes()));
+                for name in names {
+                    frame.push_bulk(Bytes::from(name.into_bytes()));
+                }
+            }
+        }
+        frame
+    }
+}
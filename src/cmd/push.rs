@@ -0,0 +1,68 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, Side},
+    parse::Parse,
+    Connection, Frame,
+};
+
+/// `LPUSH` / `RPUSH`, depending on `side`.
+#[derive(Debug)]
+pub struct Push {
+    key: String,
+    values: Vec<Bytes>,
+    side: Side,
+}
+
+impl Push {
+    pub(crate) fn new(key: impl ToString, values: Vec<Bytes>, side: Side) -> Push {
+        Push {
+            key: key.to_string(),
+            values,
+            side,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, side: Side) -> crate::Result<Push> {
+        let key = parse.next_string()?;
+        let mut values = vec![parse.next_bytes()?];
+        values.extend(parse.remaining_bytes()?);
+
+        Ok(Push { key, values, side })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.push(&self.key, self.values, self.side) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        let name = match self.side {
+            Side::Left => "lpush",
+            Side::Right => "rpush",
+        };
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        for value in self.values {
+            frame.push_bulk(value);
+        }
+        frame
+    }
+}
@@ -0,0 +1,62 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+/// `RENAME`, or `RENAMENX` when `nx` is set.
+#[derive(Debug)]
+pub struct Rename {
+    key: String,
+    newkey: String,
+    nx: bool,
+}
+
+impl Rename {
+    pub fn new(key: impl ToString, newkey: impl ToString, nx: bool) -> Rename {
+        Rename {
+            key: key.to_string(),
+            newkey: newkey.to_string(),
+            nx,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn newkey(&self) -> &str {
+        &self.newkey
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, nx: bool) -> crate::Result<Rename> {
+        let key = parse.next_string()?;
+        let newkey = parse.next_string()?;
+        Ok(Rename { key, newkey, nx })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.rename(&self.key, &self.newkey, self.nx) {
+            Ok(renamed) if self.nx => Frame::Integer(renamed as i64),
+            Ok(_) => Frame::Simple("OK".to_string()),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        let name = if self.nx { "renamenx" } else { "rename" };
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.newkey.into_bytes()));
+        frame
+    }
+}
@@ -0,0 +1,100 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// Sent by replicas to tell their master about themselves before `PSYNC`,
+/// and then to acknowledge what they received of the replication stream.
+#[derive(Debug)]
+pub struct Replconf {
+    options: Vec<(String, String)>,
+}
+
+impl Replconf {
+    /// Tells the master the port the replica accepts connections on.
+    pub(crate) fn listening_port(port: u16) -> Replconf {
+        Replconf {
+            options: vec![("listening-port".to_string(), port.to_string())],
+        }
+    }
+
+    /// Acknowledges that the replica received the stream up to `offset`.
+    pub(crate) fn ack(offset: u64) -> Replconf {
+        Replconf {
+            options: vec![("ack".to_string(), offset.to_string())],
+        }
+    }
+
+    /// Asks replicas to acknowledge what they received right away, see
+    /// `WAIT`.
+    pub(crate) fn getack() -> Replconf {
+        Replconf {
+            options: vec![("getack".to_string(), "*".to_string())],
+        }
+    }
+
+    /// The offset acknowledged by a replica, if this is `REPLCONF ACK`.
+    pub(crate) fn acked(&self) -> Option<u64> {
+        match &self.options[..] {
+            [(option, offset)] if option == "ack" => offset.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Whether this is `REPLCONF GETACK`.
+    pub(crate) fn is_getack(&self) -> bool {
+        matches!(&self.options[..], [(option, _)] if option == "getack")
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Replconf> {
+        use ParseError::EndOfStream;
+
+        let mut options = vec![];
+        loop {
+            let option = match parse.next_string() {
+                Ok(option) => option.to_lowercase(),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            };
+            let value = parse.next_string().map_err(|_| "ERR syntax error")?;
+            options.push((option, value));
+        }
+        Ok(Replconf { options })
+    }
+
+    #[instrument(skip(self, dst))]
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(dst);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Records the options about the replica on its connection. Those that
+    /// are unknown, like the capabilities, are ignored.
+    fn execute(self, dst: &mut Connection) -> Frame {
+        for (option, value) in self.options {
+            if option == "listening-port" {
+                match value.parse() {
+                    Ok(port) => dst.set_listening_port(port),
+                    Err(_) => return Frame::Error("ERR Invalid listening port".to_string()),
+                }
+            }
+        }
+        Frame::Simple("OK".to_string())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("replconf".as_bytes()));
+        for (option, value) in self.options {
+            frame.push_bulk(Bytes::from(option.into_bytes()));
+            frame.push_bulk(Bytes::from(value.into_bytes()));
+        }
+        frame
+    }
+}
@@ -0,0 +1,77 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, Connection, Frame};
+
+/// Makes the server a replica of another one, or a master again with
+/// `REPLICAOF NO ONE`.
+#[derive(Debug)]
+pub struct Replicaof {
+    master: Option<(String, u16)>,
+}
+
+impl Replicaof {
+    /// Replicates the server at `host` and `port`, or stops replicating when
+    /// `master` is `None`.
+    pub fn new(master: Option<(String, u16)>) -> Replicaof {
+        Replicaof { master }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Replicaof> {
+        let host = parse.next_string()?;
+        let port = parse.next_string()?;
+        if host.eq_ignore_ascii_case("no") && port.eq_ignore_ascii_case("one") {
+            return Ok(Replicaof { master: None });
+        }
+
+        let port = port.parse().map_err(|_| "ERR Invalid master port")?;
+        Ok(Replicaof {
+            master: Some((host, port)),
+        })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases) -> Frame {
+        match self.master {
+            Some((host, port)) => {
+                if databases.replicaof(host, port) {
+                    Frame::Simple("OK".to_string())
+                } else {
+                    Frame::Simple("OK Already connected to specified master".to_string())
+                }
+            }
+            None => {
+                databases.replicaof_no_one();
+                Frame::Simple("OK".to_string())
+            }
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("replicaof".as_bytes()));
+        match self.master {
+            Some((host, port)) => {
+                frame.push_bulk(Bytes::from(host.into_bytes()));
+                frame.push_bulk(Bytes::from(port.to_string().into_bytes()));
+            }
+            None => {
+                frame.push_bulk(Bytes::from("no".as_bytes()));
+                frame.push_bulk(Bytes::from("one".as_bytes()));
+            }
+        }
+        frame
+    }
+}
@@ -0,0 +1,62 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, Connection, Frame};
+
+/// `SAVE`, or `BGSAVE` when `background` is set, which write a snapshot of
+/// every database to the configured file.
+#[derive(Debug)]
+pub struct Save {
+    background: bool,
+}
+
+impl Save {
+    pub fn new(background: bool) -> Save {
+        Save { background }
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse, background: bool) -> crate::Result<Save> {
+        Ok(Save { background })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = if self.background {
+            self.execute(databases)
+        } else {
+            match databases.save().await {
+                Ok(()) => Frame::Simple("OK".to_string()),
+                Err(err) => Frame::Error(err.to_string()),
+            }
+        };
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases) -> Frame {
+        if self.background {
+            match databases.bgsave() {
+                Ok(()) => Frame::Simple("Background saving started".to_string()),
+                Err(err) => Frame::Error(err.to_string()),
+            }
+        } else {
+            match databases.save_in_place() {
+                Ok(()) => Frame::Simple("OK".to_string()),
+                Err(err) => Frame::Error(err.to_string()),
+            }
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        let name = if self.background { "bgsave" } else { "save" };
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        frame
+    }
+}
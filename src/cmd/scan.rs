@@ -0,0 +1,169 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{format_score, Db, ScanOptions},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// What a `Scan` iterates over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ScanKind {
+    /// `SCAN`, the keyspace.
+    Keys,
+    /// `HSCAN`, the fields and values of a hash.
+    Hash,
+    /// `SSCAN`, the members of a set.
+    Set,
+    /// `ZSCAN`, the members and scores of a sorted set.
+    ZSet,
+}
+
+/// `SCAN`, `HSCAN`, `SSCAN` or `ZSCAN`. Replies with the next cursor and a
+/// page of elements, the cursor is `0` once the iteration is complete.
+#[derive(Debug)]
+pub struct Scan {
+    kind: ScanKind,
+    key: Option<String>,
+    cursor: u64,
+    options: ScanOptions,
+}
+
+impl Scan {
+    /// `key` is required by every kind but `ScanKind::Keys`.
+    pub(crate) fn new(
+        kind: ScanKind,
+        key: Option<String>,
+        cursor: u64,
+        options: ScanOptions,
+    ) -> Scan {
+        Scan {
+            kind,
+            key,
+            cursor,
+            options,
+        }
+    }
+
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, kind: ScanKind) -> crate::Result<Scan> {
+        use ParseError::EndOfStream;
+
+        let key = match kind {
+            ScanKind::Keys => None,
+            _ => Some(parse.next_string()?),
+        };
+        let cursor = parse
+            .next_string()?
+            .parse()
+            .map_err(|_| "ERR invalid cursor")?;
+
+        let mut options = ScanOptions::new();
+        loop {
+            let option = match parse.next_string() {
+                Ok(option) => option.to_uppercase(),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            };
+            match &option[..] {
+                "COUNT" => options.count = Some(parse.next_int()? as usize),
+                "MATCH" => options.pattern = Some(parse.next_bytes()?),
+                "TYPE" if kind == ScanKind::Keys => options.key_type = Some(parse.next_string()?),
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+        options.validate()?;
+
+        Ok(Scan {
+            kind,
+            key,
+            cursor,
+            options,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self.page(db) {
+            Ok((cursor, elements)) => Frame::Array(vec![
+                Frame::Bulk(Bytes::from(cursor.to_string())),
+                Frame::Array(elements.into_iter().map(Frame::Bulk).collect()),
+            ]),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let name = match self.kind {
+            ScanKind::Keys => "scan",
+            ScanKind::Hash => "hscan",
+            ScanKind::Set => "sscan",
+            ScanKind::ZSet => "zscan",
+        };
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        if let Some(key) = self.key {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+        frame.push_bulk(Bytes::from(self.cursor.to_string()));
+
+        let options = self.options;
+        if let Some(count) = options.count {
+            frame.push_bulk(Bytes::from("count".as_bytes()));
+            frame.push_int(count as i64);
+        }
+        if let Some(pattern) = options.pattern {
+            frame.push_bulk(Bytes::from("match".as_bytes()));
+            frame.push_bulk(pattern);
+        }
+        if let Some(key_type) = options.key_type {
+            frame.push_bulk(Bytes::from("type".as_bytes()));
+            frame.push_bulk(Bytes::from(key_type.into_bytes()));
+        }
+        frame
+    }
+
+    /// Runs the scan, flattening the page into the elements of the reply.
+    fn page(&self, db: &Db) -> crate::Result<(u64, Vec<Bytes>)> {
+        let key = self.key.as_deref().unwrap_or_default();
+        let (cursor, elements) = match self.kind {
+            ScanKind::Keys => {
+                let (cursor, keys) = db.scan(self.cursor, &self.options);
+                let keys = keys.into_iter().map(|key| Bytes::from(key.into_bytes()));
+                (cursor, keys.collect())
+            }
+            ScanKind::Hash => {
+                let (cursor, fields) = db.hscan(key, self.cursor, &self.options)?;
+                let fields = fields
+                    .into_iter()
+                    .flat_map(|(field, value)| [Bytes::from(field.into_bytes()), value]);
+                (cursor, fields.collect())
+            }
+            ScanKind::Set => db.sscan(key, self.cursor, &self.options)?,
+            ScanKind::ZSet => {
+                let (cursor, members) = db.zscan(key, self.cursor, &self.options)?;
+                let members = members
+                    .into_iter()
+                    .flat_map(|(member, score)| [member, Bytes::from(format_score(score))]);
+                (cursor, members.collect())
+            }
+        };
+        Ok((cursor, elements))
+    }
+}
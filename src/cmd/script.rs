@@ -0,0 +1,135 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::Databases,
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// The `LOAD`, `EXISTS` and `FLUSH` subcommands of `SCRIPT`, which manage
+/// the scripts cached for `EVALSHA`, and `KILL`, which stops the script
+/// running.
+#[derive(Debug)]
+pub struct Script {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Load(Bytes),
+    Exists(Vec<String>),
+    Flush,
+    Kill,
+}
+
+impl Script {
+    pub fn load(body: Bytes) -> Script {
+        Script {
+            subcommand: Subcommand::Load(body),
+        }
+    }
+
+    pub fn exists(shas: Vec<String>) -> Script {
+        Script {
+            subcommand: Subcommand::Exists(shas),
+        }
+    }
+
+    pub fn flush() -> Script {
+        Script {
+            subcommand: Subcommand::Flush,
+        }
+    }
+
+    /// Kills the script running, unless it changed keys.
+    pub fn kill() -> Script {
+        Script {
+            subcommand: Subcommand::Kill,
+        }
+    }
+
+    pub(crate) fn is_kill(&self) -> bool {
+        matches!(self.subcommand, Subcommand::Kill)
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Script> {
+        use ParseError::EndOfStream;
+
+        let subcommand = match &parse.next_string()?.to_uppercase()[..] {
+            "LOAD" => Subcommand::Load(parse.next_bytes()?),
+            "EXISTS" => {
+                let mut shas = vec![parse.next_string()?];
+                shas.extend(parse.remaining_strings()?);
+                Subcommand::Exists(shas)
+            }
+            "FLUSH" => {
+                // The cache is always flushed right away.
+                match parse.next_string() {
+                    Ok(mode) if ["ASYNC", "SYNC"].contains(&&mode.to_uppercase()[..]) => {}
+                    Ok(_) => return Err("ERR syntax error".into()),
+                    Err(EndOfStream) => {}
+                    Err(err) => return Err(err.into()),
+                }
+                Subcommand::Flush
+            }
+            "KILL" => Subcommand::Kill,
+            subcommand => return Err(format!("ERR unknown subcommand '{}'", subcommand).into()),
+        };
+
+        Ok(Script { subcommand })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases) -> Frame {
+        let scripts = databases.scripts();
+        match self.subcommand {
+            Subcommand::Load(body) => Frame::Bulk(Bytes::from(scripts.load(body).into_bytes())),
+            Subcommand::Exists(shas) => Frame::Array(
+                shas.iter()
+                    .map(|sha| Frame::Integer(scripts.exists(sha) as i64))
+                    .collect(),
+            ),
+            Subcommand::Flush => {
+                scripts.flush();
+                Frame::Simple("OK".to_string())
+            }
+            Subcommand::Kill => match scripts.kill() {
+                Ok(()) => Frame::Simple("OK".to_string()),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("script".as_bytes()));
+        match self.subcommand {
+            Subcommand::Load(body) => {
+                frame.push_bulk(Bytes::from("load".as_bytes()));
+                frame.push_bulk(body);
+            }
+            Subcommand::Exists(shas) => {
+                frame.push_bulk(Bytes::from("exists".as_bytes()));
+                for sha in shas {
+                    frame.push_bulk(Bytes::from(sha.into_bytes()));
+                }
+            }
+            Subcommand::Flush => frame.push_bulk(Bytes::from("flush".as_bytes())),
+            Subcommand::Kill => frame.push_bulk(Bytes::from("kill".as_bytes())),
+        }
+        frame
+    }
+}
@@ -0,0 +1,56 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, Connection, Frame};
+
+/// Switches the connection to another database.
+#[derive(Debug)]
+pub struct Select {
+    index: i64,
+}
+
+impl Select {
+    pub fn new(index: i64) -> Select {
+        Select { index }
+    }
+
+    pub fn index(&self) -> i64 {
+        self.index
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Select> {
+        let index = parse.next_signed_int()?;
+        Ok(Select { index })
+    }
+
+    #[instrument(skip(self, databases, selected, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        selected: &mut usize,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases, selected);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases, selected: &mut usize) -> Frame {
+        match databases.index(self.index) {
+            Ok(index) => {
+                *selected = index;
+                Frame::Simple("OK".to_string())
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("select".as_bytes()));
+        frame.push_int(self.index);
+        frame
+    }
+}
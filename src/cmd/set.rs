@@ -4,7 +4,7 @@ use bytes::Bytes;
 use tracing::{debug, instrument};
 
 use crate::{
-    db::Db,
+    db::{Db, Expiry, SetOptions},
     parse::{Parse, ParseError},
     Connection, Frame,
 };
@@ -13,15 +13,23 @@ use crate::{
 pub struct Set {
     key: String,
     value: Bytes,
-    expire: Option<Duration>,
+    options: SetOptions,
 }
 
 impl Set {
     pub fn new(key: impl ToString, value: Bytes, expire: Option<Duration>) -> Set {
+        let options = match expire {
+            Some(expire) => SetOptions::new().px(expire.as_millis() as u64),
+            None => SetOptions::new(),
+        };
+        Set::with_options(key, value, options)
+    }
+
+    pub fn with_options(key: impl ToString, value: Bytes, options: SetOptions) -> Set {
         Set {
             key: key.to_string(),
             value,
-            expire,
+            options,
         }
     }
 
@@ -33,8 +41,20 @@ impl Set {
         &self.value
     }
 
+    /// The relative expiration of the key, if it was given with `EX` or `PX`.
     pub fn expire(&self) -> Option<Duration> {
-        self.expire
+        match self.options.expire {
+            Some(Expiry::Ex(secs)) => Some(Duration::from_secs(secs)),
+            Some(Expiry::Px(ms)) => Some(Duration::from_millis(ms)),
+            _ => None,
+        }
+    }
+
+    /// The same command with a relative expiration turned into a Unix time,
+    /// the way it is propagated.
+    pub(crate) fn absolute(mut self) -> Set {
+        self.options.expire = self.options.expire.map(Expiry::absolute);
+        self
     }
 
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Set> {
@@ -43,45 +63,118 @@ impl Set {
         let key = parse.next_string()?;
         let value = parse.next_bytes()?;
 
-        let mut expire = None;
-
-        match parse.next_string() {
-            Ok(s) if s.to_uppercase() == "EX" => {
-                let secs = parse.next_int()?;
-                expire = Some(Duration::from_secs(secs));
-            }
-            Ok(s) if s.to_uppercase() == "PX" => {
-                let ms = parse.next_int()?;
-                expire = Some(Duration::from_millis(ms));
+        let mut options = SetOptions::new();
+        loop {
+            let option = match parse.next_string() {
+                Ok(option) => option.to_uppercase(),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            };
+
+            let expire = match &option[..] {
+                "NX" => {
+                    options = options.nx();
+                    continue;
+                }
+                "XX" => {
+                    options = options.xx();
+                    continue;
+                }
+                "GET" => {
+                    options = options.get();
+                    continue;
+                }
+                "KEEPTTL" => Expiry::KeepTtl,
+                option => parse_expiry("set", option, parse)?.ok_or("ERR syntax error")?,
+            };
+            // Only one of the expiration options may be given.
+            if options.expire.replace(expire).is_some() {
+                return Err("ERR syntax error".into());
             }
-            Ok(_) => return Err("currently `SET` only supports the expiration option".into()),
-            Err(EndOfStream) => {}
-            Err(err) => return Err(err.into()),
         }
+        options.validate()?;
 
-        Ok(Self { key, value, expire })
+        Ok(Set {
+            key,
+            value,
+            options,
+        })
     }
 
     #[instrument(skip(self, db, dst))]
     pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
-        db.set(self.key, self.value, self.expire);
-        let response = Frame::Simple("OK".to_string());
+        let response = self.execute(db);
         debug!(?response);
         dst.write_frame(&response).await?;
 
         Ok(())
     }
 
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.set(self.key, self.value, self.options) {
+            Ok((_, Some(prev))) if self.options.get => Frame::Bulk(prev),
+            Ok((_, None)) if self.options.get => Frame::Null,
+            Ok((true, _)) => Frame::Simple("OK".to_string()),
+            Ok((false, _)) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
     pub(crate) fn into_frame(self) -> Frame {
         let mut frame = Frame::array();
         frame.push_bulk(Bytes::from("set".as_bytes()));
         frame.push_bulk(Bytes::from(self.key.into_bytes()));
         frame.push_bulk(self.value);
 
-        if let Some(ms) = self.expire {
-            frame.push_bulk(Bytes::from("px".as_bytes()));
-            frame.push_int(ms.as_millis() as u64);
+        let options = self.options;
+        if options.nx {
+            frame.push_bulk(Bytes::from("nx".as_bytes()));
+        }
+        if options.xx {
+            frame.push_bulk(Bytes::from("xx".as_bytes()));
+        }
+        if options.get {
+            frame.push_bulk(Bytes::from("get".as_bytes()));
+        }
+        if let Some(expire) = options.expire {
+            push_expiry(&mut frame, expire);
         }
         frame
     }
 }
+
+/// Parses the argument of the `EX`, `PX`, `EXAT` or `PXAT` option named by
+/// `option` of `command`, returning `None` for any other option. The time
+/// has to be positive.
+pub(crate) fn parse_expiry(
+    command: &str,
+    option: &str,
+    parse: &mut Parse,
+) -> crate::Result<Option<Expiry>> {
+    let expiry: fn(u64) -> Expiry = match option {
+        "EX" => Expiry::Ex,
+        "PX" => Expiry::Px,
+        "EXAT" => Expiry::ExAt,
+        "PXAT" => Expiry::PxAt,
+        _ => return Ok(None),
+    };
+    match parse.next_signed_int()? {
+        time if time > 0 => Ok(Some(expiry(time as u64))),
+        _ => Err(format!("ERR invalid expire time in '{}' command", command).into()),
+    }
+}
+
+pub(crate) fn push_expiry(frame: &mut Frame, expire: Expiry) {
+    let (name, value) = match expire {
+        Expiry::Ex(secs) => ("ex", Some(secs)),
+        Expiry::Px(ms) => ("px", Some(ms)),
+        Expiry::ExAt(secs) => ("exat", Some(secs)),
+        Expiry::PxAt(ms) => ("pxat", Some(ms)),
+        Expiry::KeepTtl => ("keepttl", None),
+        Expiry::Persist => ("persist", None),
+    };
+    frame.push_bulk(Bytes::from(name.as_bytes()));
+    if let Some(value) = value {
+        frame.push_int(value as i64);
+    }
+}
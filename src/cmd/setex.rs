@@ -0,0 +1,79 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    cmd::Set,
+    db::{Db, SetOptions},
+    parse::Parse,
+    Connection, Frame,
+};
+
+/// The legacy form of `SET key value EX seconds`.
+#[derive(Debug)]
+pub struct Setex {
+    key: String,
+    seconds: u64,
+    value: Bytes,
+}
+
+impl Setex {
+    pub fn new(key: impl ToString, seconds: u64, value: Bytes) -> Setex {
+        Setex {
+            key: key.to_string(),
+            seconds,
+            value,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The equivalent `SET`.
+    pub(crate) fn into_set(self) -> Set {
+        Set::with_options(self.key, self.value, SetOptions::new().ex(self.seconds))
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Setex> {
+        let key = parse.next_string()?;
+        let seconds = parse.next_signed_int()?;
+        let value = parse.next_bytes()?;
+
+        if seconds <= 0 {
+            return Err("ERR invalid expire time in 'setex' command".into());
+        }
+        let seconds = seconds as u64;
+
+        Ok(Setex {
+            key,
+            seconds,
+            value,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let options = SetOptions::new().ex(self.seconds);
+        match db.set(self.key, self.value, options) {
+            Ok(_) => Frame::Simple("OK".to_string()),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("setex".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.seconds as i64);
+        frame.push_bulk(self.value);
+        frame
+    }
+}
@@ -0,0 +1,58 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, SetOptions},
+    parse::Parse,
+    Connection, Frame,
+};
+
+/// The legacy form of `SET key value NX`, replying with an integer.
+#[derive(Debug)]
+pub struct Setnx {
+    key: String,
+    value: Bytes,
+}
+
+impl Setnx {
+    pub fn new(key: impl ToString, value: Bytes) -> Setnx {
+        Setnx {
+            key: key.to_string(),
+            value,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Setnx> {
+        let key = parse.next_string()?;
+        let value = parse.next_bytes()?;
+        Ok(Setnx { key, value })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.set(self.key, self.value, SetOptions::new().nx()) {
+            Ok((written, _)) => Frame::Integer(written as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("setnx".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(self.value);
+        frame
+    }
+}
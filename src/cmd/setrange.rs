@@ -0,0 +1,57 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Db, parse::Parse, Connection, Frame};
+
+#[derive(Debug)]
+pub struct Setrange {
+    key: String,
+    offset: u64,
+    value: Bytes,
+}
+
+impl Setrange {
+    pub fn new(key: impl ToString, offset: u64, value: Bytes) -> Setrange {
+        Setrange {
+            key: key.to_string(),
+            offset,
+            value,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Setrange> {
+        let key = parse.next_string()?;
+        let offset = parse.next_int().map_err(|_| "ERR offset is out of range")?;
+        let value = parse.next_bytes()?;
+        Ok(Setrange { key, offset, value })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.setrange(&self.key, self.offset as usize, self.value) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("setrange".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_int(self.offset as i64);
+        frame.push_bulk(self.value);
+        frame
+    }
+}
@@ -1,10 +1,12 @@
 use bytes::Bytes;
+use tokio::sync::broadcast::error::RecvError;
 use tokio_stream::{Stream, StreamExt, StreamMap};
 
 use crate::{
     cmd::Unknown,
-    db::Db,
+    db::{Databases, Receiver},
     parse::{Parse, ParseError},
+    server::SlowSubscribers,
     shutdown::Shutdown,
     Command, Connection, Frame,
 };
@@ -21,7 +23,26 @@ pub struct Unsubscribe {
     channels: Vec<String>,
 }
 
-type Messages = std::pin::Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+#[derive(Debug)]
+pub struct Psubscribe {
+    patterns: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Punsubscribe {
+    patterns: Vec<String>,
+}
+
+/// A channel, or a glob pattern of channels, a connection listens to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Subscription {
+    name: String,
+    pattern: bool,
+}
+
+/// The message frames of a subscription, or the number of messages dropped
+/// because the subscriber lagged behind.
+type Messages = std::pin::Pin<Box<dyn Stream<Item = Result<Frame, u64>> + Send>>;
 
 impl Subscribe {
     pub(crate) fn new(channels: Vec<String>) -> Subscribe {
@@ -29,47 +50,25 @@ impl Subscribe {
     }
 
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Subscribe> {
-        use ParseError::EndOfStream;
-        let mut channels = vec![parse.next_string()?];
-
-        loop {
-            match parse.next_string() {
-                Ok(s) => channels.push(s),
-                Err(EndOfStream) => break,
-                Err(err) => return Err(err.into()),
-            }
-        }
+        let channels = parse_names(parse)?;
         Ok(Subscribe { channels })
     }
 
     pub(crate) async fn apply(
-        mut self,
-        db: &Db,
+        self,
+        databases: &Databases,
         dst: &mut Connection,
         shutdown: &mut Shutdown,
     ) -> crate::Result<()> {
-        let mut subscriptions = StreamMap::new();
-        loop {
-            for channel_name in self.channels.drain(..) {
-                subscribe_to_channel(channel_name, &mut subscriptions, db, dst).await?;
-            }
-
-            select! {
-                Some((channel_name, msg)) = subscriptions.next() => {
-                    dst.write_frame(&make_message_frame(channel_name, msg)).await?;
-                }
-                res = dst.read_frame() => {
-                    let frame = match res? {
-                        Some(frame) => frame,
-                        None => return Ok(()),
-                    };
-                    handle_command(frame, &mut self.channels, &mut subscriptions, dst).await?;
-                }
-                _ = shutdown.recv() => {
-                    return Ok(())
-                }
-            }
-        }
+        let subscribe_to = self
+            .channels
+            .into_iter()
+            .map(|name| Subscription {
+                name,
+                pattern: false,
+            })
+            .collect();
+        subscriber_mode(subscribe_to, databases, dst, shutdown).await
     }
 
     pub(crate) fn into_frame(self) -> Frame {
@@ -82,55 +81,224 @@ impl Subscribe {
     }
 }
 
-async fn subscribe_to_channel(
-    channel_name: String,
-    subscriptions: &mut StreamMap<String, Messages>,
-    db: &Db,
+impl Psubscribe {
+    pub(crate) fn new(patterns: Vec<String>) -> Psubscribe {
+        Psubscribe { patterns }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Psubscribe> {
+        let patterns = parse_names(parse)?;
+        Ok(Psubscribe { patterns })
+    }
+
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let subscribe_to = self
+            .patterns
+            .into_iter()
+            .map(|name| Subscription {
+                name,
+                pattern: true,
+            })
+            .collect();
+        subscriber_mode(subscribe_to, databases, dst, shutdown).await
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("psubscribe".as_bytes()));
+        for pattern in self.patterns {
+            frame.push_bulk(Bytes::from(pattern.into_bytes()));
+        }
+        frame
+    }
+}
+
+/// Parses the channels or patterns to subscribe to, at least one is needed.
+fn parse_names(parse: &mut Parse) -> crate::Result<Vec<String>> {
+    use ParseError::EndOfStream;
+    let mut names = vec![parse.next_string()?];
+
+    loop {
+        match parse.next_string() {
+            Ok(s) => names.push(s),
+            Err(EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(names)
+}
+
+/// Serves a connection once it subscribed to something: messages are
+/// forwarded to it and it may only change its subscriptions.
+async fn subscriber_mode(
+    mut subscribe_to: Vec<Subscription>,
+    databases: &Databases,
     dst: &mut Connection,
+    shutdown: &mut Shutdown,
 ) -> crate::Result<()> {
-    let mut rx = db.subscribe(channel_name.clone());
-    let rx = Box::pin(async_stream::stream! {
-        loop {
-            match rx.recv().await {
-                Ok(msg) => yield msg,
-                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
-                Err(_) => break,
+    let mut subscriptions = StreamMap::new();
+    loop {
+        for subscription in subscribe_to.drain(..) {
+            subscribe(subscription, &mut subscriptions, databases, dst).await?;
+        }
+
+        select! {
+            Some((subscription, message)) = subscriptions.next() => {
+                let frame = match message {
+                    Ok(frame) => frame,
+                    Err(dropped) => match databases.slow_subscribers() {
+                        SlowSubscribers::Disconnect => {
+                            return Err(format!(
+                                "subscriber of {} lagged behind, {} messages dropped",
+                                subscription.name, dropped
+                            )
+                            .into())
+                        }
+                        SlowSubscribers::Notify => make_lagged_frame(&subscription, dropped),
+                    },
+                };
+                dst.write_frame(&frame).await?;
+            }
+            res = dst.read_request() => {
+                let frame = match res? {
+                    Some(frame) => frame,
+                    None => return Ok(()),
+                };
+                handle_command(frame, &mut subscribe_to, &mut subscriptions, dst).await?;
+            }
+            _ = shutdown.recv() => {
+                return Ok(())
             }
         }
-    });
-    subscriptions.insert(channel_name.clone(), rx);
-    let response = make_subscribe_frame(channel_name, subscriptions.len());
+    }
+}
+
+async fn subscribe(
+    subscription: Subscription,
+    subscriptions: &mut StreamMap<Subscription, Messages>,
+    databases: &Databases,
+    dst: &mut Connection,
+) -> crate::Result<()> {
+    let db = databases.pub_sub();
+    let capacity = databases.pubsub_capacity();
+    let name = subscription.name.clone();
+    let messages: Messages = if subscription.pattern {
+        let rx = db.psubscribe(name.clone(), capacity);
+        let pattern = name.clone();
+        Box::pin(receive(rx).map(move |message| {
+            message.map(|(channel, msg)| make_pmessage_frame(pattern.clone(), channel, msg))
+        }))
+    } else {
+        let rx = db.subscribe(name.clone(), capacity);
+        let channel = name.clone();
+        Box::pin(
+            receive(rx)
+                .map(move |message| message.map(|msg| make_message_frame(channel.clone(), msg))),
+        )
+    };
+
+    let kind = if subscription.pattern {
+        "psubscribe"
+    } else {
+        "subscribe"
+    };
+    subscriptions.insert(subscription, messages);
+    let response = make_subscription_frame(kind, name, subscriptions.len());
     dst.write_frame(&response).await?;
 
     Ok(())
 }
 
-async fn handle_command(
-    frame: Frame,
-    subscribe_to: &mut Vec<String>,
-    subscriptions: &mut StreamMap<String, Messages>,
+/// Unsubscribes from the channels, or patterns, `names`, or from all of them
+/// when none are given.
+async fn unsubscribe(
+    names: Vec<String>,
+    pattern: bool,
+    subscriptions: &mut StreamMap<Subscription, Messages>,
     dst: &mut Connection,
 ) -> crate::Result<()> {
-    match Command::from_frame(frame)? {
-        Command::Subscribe(subscribe) => {
-            subscribe_to.extend(subscribe.channels.into_iter());
-        }
-        Command::Unsubscribe(mut unsubscribe) => {
-            if unsubscribe.channels.is_empty() {
-                unsubscribe.channels = subscriptions
-                    .keys()
-                    .map(|channel_name| channel_name.to_string())
-                    .collect();
-            }
+    let unsubscribe_from: Vec<Subscription> = if names.is_empty() {
+        subscriptions
+            .keys()
+            .filter(|subscription| subscription.pattern == pattern)
+            .cloned()
+            .collect()
+    } else {
+        names
+            .into_iter()
+            .map(|name| Subscription { name, pattern })
+            .collect()
+    };
 
-            for channel_name in unsubscribe.channels {
-                subscriptions.remove(&channel_name);
+    let kind = if pattern {
+        "punsubscribe"
+    } else {
+        "unsubscribe"
+    };
+    for subscription in unsubscribe_from {
+        subscriptions.remove(&subscription);
 
-                let response = make_unsubscribe_frame(channel_name, subscriptions.len());
+        let response = make_subscription_frame(kind, subscription.name, subscriptions.len());
 
-                dst.write_frame(&response).await?;
+        dst.write_frame(&response).await?;
+    }
+    Ok(())
+}
+
+/// Turns `rx` into a stream of messages, or of the number of messages
+/// missed by lagging behind.
+fn receive<T: Clone + Send + 'static>(
+    mut rx: Receiver<T>,
+) -> impl Stream<Item = Result<T, u64>> + Send {
+    async_stream::stream! {
+        loop {
+            match rx.recv().await {
+                Ok(msg) => yield Ok(msg),
+                Err(RecvError::Lagged(dropped)) => yield Err(dropped),
+                Err(_) => break,
             }
         }
+    }
+}
+
+async fn handle_command(
+    frame: Frame,
+    subscribe_to: &mut Vec<Subscription>,
+    subscriptions: &mut StreamMap<Subscription, Messages>,
+    dst: &mut Connection,
+) -> crate::Result<()> {
+    let cmd = match Command::from_frame(frame) {
+        Ok(cmd) => cmd,
+        Err(err) => {
+            let response = Command::parse_error_reply(err);
+            dst.write_frame(&response).await?;
+            return Ok(());
+        }
+    };
+    match cmd {
+        Command::Subscribe(subscribe) => {
+            subscribe_to.extend(subscribe.channels.into_iter().map(|name| Subscription {
+                name,
+                pattern: false,
+            }));
+        }
+        Command::Psubscribe(psubscribe) => {
+            subscribe_to.extend(psubscribe.patterns.into_iter().map(|name| Subscription {
+                name,
+                pattern: true,
+            }));
+        }
+        Command::Unsubscribe(unsubscribe_cmd) => {
+            unsubscribe(unsubscribe_cmd.channels, false, subscriptions, dst).await?;
+        }
+        Command::Punsubscribe(punsubscribe) => {
+            unsubscribe(punsubscribe.patterns, true, subscriptions, dst).await?;
+        }
         command => {
             let cmd = Unknown::new(command.get_name());
             cmd.apply(dst).await?;
@@ -139,30 +307,47 @@ async fn handle_command(
     Ok(())
 }
 
-fn make_subscribe_frame(channel_name: String, num_subs: usize) -> Frame {
-    let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"subscribe"));
-    response.push_bulk(Bytes::from(channel_name));
-    response.push_int(num_subs as u64);
+/// The confirmation of a `kind` of (un)subscription, with how many
+/// subscriptions the connection has left.
+fn make_subscription_frame(kind: &'static str, name: String, num_subs: usize) -> Frame {
+    let mut response = Frame::Push(vec![]);
+    response.push_bulk(Bytes::from_static(kind.as_bytes()));
+    response.push_bulk(Bytes::from(name));
+    response.push_int(num_subs as i64);
     response
 }
 
-fn make_unsubscribe_frame(channel_name: String, num_subs: usize) -> Frame {
-    let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"unsubscribe"));
-    response.push_bulk(Bytes::from(channel_name));
-    response.push_int(num_subs as u64);
+/// Tells a slow subscriber how many messages of `subscription` it missed.
+fn make_lagged_frame(subscription: &Subscription, dropped: u64) -> Frame {
+    let kind: &'static [u8] = if subscription.pattern {
+        b"plagged"
+    } else {
+        b"lagged"
+    };
+    let mut response = Frame::Push(vec![]);
+    response.push_bulk(Bytes::from_static(kind));
+    response.push_bulk(Bytes::from(subscription.name.clone()));
+    response.push_int(dropped as i64);
     response
 }
 
 fn make_message_frame(channel_name: String, msg: Bytes) -> Frame {
-    let mut response = Frame::array();
+    let mut response = Frame::Push(vec![]);
     response.push_bulk(Bytes::from_static(b"message"));
     response.push_bulk(Bytes::from(channel_name));
     response.push_bulk(msg);
     response
 }
 
+fn make_pmessage_frame(pattern: String, channel_name: String, msg: Bytes) -> Frame {
+    let mut response = Frame::Push(vec![]);
+    response.push_bulk(Bytes::from_static(b"pmessage"));
+    response.push_bulk(Bytes::from(pattern));
+    response.push_bulk(Bytes::from(channel_name));
+    response.push_bulk(msg);
+    response
+}
+
 impl Unsubscribe {
     pub(crate) fn new(channels: &[String]) -> Unsubscribe {
         Unsubscribe {
@@ -171,16 +356,7 @@ impl Unsubscribe {
     }
 
     pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Unsubscribe, ParseError> {
-        use ParseError::EndOfStream;
-        let mut channels = vec![];
-        loop {
-            match parse.next_string() {
-                Ok(s) => channels.push(s),
-                Err(EndOfStream) => break,
-                Err(err) => return Err(err),
-            }
-        }
-
+        let channels = parse_optional_names(parse)?;
         Ok(Unsubscribe { channels })
     }
 
@@ -194,3 +370,40 @@ impl Unsubscribe {
         frame
     }
 }
+
+impl Punsubscribe {
+    pub(crate) fn new(patterns: &[String]) -> Punsubscribe {
+        Punsubscribe {
+            patterns: patterns.to_vec(),
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Punsubscribe, ParseError> {
+        let patterns = parse_optional_names(parse)?;
+        Ok(Punsubscribe { patterns })
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("punsubscribe".as_bytes()));
+
+        for pattern in self.patterns {
+            frame.push_bulk(Bytes::from(pattern.into_bytes()));
+        }
+        frame
+    }
+}
+
+/// Parses the channels or patterns to unsubscribe from, which may be none.
+fn parse_optional_names(parse: &mut Parse) -> Result<Vec<String>, ParseError> {
+    use ParseError::EndOfStream;
+    let mut names = vec![];
+    loop {
+        match parse.next_string() {
+            Ok(s) => names.push(s),
+            Err(EndOfStream) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(names)
+}
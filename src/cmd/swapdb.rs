@@ -0,0 +1,56 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, Connection, Frame};
+
+#[derive(Debug)]
+pub struct Swapdb {
+    index1: i64,
+    index2: i64,
+}
+
+impl Swapdb {
+    pub fn new(index1: i64, index2: i64) -> Swapdb {
+        Swapdb { index1, index2 }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Swapdb> {
+        let index1 = parse.next_signed_int()?;
+        let index2 = parse.next_signed_int()?;
+        Ok(Swapdb { index1, index2 })
+    }
+
+    #[instrument(skip(self, databases, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = self.execute(databases);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, databases: &Databases) -> Frame {
+        let indexes = databases
+            .index(self.index1)
+            .and_then(|index1| Ok((index1, databases.index(self.index2)?)));
+        match indexes {
+            Ok((index1, index2)) => {
+                databases.swap(index1, index2);
+                Frame::Simple("OK".to_string())
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("swapdb".as_bytes()));
+        frame.push_int(self.index1);
+        frame.push_int(self.index2);
+        frame
+    }
+}
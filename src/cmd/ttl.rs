@@ -0,0 +1,87 @@
+use bytes::Bytes;
+use tokio::time::Instant;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{unix_ms_at, Db},
+    parse::Parse,
+    Connection, Frame,
+};
+
+/// How `Ttl` reports the expiration of a key.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum TtlFormat {
+    /// `TTL`, the seconds left, rounded.
+    Seconds,
+    /// `PTTL`, the milliseconds left.
+    Millis,
+    /// `EXPIRETIME`, the Unix time in seconds.
+    UnixSeconds,
+    /// `PEXPIRETIME`, the Unix time in milliseconds.
+    UnixMillis,
+}
+
+/// `TTL`, `PTTL`, `EXPIRETIME` or `PEXPIRETIME`. Replies with `-2` when the
+/// key does not exist and `-1` when it has no expiration.
+#[derive(Debug)]
+pub struct Ttl {
+    key: String,
+    format: TtlFormat,
+}
+
+impl Ttl {
+    pub(crate) fn new(key: impl ToString, format: TtlFormat) -> Ttl {
+        Ttl {
+            key: key.to_string(),
+            format,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, format: TtlFormat) -> crate::Result<Ttl> {
+        let key = parse.next_string()?;
+        Ok(Ttl { key, format })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.expires_at(&self.key) {
+            None => Frame::Integer(-2),
+            Some(None) => Frame::Integer(-1),
+            Some(Some(when)) => {
+                let now = Instant::now();
+                let left = when.saturating_duration_since(now).as_millis() as i64;
+                Frame::Integer(match self.format {
+                    TtlFormat::Seconds => (left + 500) / 1000,
+                    TtlFormat::Millis => left,
+                    TtlFormat::UnixSeconds => unix_ms_at(when, now) as i64 / 1000,
+                    TtlFormat::UnixMillis => unix_ms_at(when, now) as i64,
+                })
+            }
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let name = match self.format {
+            TtlFormat::Seconds => "ttl",
+            TtlFormat::Millis => "pttl",
+            TtlFormat::UnixSeconds => "expiretime",
+            TtlFormat::UnixMillis => "pexpiretime",
+        };
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from(name.as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame
+    }
+}
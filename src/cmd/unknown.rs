@@ -19,10 +19,14 @@ impl Unknown {
     }
 
     pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
-        let response = Frame::Error(format!("ERR unknown command {}", self.command_name));
+        let response = self.execute();
 
         debug!(?response);
         dst.write_frame(&response).await?;
         Ok(())
     }
+
+    pub(crate) fn execute(self) -> Frame {
+        Frame::Error(format!("ERR unknown command {}", self.command_name))
+    }
 }
@@ -0,0 +1,44 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{cmd::MultiState, parse::Parse, Connection, Frame};
+
+#[derive(Debug, Default)]
+pub struct Unwatch;
+
+impl Unwatch {
+    pub fn new() -> Unwatch {
+        Unwatch
+    }
+
+    pub(crate) fn parse_frames(_parse: &mut Parse) -> crate::Result<Unwatch> {
+        Ok(Unwatch)
+    }
+
+    #[instrument(skip(self, transaction, dst))]
+    pub(crate) async fn apply(
+        self,
+        transaction: &mut MultiState,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        transaction.unwatch();
+
+        let response = Frame::Simple("OK".to_string());
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Queued inside `MULTI`, where it has nothing left to do: `EXEC`
+    /// unwatches every key anyway.
+    pub(crate) fn execute(self) -> Frame {
+        Frame::Simple("OK".to_string())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("unwatch".as_bytes()));
+        frame
+    }
+}
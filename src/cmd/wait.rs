@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::select;
+use tracing::{debug, instrument};
+
+use crate::{db::Databases, parse::Parse, shutdown::Shutdown, Connection, Frame};
+
+/// Blocks until the client's writes reached `numreplicas` replicas, or
+/// `timeout` elapsed, replying with how many replicas they reached.
+#[derive(Debug)]
+pub struct Wait {
+    numreplicas: u64,
+    /// In milliseconds, `0` to wait for as long as it takes.
+    timeout: u64,
+}
+
+impl Wait {
+    pub fn new(numreplicas: u64, timeout: Option<Duration>) -> Wait {
+        Wait {
+            numreplicas,
+            timeout: timeout.map_or(0, |timeout| (timeout.as_millis() as u64).max(1)),
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Wait> {
+        let numreplicas = parse.next_int()?;
+        let timeout = parse.next_int()?;
+        Ok(Wait {
+            numreplicas,
+            timeout,
+        })
+    }
+
+    /// Waits for the replicas to acknowledge the stream up to `offset`, where
+    /// the last write of the client ended, see `server::Handler`.
+    #[instrument(skip(self, databases, dst, shutdown))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        offset: u64,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = if databases.read_only() {
+            Frame::Error("ERR WAIT cannot be used with replica instances".to_string())
+        } else {
+            let timeout = (self.timeout > 0).then(|| Duration::from_millis(self.timeout));
+            let numreplicas = self.numreplicas as usize;
+            select! {
+                acked = databases.wait_for_acks(offset, numreplicas, timeout) => {
+                    Frame::Integer(acked as i64)
+                }
+                _ = shutdown.recv() => return Ok(()),
+            }
+        };
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("wait".as_bytes()));
+        frame.push_int(self.numreplicas as i64);
+        frame.push_int(self.timeout as i64);
+        frame
+    }
+}
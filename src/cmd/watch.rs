@@ -0,0 +1,57 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{cmd::MultiState, db::Databases, parse::Parse, Connection, Frame};
+
+/// Makes the next `EXEC` fail if any of `keys` is modified before it runs.
+#[derive(Debug)]
+pub struct Watch {
+    keys: Vec<String>,
+}
+
+impl Watch {
+    pub fn new(keys: Vec<String>) -> Watch {
+        Watch { keys }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Watch> {
+        let mut keys = vec![parse.next_string()?];
+        keys.extend(parse.remaining_strings()?);
+        Ok(Watch { keys })
+    }
+
+    #[instrument(skip(self, databases, transaction, dst))]
+    pub(crate) async fn apply(
+        self,
+        databases: &Databases,
+        selected: usize,
+        transaction: &mut MultiState,
+        dst: &mut Connection,
+    ) -> crate::Result<()> {
+        let response = if transaction.is_queuing() {
+            Frame::Error("ERR WATCH inside MULTI is not allowed".to_string())
+        } else {
+            for key in self.keys {
+                transaction.watch(databases, selected, key);
+            }
+            Frame::Simple("OK".to_string())
+        };
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("watch".as_bytes()));
+        for key in self.keys {
+            frame.push_bulk(Bytes::from(key.into_bytes()));
+        }
+        frame
+    }
+}
@@ -0,0 +1,71 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, StreamId},
+    parse::Parse,
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Xack {
+    key: String,
+    group: String,
+    ids: Vec<String>,
+}
+
+impl Xack {
+    pub fn new(key: impl ToString, group: impl ToString, ids: Vec<String>) -> Xack {
+        Xack {
+            key: key.to_string(),
+            group: group.to_string(),
+            ids,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xack> {
+        let key = parse.next_string()?;
+        let group = parse.next_string()?;
+        let mut ids = vec![parse.next_string()?];
+        ids.extend(parse.remaining_strings()?);
+        Ok(Xack { key, group, ids })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let acked = self
+            .ids
+            .iter()
+            .map(|id| StreamId::parse(id))
+            .collect::<crate::Result<Vec<_>>>()
+            .and_then(|ids| db.xack(&self.key, &self.group, &ids));
+
+        match acked {
+            Ok(acked) => Frame::Integer(acked as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xack".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.group.into_bytes()));
+        for id in self.ids {
+            frame.push_bulk(Bytes::from(id.into_bytes()));
+        }
+        frame
+    }
+}
@@ -0,0 +1,132 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, Fields, XaddId},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Xadd {
+    key: String,
+    id: String,
+    fields: Fields,
+    maxlen: Option<u64>,
+    nomkstream: bool,
+}
+
+impl Xadd {
+    pub(crate) fn new(
+        key: impl ToString,
+        id: impl ToString,
+        fields: Fields,
+        maxlen: Option<u64>,
+    ) -> Xadd {
+        Xadd {
+            key: key.to_string(),
+            id: id.to_string(),
+            fields,
+            maxlen,
+            nomkstream: false,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The same command with the ID the entry was added with, rather than
+    /// one to generate it from.
+    pub(crate) fn with_id(mut self, id: impl ToString) -> Xadd {
+        self.id = id.to_string();
+        self
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xadd> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let mut maxlen = None;
+        let mut nomkstream = false;
+
+        // Options come first, the first argument that is not an option is the ID.
+        let id = loop {
+            let arg = parse.next_string()?;
+            match &arg.to_uppercase()[..] {
+                "NOMKSTREAM" => nomkstream = true,
+                "MAXLEN" => maxlen = Some(parse_maxlen(parse)?),
+                _ => break arg,
+            }
+        };
+
+        let mut fields = vec![(parse.next_bytes()?, parse.next_bytes()?)];
+        loop {
+            match parse.next_bytes() {
+                Ok(field) => fields.push((field, parse.next_bytes()?)),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Xadd {
+            key,
+            id,
+            fields,
+            maxlen,
+            nomkstream,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let maxlen = self.maxlen.map(|maxlen| maxlen as usize);
+        let id = XaddId::parse(&self.id)
+            .and_then(|id| db.xadd(&self.key, id, self.fields, maxlen, self.nomkstream));
+
+        match id {
+            Ok(Some(id)) => Frame::Bulk(Bytes::from(id.to_string())),
+            Ok(None) => Frame::Null,
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xadd".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        if self.nomkstream {
+            frame.push_bulk(Bytes::from("NOMKSTREAM".as_bytes()));
+        }
+        if let Some(maxlen) = self.maxlen {
+            frame.push_bulk(Bytes::from("MAXLEN".as_bytes()));
+            frame.push_bulk(Bytes::from(maxlen.to_string()));
+        }
+        frame.push_bulk(Bytes::from(self.id.into_bytes()));
+        for (field, value) in self.fields {
+            frame.push_bulk(field);
+            frame.push_bulk(value);
+        }
+        frame
+    }
+}
+
+/// Parses the argument of `MAXLEN`, with the optional `=` or `~` modifier.
+/// Approximate trimming is honored by trimming exactly.
+pub(crate) fn parse_maxlen(parse: &mut Parse) -> crate::Result<u64> {
+    let arg = parse.next_string()?;
+    let arg = match &arg[..] {
+        "=" | "~" => parse.next_string()?,
+        _ => arg,
+    };
+    arg.parse()
+        .map_err(|_| "ERR value is not an integer or out of range".into())
+}
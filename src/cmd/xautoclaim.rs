@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    cmd::{
+        xclaim::{claims, ids_frame},
+        xrange::records_frame,
+    },
+    db::{Db, StreamId},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Xautoclaim {
+    key: String,
+    group: String,
+    consumer: String,
+    min_idle: u64,
+    start: String,
+    count: Option<u64>,
+    justid: bool,
+}
+
+impl Xautoclaim {
+    pub fn new(
+        key: impl ToString,
+        group: impl ToString,
+        consumer: impl ToString,
+        min_idle: u64,
+        start: impl ToString,
+        count: Option<u64>,
+    ) -> Xautoclaim {
+        Xautoclaim {
+            key: key.to_string(),
+            group: group.to_string(),
+            consumer: consumer.to_string(),
+            min_idle,
+            start: start.to_string(),
+            count,
+            justid: false,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xautoclaim> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let group = parse.next_string()?;
+        let consumer = parse.next_string()?;
+        let min_idle = parse.next_int()?;
+        let start = parse.next_string()?;
+
+        let mut count = None;
+        let mut justid = false;
+        loop {
+            match parse.next_string() {
+                Ok(s) => match &s.to_uppercase()[..] {
+                    "COUNT" => count = Some(parse.next_int()?),
+                    "JUSTID" => justid = true,
+                    _ => return Err("ERR syntax error".into()),
+                },
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(Xautoclaim {
+            key,
+            group,
+            consumer,
+            min_idle,
+            start,
+            count,
+            justid,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        self.execute_with_claims(db).0
+    }
+
+    /// Runs the command like `execute`, also returning the `XCLAIM` commands
+    /// its changes are propagated as, see `xclaim::claims`.
+    pub(crate) fn execute_with_claims(self, db: &Db) -> (Frame, Vec<Frame>) {
+        let min_idle = Duration::from_millis(self.min_idle);
+        // Like `XAUTOCLAIM` in Redis, at most 100 records are claimed by default.
+        let count = self.count.unwrap_or(100) as usize;
+        let result = StreamId::parse_start(&self.start).and_then(|start| {
+            db.xautoclaim(
+                &self.key,
+                &self.group,
+                &self.consumer,
+                min_idle,
+                start,
+                count,
+                self.justid,
+            )
+        });
+
+        match result {
+            Ok((next, claimed)) => {
+                let claims = claims(&self.key, &self.group, &self.consumer, &claimed);
+                let records = if self.justid {
+                    ids_frame(claimed.records)
+                } else {
+                    records_frame(claimed.records)
+                };
+                let deleted = claimed
+                    .deleted
+                    .into_iter()
+                    .map(|id| Frame::Bulk(Bytes::from(id.to_string())))
+                    .collect();
+                let response = Frame::Array(vec![
+                    Frame::Bulk(Bytes::from(next.to_string())),
+                    records,
+                    Frame::Array(deleted),
+                ]);
+                (response, claims)
+            }
+            Err(err) => (Frame::Error(err.to_string()), vec![]),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xautoclaim".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.group.into_bytes()));
+        frame.push_bulk(Bytes::from(self.consumer.into_bytes()));
+        frame.push_int(self.min_idle as i64);
+        frame.push_bulk(Bytes::from(self.start.into_bytes()));
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("COUNT".as_bytes()));
+            frame.push_int(count as i64);
+        }
+        if self.justid {
+            frame.push_bulk(Bytes::from("JUSTID".as_bytes()));
+        }
+        frame
+    }
+}
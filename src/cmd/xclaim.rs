@@ -0,0 +1,209 @@
+use bytes::Bytes;
+use tokio::time::{Duration, Instant};
+use tracing::{debug, instrument};
+
+use crate::{
+    cmd::xrange::records_frame,
+    db::{instant_at, unix_ms_at, ClaimOptions, Claimed, Db, Fields, StreamId},
+    parse::Parse,
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Xclaim {
+    key: String,
+    group: String,
+    consumer: String,
+    min_idle: u64,
+    ids: Vec<String>,
+    /// Sets the idle time of the claimed records, in milliseconds.
+    idle: Option<u64>,
+    /// Sets when the claimed records were delivered, as a Unix time in
+    /// milliseconds.
+    time: Option<u64>,
+    retrycount: Option<u64>,
+    force: bool,
+    justid: bool,
+}
+
+impl Xclaim {
+    pub fn new(
+        key: impl ToString,
+        group: impl ToString,
+        consumer: impl ToString,
+        min_idle: u64,
+        ids: Vec<String>,
+    ) -> Xclaim {
+        Xclaim {
+            key: key.to_string(),
+            group: group.to_string(),
+            consumer: consumer.to_string(),
+            min_idle,
+            ids,
+            idle: None,
+            time: None,
+            retrycount: None,
+            force: false,
+            justid: false,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xclaim> {
+        let key = parse.next_string()?;
+        let group = parse.next_string()?;
+        let consumer = parse.next_string()?;
+        let min_idle = parse.next_int()?;
+        let mut xclaim = Xclaim::new(key, group, consumer, min_idle, vec![parse.next_string()?]);
+
+        // IDs come first, then the options.
+        let mut args = parse.remaining_strings()?.into_iter();
+        let mut options = false;
+        while let Some(arg) = args.next() {
+            let mut int = || {
+                args.next()
+                    .and_then(|arg| arg.parse().ok())
+                    .ok_or("ERR value is not an integer or out of range")
+            };
+            match &arg.to_uppercase()[..] {
+                "IDLE" => xclaim.idle = Some(int()?),
+                "TIME" => xclaim.time = Some(int()?),
+                "RETRYCOUNT" => xclaim.retrycount = Some(int()?),
+                "FORCE" => xclaim.force = true,
+                "JUSTID" => xclaim.justid = true,
+                _ if options => return Err("ERR syntax error".into()),
+                _ => {
+                    xclaim.ids.push(arg);
+                    continue;
+                }
+            }
+            options = true;
+        }
+
+        Ok(xclaim)
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        self.execute_with_claims(db).0
+    }
+
+    /// Runs the command like `execute`, also returning the `XCLAIM` commands
+    /// its changes are propagated as, see `claims`.
+    pub(crate) fn execute_with_claims(self, db: &Db) -> (Frame, Vec<Frame>) {
+        let now = Instant::now();
+        let delivered_at = match (self.idle, self.time) {
+            (Some(idle), _) => Some(now.checked_sub(Duration::from_millis(idle)).unwrap_or(now)),
+            (None, Some(time)) => Some(instant_at(time, now).unwrap_or(now)),
+            (None, None) => None,
+        };
+        let options = ClaimOptions {
+            delivered_at,
+            deliveries: self.retrycount,
+            force: self.force,
+            justid: self.justid,
+        };
+        let min_idle = Duration::from_millis(self.min_idle);
+        let claimed = self
+            .ids
+            .iter()
+            .map(|id| StreamId::parse(id))
+            .collect::<crate::Result<Vec<_>>>()
+            .and_then(|ids| {
+                db.xclaim(
+                    &self.key,
+                    &self.group,
+                    &self.consumer,
+                    min_idle,
+                    &ids,
+                    options,
+                )
+            });
+
+        match claimed {
+            Ok(claimed) => {
+                let claims = claims(&self.key, &self.group, &self.consumer, &claimed);
+                let response = if self.justid {
+                    ids_frame(claimed.records)
+                } else {
+                    records_frame(claimed.records)
+                };
+                (response, claims)
+            }
+            Err(err) => (Frame::Error(err.to_string()), vec![]),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xclaim".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.group.into_bytes()));
+        frame.push_bulk(Bytes::from(self.consumer.into_bytes()));
+        frame.push_int(self.min_idle as i64);
+        for id in self.ids {
+            frame.push_bulk(Bytes::from(id.into_bytes()));
+        }
+        if let Some(idle) = self.idle {
+            frame.push_bulk(Bytes::from("IDLE".as_bytes()));
+            frame.push_int(idle as i64);
+        }
+        if let Some(time) = self.time {
+            frame.push_bulk(Bytes::from("TIME".as_bytes()));
+            frame.push_int(time as i64);
+        }
+        if let Some(retrycount) = self.retrycount {
+            frame.push_bulk(Bytes::from("RETRYCOUNT".as_bytes()));
+            frame.push_int(retrycount as i64);
+        }
+        if self.force {
+            frame.push_bulk(Bytes::from("FORCE".as_bytes()));
+        }
+        if self.justid {
+            frame.push_bulk(Bytes::from("JUSTID".as_bytes()));
+        }
+        frame
+    }
+}
+
+/// The claims of `XCLAIM` and `XAUTOCLAIM` as they are propagated, like in
+/// Redis: one `XCLAIM` per record, setting its PEL entry to what it was left
+/// as, which does not depend on how idle the record is when replayed. Deleted
+/// records are dropped from the PEL again as they are still missing.
+pub(crate) fn claims(key: &str, group: &str, consumer: &str, claimed: &Claimed) -> Vec<Frame> {
+    let now = Instant::now();
+    claimed
+        .changes
+        .iter()
+        .map(|(id, pending)| {
+            Xclaim {
+                time: Some(unix_ms_at(pending.delivered_at, now)),
+                retrycount: Some(pending.deliveries),
+                force: true,
+                justid: true,
+                ..Xclaim::new(key, group, consumer, 0, vec![id.to_string()])
+            }
+            .into_frame()
+        })
+        .collect()
+}
+
+pub(crate) fn ids_frame(records: Vec<(StreamId, Fields)>) -> Frame {
+    Frame::Array(
+        records
+            .into_iter()
+            .map(|(id, _)| Frame::Bulk(Bytes::from(id.to_string())))
+            .collect(),
+    )
+}
@@ -0,0 +1,153 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, StreamId},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// The `CREATE` and `DESTROY` subcommands of `XGROUP`.
+#[derive(Debug)]
+pub struct Xgroup {
+    subcommand: Subcommand,
+}
+
+#[derive(Debug)]
+enum Subcommand {
+    Create {
+        key: String,
+        group: String,
+        id: String,
+        mkstream: bool,
+    },
+    Destroy {
+        key: String,
+        group: String,
+    },
+}
+
+impl Xgroup {
+    pub(crate) fn create(
+        key: impl ToString,
+        group: impl ToString,
+        id: impl ToString,
+        mkstream: bool,
+    ) -> Xgroup {
+        Xgroup {
+            subcommand: Subcommand::Create {
+                key: key.to_string(),
+                group: group.to_string(),
+                id: id.to_string(),
+                mkstream,
+            },
+        }
+    }
+
+    pub(crate) fn destroy(key: impl ToString, group: impl ToString) -> Xgroup {
+        Xgroup {
+            subcommand: Subcommand::Destroy {
+                key: key.to_string(),
+                group: group.to_string(),
+            },
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        match &self.subcommand {
+            Subcommand::Create { key, .. } | Subcommand::Destroy { key, .. } => key,
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xgroup> {
+        use ParseError::EndOfStream;
+
+        let subcommand = parse.next_string()?.to_uppercase();
+        let key = parse.next_string()?;
+        let group = parse.next_string()?;
+
+        let subcommand = match &subcommand[..] {
+            "CREATE" => {
+                let id = parse.next_string()?;
+                let mkstream = match parse.next_string() {
+                    Ok(s) if s.to_uppercase() == "MKSTREAM" => true,
+                    Ok(_) => return Err("ERR syntax error".into()),
+                    Err(EndOfStream) => false,
+                    Err(err) => return Err(err.into()),
+                };
+                Subcommand::Create {
+                    key,
+                    group,
+                    id,
+                    mkstream,
+                }
+            }
+            "DESTROY" => Subcommand::Destroy { key, group },
+            _ => {
+                return Err(format!("ERR unknown subcommand '{}' for `XGROUP`", subcommand).into())
+            }
+        };
+
+        Ok(Xgroup { subcommand })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self.subcommand {
+            Subcommand::Create {
+                key,
+                group,
+                id,
+                mkstream,
+            } => {
+                let id = match &id[..] {
+                    "$" => Ok(None),
+                    id => StreamId::parse(id).map(Some),
+                };
+                match id.and_then(|id| db.xgroup_create(&key, &group, id, mkstream)) {
+                    Ok(()) => Frame::Simple("OK".to_string()),
+                    Err(err) => Frame::Error(err.to_string()),
+                }
+            }
+            Subcommand::Destroy { key, group } => match db.xgroup_destroy(&key, &group) {
+                Ok(destroyed) => Frame::Integer(destroyed as i64),
+                Err(err) => Frame::Error(err.to_string()),
+            },
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xgroup".as_bytes()));
+        match self.subcommand {
+            Subcommand::Create {
+                key,
+                group,
+                id,
+                mkstream,
+            } => {
+                frame.push_bulk(Bytes::from("CREATE".as_bytes()));
+                frame.push_bulk(Bytes::from(key.into_bytes()));
+                frame.push_bulk(Bytes::from(group.into_bytes()));
+                frame.push_bulk(Bytes::from(id.into_bytes()));
+                if mkstream {
+                    frame.push_bulk(Bytes::from("MKSTREAM".as_bytes()));
+                }
+            }
+            Subcommand::Destroy { key, group } => {
+                frame.push_bulk(Bytes::from("DESTROY".as_bytes()));
+                frame.push_bulk(Bytes::from(key.into_bytes()));
+                frame.push_bulk(Bytes::from(group.into_bytes()));
+            }
+        }
+        frame
+    }
+}
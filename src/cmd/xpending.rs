@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::time::Instant;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, StreamId},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// `XPENDING`, either the summary form or the extended form when a range is
+/// given.
+#[derive(Debug)]
+pub struct Xpending {
+    key: String,
+    group: String,
+    range: Option<PendingRange>,
+}
+
+#[derive(Debug)]
+struct PendingRange {
+    min_idle: Option<u64>,
+    start: String,
+    end: String,
+    count: u64,
+    consumer: Option<String>,
+}
+
+impl Xpending {
+    pub fn new(key: impl ToString, group: impl ToString) -> Xpending {
+        Xpending {
+            key: key.to_string(),
+            group: group.to_string(),
+            range: None,
+        }
+    }
+
+    pub(crate) fn range(
+        key: impl ToString,
+        group: impl ToString,
+        start: impl ToString,
+        end: impl ToString,
+        count: u64,
+    ) -> Xpending {
+        Xpending {
+            key: key.to_string(),
+            group: group.to_string(),
+            range: Some(PendingRange {
+                min_idle: None,
+                start: start.to_string(),
+                end: end.to_string(),
+                count,
+                consumer: None,
+            }),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xpending> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let group = parse.next_string()?;
+
+        let mut min_idle = None;
+        let start = match parse.next_string() {
+            Ok(s) if s.to_uppercase() == "IDLE" => {
+                min_idle = Some(parse.next_int()?);
+                parse.next_string()?
+            }
+            Ok(s) => s,
+            Err(EndOfStream) => {
+                return Ok(Xpending {
+                    key,
+                    group,
+                    range: None,
+                })
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let end = parse.next_string()?;
+        let count = parse.next_int()?;
+        let consumer = match parse.next_string() {
+            Ok(consumer) => Some(consumer),
+            Err(EndOfStream) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Xpending {
+            key,
+            group,
+            range: Some(PendingRange {
+                min_idle,
+                start,
+                end,
+                count,
+                consumer,
+            }),
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match self.range {
+            None => match db.xpending_summary(&self.key, &self.group) {
+                Ok(summary) => {
+                    let (min, max) = match summary.bounds {
+                        Some((min, max)) => (
+                            Frame::Bulk(Bytes::from(min.to_string())),
+                            Frame::Bulk(Bytes::from(max.to_string())),
+                        ),
+                        None => (Frame::Null, Frame::Null),
+                    };
+                    let consumers = if summary.consumers.is_empty() {
+                        Frame::Null
+                    } else {
+                        Frame::Array(
+                            summary
+                                .consumers
+                                .into_iter()
+                                .map(|(consumer, count)| {
+                                    Frame::Array(vec![
+                                        Frame::Bulk(Bytes::from(consumer)),
+                                        Frame::Bulk(Bytes::from(count.to_string())),
+                                    ])
+                                })
+                                .collect(),
+                        )
+                    };
+                    Frame::Array(vec![
+                        Frame::Integer(summary.count as i64),
+                        min,
+                        max,
+                        consumers,
+                    ])
+                }
+                Err(err) => Frame::Error(err.to_string()),
+            },
+            Some(range) => {
+                let min_idle = Duration::from_millis(range.min_idle.unwrap_or(0));
+                let pending = StreamId::parse_start(&range.start).and_then(|start| {
+                    let end = StreamId::parse_end(&range.end)?;
+                    db.xpending_range(
+                        &self.key,
+                        &self.group,
+                        min_idle,
+                        start,
+                        end,
+                        range.count as usize,
+                        range.consumer.as_deref(),
+                    )
+                });
+
+                let now = Instant::now();
+                match pending {
+                    Ok(pending) => Frame::Array(
+                        pending
+                            .into_iter()
+                            .map(|(id, pending)| {
+                                let idle = now - pending.delivered_at;
+                                Frame::Array(vec![
+                                    Frame::Bulk(Bytes::from(id.to_string())),
+                                    Frame::Bulk(Bytes::from(pending.consumer)),
+                                    Frame::Integer(idle.as_millis() as i64),
+                                    Frame::Integer(pending.deliveries as i64),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                    Err(err) => Frame::Error(err.to_string()),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xpending".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.group.into_bytes()));
+        if let Some(range) = self.range {
+            if let Some(min_idle) = range.min_idle {
+                frame.push_bulk(Bytes::from("IDLE".as_bytes()));
+                frame.push_int(min_idle as i64);
+            }
+            frame.push_bulk(Bytes::from(range.start.into_bytes()));
+            frame.push_bulk(Bytes::from(range.end.into_bytes()));
+            frame.push_int(range.count as i64);
+            if let Some(consumer) = range.consumer {
+                frame.push_bulk(Bytes::from(consumer.into_bytes()));
+            }
+        }
+        frame
+    }
+}
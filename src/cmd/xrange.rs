@@ -0,0 +1,135 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, Fields, StreamId},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// `XRANGE`, or `XREVRANGE` when `rev` is set.
+#[derive(Debug)]
+pub struct Xrange {
+    key: String,
+    start: String,
+    end: String,
+    count: Option<u64>,
+    rev: bool,
+}
+
+impl Xrange {
+    pub(crate) fn new(
+        key: impl ToString,
+        start: impl ToString,
+        end: impl ToString,
+        count: Option<u64>,
+        rev: bool,
+    ) -> Xrange {
+        Xrange {
+            key: key.to_string(),
+            start: start.to_string(),
+            end: end.to_string(),
+            count,
+            rev,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, rev: bool) -> crate::Result<Xrange> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let (start, end) = if rev {
+            let end = parse.next_string()?;
+            (parse.next_string()?, end)
+        } else {
+            (parse.next_string()?, parse.next_string()?)
+        };
+
+        let count = match parse.next_string() {
+            Ok(s) if s.to_uppercase() == "COUNT" => Some(parse.next_int()?),
+            Ok(_) => return Err("ERR syntax error".into()),
+            Err(EndOfStream) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Xrange {
+            key,
+            start,
+            end,
+            count,
+            rev,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let count = self.count.map(|count| count as usize);
+        let records = StreamId::parse_start(&self.start).and_then(|start| {
+            let end = StreamId::parse_end(&self.end)?;
+            db.xrange(&self.key, start, end, count, self.rev)
+        });
+
+        match records {
+            Ok(records) => records_frame(records),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        if self.rev {
+            frame.push_bulk(Bytes::from("xrevrange".as_bytes()));
+            frame.push_bulk(Bytes::from(self.key.into_bytes()));
+            frame.push_bulk(Bytes::from(self.end.into_bytes()));
+            frame.push_bulk(Bytes::from(self.start.into_bytes()));
+        } else {
+            frame.push_bulk(Bytes::from("xrange".as_bytes()));
+            frame.push_bulk(Bytes::from(self.key.into_bytes()));
+            frame.push_bulk(Bytes::from(self.start.into_bytes()));
+            frame.push_bulk(Bytes::from(self.end.into_bytes()));
+        }
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("COUNT".as_bytes()));
+            frame.push_int(count as i64);
+        }
+        frame
+    }
+}
+
+/// `[id, [field, value, ...]]`, or `[id, nil]` for a deleted record.
+pub(crate) fn record_frame(id: StreamId, fields: Option<Fields>) -> Frame {
+    let fields = match fields {
+        Some(fields) => {
+            let mut frame = Frame::array();
+            for (field, value) in fields {
+                frame.push_bulk(field);
+                frame.push_bulk(value);
+            }
+            frame
+        }
+        None => Frame::Null,
+    };
+
+    Frame::Array(vec![Frame::Bulk(Bytes::from(id.to_string())), fields])
+}
+
+pub(crate) fn records_frame(records: Vec<(StreamId, Fields)>) -> Frame {
+    Frame::Array(
+        records
+            .into_iter()
+            .map(|(id, fields)| record_frame(id, Some(fields)))
+            .collect(),
+    )
+}
@@ -0,0 +1,135 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    cmd::xrange::record_frame,
+    db::{Db, ReadFrom, StreamId},
+    parse::Parse,
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Xreadgroup {
+    group: String,
+    consumer: String,
+    count: Option<u64>,
+    noack: bool,
+    streams: Vec<(String, String)>,
+}
+
+impl Xreadgroup {
+    pub(crate) fn new(
+        group: impl ToString,
+        consumer: impl ToString,
+        count: Option<u64>,
+        streams: Vec<(String, String)>,
+    ) -> Xreadgroup {
+        Xreadgroup {
+            group: group.to_string(),
+            consumer: consumer.to_string(),
+            count,
+            noack: false,
+            streams,
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.streams.iter().map(|(key, _)| &key[..])
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xreadgroup> {
+        if parse.next_string()?.to_uppercase() != "GROUP" {
+            return Err("ERR syntax error".into());
+        }
+        let group = parse.next_string()?;
+        let consumer = parse.next_string()?;
+
+        let mut count = None;
+        let mut noack = false;
+        loop {
+            match &parse.next_string()?.to_uppercase()[..] {
+                "COUNT" => count = Some(parse.next_int()?),
+                "NOACK" => noack = true,
+                "STREAMS" => break,
+                _ => return Err("ERR syntax error".into()),
+            }
+        }
+
+        let mut args = parse.remaining_strings()?;
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err("ERR Unbalanced 'xreadgroup' list of streams: for each stream key an ID or '>' must be specified.".into());
+        }
+        let ids = args.split_off(args.len() / 2);
+        let streams = args.into_iter().zip(ids).collect();
+
+        Ok(Xreadgroup {
+            group,
+            consumer,
+            count,
+            noack,
+            streams,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let streams = self
+            .streams
+            .into_iter()
+            .map(|(key, id)| match &id[..] {
+                ">" => Ok((key, ReadFrom::New)),
+                id => Ok((key, ReadFrom::Pending(StreamId::parse(id)?))),
+            })
+            .collect::<crate::Result<Vec<_>>>();
+        let count = self.count.map(|count| count as usize);
+        let result = streams.and_then(|streams| {
+            db.xreadgroup(&self.group, &self.consumer, &streams, count, self.noack)
+        });
+
+        match result {
+            Ok(streams) if streams.is_empty() => Frame::Null,
+            Ok(streams) => Frame::Array(
+                streams
+                    .into_iter()
+                    .map(|(key, records)| {
+                        let records = records
+                            .into_iter()
+                            .map(|(id, fields)| record_frame(id, fields))
+                            .collect();
+                        Frame::Array(vec![Frame::Bulk(Bytes::from(key)), Frame::Array(records)])
+                    })
+                    .collect(),
+            ),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xreadgroup".as_bytes()));
+        frame.push_bulk(Bytes::from("GROUP".as_bytes()));
+        frame.push_bulk(Bytes::from(self.group.into_bytes()));
+        frame.push_bulk(Bytes::from(self.consumer.into_bytes()));
+        if let Some(count) = self.count {
+            frame.push_bulk(Bytes::from("COUNT".as_bytes()));
+            frame.push_int(count as i64);
+        }
+        if self.noack {
+            frame.push_bulk(Bytes::from("NOACK".as_bytes()));
+        }
+        frame.push_bulk(Bytes::from("STREAMS".as_bytes()));
+        let (keys, ids): (Vec<_>, Vec<_>) = self.streams.into_iter().unzip();
+        for arg in keys.into_iter().chain(ids) {
+            frame.push_bulk(Bytes::from(arg.into_bytes()));
+        }
+        frame
+    }
+}
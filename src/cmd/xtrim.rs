@@ -0,0 +1,57 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{cmd::xadd::parse_maxlen, db::Db, parse::Parse, Connection, Frame};
+
+#[derive(Debug)]
+pub struct Xtrim {
+    key: String,
+    maxlen: u64,
+}
+
+impl Xtrim {
+    pub fn new(key: impl ToString, maxlen: u64) -> Xtrim {
+        Xtrim {
+            key: key.to_string(),
+            maxlen,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xtrim> {
+        let key = parse.next_string()?;
+        if parse.next_string()?.to_uppercase() != "MAXLEN" {
+            return Err("ERR syntax error, `XTRIM` only supports the MAXLEN strategy".into());
+        }
+        let maxlen = parse_maxlen(parse)?;
+        Ok(Xtrim { key, maxlen })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.xtrim(&self.key, self.maxlen as usize) {
+            Ok(removed) => Frame::Integer(removed as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("xtrim".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from("MAXLEN".as_bytes()));
+        frame.push_bulk(Bytes::from(self.maxlen.to_string()));
+        frame
+    }
+}
@@ -0,0 +1,122 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{format_score, parse_score, Db, ZaddOptions},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Zadd {
+    key: String,
+    options: ZaddOptions,
+    incr: bool,
+    members: Vec<(f64, Bytes)>,
+}
+
+impl Zadd {
+    pub(crate) fn new(
+        key: impl ToString,
+        options: ZaddOptions,
+        incr: bool,
+        members: Vec<(f64, Bytes)>,
+    ) -> Zadd {
+        Zadd {
+            key: key.to_string(),
+            options,
+            incr,
+            members,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zadd> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let mut options = ZaddOptions::default();
+        let mut incr = false;
+
+        // Flags come first, the first argument that is not a flag is a score.
+        let first = loop {
+            let arg = parse.next_string()?;
+            match &arg.to_uppercase()[..] {
+                "NX" => options.nx = true,
+                "XX" => options.xx = true,
+                "GT" => options.gt = true,
+                "LT" => options.lt = true,
+                "CH" => options.ch = true,
+                "INCR" => incr = true,
+                _ => break arg,
+            }
+        };
+
+        let score = parse_score(&first).ok_or("ERR value is not a valid float")?;
+        let mut members = vec![(score, parse.next_bytes()?)];
+        loop {
+            match parse.next_double() {
+                Ok(score) => members.push((score, parse.next_bytes()?)),
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        options.validate()?;
+        if incr && members.len() > 1 {
+            return Err("ERR INCR option supports a single increment-element pair".into());
+        }
+
+        Ok(Zadd {
+            key,
+            options,
+            incr,
+            members,
+        })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.zadd(&self.key, self.options, self.incr, self.members) {
+            Ok((_, Some(score))) if self.incr => Frame::Bulk(Bytes::from(format_score(score))),
+            Ok((_, None)) if self.incr => Frame::Null,
+            Ok((count, _)) => Frame::Integer(count as i64),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("zadd".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+
+        let flags = [
+            (self.options.nx, "NX"),
+            (self.options.xx, "XX"),
+            (self.options.gt, "GT"),
+            (self.options.lt, "LT"),
+            (self.options.ch, "CH"),
+            (self.incr, "INCR"),
+        ];
+        for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+            frame.push_bulk(Bytes::from(flag.as_bytes()));
+        }
+
+        for (score, member) in self.members {
+            frame.push_bulk(Bytes::from(format_score(score)));
+            frame.push_bulk(member);
+        }
+        frame
+    }
+}
@@ -0,0 +1,61 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{format_score, Db},
+    parse::Parse,
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Zincrby {
+    key: String,
+    delta: f64,
+    member: Bytes,
+}
+
+impl Zincrby {
+    pub fn new(key: impl ToString, delta: f64, member: Bytes) -> Zincrby {
+        Zincrby {
+            key: key.to_string(),
+            delta,
+            member,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zincrby> {
+        let key = parse.next_string()?;
+        let delta = parse.next_double()?;
+        let member = parse.next_bytes()?;
+        Ok(Zincrby { key, delta, member })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        match db.zincrby(&self.key, self.delta, self.member) {
+            Ok(score) => Frame::Double(score),
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("zincrby".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(format_score(self.delta)));
+        frame.push_bulk(self.member);
+        frame
+    }
+}
@@ -0,0 +1,75 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::Db,
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+#[derive(Debug)]
+pub struct Zpopmin {
+    key: String,
+    count: Option<u64>,
+}
+
+impl Zpopmin {
+    pub fn new(key: impl ToString, count: Option<u64>) -> Zpopmin {
+        Zpopmin {
+            key: key.to_string(),
+            count,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zpopmin> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let count = match parse.next_int() {
+            Ok(count) => Some(count),
+            Err(EndOfStream) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Zpopmin { key, count })
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let count = self.count.unwrap_or(1) as usize;
+        match db.zpopmin(&self.key, count) {
+            Ok(members) => {
+                let mut response = vec![];
+                for (member, score) in members {
+                    response.push(Frame::Bulk(member));
+                    // Written as a bulk string to RESP2 connections.
+                    response.push(Frame::Double(score));
+                }
+                Frame::Array(response)
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("zpopmin".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        if let Some(count) = self.count {
+            frame.push_int(count as i64);
+        }
+        frame
+    }
+}
@@ -0,0 +1,191 @@
+use bytes::Bytes;
+use tracing::{debug, instrument};
+
+use crate::{
+    db::{Db, LexBound, RangeBy, ScoreBound},
+    parse::{Parse, ParseError},
+    Connection, Frame,
+};
+
+/// `ZRANGE` in all its forms. `ZRANGEBYSCORE` parses into the equivalent
+/// `ZRANGE ... BYSCORE`.
+#[derive(Debug)]
+pub struct Zrange {
+    key: String,
+    start: String,
+    stop: String,
+    kind: RangeKind,
+    rev: bool,
+    limit: Option<(i64, i64)>,
+    withscores: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RangeKind {
+    Rank,
+    Score,
+    Lex,
+}
+
+impl Zrange {
+    pub(crate) fn new(
+        key: impl ToString,
+        start: impl ToString,
+        stop: impl ToString,
+        kind: RangeKind,
+    ) -> Zrange {
+        Zrange {
+            key: key.to_string(),
+            start: start.to_string(),
+            stop: stop.to_string(),
+            kind,
+            rev: false,
+            limit: None,
+            withscores: false,
+        }
+    }
+
+    pub(crate) fn rev(mut self) -> Zrange {
+        self.rev = true;
+        self
+    }
+
+    pub(crate) fn limit(mut self, offset: i64, count: i64) -> Zrange {
+        self.limit = Some((offset, count));
+        self
+    }
+
+    pub(crate) fn withscores(mut self) -> Zrange {
+        self.withscores = true;
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, by_score: bool) -> crate::Result<Zrange> {
+        use ParseError::EndOfStream;
+
+        let key = parse.next_string()?;
+        let start = parse.next_string()?;
+        let stop = parse.next_string()?;
+        let kind = if by_score {
+            RangeKind::Score
+        } else {
+            RangeKind::Rank
+        };
+        let mut zrange = Zrange::new(key, start, stop, kind);
+
+        loop {
+            match parse.next_string() {
+                Ok(s) => match &s.to_uppercase()[..] {
+                    "BYSCORE" if !by_score => zrange.kind = RangeKind::Score,
+                    "BYLEX" if !by_score => zrange.kind = RangeKind::Lex,
+                    "REV" if !by_score => zrange.rev = true,
+                    "WITHSCORES" => zrange.withscores = true,
+                    "LIMIT" => {
+                        let offset = parse.next_signed_int()?;
+                        let count = parse.next_signed_int()?;
+                        zrange.limit = Some((offset, count));
+                    }
+                    _ => return Err("ERR syntax error".into()),
+                },
+                Err(EndOfStream) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(zrange)
+    }
+
+    /// Interprets `start` and `stop` according to the range kind. With `REV`
+    /// the score and lex forms take the upper bound first.
+    fn range_by(&self) -> crate::Result<RangeBy> {
+        let (min, max) = if self.rev && self.kind != RangeKind::Rank {
+            (&self.stop, &self.start)
+        } else {
+            (&self.start, &self.stop)
+        };
+
+        match self.kind {
+            RangeKind::Rank if self.limit.is_some() => Err(
+                "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                    .into(),
+            ),
+            RangeKind::Rank => {
+                const MSG: &str = "ERR value is not an integer or out of range";
+                let start = min.parse().map_err(|_| MSG)?;
+                let stop = max.parse().map_err(|_| MSG)?;
+                Ok(RangeBy::Rank(start, stop))
+            }
+            RangeKind::Score => Ok(RangeBy::Score(
+                ScoreBound::parse(min)?,
+                ScoreBound::parse(max)?,
+            )),
+            RangeKind::Lex if self.withscores => {
+                Err("ERR syntax error, WITHSCORES not supported in combination with BYLEX".into())
+            }
+            RangeKind::Lex => Ok(RangeBy::Lex(
+                LexBound::parse(&Bytes::from(min.clone()))?,
+                LexBound::parse(&Bytes::from(max.clone()))?,
+            )),
+        }
+    }
+
+    #[instrument(skip(self, db, dst))]
+    pub(crate) async fn apply(self, db: &Db, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db);
+        debug!(?response);
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self, db: &Db) -> Frame {
+        let members = self
+            .range_by()
+            .and_then(|by| db.zrange(&self.key, &by, self.rev, self.limit));
+
+        match members {
+            Ok(members) => {
+                let mut response = vec![];
+                for (member, score) in members {
+                    response.push(Frame::Bulk(member));
+                    if self.withscores {
+                        // Written as a bulk string to RESP2 connections.
+                        response.push(Frame::Double(score));
+                    }
+                }
+                Frame::Array(response)
+            }
+            Err(err) => Frame::Error(err.to_string()),
+        }
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        let mut frame = Frame::array();
+        frame.push_bulk(Bytes::from("zrange".as_bytes()));
+        frame.push_bulk(Bytes::from(self.key.into_bytes()));
+        frame.push_bulk(Bytes::from(self.start.into_bytes()));
+        frame.push_bulk(Bytes::from(self.stop.into_bytes()));
+
+        match self.kind {
+            RangeKind::Rank => {}
+            RangeKind::Score => frame.push_bulk(Bytes::from("BYSCORE".as_bytes())),
+            RangeKind::Lex => frame.push_bulk(Bytes::from("BYLEX".as_bytes())),
+        }
+        if self.rev {
+            frame.push_bulk(Bytes::from("REV".as_bytes()));
+        }
+        if let Some((offset, count)) = self.limit {
+            frame.push_bulk(Bytes::from("LIMIT".as_bytes()));
+            frame.push_bulk(Bytes::from(offset.to_string()));
+            frame.push_bulk(Bytes::from(count.to_string()));
+        }
+        if self.withscores {
+            frame.push_bulk(Bytes::from("WITHSCORES".as_bytes()));
+        }
+        frame
+    }
+}
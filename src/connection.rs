@@ -1,12 +1,19 @@
-use std::io::{self, Cursor};
+use std::{
+    io::{self, Cursor},
+    net::SocketAddr,
+};
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt, BufWriter},
     net::TcpStream,
 };
 
-use crate::frame::{self, Frame};
+use crate::{
+    db::format_score,
+    frame::{self, Frame},
+    Command,
+};
 
 /*
  * Copyright (c) QieTv, Inc. 2018
@@ -15,10 +22,23 @@ use crate::frame::{self, Frame};
  * @Last Modified by: idzeir
  * @Last Modified time: 2023-10-23 16:18:27
  */
+/// Inline commands may not be longer than this, like in Redis.
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+const UNBALANCED_QUOTES: &str = "ERR Protocol error: unbalanced quotes in request";
+
 #[derive(Debug)]
 pub struct Connection {
     stream: BufWriter<TcpStream>,
     buffer: BytesMut,
+    /// Whether frames are written with the RESP3 types, see `HELLO`.
+    resp3: bool,
+    /// The port the peer accepts connections on, if it is a replica, see
+    /// `REPLCONF`.
+    listening_port: Option<u16>,
+    /// Whether the next command may use a slot this node is importing, see
+    /// `ASKING`.
+    asking: bool,
 }
 
 impl Connection {
@@ -26,28 +46,123 @@ impl Connection {
         Connection {
             stream: BufWriter::new(socket),
             buffer: BytesMut::with_capacity(4 * 1024),
+            resp3: false,
+            listening_port: None,
+            asking: false,
+        }
+    }
+
+    /// The RESP version frames are written with, `2` unless changed.
+    pub fn protocol(&self) -> u8 {
+        if self.resp3 {
+            3
+        } else {
+            2
         }
     }
 
+    /// Switches to RESP3, or back to RESP2. Frames are always read with
+    /// both.
+    pub fn set_protocol(&mut self, version: u8) {
+        self.resp3 = version == 3;
+    }
+
+    pub(crate) fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.get_ref().peer_addr()
+    }
+
+    pub(crate) fn listening_port(&self) -> Option<u16> {
+        self.listening_port
+    }
+
+    pub(crate) fn set_listening_port(&mut self, port: u16) {
+        self.listening_port = Some(port);
+    }
+
+    pub(crate) fn set_asking(&mut self) {
+        self.asking = true;
+    }
+
+    /// Whether the command about to run follows `ASKING`, which only lasts
+    /// for one command.
+    pub(crate) fn take_asking(&mut self) -> bool {
+        std::mem::take(&mut self.asking)
+    }
+
     pub async fn read_frame(&mut self) -> crate::Result<Option<Frame>> {
+        Ok(self.read_frame_len().await?.map(|(frame, _)| frame))
+    }
+
+    /// Like `read_frame`, also returning how many bytes the frame took, which
+    /// is how replicas tell how far along the replication stream they are.
+    pub(crate) async fn read_frame_len(&mut self) -> crate::Result<Option<(Frame, usize)>> {
         loop {
+            let len = self.buffer.len();
             if let Some(frame) = self.parse_frame()? {
-                return Ok(Some(frame));
+                return Ok(Some((frame, len - self.buffer.len())));
             }
 
-            if 0 == self.stream.read_buf(&mut self.buffer).await? {
-                if self.buffer.is_empty() {
-                    return Ok(None);
-                } else {
-                    return Err("connection reset by peer".into());
+            if !self.read_more().await? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Like `read_frame`, for the server reading the requests of a client. A
+    /// request that can't be parsed is replied to with the error, like Redis
+    /// does, before the error closes the connection.
+    pub(crate) async fn read_request(&mut self) -> crate::Result<Option<Frame>> {
+        loop {
+            match self.parse_frame() {
+                Ok(Some(frame)) => return Ok(Some(frame)),
+                Ok(None) => {}
+                Err(err) => {
+                    let response = Command::parse_error_reply(err.to_string().into());
+                    self.write_frame(&response).await?;
+                    return Err(err);
                 }
             }
+
+            if !self.read_more().await? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Reads more data into the buffer, returning `false` if the peer closed
+    /// the connection in between frames.
+    async fn read_more(&mut self) -> crate::Result<bool> {
+        if 0 == self.stream.read_buf(&mut self.buffer).await? {
+            if self.buffer.is_empty() {
+                return Ok(false);
+            } else {
+                return Err("connection reset by peer".into());
+            }
         }
+        Ok(true)
     }
 
     fn parse_frame(&mut self) -> crate::Result<Option<Frame>> {
         use frame::Error::Incomplete;
 
+        // Clients send commands as arrays, but people typing them in `nc` or
+        // `telnet` send them inline, as space separated arguments.
+        while let Some(&byte) = self.buffer.first() {
+            if Frame::is_type_byte(byte) {
+                break;
+            }
+            match self.parse_inline()? {
+                // Like Redis, empty lines are ignored.
+                Some(args) if args.is_empty() => {}
+                Some(args) => {
+                    return Ok(Some(Frame::Array(
+                        args.into_iter().map(Frame::Bulk).collect(),
+                    )))
+                }
+                None => return Ok(None),
+            }
+        }
+
         let mut buf = Cursor::new(&self.buffer[..]);
 
         match Frame::check(&mut buf) {
@@ -63,17 +178,35 @@ impl Connection {
         }
     }
 
-    pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::Array(val) => {
-                self.stream.write_u8(b'*').await?;
-                self.write_decimal(val.len() as u64).await?;
-                for entry in &**val {
-                    self.write_value(entry).await?;
-                }
+    /// Parses the arguments of the inline command at the start of the buffer,
+    /// or returns `None` if its line is incomplete.
+    fn parse_inline(&mut self) -> crate::Result<Option<Vec<Bytes>>> {
+        let end = match self.buffer.iter().position(|&byte| byte == b'\n') {
+            Some(end) => end,
+            None if self.buffer.len() > MAX_INLINE_LEN => {
+                return Err("ERR Protocol error: too big inline request".into())
             }
-            _ => self.write_value(frame).await?,
-        }
+            None => return Ok(None),
+        };
+
+        let line = self.buffer.split_to(end + 1);
+        let line = &line[..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        split_args(line).map(Some)
+    }
+
+    /// Writes `frame`, downgrading the RESP3 types for RESP2 connections like
+    /// Redis does: maps are flattened to arrays, doubles and big numbers
+    /// become bulk strings, and attributes are dropped.
+    pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        self.write_value(frame).await?;
+
+        self.stream.flush().await
+    }
+
+    /// Writes bytes that are already encoded, like the replication stream.
+    pub(crate) async fn write_raw(&mut self, src: &[u8]) -> io::Result<()> {
+        self.stream.write_all(src).await?;
 
         self.stream.flush().await
     }
@@ -94,22 +227,111 @@ impl Connection {
                 self.stream.write_u8(b':').await?;
                 self.write_decimal(*val).await?;
             }
+            Frame::Null if self.resp3 => {
+                self.stream.write_all(b"_\r\n").await?;
+            }
             Frame::Null => {
                 self.stream.write_all(b"$-1\r\n").await?;
             }
-            Frame::Bulk(val) => {
-                let len = val.len();
-                self.stream.write_u8(b'$').await?;
-                self.write_decimal(len as u64).await?;
-                self.stream.write_all(val).await?;
-                self.stream.write_all(b"\r\n").await?;
+            Frame::Bulk(val) => self.write_bulk(val).await?,
+            Frame::Array(val) => self.write_aggregate(b'*', val).await?,
+            Frame::Set(val) => self.write_aggregate(b'~', val).await?,
+            Frame::Push(val) => self.write_aggregate(b'>', val).await?,
+            Frame::Map(pairs) => {
+                if self.resp3 {
+                    self.stream.write_u8(b'%').await?;
+                    self.write_decimal(pairs.len() as i64).await?;
+                } else {
+                    self.stream.write_u8(b'*').await?;
+                    self.write_decimal(2 * pairs.len() as i64).await?;
+                }
+                self.write_pairs(pairs).await?;
+            }
+            Frame::Double(val) => {
+                let val = format_score(*val);
+                if self.resp3 {
+                    self.write_line(b',', val.as_bytes()).await?;
+                } else {
+                    self.write_bulk(val.as_bytes()).await?;
+                }
+            }
+            Frame::Boolean(val) => {
+                if self.resp3 {
+                    let val: &[u8] = if *val { b"t" } else { b"f" };
+                    self.write_line(b'#', val).await?;
+                } else {
+                    self.stream.write_u8(b':').await?;
+                    self.write_decimal(*val as i64).await?;
+                }
+            }
+            Frame::BigNumber(val) => {
+                if self.resp3 {
+                    self.write_line(b'(', val.as_bytes()).await?;
+                } else {
+                    self.write_bulk(val.as_bytes()).await?;
+                }
+            }
+            Frame::Verbatim { format, text } => {
+                if self.resp3 {
+                    self.stream.write_u8(b'=').await?;
+                    self.write_decimal((format.len() + 1 + text.len()) as i64)
+                        .await?;
+                    self.stream.write_all(format.as_bytes()).await?;
+                    self.stream.write_u8(b':').await?;
+                    self.stream.write_all(text).await?;
+                    self.stream.write_all(b"\r\n").await?;
+                } else {
+                    self.write_bulk(text).await?;
+                }
+            }
+            Frame::Attribute { attributes, frame } => {
+                if self.resp3 {
+                    self.stream.write_u8(b'|').await?;
+                    self.write_decimal(attributes.len() as i64).await?;
+                    self.write_pairs(attributes).await?;
+                }
+                Box::pin(self.write_value(frame)).await?;
             }
-            Frame::Array(_) => unreachable!(),
         }
         Ok(())
     }
 
-    async fn write_decimal(&mut self, val: u64) -> io::Result<()> {
+    async fn write_bulk(&mut self, val: &[u8]) -> io::Result<()> {
+        self.stream.write_u8(b'$').await?;
+        self.write_decimal(val.len() as i64).await?;
+        self.stream.write_all(val).await?;
+        self.stream.write_all(b"\r\n").await
+    }
+
+    async fn write_line(&mut self, prefix: u8, line: &[u8]) -> io::Result<()> {
+        self.stream.write_u8(prefix).await?;
+        self.stream.write_all(line).await?;
+        self.stream.write_all(b"\r\n").await
+    }
+
+    /// Writes an array, or a set or push frame, which RESP2 only knows as
+    /// arrays.
+    async fn write_aggregate(&mut self, prefix: u8, val: &[Frame]) -> io::Result<()> {
+        let prefix = if self.resp3 { prefix } else { b'*' };
+        self.stream.write_u8(prefix).await?;
+        self.write_decimal(val.len() as i64).await?;
+        for entry in val {
+            // Arrays may nest, e.g. stream records, so the recursive call
+            // has to be boxed.
+            Box::pin(self.write_value(entry)).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_pairs(&mut self, pairs: &[(Frame, Frame)]) -> io::Result<()> {
+        for (key, value) in pairs {
+            Box::pin(self.write_value(key)).await?;
+            Box::pin(self.write_value(value)).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_decimal(&mut self, val: i64) -> io::Result<()> {
         use std::io::Write;
         let mut buf = [0u8; 20];
         let mut buf = Cursor::new(&mut buf[..]);
@@ -121,3 +343,77 @@ impl Connection {
         Ok(())
     }
 }
+
+/// Splits an inline command into its arguments, following the quoting rules
+/// of Redis: double quoted arguments may hold escapes such as `\n` or `\x41`,
+/// single quoted ones only `\'`.
+fn split_args(line: &[u8]) -> crate::Result<Vec<Bytes>> {
+    let mut args = vec![];
+    let mut i = 0;
+    loop {
+        while line.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        if i == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = vec![];
+        match line[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                loop {
+                    match line.get(i) {
+                        None => return Err(UNBALANCED_QUOTES.into()),
+                        Some(&byte) if byte == quote => break,
+                        Some(b'\\') if quote == b'"' => {
+                            let (byte, len) = unescape(&line[i + 1..]);
+                            arg.push(byte);
+                            i += len;
+                        }
+                        Some(b'\\') if line.get(i + 1) == Some(&b'\'') => {
+                            arg.push(b'\'');
+                            i += 1;
+                        }
+                        Some(&byte) => arg.push(byte),
+                    }
+                    i += 1;
+                }
+                // The closing quote must end the argument.
+                i += 1;
+                if line.get(i).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+                    return Err(UNBALANCED_QUOTES.into());
+                }
+            }
+            _ => {
+                while let Some(&byte) = line.get(i) {
+                    if byte.is_ascii_whitespace() {
+                        break;
+                    }
+                    arg.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        args.push(Bytes::from(arg));
+    }
+}
+
+/// Decodes the escape sequence following a backslash, returning the byte
+/// and how many bytes of `rest` it used.
+fn unescape(rest: &[u8]) -> (u8, usize) {
+    let hex = |byte: u8| (byte as char).to_digit(16);
+    match rest {
+        [b'x', high, low, ..] => match (hex(*high), hex(*low)) {
+            (Some(high), Some(low)) => ((high * 16 + low) as u8, 3),
+            _ => (b'x', 1),
+        },
+        [b'n', ..] => (b'\n', 1),
+        [b'r', ..] => (b'\r', 1),
+        [b't', ..] => (b'\t', 1),
+        [b'b', ..] => (0x08, 1),
+        [b'a', ..] => (0x07, 1),
+        [byte, ..] => (*byte, 1),
+        [] => (b'\\', 0),
+    }
+}
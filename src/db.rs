@@ -1,18 +1,83 @@
 use std::{
-    collections::{BTreeSet, HashMap},
-    sync::{Arc, Mutex},
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    future::{poll_fn, Future},
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    task::Poll,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use tokio::{
-    sync::{broadcast, Notify},
+    runtime::{Handle, RuntimeFlavor},
+    sync::{broadcast, futures::Notified, Notify},
     time::{self, Duration, Instant},
 };
 use tracing::debug;
 
+use crate::{
+    glob::glob_match,
+    server::{Config, SlowSubscribers},
+};
+
+mod scan;
+use scan::scan_page;
+pub use scan::ScanOptions;
+
+mod scripts;
+pub(crate) use scripts::{sha1_hex, ScriptCache};
+
+mod aof;
+use aof::Aof;
+
+mod cluster;
+use cluster::Cluster;
+pub(crate) use cluster::SLOTS;
+
+mod propagation;
+use propagation::Propagation;
+
+mod replication;
+use replication::Replication;
+pub(crate) use replication::Resync;
+
+mod snapshot;
+use snapshot::Snapshots;
+
+mod stream;
+pub(crate) use stream::{
+    AutoClaimed, ClaimOptions, Claimed, Delivered, Fields, Pending, PendingSummary, ReadFrom,
+    Stream, StreamId, XaddId,
+};
+
+mod zset;
+pub use zset::ZaddOptions;
+pub(crate) use zset::{format_score, parse_score, LexBound, RangeBy, ScoreBound, SortedSet};
+
 #[derive(Debug)]
 pub(crate) struct DbDropGuard {
-    db: Db,
+    databases: Databases,
+}
+
+/// The numbered databases of a server, each connection works against the
+/// one it selected. Pub/sub channels are server wide, so they all live in
+/// database `0`, and so are Lua scripts.
+#[derive(Debug, Clone)]
+pub(crate) struct Databases {
+    dbs: Arc<[Db]>,
+    scripts: ScriptCache,
+    pubsub_capacity: usize,
+    slow_subscribers: SlowSubscribers,
+    snapshots: Arc<Snapshots>,
+    aof: Arc<Aof>,
+    propagation: Arc<Propagation>,
+    replication: Arc<Replication>,
+    /// The cluster this server is a node of, if it runs in cluster mode.
+    cluster: Option<Arc<Cluster>>,
 }
 
 #[derive(Debug, Clone)]
@@ -24,29 +89,512 @@ pub(crate) struct Db {
 struct Shared {
     state: Mutex<State>,
     background_task: Notify,
+    /// How many `Databases::exclusive` hold, or wait for, the state, which
+    /// they may keep for as long as a script runs.
+    exclusive: AtomicUsize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct State {
     entries: HashMap<String, Entry>,
-    pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
+    pub_sub: HashMap<String, Channel<Bytes>>,
+    /// Subscriptions to glob patterns of channels, which receive the name of
+    /// the channel along with each message.
+    patterns: HashMap<String, Channel<(String, Bytes)>>,
     expirations: BTreeSet<(Instant, String)>,
+    /// Wakes the clients blocked on a key whenever a list is pushed to it.
+    /// Entries are removed once the last blocked client gives up.
+    blocked: HashMap<String, Arc<Notify>>,
+    /// The keys under `WATCH`, see `State::touch`.
+    watched: HashMap<String, Watched>,
+    /// How many times keys were modified, to tell when a snapshot is due.
+    dirty: u64,
+    /// Set while the append only file is replayed, during which keys don't
+    /// expire so that commands replay the way they originally ran.
+    loading: bool,
     shutdown: bool,
 }
 
+/// A pub/sub channel, or pattern of channels, with at least one subscriber.
+#[derive(Debug)]
+struct Channel<T> {
+    tx: broadcast::Sender<T>,
+    /// The messages missed by subscribers too slow to keep up.
+    dropped: u64,
+}
+
+/// The modification version of a watched key, kept for as long as at least
+/// one connection watches it.
+#[derive(Debug)]
+struct Watched {
+    version: u64,
+    watchers: usize,
+}
+
 #[derive(Debug)]
 struct Entry {
-    data: Bytes,
+    /// Shared with the snapshots being written, so that taking one doesn't
+    /// copy the values. Changes copy a value first if it is still shared.
+    data: Arc<Value>,
     expires_at: Option<Instant>,
 }
 
+#[derive(Debug, Clone)]
+enum Value {
+    String(Bytes),
+    List(VecDeque<Bytes>),
+    Hash(HashMap<String, Bytes>),
+    Set(HashSet<Bytes>),
+    ZSet(SortedSet),
+    Stream(Stream),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::ZSet(_) => "zset",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    /// The number of elements, or `1` for a string.
+    fn len(&self) -> usize {
+        match self {
+            Value::String(_) => 1,
+            Value::List(list) => list.len(),
+            Value::Hash(hash) => hash.len(),
+            Value::Set(set) => set.len(),
+            Value::ZSet(zset) => zset.len(),
+            Value::Stream(stream) => stream.len(),
+        }
+    }
+}
+
+/// The end of a list a push or pop operates on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// The algebra applied by `SINTER`, `SUNION`, `SDIFF` and their `STORE` variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+/// The `NX`, `XX`, `GET` and expiration options of `SET`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SetOptions {
+    pub(crate) nx: bool,
+    pub(crate) xx: bool,
+    pub(crate) get: bool,
+    pub(crate) expire: Option<Expiry>,
+}
+
+/// The `NX`, `XX`, `GT` and `LT` flags of `EXPIRE` and its variants.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExpireOptions {
+    pub(crate) nx: bool,
+    pub(crate) xx: bool,
+    pub(crate) gt: bool,
+    pub(crate) lt: bool,
+}
+
+/// How `SET`, `GETEX` and `EXPIRE` change the expiration of a key. Absolute variants
+/// are Unix timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Expiry {
+    Ex(u64),
+    Px(u64),
+    ExAt(u64),
+    PxAt(u64),
+    /// `SET` only, keep the current expiration.
+    KeepTtl,
+    /// `GETEX` only, remove the current expiration.
+    Persist,
+}
+
+/// Values with more elements than this are freed on a blocking thread by
+/// `UNLINK`, like Redis' `lazyfree` threshold.
+const LAZYFREE_THRESHOLD: usize = 64;
+
+pub(crate) const WRONGTYPE: &str =
+    "WRONGTYPE Operation against a key holding the wrong kind of value";
+const NOT_INTEGER: &str = "ERR value is not an integer or out of range";
+
+/// Strings may not grow past 512MB, like the default `proto-max-bulk-len`.
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
 impl DbDropGuard {
-    pub(crate) fn new() -> DbDropGuard {
-        DbDropGuard { db: Db::new() }
+    pub(crate) fn new(config: &Config) -> DbDropGuard {
+        let dbs = (0..config.databases).map(|_| Db::new()).collect();
+        DbDropGuard {
+            databases: Databases {
+                dbs,
+                scripts: ScriptCache::new(config.lua_time_limit),
+                pubsub_capacity: config.pubsub_capacity,
+                slow_subscribers: config.slow_subscribers,
+                snapshots: Arc::new(Snapshots::new(
+                    config.snapshot_path.clone(),
+                    config.save_rules.clone(),
+                )),
+                aof: Arc::new(Aof::new(config.aof_path.clone(), config.appendfsync)),
+                propagation: Arc::new(Propagation::default()),
+                replication: Arc::new(Replication::new(config.repl_backlog_size)),
+                cluster: config
+                    .cluster
+                    .as_ref()
+                    .map(|cluster| Arc::new(Cluster::new(cluster))),
+            },
+        }
+    }
+
+    pub(crate) fn databases(&self) -> Databases {
+        self.databases.clone()
+    }
+}
+
+impl Drop for DbDropGuard {
+    fn drop(&mut self) {
+        self.databases.replication.stop();
+        for db in self.databases.dbs.iter() {
+            db.shutdown_purge_task();
+        }
+    }
+}
+
+impl Databases {
+    pub(crate) fn len(&self) -> usize {
+        self.dbs.len()
+    }
+
+    /// The database at `index`, which must be valid, see `Databases::index`.
+    pub(crate) fn db(&self, index: usize) -> &Db {
+        &self.dbs[index]
+    }
+
+    /// The database holding the pub/sub channels.
+    pub(crate) fn pub_sub(&self) -> &Db {
+        &self.dbs[0]
+    }
+
+    pub(crate) fn scripts(&self) -> &ScriptCache {
+        &self.scripts
+    }
+
+    /// How many times keys were modified since the server started.
+    pub(crate) fn dirty(&self) -> u64 {
+        self.dbs.iter().map(Db::dirty).sum()
+    }
+
+    /// Loads the data of a previous run before the server accepts
+    /// connections: the append only file if one is configured, like Redis,
+    /// or else the snapshot.
+    pub(crate) fn load(&self) -> crate::Result<()> {
+        if self.aof.is_enabled() {
+            self.load_aof()
+        } else {
+            self.load_snapshot()
+        }
+    }
+
+    /// How many messages may wait for a subscriber, see `Db::subscribe`.
+    pub(crate) fn pubsub_capacity(&self) -> usize {
+        self.pubsub_capacity
+    }
+
+    pub(crate) fn slow_subscribers(&self) -> SlowSubscribers {
+        self.slow_subscribers
+    }
+
+    /// Checks a database index given by a client.
+    pub(crate) fn index(&self, index: i64) -> crate::Result<usize> {
+        usize::try_from(index)
+            .ok()
+            .filter(|&index| index < self.len())
+            .ok_or_else(|| "ERR DB index is out of range".into())
+    }
+
+    /// Exchanges the contents of two databases. Connections keep their
+    /// selected index, so they see the other database's keys from now on.
+    pub(crate) fn swap(&self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+
+        let (mut state_a, mut state_b) = self.lock_pair(a, b);
+        std::mem::swap(&mut state_a.entries, &mut state_b.entries);
+        std::mem::swap(&mut state_a.expirations, &mut state_b.expirations);
+        // Blocked clients recheck their keys against the new contents, and
+        // watched keys count as modified.
+        state_a.wake_all_blocked();
+        state_b.wake_all_blocked();
+        state_a.touch_all();
+        state_b.touch_all();
+        drop((state_a, state_b));
+
+        self.dbs[a].shared.background_task.notify_one();
+        self.dbs[b].shared.background_task.notify_one();
+    }
+
+    /// Moves `key` with its expiration from database `from` to `to`. Returns
+    /// whether it was moved, which fails when `to` already holds the key.
+    pub(crate) fn move_key(&self, key: &str, from: usize, to: usize) -> crate::Result<bool> {
+        if from == to {
+            return Err("ERR source and destination objects are the same".into());
+        }
+
+        let (mut source, mut destination) = self.lock_pair(from, to);
+        if !source.entries.contains_key(key) || destination.entries.contains_key(key) {
+            return Ok(false);
+        }
+
+        let entry = source.remove_entry(key).unwrap();
+        let notify = destination.insert_entry(key.to_string(), entry.data, entry.expires_at);
+        destination.wake_blocked(key);
+        drop((source, destination));
+
+        if notify {
+            self.dbs[to].shared.background_task.notify_one();
+        }
+
+        Ok(true)
+    }
+
+    /// Runs `f` while holding the lock of every database, so that no other
+    /// command runs until it returns. `f` gets databases detached from the
+    /// server that own the locked states, which are put back afterwards.
+    ///
+    /// The changes `f` makes are propagated before the locks are released,
+    /// which keeps them in order without holding up the propagation of
+    /// commands that wait for the locks while `f` runs.
+    pub(crate) fn exclusive<T>(&self, f: impl FnOnce(&Databases) -> T) -> T {
+        for db in self.dbs.iter() {
+            db.shared.exclusive.fetch_add(1, Ordering::AcqRel);
+        }
+        // Commands that are propagated lock databases while they hold
+        // `order`, taking it first waits for them to be propagated.
+        let order = self.order();
+        // Always in index order, like `lock_pair`.
+        let mut states: Vec<_> = self.dbs.iter().map(|db| db.shared.lock()).collect();
+        let propagating = self.is_propagating();
+        drop(order);
+
+        let detached = Databases {
+            dbs: states
+                .iter_mut()
+                .map(|state| Db::detached(std::mem::take(&mut **state)))
+                .collect(),
+            scripts: self.scripts.clone(),
+            pubsub_capacity: self.pubsub_capacity,
+            slow_subscribers: self.slow_subscribers,
+            snapshots: self.snapshots.clone(),
+            aof: self.aof.clone(),
+            propagation: Arc::new(Propagation::detached(propagating)),
+            replication: self.replication.clone(),
+            cluster: self.cluster.clone(),
+        };
+
+        // A panic is only resumed once the states are put back, so that it
+        // neither loses them nor poisons the locks.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&detached)));
+        self.propagate_all(detached.take_pending());
+
+        for (state, db) in states.iter_mut().zip(detached.dbs.iter()) {
+            let mut detached = db
+                .shared
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            **state = std::mem::take(&mut *detached);
+        }
+        drop(states);
+
+        // Deadlines may have changed, let the purge tasks, which waited for
+        // the locks, recompute them.
+        for db in self.dbs.iter() {
+            db.shared.exclusive.fetch_sub(1, Ordering::AcqRel);
+            db.shared.background_task.notify_one();
+        }
+        result.unwrap_or_else(|panic| panic::resume_unwind(panic))
+    }
+
+    pub(crate) fn flush_all(&self, lazy: bool) {
+        for db in self.dbs.iter() {
+            db.flush(lazy);
+        }
+    }
+
+    /// Locks two distinct databases, always in index order so that
+    /// concurrent callers can't deadlock.
+    /// Whether `exclusive` holds, or waits for, the databases.
+    fn is_exclusive(&self) -> bool {
+        self.dbs.iter().any(|db| db.shared.is_exclusive())
+    }
+
+    fn lock_pair(&self, a: usize, b: usize) -> (MutexGuard<'_, State>, MutexGuard<'_, State>) {
+        debug_assert_ne!(a, b);
+        if a < b {
+            let state_a = self.dbs[a].shared.lock();
+            (state_a, self.dbs[b].shared.lock())
+        } else {
+            let state_b = self.dbs[b].shared.lock();
+            (self.dbs[a].shared.lock(), state_b)
+        }
+    }
+}
+
+impl SetOptions {
+    pub fn new() -> SetOptions {
+        SetOptions::default()
+    }
+
+    /// Only set the key if it does not already exist.
+    pub fn nx(mut self) -> SetOptions {
+        self.nx = true;
+        self
+    }
+
+    /// Only set the key if it already exists.
+    pub fn xx(mut self) -> SetOptions {
+        self.xx = true;
+        self
+    }
+
+    /// Reply with the previous value of the key.
+    pub fn get(mut self) -> SetOptions {
+        self.get = true;
+        self
+    }
+
+    /// Expire the key after `secs` seconds.
+    pub fn ex(mut self, secs: u64) -> SetOptions {
+        self.expire = Some(Expiry::Ex(secs));
+        self
+    }
+
+    /// Expire the key after `ms` milliseconds.
+    pub fn px(mut self, ms: u64) -> SetOptions {
+        self.expire = Some(Expiry::Px(ms));
+        self
+    }
+
+    /// Expire the key at the Unix time `secs`, in seconds.
+    pub fn exat(mut self, secs: u64) -> SetOptions {
+        self.expire = Some(Expiry::ExAt(secs));
+        self
+    }
+
+    /// Expire the key at the Unix time `ms`, in milliseconds.
+    pub fn pxat(mut self, ms: u64) -> SetOptions {
+        self.expire = Some(Expiry::PxAt(ms));
+        self
+    }
+
+    /// Keep the expiration of the key being overwritten.
+    pub fn keepttl(mut self) -> SetOptions {
+        self.expire = Some(Expiry::KeepTtl);
+        self
+    }
+
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        if self.nx && self.xx {
+            return Err("ERR syntax error".into());
+        }
+        match self.expire {
+            Some(Expiry::Ex(0) | Expiry::Px(0) | Expiry::ExAt(0) | Expiry::PxAt(0)) => {
+                Err("ERR invalid expire time in 'set' command".into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ExpireOptions {
+    pub fn new() -> ExpireOptions {
+        ExpireOptions::default()
+    }
+
+    /// Only set the expiration if the key has none.
+    pub fn nx(mut self) -> ExpireOptions {
+        self.nx = true;
+        self
+    }
+
+    /// Only set the expiration if the key already has one.
+    pub fn xx(mut self) -> ExpireOptions {
+        self.xx = true;
+        self
+    }
+
+    /// Only set the expiration if it is later than the current one. Keys
+    /// without an expiration never expire, so they are never updated.
+    pub fn gt(mut self) -> ExpireOptions {
+        self.gt = true;
+        self
+    }
+
+    /// Only set the expiration if it is earlier than the current one. Keys
+    /// without an expiration are always updated.
+    pub fn lt(mut self) -> ExpireOptions {
+        self.lt = true;
+        self
+    }
+
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        if self.nx && (self.xx || self.gt || self.lt) {
+            return Err(
+                "ERR NX and XX, GT or LT options at the same time are not compatible".into(),
+            );
+        }
+        if self.gt && self.lt {
+            return Err("ERR GT and LT options at the same time are not compatible".into());
+        }
+        Ok(())
+    }
+
+    /// Whether a key currently expiring at `current` may expire at `new`.
+    fn allows(&self, current: Option<Instant>, new: Instant) -> bool {
+        match current {
+            Some(current) => !self.nx && (!self.gt || new > current) && (!self.lt || new < current),
+            None => !self.xx && !self.gt,
+        }
+    }
+}
+
+impl Expiry {
+    /// Resolves the expiration against `now`. `KEEPTTL` and `PERSIST` have no
+    /// deadline of their own and must be handled by the caller.
+    fn deadline(self, now: Instant, command: &str) -> crate::Result<Instant> {
+        let deadline = match self {
+            Expiry::Ex(secs) => now.checked_add(Duration::from_secs(secs)),
+            Expiry::Px(ms) => now.checked_add(Duration::from_millis(ms)),
+            Expiry::ExAt(secs) => secs.checked_mul(1000).and_then(|ms| instant_at(ms, now)),
+            Expiry::PxAt(ms) => instant_at(ms, now),
+            Expiry::KeepTtl | Expiry::Persist => unreachable!("{:?} has no deadline", self),
+        };
+        deadline.ok_or_else(|| format!("ERR invalid expire time in '{}' command", command).into())
     }
 
-    pub(crate) fn db(&self) -> Db {
-        self.db.clone()
+    /// Turns a relative expiration into a Unix time in milliseconds, so that
+    /// it means the same whenever the command is replayed.
+    pub(crate) fn absolute(self) -> Expiry {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        match self {
+            Expiry::Ex(secs) => Expiry::PxAt(now.saturating_add(secs.saturating_mul(1000))),
+            Expiry::Px(ms) => Expiry::PxAt(now.saturating_add(ms)),
+            expiry => expiry,
+        }
     }
 }
 
@@ -56,125 +604,2090 @@ impl Db {
             state: Mutex::new(State {
                 entries: HashMap::new(),
                 pub_sub: HashMap::new(),
+                patterns: HashMap::new(),
                 expirations: BTreeSet::new(),
+                blocked: HashMap::new(),
+                watched: HashMap::new(),
+                dirty: 0,
+                loading: false,
                 shutdown: false,
             }),
             background_task: Notify::new(),
+            exclusive: AtomicUsize::new(0),
         });
 
         tokio::spawn(purge_expired_tasks(Arc::clone(&shared)));
         Db { shared }
     }
 
-    pub(crate) fn get(&self, key: &str) -> Option<Bytes> {
-        let state = self.shared.state.lock().unwrap();
-        state.entries.get(key).map(|entry| entry.data.clone())
+    /// A database owning `state` outright, without a purge task.
+    fn detached(state: State) -> Db {
+        Db {
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                background_task: Notify::new(),
+                exclusive: AtomicUsize::new(0),
+            }),
+        }
     }
 
-    pub(crate) fn set(&self, key: String, value: Bytes, expire: Option<Duration>) {
-        let mut state = self.shared.state.lock().unwrap();
-        let mut notify = false;
-        let expires_at = expire.map(|duration| {
-            let when = Instant::now() + duration;
-
-            notify = state
-                .next_expiration()
-                .map(|expiration| expiration > when)
-                .unwrap_or(true);
-            state.expirations.insert((when, key.clone()));
-            when
+    /// Starts watching `key`, returning its current version.
+    pub(crate) fn watch(&self, key: &str) -> u64 {
+        let mut state = self.shared.lock();
+        let watched = state.watched.entry(key.to_string()).or_insert(Watched {
+            version: 0,
+            watchers: 0,
         });
+        watched.watchers += 1;
+        watched.version
+    }
 
-        let prev = state.entries.insert(
-            key.clone(),
-            Entry {
-                data: value,
-                expires_at,
-            },
-        );
-
-        if let Some(prev) = prev {
-            if let Some(when) = prev.expires_at {
-                state.expirations.remove(&(when, key));
+    pub(crate) fn unwatch(&self, key: &str) {
+        let mut state = self.shared.lock();
+        if let Some(watched) = state.watched.get_mut(key) {
+            watched.watchers -= 1;
+            if watched.watchers == 0 {
+                state.watched.remove(key);
             }
         }
+    }
 
-        drop(state);
+    /// How many times keys of this database were modified since the server
+    /// started.
+    pub(crate) fn dirty(&self) -> u64 {
+        self.shared.lock().dirty
+    }
 
-        if notify {
-            self.shared.background_task.notify_one();
-        }
+    /// Whether `key` was modified since it was watched at `version`.
+    pub(crate) fn modified_since(&self, key: &str, version: u64) -> bool {
+        let state = self.shared.lock();
+        state
+            .watched
+            .get(key)
+            .is_none_or(|watched| watched.version != version)
     }
 
-    pub(crate) fn subscribe(&self, key: String) -> broadcast::Receiver<Bytes> {
-        use std::collections::hash_map::Entry;
+    /// Removes `keys`, returning how many existed.
+    pub(crate) fn del(&self, keys: &[String]) -> usize {
+        let mut state = self.shared.lock();
+        keys.iter()
+            .filter(|key| state.remove_entry(key).is_some())
+            .count()
+    }
 
-        let mut state = self.shared.state.lock().unwrap();
-        match state.pub_sub.entry(key) {
-            Entry::Occupied(e) => e.get().subscribe(),
-            Entry::Vacant(e) => {
-                let (tx, rx) = broadcast::channel(1024);
-                e.insert(tx);
-                rx
-            }
+    /// Like `del`, but large values are dropped on a blocking thread instead
+    /// of while holding the lock.
+    pub(crate) fn unlink(&self, keys: &[String]) -> usize {
+        let mut state = self.shared.lock();
+        let removed: Vec<Entry> = keys
+            .iter()
+            .filter_map(|key| state.remove_entry(key))
+            .collect();
+        drop(state);
+
+        let count = removed.len();
+        let large =
+            removed.iter().map(|entry| entry.data.len()).sum::<usize>() > LAZYFREE_THRESHOLD;
+        if large {
+            tokio::task::spawn_blocking(move || drop(removed));
         }
+        count
     }
 
-    pub(crate) fn publish(&self, key: &str, value: Bytes) -> usize {
-        let state = self.shared.state.lock().unwrap();
+    /// Counts how many of `keys` exist, counting repeated keys every time.
+    pub(crate) fn exists(&self, keys: &[String]) -> usize {
+        let state = self.shared.lock();
+        keys.iter()
+            .filter(|key| state.entries.contains_key(&key[..]))
+            .count()
+    }
+
+    /// The name of the type stored at `key`, or `none`.
+    pub(crate) fn key_type(&self, key: &str) -> &'static str {
+        let state = self.shared.lock();
         state
-            .pub_sub
+            .entries
             .get(key)
-            .map(|tx| tx.send(value).unwrap_or(0))
-            .unwrap_or(0)
+            .map(|entry| entry.data.type_name())
+            .unwrap_or("none")
     }
-    #[allow(unused)]
-    fn shutdown_purge_task(&self) {
-        let mut state = self.shared.state.lock().unwrap();
-        state.shutdown = true;
+
+    pub(crate) fn dbsize(&self) -> usize {
+        self.shared.lock().entries.len()
+    }
+
+    /// Removes every key. With `lazy`, the values are freed on a blocking
+    /// thread instead of by the caller.
+    pub(crate) fn flush(&self, lazy: bool) {
+        let mut state = self.shared.lock();
+        let entries = std::mem::take(&mut state.entries);
+        state.expirations.clear();
+        state.touch_all();
         drop(state);
-        self.shared.background_task.notify_one();
+
+        if lazy {
+            tokio::task::spawn_blocking(move || drop(entries));
+        }
     }
-}
 
-impl Shared {
-    fn purge_expired_keys(&self) -> Option<Instant> {
-        let mut state = self.state.lock().unwrap();
+    /// All keys matching the glob `pattern`.
+    pub(crate) fn keys(&self, pattern: &[u8]) -> Vec<String> {
+        let state = self.shared.lock();
+        state
+            .entries
+            .keys()
+            .filter(|key| glob_match(pattern, key.as_bytes()))
+            .cloned()
+            .collect()
+    }
 
-        if state.shutdown {
-            return None;
+    /// Returns the page of keys at `cursor` along with the next cursor, see
+    /// `scan_page` for how cursors work.
+    pub(crate) fn scan(&self, cursor: u64, options: &ScanOptions) -> (u64, Vec<String>) {
+        let state = self.shared.lock();
+        let keys = state
+            .entries
+            .iter()
+            .filter(|(key, entry)| {
+                options.matches(key.as_bytes())
+                    && options
+                        .key_type
+                        .as_ref()
+                        .is_none_or(|name| name.eq_ignore_ascii_case(entry.data.type_name()))
+            })
+            .map(|(key, _)| (key.as_bytes(), key.clone()));
+        scan_page(keys, cursor, options)
+    }
+
+    pub(crate) fn hscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        options: &ScanOptions,
+    ) -> crate::Result<(u64, Vec<(String, Bytes)>)> {
+        let state = self.shared.lock();
+        let hash = match state.hash(key)? {
+            Some(hash) => hash,
+            None => return Ok((0, vec![])),
+        };
+        let fields = hash
+            .iter()
+            .filter(|(field, _)| options.matches(field.as_bytes()))
+            .map(|(field, value)| (field.as_bytes(), (field.clone(), value.clone())));
+        Ok(scan_page(fields, cursor, options))
+    }
+
+    pub(crate) fn sscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        options: &ScanOptions,
+    ) -> crate::Result<(u64, Vec<Bytes>)> {
+        let state = self.shared.lock();
+        let set = match state.set(key)? {
+            Some(set) => set,
+            None => return Ok((0, vec![])),
+        };
+        let members = set
+            .iter()
+            .filter(|member| options.matches(member))
+            .map(|member| (&member[..], member.clone()));
+        Ok(scan_page(members, cursor, options))
+    }
+
+    pub(crate) fn zscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        options: &ScanOptions,
+    ) -> crate::Result<(u64, Vec<(Bytes, f64)>)> {
+        let state = self.shared.lock();
+        let zset = match state.zset(key)? {
+            Some(zset) => zset,
+            None => return Ok((0, vec![])),
+        };
+        let members = zset
+            .iter()
+            .filter(|(member, _)| options.matches(member))
+            .map(|(member, score)| (&member[..], (member.clone(), score)));
+        Ok(scan_page(members, cursor, options))
+    }
+
+    /// Moves `key` to `newkey` along with its expiration, overwriting
+    /// `newkey` unless `nx` is set. Returns whether the key was renamed.
+    pub(crate) fn rename(&self, key: &str, newkey: &str, nx: bool) -> crate::Result<bool> {
+        let mut state = self.shared.lock();
+        if !state.entries.contains_key(key) {
+            return Err("ERR no such key".into());
+        }
+        if key == newkey {
+            return Ok(!nx);
+        }
+        if nx && state.entries.contains_key(newkey) {
+            return Ok(false);
         }
 
-        let state = &mut *state;
-        let now = Instant::now();
+        let entry = state.remove_entry(key).unwrap();
+        state.remove_entry(newkey);
+        // The deadline is unchanged, so the purge task needs no wakeup.
+        state.insert_entry(newkey.to_string(), entry.data, entry.expires_at);
+        state.wake_blocked(newkey);
+        Ok(true)
+    }
 
-        while let Some(&(when, ref key)) = state.expirations.iter().next() {
-            if when > now {
-                return Some(when);
+    /// Copies `source` to `destination` along with its expiration. Returns
+    /// whether the key was copied, which fails if `destination` exists and
+    /// `replace` is not set.
+    pub(crate) fn copy(
+        &self,
+        source: &str,
+        destination: &str,
+        replace: bool,
+    ) -> crate::Result<bool> {
+        if source == destination {
+            return Err("ERR source and destination objects are the same".into());
+        }
+
+        let mut state = self.shared.lock();
+        let (data, expires_at) = match state.entries.get(source) {
+            Some(entry) => (Arc::clone(&entry.data), entry.expires_at),
+            None => return Ok(false),
+        };
+        if state.entries.contains_key(destination) {
+            if !replace {
+                return Ok(false);
             }
+            state.remove_entry(destination);
+        }
 
-            state.entries.remove(key);
-            state.expirations.remove(&(when, key.clone()));
+        // The deadline is already tracked for `source`, so the purge task
+        // needs no wakeup.
+        state.insert_entry(destination.to_string(), data, expires_at);
+        state.wake_blocked(destination);
+        Ok(true)
+    }
+
+    pub(crate) fn get(&self, key: &str) -> crate::Result<Option<Bytes>> {
+        let state = self.shared.lock();
+        Ok(state.string(key)?.cloned())
+    }
+
+    /// Writes `value` at `key` as allowed by `options`. Returns whether the
+    /// value was written along with the previous value of the key.
+    pub(crate) fn set(
+        &self,
+        key: String,
+        value: Bytes,
+        options: SetOptions,
+    ) -> crate::Result<(bool, Option<Bytes>)> {
+        let mut state = self.shared.lock();
+
+        // `GET` fails on non-string values, `NX` and `XX` only care whether
+        // the key exists.
+        let prev = match state.entries.get(&key) {
+            Some(entry) => match &*entry.data {
+                Value::String(prev) => Some(Some(prev.clone())),
+                _ if options.get => return Err(WRONGTYPE.into()),
+                _ => Some(None),
+            },
+            None => None,
+        };
+        let prev_value = prev.clone().flatten();
+        if (options.nx && prev.is_some()) || (options.xx && prev.is_none()) {
+            return Ok((false, prev_value));
         }
-        None
+
+        let now = Instant::now();
+        let expires_at = match options.expire {
+            Some(Expiry::KeepTtl) => state.entries.get(&key).and_then(|entry| entry.expires_at),
+            Some(expire) => Some(expire.deadline(now, "set")?),
+            None => None,
+        };
+
+        state.remove_entry(&key);
+        if expires_at.is_some_and(|when| when <= now) && !state.loading {
+            // Expiring in the past is the same as deleting the key.
+            return Ok((true, prev_value));
+        }
+
+        let notify = state.insert_entry(key, Arc::new(Value::String(value)), expires_at);
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+
+        Ok((true, prev_value))
     }
 
-    fn is_shutdown(&self) -> bool {
-        self.state.lock().unwrap().shutdown
+    /// Reads several keys at once. Keys holding other types read as `None`.
+    pub(crate) fn mget(&self, keys: &[String]) -> Vec<Option<Bytes>> {
+        let state = self.shared.lock();
+        keys.iter()
+            .map(|key| state.string(key).ok().flatten().cloned())
+            .collect()
     }
-}
 
-impl State {
-    fn next_expiration(&self) -> Option<Instant> {
-        self.expirations
-            .iter()
-            .next()
-            .map(|expiration| expiration.0)
+    /// Writes all `pairs` under a single lock, clearing their expirations.
+    /// With `nx`, nothing is written if any of the keys already exists.
+    pub(crate) fn mset(&self, pairs: Vec<(String, Bytes)>, nx: bool) -> bool {
+        let mut state = self.shared.lock();
+        if nx && pairs.iter().any(|(key, _)| state.entries.contains_key(key)) {
+            return false;
+        }
+
+        for (key, value) in pairs {
+            state.remove_entry(&key);
+            state.insert_entry(key, Arc::new(Value::String(value)), None);
+        }
+        true
     }
-}
 
-async fn purge_expired_tasks(shared: Arc<Shared>) {
-    while !shared.is_shutdown() {
+    pub(crate) fn getdel(&self, key: &str) -> crate::Result<Option<Bytes>> {
+        let mut state = self.shared.lock();
+        let value = state.string(key)?.cloned();
+        if value.is_some() {
+            state.remove_entry(key);
+        }
+        Ok(value)
+    }
+
+    /// Reads `key`, changing its expiration as given by `expire`.
+    pub(crate) fn getex(&self, key: &str, expire: Option<Expiry>) -> crate::Result<Option<Bytes>> {
+        let mut state = self.shared.lock();
+        let value = match state.string(key)? {
+            Some(value) => value.clone(),
+            None => return Ok(None),
+        };
+
+        let now = Instant::now();
+        let expires_at = match expire {
+            None => return Ok(Some(value)),
+            Some(Expiry::Persist) => None,
+            Some(expire) => Some(expire.deadline(now, "getex")?),
+        };
+
+        if expires_at.is_some_and(|when| when <= now) && !state.loading {
+            state.remove_entry(key);
+            return Ok(Some(value));
+        }
+        let notify = state.set_expiration(key, expires_at);
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+
+        Ok(Some(value))
+    }
+
+    /// Makes `key` expire as given by `expire`, subject to `options`. Returns
+    /// whether the expiration was changed. A deadline in the past deletes the
+    /// key.
+    pub(crate) fn expire(
+        &self,
+        key: &str,
+        expire: Expiry,
+        options: ExpireOptions,
+        command: &str,
+    ) -> crate::Result<bool> {
+        let now = Instant::now();
+        let when = expire.deadline(now, command)?;
+
+        let mut state = self.shared.lock();
+        let current = match state.entries.get(key) {
+            Some(entry) => entry.expires_at,
+            None => return Ok(false),
+        };
+        if !options.allows(current, when) {
+            return Ok(false);
+        }
+
+        if when <= now && !state.loading {
+            state.remove_entry(key);
+            return Ok(true);
+        }
+        let notify = state.set_expiration(key, Some(when));
+        drop(state);
+
+        if notify {
+            self.shared.background_task.notify_one();
+        }
+
+        Ok(true)
+    }
+
+    /// The expiration of `key`: `None` if the key does not exist, `Some(None)`
+    /// if it never expires.
+    pub(crate) fn expires_at(&self, key: &str) -> Option<Option<Instant>> {
+        let state = self.shared.lock();
+        state.entries.get(key).map(|entry| entry.expires_at)
+    }
+
+    /// Removes the expiration of `key`, returning whether it had one.
+    pub(crate) fn persist(&self, key: &str) -> bool {
+        let mut state = self.shared.lock();
+        let expiring = state
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.expires_at.is_some());
+        if expiring {
+            state.set_expiration(key, None);
+        }
+        expiring
+    }
+
+    /// Adds `delta` to the integer stored at `key`, treating a missing key as
+    /// `0`. The expiration of an existing key is kept.
+    pub(crate) fn incr_by(&self, key: &str, delta: i64) -> crate::Result<i64> {
+        let mut state = self.shared.lock();
+        let current = match state.string(key)? {
+            Some(value) => parse_i64(value).ok_or(NOT_INTEGER)?,
+            None => 0,
+        };
+        let value = current
+            .checked_add(delta)
+            .ok_or("ERR increment or decrement would overflow")?;
+
+        *state.string_or_default(key)? = Bytes::from(value.to_string());
+        state.touch(key);
+        Ok(value)
+    }
+
+    /// Like `incr_by`, for floating point values. Returns the new value
+    /// formatted the way it is stored.
+    pub(crate) fn incr_by_float(&self, key: &str, delta: f64) -> crate::Result<Bytes> {
+        let mut state = self.shared.lock();
+        let current = match state.string(key)? {
+            Some(value) => std::str::from_utf8(value)
+                .ok()
+                .and_then(parse_score)
+                .ok_or("ERR value is not a valid float")?,
+            None => 0.0,
+        };
+        let value = current + delta;
+        if !value.is_finite() {
+            return Err("ERR increment would produce NaN or Infinity".into());
+        }
+
+        let value = Bytes::from(format_score(value));
+        *state.string_or_default(key)? = value.clone();
+        state.touch(key);
+        Ok(value)
+    }
+
+    /// Appends `value` to the string at `key`, returning its new length.
+    pub(crate) fn append(&self, key: &str, value: Bytes) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        // Checked before creating the key, so a refused append leaves no
+        // empty string behind.
+        let len = state.string(key)?.map(Bytes::len).unwrap_or(0);
+        if len + value.len() > MAX_STRING_LEN {
+            return Err("ERR string exceeds maximum allowed size (proto-max-bulk-len)".into());
+        }
+
+        let string = state.string_or_default(key)?;
+        let mut appended = BytesMut::with_capacity(string.len() + value.len());
+        appended.extend_from_slice(string);
+        appended.extend_from_slice(&value);
+        *string = appended.freeze();
+        let len = string.len();
+        state.touch(key);
+        Ok(len)
+    }
+
+    pub(crate) fn strlen(&self, key: &str) -> crate::Result<usize> {
+        let state = self.shared.lock();
+        Ok(state.string(key)?.map(Bytes::len).unwrap_or(0))
+    }
+
+    /// Returns the inclusive byte range `start..=end` of the string at `key`.
+    pub(crate) fn getrange(&self, key: &str, start: i64, end: i64) -> crate::Result<Bytes> {
+        let state = self.shared.lock();
+        let string = match state.string(key)? {
+            Some(string) => string,
+            None => return Ok(Bytes::new()),
+        };
+
+        Ok(match normalize_range(start, end, string.len()) {
+            Some((start, end)) => string.slice(start..=end),
+            None => Bytes::new(),
+        })
+    }
+
+    /// Overwrites the string at `key` from `offset` on, padding it with zero
+    /// bytes when it is shorter than `offset`. Returns the new length.
+    pub(crate) fn setrange(&self, key: &str, offset: usize, value: Bytes) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        if value.is_empty() {
+            // Nothing to write, and an empty string is not worth creating.
+            return Ok(state.string(key)?.map(Bytes::len).unwrap_or(0));
+        }
+        if offset + value.len() > MAX_STRING_LEN {
+            return Err("ERR string exceeds maximum allowed size (proto-max-bulk-len)".into());
+        }
+
+        let string = state.string_or_default(key)?;
+        let mut updated = BytesMut::from(&string[..]);
+        if updated.len() < offset + value.len() {
+            updated.resize(offset + value.len(), 0);
+        }
+        updated[offset..offset + value.len()].copy_from_slice(&value);
+        *string = updated.freeze();
+        let len = string.len();
+        state.touch(key);
+        Ok(len)
+    }
+
+    pub(crate) fn push(&self, key: &str, values: Vec<Bytes>, side: Side) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let list = state.list_or_default(key)?;
+
+        for value in values {
+            match side {
+                Side::Left => list.push_front(value),
+                Side::Right => list.push_back(value),
+            }
+        }
+
+        let len = list.len();
+        state.touch(key);
+        state.wake_blocked(key);
+        Ok(len)
+    }
+
+    /// Pops up to `count` elements, returning `None` when the key does not exist.
+    pub(crate) fn pop(
+        &self,
+        key: &str,
+        side: Side,
+        count: usize,
+    ) -> crate::Result<Option<Vec<Bytes>>> {
+        let mut state = self.shared.lock();
+        let list = match state.list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+
+        let count = count.min(list.len());
+        let popped: Vec<_> = match side {
+            Side::Left => list.drain(..count).collect(),
+            Side::Right => list.drain(list.len() - count..).rev().collect(),
+        };
+
+        if !popped.is_empty() {
+            state.touch(key);
+            state.remove_if_empty(key);
+        }
+        Ok(Some(popped))
+    }
+
+    /// Pops an element from the first non-empty list of `keys`, waiting up to
+    /// `timeout` (forever when `None`) for one to be pushed.
+    pub(crate) async fn blocking_pop(
+        &self,
+        keys: &[String],
+        side: Side,
+        timeout: Option<Duration>,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        self.block_on(keys, timeout, |state| state.pop_first(keys, side))
+            .await
+    }
+
+    /// Waits until one of `keys` exists, up to `deadline` (forever when
+    /// `None`), returning whether one does. Blocking commands wait this way
+    /// when their changes are propagated, then run without blocking.
+    pub(crate) async fn wait_for_keys(&self, keys: &[String], deadline: Option<Instant>) -> bool {
+        let timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let exists = |state: &mut State| {
+            Ok(keys
+                .iter()
+                .any(|key| state.entries.contains_key(key))
+                .then_some(()))
+        };
+        matches!(self.block_on(keys, timeout, exists).await, Ok(Some(())))
+    }
+
+    /// `blocking_pop` without the wait, for blocking commands run inside
+    /// `MULTI`.
+    pub(crate) fn pop_first(
+        &self,
+        keys: &[String],
+        side: Side,
+    ) -> crate::Result<Option<(String, Bytes)>> {
+        let mut state = self.shared.lock();
+        state.pop_first(keys, side)
+    }
+
+    /// Atomically moves an element from `source` to `destination`, waiting up
+    /// to `timeout` (forever when `None`) for `source` to be pushed to.
+    pub(crate) async fn blocking_move(
+        &self,
+        source: &str,
+        destination: &str,
+        from: Side,
+        to: Side,
+        timeout: Option<Duration>,
+    ) -> crate::Result<Option<Bytes>> {
+        let keys = [source.to_string()];
+        self.block_on(&keys, timeout, |state| {
+            state.move_element(source, destination, from, to)
+        })
+        .await
+    }
+
+    /// `blocking_move` without the wait, for blocking commands run inside
+    /// `MULTI`.
+    pub(crate) fn move_element(
+        &self,
+        source: &str,
+        destination: &str,
+        from: Side,
+        to: Side,
+    ) -> crate::Result<Option<Bytes>> {
+        let mut state = self.shared.lock();
+        state.move_element(source, destination, from, to)
+    }
+
+    pub(crate) fn lrange(&self, key: &str, start: i64, stop: i64) -> crate::Result<Vec<Bytes>> {
+        let state = self.shared.lock();
+        let list = match state.list(key)? {
+            Some(list) => list,
+            None => return Ok(vec![]),
+        };
+
+        Ok(match normalize_range(start, stop, list.len()) {
+            Some((start, stop)) => list.range(start..=stop).cloned().collect(),
+            None => vec![],
+        })
+    }
+
+    pub(crate) fn llen(&self, key: &str) -> crate::Result<usize> {
+        let state = self.shared.lock();
+        Ok(state.list(key)?.map(VecDeque::len).unwrap_or(0))
+    }
+
+    pub(crate) fn lindex(&self, key: &str, index: i64) -> crate::Result<Option<Bytes>> {
+        let state = self.shared.lock();
+        Ok(state
+            .list(key)?
+            .and_then(|list| normalize_index(index, list.len()).map(|i| list[i].clone())))
+    }
+
+    pub(crate) fn lset(&self, key: &str, index: i64, value: Bytes) -> crate::Result<()> {
+        let mut state = self.shared.lock();
+        let list = match state.list_mut(key)? {
+            Some(list) => list,
+            None => return Err("ERR no such key".into()),
+        };
+
+        match normalize_index(index, list.len()) {
+            Some(i) => {
+                list[i] = value;
+                state.touch(key);
+                Ok(())
+            }
+            None => Err("ERR index out of range".into()),
+        }
+    }
+
+    pub(crate) fn ltrim(&self, key: &str, start: i64, stop: i64) -> crate::Result<()> {
+        let mut state = self.shared.lock();
+        let list = match state.list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(()),
+        };
+
+        let len = list.len();
+        match normalize_range(start, stop, list.len()) {
+            Some((start, stop)) => {
+                list.truncate(stop + 1);
+                list.drain(..start);
+            }
+            None => list.clear(),
+        }
+
+        if list.len() != len {
+            state.touch(key);
+            state.remove_if_empty(key);
+        }
+        Ok(())
+    }
+
+    /// Removes occurrences of `value`: from the head when `count` is positive,
+    /// from the tail when negative and all of them when zero.
+    pub(crate) fn lrem(&self, key: &str, count: i64, value: &Bytes) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let list = match state.list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(0),
+        };
+
+        let limit = match count {
+            0 => usize::MAX,
+            n => n.unsigned_abs() as usize,
+        };
+        let mut removed = 0;
+
+        if count < 0 {
+            let mut i = list.len();
+            while i > 0 && removed < limit {
+                i -= 1;
+                if list[i] == value {
+                    list.remove(i);
+                    removed += 1;
+                }
+            }
+        } else {
+            let mut i = 0;
+            while i < list.len() && removed < limit {
+                if list[i] == value {
+                    list.remove(i);
+                    removed += 1;
+                } else {
+                    i += 1;
+                }
+            }
+        }
+
+        if removed > 0 {
+            state.touch(key);
+            state.remove_if_empty(key);
+        }
+        Ok(removed)
+    }
+
+    /// Sets the given fields, returning how many of them were newly added.
+    pub(crate) fn hset(&self, key: &str, fields: Vec<(String, Bytes)>) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let hash = state.hash_or_default(key)?;
+
+        let mut added = 0;
+        for (field, value) in fields {
+            if hash.insert(field, value).is_none() {
+                added += 1;
+            }
+        }
+
+        state.touch(key);
+        Ok(added)
+    }
+
+    pub(crate) fn hget(&self, key: &str, field: &str) -> crate::Result<Option<Bytes>> {
+        let state = self.shared.lock();
+        Ok(state.hash(key)?.and_then(|hash| hash.get(field).cloned()))
+    }
+
+    pub(crate) fn hmget(&self, key: &str, fields: &[String]) -> crate::Result<Vec<Option<Bytes>>> {
+        let state = self.shared.lock();
+        let hash = state.hash(key)?;
+        Ok(fields
+            .iter()
+            .map(|field| hash.and_then(|hash| hash.get(field).cloned()))
+            .collect())
+    }
+
+    pub(crate) fn hdel(&self, key: &str, fields: &[String]) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let hash = match state.hash_mut(key)? {
+            Some(hash) => hash,
+            None => return Ok(0),
+        };
+
+        let removed = fields
+            .iter()
+            .filter(|field| hash.remove(*field).is_some())
+            .count();
+
+        if removed > 0 {
+            state.touch(key);
+            state.remove_if_empty(key);
+        }
+        Ok(removed)
+    }
+
+    pub(crate) fn hgetall(&self, key: &str) -> crate::Result<Vec<(String, Bytes)>> {
+        let state = self.shared.lock();
+        Ok(state
+            .hash(key)?
+            .map(|hash| {
+                hash.iter()
+                    .map(|(field, value)| (field.clone(), value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn hkeys(&self, key: &str) -> crate::Result<Vec<String>> {
+        let state = self.shared.lock();
+        Ok(state
+            .hash(key)?
+            .map(|hash| hash.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn hvals(&self, key: &str) -> crate::Result<Vec<Bytes>> {
+        let state = self.shared.lock();
+        Ok(state
+            .hash(key)?
+            .map(|hash| hash.values().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn hlen(&self, key: &str) -> crate::Result<usize> {
+        let state = self.shared.lock();
+        Ok(state.hash(key)?.map(HashMap::len).unwrap_or(0))
+    }
+
+    pub(crate) fn hexists(&self, key: &str, field: &str) -> crate::Result<bool> {
+        let state = self.shared.lock();
+        Ok(state
+            .hash(key)?
+            .map(|hash| hash.contains_key(field))
+            .unwrap_or(false))
+    }
+
+    pub(crate) fn hincrby(&self, key: &str, field: &str, delta: i64) -> crate::Result<i64> {
+        let mut state = self.shared.lock();
+        let hash = state.hash_or_default(key)?;
+
+        let current = match hash.get(field) {
+            Some(value) => parse_i64(value).ok_or("ERR hash value is not an integer")?,
+            None => 0,
+        };
+        let value = current
+            .checked_add(delta)
+            .ok_or("ERR increment or decrement would overflow")?;
+
+        hash.insert(field.to_string(), Bytes::from(value.to_string()));
+        state.touch(key);
+        Ok(value)
+    }
+
+    /// Adds the members, returning how many of them were not already present.
+    pub(crate) fn sadd(&self, key: &str, members: Vec<Bytes>) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let set = state.set_or_default(key)?;
+        let added = members
+            .into_iter()
+            .filter(|member| set.insert(member.clone()))
+            .count();
+
+        if added > 0 {
+            state.touch(key);
+        }
+        Ok(added)
+    }
+
+    pub(crate) fn srem(&self, key: &str, members: &[Bytes]) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let set = match state.set_mut(key)? {
+            Some(set) => set,
+            None => return Ok(0),
+        };
+
+        let removed = members.iter().filter(|member| set.remove(*member)).count();
+
+        if removed > 0 {
+            state.touch(key);
+            state.remove_if_empty(key);
+        }
+        Ok(removed)
+    }
+
+    pub(crate) fn smembers(&self, key: &str) -> crate::Result<Vec<Bytes>> {
+        let state = self.shared.lock();
+        Ok(state
+            .set(key)?
+            .map(|set| set.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn sismember(&self, key: &str, member: &Bytes) -> crate::Result<bool> {
+        let state = self.shared.lock();
+        Ok(state
+            .set(key)?
+            .map(|set| set.contains(member))
+            .unwrap_or(false))
+    }
+
+    pub(crate) fn scard(&self, key: &str) -> crate::Result<usize> {
+        let state = self.shared.lock();
+        Ok(state.set(key)?.map(HashSet::len).unwrap_or(0))
+    }
+
+    pub(crate) fn combine(&self, op: SetOp, keys: &[String]) -> crate::Result<Vec<Bytes>> {
+        let state = self.shared.lock();
+        Ok(state.combine(op, keys)?.into_iter().collect())
+    }
+
+    /// Computes `op` and overwrites `destination` with the result while holding
+    /// the lock, so no other client can observe a partially written result.
+    pub(crate) fn combine_store(
+        &self,
+        op: SetOp,
+        destination: &str,
+        keys: &[String],
+    ) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let result = state.combine(op, keys)?;
+        let len = result.len();
+
+        state.remove_entry(destination);
+        if len > 0 {
+            state.insert_entry(destination.to_string(), Arc::new(Value::Set(result)), None);
+        }
+
+        Ok(len)
+    }
+
+    /// Adds or updates members according to `options`.
+    ///
+    /// Returns the number of added members (or changed ones with `CH`) and the
+    /// score of the last member written, which is what `ZADD INCR` replies with.
+    pub(crate) fn zadd(
+        &self,
+        key: &str,
+        options: ZaddOptions,
+        incr: bool,
+        members: Vec<(f64, Bytes)>,
+    ) -> crate::Result<(usize, Option<f64>)> {
+        let mut state = self.shared.lock();
+        let zset = state.zset_or_default(key)?;
+
+        let mut added = 0;
+        let mut changed = 0;
+        let mut last = None;
+        let mut written = false;
+        let mut nan = false;
+
+        for (score, member) in members {
+            let old = zset.score(&member);
+            let score = match (incr, old) {
+                (true, Some(old)) => old + score,
+                _ => score,
+            };
+            if score.is_nan() {
+                nan = true;
+                break;
+            }
+
+            match old {
+                None if options.xx => continue,
+                None => added += 1,
+                Some(old) if !options.allows_update(old, score) => continue,
+                Some(old) if old != score => changed += 1,
+                Some(_) => {}
+            }
+
+            zset.insert(member, score);
+            last = Some(score);
+            written = true;
+        }
+
+        if written {
+            state.touch(key);
+        }
+        state.remove_if_empty(key);
+        if nan {
+            return Err("ERR resulting score is not a number (NaN)".into());
+        }
+        Ok((if options.ch { added + changed } else { added }, last))
+    }
+
+    pub(crate) fn zincrby(&self, key: &str, delta: f64, member: Bytes) -> crate::Result<f64> {
+        let (_, score) = self.zadd(key, ZaddOptions::default(), true, vec![(delta, member)])?;
+        Ok(score.unwrap_or(delta))
+    }
+
+    /// Members (with their scores) selected by `by`, optionally reversed and
+    /// paginated with `LIMIT offset count`.
+    pub(crate) fn zrange(
+        &self,
+        key: &str,
+        by: &RangeBy,
+        rev: bool,
+        limit: Option<(i64, i64)>,
+    ) -> crate::Result<Vec<(Bytes, f64)>> {
+        let state = self.shared.lock();
+        let zset = match state.zset(key)? {
+            Some(zset) => zset,
+            None => return Ok(vec![]),
+        };
+
+        let members: Box<dyn Iterator<Item = (&Bytes, f64)>> = match (by, rev) {
+            (RangeBy::Rank(start, stop), rev) => match normalize_range(*start, *stop, zset.len()) {
+                Some((start, stop)) if rev => {
+                    Box::new(zset.iter().rev().skip(start).take(stop - start + 1))
+                }
+                Some((start, stop)) => Box::new(zset.iter().skip(start).take(stop - start + 1)),
+                None => Box::new(std::iter::empty()),
+            },
+            (RangeBy::Score(min, max), false) => Box::new(zset.range_by_score(*min, *max)),
+            (RangeBy::Score(min, max), true) => Box::new(zset.range_by_score(*min, *max).rev()),
+            (RangeBy::Lex(min, max), false) => Box::new(zset.range_by_lex(min, max)),
+            (RangeBy::Lex(min, max), true) => Box::new(zset.range_by_lex(min, max).rev()),
+        };
+
+        let members = members.map(|(member, score)| (member.clone(), score));
+        Ok(match limit {
+            Some((offset, _)) if offset < 0 => vec![],
+            Some((offset, count)) if count >= 0 => {
+                members.skip(offset as usize).take(count as usize).collect()
+            }
+            Some((offset, _)) => members.skip(offset as usize).collect(),
+            None => members.collect(),
+        })
+    }
+
+    pub(crate) fn zrank(&self, key: &str, member: &Bytes) -> crate::Result<Option<usize>> {
+        let state = self.shared.lock();
+        Ok(state.zset(key)?.and_then(|zset| zset.rank(member)))
+    }
+
+    pub(crate) fn zscore(&self, key: &str, member: &Bytes) -> crate::Result<Option<f64>> {
+        let state = self.shared.lock();
+        Ok(state.zset(key)?.and_then(|zset| zset.score(member)))
+    }
+
+    pub(crate) fn zrem(&self, key: &str, members: &[Bytes]) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let zset = match state.zset_mut(key)? {
+            Some(zset) => zset,
+            None => return Ok(0),
+        };
+
+        let removed = members
+            .iter()
+            .filter(|member| zset.remove(member).is_some())
+            .count();
+
+        if removed > 0 {
+            state.touch(key);
+            state.remove_if_empty(key);
+        }
+        Ok(removed)
+    }
+
+    pub(crate) fn zpopmin(&self, key: &str, count: usize) -> crate::Result<Vec<(Bytes, f64)>> {
+        let mut state = self.shared.lock();
+        let popped = match state.zset_mut(key)? {
+            Some(zset) => zset.pop_min(count),
+            None => return Ok(vec![]),
+        };
+
+        if !popped.is_empty() {
+            state.touch(key);
+            state.remove_if_empty(key);
+        }
+        Ok(popped)
+    }
+
+    /// Appends a record, returning `None` when `nomkstream` is set and the
+    /// stream does not exist.
+    pub(crate) fn xadd(
+        &self,
+        key: &str,
+        id: XaddId,
+        fields: Fields,
+        maxlen: Option<usize>,
+        nomkstream: bool,
+    ) -> crate::Result<Option<StreamId>> {
+        let mut state = self.shared.lock();
+        if nomkstream && state.stream(key)?.is_none() {
+            return Ok(None);
+        }
+
+        let stream = state.stream_or_default(key)?;
+        let id = match stream.add(id, fields) {
+            Ok(id) => id,
+            Err(err) => {
+                // Do not leave behind the empty stream created for this call.
+                if stream.len() == 0 && stream.last_id() == StreamId::MIN {
+                    state.discard_entry(key);
+                }
+                return Err(err);
+            }
+        };
+
+        if let Some(maxlen) = maxlen {
+            stream.trim(maxlen);
+        }
+        state.touch(key);
+        Ok(Some(id))
+    }
+
+    pub(crate) fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> crate::Result<Vec<(StreamId, Fields)>> {
+        let state = self.shared.lock();
+        Ok(state
+            .stream(key)?
+            .map(|stream| stream.range(start, end, count, rev))
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn xlen(&self, key: &str) -> crate::Result<usize> {
+        let state = self.shared.lock();
+        Ok(state.stream(key)?.map(Stream::len).unwrap_or(0))
+    }
+
+    pub(crate) fn xtrim(&self, key: &str, maxlen: usize) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let trimmed = state
+            .stream_mut(key)?
+            .map(|stream| stream.trim(maxlen))
+            .unwrap_or(0);
+
+        if trimmed > 0 {
+            state.touch(key);
+        }
+        Ok(trimmed)
+    }
+
+    /// Creates a consumer group starting after `id`, or after the last
+    /// record when `id` is `None` (`$`).
+    pub(crate) fn xgroup_create(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+        mkstream: bool,
+    ) -> crate::Result<()> {
+        let mut state = self.shared.lock();
+        let stream = match state.stream_mut(key)? {
+            Some(stream) => stream,
+            None if mkstream => state.stream_or_default(key)?,
+            None => {
+                return Err("ERR The XGROUP subcommand requires the key to exist. \
+                    Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically."
+                    .into())
+            }
+        };
+
+        let id = id.unwrap_or_else(|| stream.last_id());
+        stream.create_group(group, id)?;
+        state.touch(key);
+        Ok(())
+    }
+
+    pub(crate) fn xgroup_destroy(&self, key: &str, group: &str) -> crate::Result<bool> {
+        let mut state = self.shared.lock();
+        let destroyed = state.group_stream_mut(key)?.destroy_group(group);
+
+        if destroyed {
+            state.touch(key);
+        }
+        Ok(destroyed)
+    }
+
+    /// Reads from each of `streams` on behalf of `consumer`, skipping streams
+    /// with nothing to deliver.
+    pub(crate) fn xreadgroup(
+        &self,
+        group: &str,
+        consumer: &str,
+        streams: &[(String, ReadFrom)],
+        count: Option<usize>,
+        noack: bool,
+    ) -> crate::Result<Vec<(String, Delivered)>> {
+        let mut state = self.shared.lock();
+
+        let mut result = vec![];
+        for (key, from) in streams {
+            let stream = state.group_stream_mut(key)?;
+            let records = stream.read_group(group, consumer, *from, count, noack)?;
+            match from {
+                // Delivering new records moves the group forward, reading
+                // the pending ones changes nothing.
+                ReadFrom::New if !records.is_empty() => state.touch(key),
+                ReadFrom::New => continue,
+                ReadFrom::Pending(_) => {}
+            }
+            result.push((key.clone(), records));
+        }
+        Ok(result)
+    }
+
+    pub(crate) fn xack(&self, key: &str, group: &str, ids: &[StreamId]) -> crate::Result<usize> {
+        let mut state = self.shared.lock();
+        let acked = match state.stream_mut(key)? {
+            Some(stream) => stream.ack(group, ids)?,
+            None => 0,
+        };
+
+        if acked > 0 {
+            state.touch(key);
+        }
+        Ok(acked)
+    }
+
+    pub(crate) fn xpending_summary(&self, key: &str, group: &str) -> crate::Result<PendingSummary> {
+        let state = self.shared.lock();
+        state.group_stream(key)?.pending_summary(group)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn xpending_range(
+        &self,
+        key: &str,
+        group: &str,
+        min_idle: Duration,
+        start: StreamId,
+        end: StreamId,
+        count: usize,
+        consumer: Option<&str>,
+    ) -> crate::Result<Vec<(StreamId, Pending)>> {
+        let state = self.shared.lock();
+        state
+            .group_stream(key)?
+            .pending_range(group, min_idle, start, end, count, consumer)
+    }
+
+    pub(crate) fn xclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle: Duration,
+        ids: &[StreamId],
+        options: ClaimOptions,
+    ) -> crate::Result<Claimed> {
+        let mut state = self.shared.lock();
+        let claimed = state
+            .group_stream_mut(key)?
+            .claim(group, consumer, min_idle, ids, options)?;
+
+        if !claimed.changes.is_empty() {
+            state.touch(key);
+        }
+        Ok(claimed)
+    }
+
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub(crate) fn xautoclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle: Duration,
+        start: StreamId,
+        count: usize,
+        justid: bool,
+    ) -> crate::Result<AutoClaimed> {
+        let mut state = self.shared.lock();
+        let autoclaimed = state
+            .group_stream_mut(key)?
+            .autoclaim(group, consumer, min_idle, start, count, justid)?;
+
+        if !autoclaimed.1.changes.is_empty() {
+            state.touch(key);
+        }
+        Ok(autoclaimed)
+    }
+
+    /// Subscribes to the channel `key`. Once `capacity` messages wait for
+    /// the subscriber, older ones are dropped.
+    pub(crate) fn subscribe(&self, key: String, capacity: usize) -> Receiver<Bytes> {
+        let mut state = self.shared.lock();
+        let rx = Channel::subscribe(&mut state.pub_sub, key.clone(), capacity);
+        Receiver {
+            rx,
+            db: self.clone(),
+            name: key,
+            pattern: false,
+        }
+    }
+
+    /// Subscribes to every channel matching the glob `pattern`, like
+    /// `Db::subscribe`.
+    pub(crate) fn psubscribe(&self, pattern: String, capacity: usize) -> Receiver<(String, Bytes)> {
+        let mut state = self.shared.lock();
+        let rx = Channel::subscribe(&mut state.patterns, pattern.clone(), capacity);
+        Receiver {
+            rx,
+            db: self.clone(),
+            name: pattern,
+            pattern: true,
+        }
+    }
+
+    /// The channels with subscribers, optionally only those matching the
+    /// glob `pattern`.
+    pub(crate) fn channels(&self, pattern: Option<&str>) -> Vec<String> {
+        let state = self.shared.lock();
+        state
+            .pub_sub
+            .keys()
+            .filter(|channel| {
+                pattern.is_none_or(|pattern| glob_match(pattern.as_bytes(), channel.as_bytes()))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The number of subscribers of `channel`, not counting those subscribed
+    /// through a pattern.
+    pub(crate) fn num_subscribers(&self, channel: &str) -> usize {
+        let state = self.shared.lock();
+        state
+            .pub_sub
+            .get(channel)
+            .map_or(0, |channel| channel.tx.receiver_count())
+    }
+
+    /// The number of distinct patterns subscribed to.
+    pub(crate) fn num_patterns(&self) -> usize {
+        self.shared.lock().patterns.len()
+    }
+
+    /// The number of messages dropped by slow subscribers of `name`, either
+    /// as a channel or as a pattern, since it was first subscribed to.
+    pub(crate) fn dropped_messages(&self, name: &str) -> u64 {
+        let state = self.shared.lock();
+        let channel = state.pub_sub.get(name).map_or(0, |channel| channel.dropped);
+        let pattern = state
+            .patterns
+            .get(name)
+            .map_or(0, |pattern| pattern.dropped);
+        channel + pattern
+    }
+
+    /// Publishes `value` to `key`, returning how many subscribers received
+    /// it, counting those subscribed through a pattern.
+    pub(crate) fn publish(&self, key: &str, value: Bytes) -> usize {
+        let state = self.shared.lock();
+        let mut num_subscribers = state
+            .pub_sub
+            .get(key)
+            .map(|channel| channel.tx.send(value.clone()).unwrap_or(0))
+            .unwrap_or(0);
+
+        for (pattern, channel) in &state.patterns {
+            if glob_match(pattern.as_bytes(), key.as_bytes()) {
+                num_subscribers += channel
+                    .tx
+                    .send((key.to_string(), value.clone()))
+                    .unwrap_or(0);
+            }
+        }
+        num_subscribers
+    }
+
+    /// Runs `op` until it produces a value, parking in between until one of
+    /// `keys` is pushed to. Gives up with `None` once `timeout` elapses.
+    async fn block_on<T>(
+        &self,
+        keys: &[String],
+        timeout: Option<Duration>,
+        mut op: impl FnMut(&mut State) -> crate::Result<Option<T>>,
+    ) -> crate::Result<Option<T>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let _guard = BlockedGuard { db: self, keys };
+
+        loop {
+            let notifies: Vec<Arc<Notify>>;
+            let mut notified: Vec<Pin<Box<Notified<'_>>>>;
+            {
+                let mut state = self.shared.lock();
+                if let Some(value) = op(&mut state)? {
+                    return Ok(Some(value));
+                }
+
+                notifies = keys
+                    .iter()
+                    .map(|key| Arc::clone(state.blocked.entry(key.clone()).or_default()))
+                    .collect();
+                // Register interest while still holding the lock so that a
+                // push right after it is released is not missed.
+                notified = notifies
+                    .iter()
+                    .map(|notify| Box::pin(notify.notified()))
+                    .collect();
+                for notified in &mut notified {
+                    notified.as_mut().enable();
+                }
+            }
+
+            let woken = poll_fn(|cx| {
+                let ready = notified
+                    .iter_mut()
+                    .any(|notified| notified.as_mut().poll(cx).is_ready());
+                if ready {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            });
+            match deadline {
+                Some(deadline) => {
+                    if time::timeout_at(deadline, woken).await.is_err() {
+                        return Ok(None);
+                    }
+                }
+                None => woken.await,
+            }
+        }
+    }
+
+    fn shutdown_purge_task(&self) {
+        let mut state = self.shared.lock();
+        state.shutdown = true;
+        drop(state);
+        self.shared.background_task.notify_one();
+    }
+}
+
+impl<T: Clone> Channel<T> {
+    /// Subscribes to `name` in `channels`, creating it as needed.
+    fn subscribe(
+        channels: &mut HashMap<String, Channel<T>>,
+        name: String,
+        capacity: usize,
+    ) -> broadcast::Receiver<T> {
+        use std::collections::hash_map::Entry;
+
+        match channels.entry(name) {
+            Entry::Occupied(e) => e.get().tx.subscribe(),
+            Entry::Vacant(e) => {
+                let (tx, rx) = broadcast::channel(capacity);
+                e.insert(Channel { tx, dropped: 0 });
+                rx
+            }
+        }
+    }
+}
+
+/// The messages of a channel, or of a pattern of channels, subscribed to
+/// with `Db::subscribe` or `Db::psubscribe`. Dropping the last receiver of
+/// a channel forgets about it.
+#[derive(Debug)]
+pub(crate) struct Receiver<T> {
+    rx: broadcast::Receiver<T>,
+    db: Db,
+    name: String,
+    pattern: bool,
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Receives the next message. Messages dropped because the receiver
+    /// lagged behind are reported as `RecvError::Lagged`, and counted.
+    pub(crate) async fn recv(&mut self) -> Result<T, broadcast::error::RecvError> {
+        let res = self.rx.recv().await;
+        if let Err(broadcast::error::RecvError::Lagged(dropped)) = res {
+            let mut state = self.db.shared.lock();
+            if let Some(count) = state.dropped_mut(&self.name, self.pattern) {
+                *count += dropped;
+            }
+        }
+        res
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.db.shared.lock();
+        // `self.rx` is the last receiver when it is the only one left.
+        if self.pattern {
+            if let Some(pattern) = state.patterns.get(&self.name) {
+                if pattern.tx.receiver_count() == 1 {
+                    state.patterns.remove(&self.name);
+                }
+            }
+        } else if let Some(channel) = state.pub_sub.get(&self.name) {
+            if channel.tx.receiver_count() == 1 {
+                state.pub_sub.remove(&self.name);
+            }
+        }
+    }
+}
+
+struct BlockedGuard<'a> {
+    db: &'a Db,
+    keys: &'a [String],
+}
+
+/// Unregisters a blocked client from its keys when it stops waiting, whether
+/// it was served, timed out or was cancelled.
+impl Drop for BlockedGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.db.shared.lock();
+        for key in self.keys {
+            if let Some(notify) = state.blocked.get(key) {
+                if Arc::strong_count(notify) == 1 {
+                    state.blocked.remove(key);
+                }
+            }
+        }
+    }
+}
+
+impl Shared {
+    /// Locks the state. Tasks waiting for a script to release it hand the
+    /// other tasks of their worker over to another one, rather than
+    /// stalling them until the script returns.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        if let Ok(state) = self.state.try_lock() {
+            return state;
+        }
+        wait_for(self.is_exclusive(), || self.state.lock().unwrap())
+    }
+
+    fn is_exclusive(&self) -> bool {
+        self.exclusive.load(Ordering::Acquire) > 0
+    }
+
+    fn purge_expired_keys(&self) -> Option<Instant> {
+        let mut state = self.lock();
+
+        if state.shutdown || state.loading {
+            return None;
+        }
+
+        let state = &mut *state;
+        let now = Instant::now();
+
+        while let Some((when, key)) = state.expirations.first().cloned() {
+            if when > now {
+                return Some(when);
+            }
+
+            state.remove_entry(&key);
+        }
+        None
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.lock().shutdown
+    }
+}
+
+impl State {
+    /// The counter of messages dropped for the channel, or pattern, `name`.
+    fn dropped_mut(&mut self, name: &str, pattern: bool) -> Option<&mut u64> {
+        if pattern {
+            self.patterns
+                .get_mut(name)
+                .map(|pattern| &mut pattern.dropped)
+        } else {
+            self.pub_sub
+                .get_mut(name)
+                .map(|channel| &mut channel.dropped)
+        }
+    }
+
+    fn next_expiration(&self) -> Option<Instant> {
+        self.expirations
+            .iter()
+            .next()
+            .map(|expiration| expiration.0)
+    }
+
+    fn string(&self, key: &str) -> crate::Result<Option<&Bytes>> {
+        match self.entries.get(key).map(|entry| &*entry.data) {
+            Some(Value::String(string)) => Ok(Some(string)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Like `string`, but mutable and creating an empty string when the key
+    /// does not exist. Callers `touch` the key once they changed it.
+    fn string_or_default(&mut self, key: &str) -> crate::Result<&mut Bytes> {
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                data: Arc::new(Value::String(Bytes::new())),
+                expires_at: None,
+            });
+
+        match Arc::make_mut(&mut entry.data) {
+            Value::String(string) => Ok(string),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    fn list(&self, key: &str) -> crate::Result<Option<&VecDeque<Bytes>>> {
+        match self.entries.get(key).map(|entry| &*entry.data) {
+            Some(Value::List(list)) => Ok(Some(list)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn list_mut(&mut self, key: &str) -> crate::Result<Option<&mut VecDeque<Bytes>>> {
+        match self
+            .entries
+            .get_mut(key)
+            .map(|entry| Arc::make_mut(&mut entry.data))
+        {
+            Some(Value::List(list)) => Ok(Some(list)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Like `list_mut`, but creates an empty list when the key does not exist.
+    fn list_or_default(&mut self, key: &str) -> crate::Result<&mut VecDeque<Bytes>> {
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                data: Arc::new(Value::List(VecDeque::new())),
+                expires_at: None,
+            });
+
+        match Arc::make_mut(&mut entry.data) {
+            Value::List(list) => Ok(list),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    fn pop_one(&mut self, key: &str, side: Side) -> crate::Result<Option<Bytes>> {
+        let value = match self.list_mut(key)? {
+            Some(list) => match side {
+                Side::Left => list.pop_front(),
+                Side::Right => list.pop_back(),
+            },
+            None => None,
+        };
+        if value.is_some() {
+            self.touch(key);
+            self.remove_if_empty(key);
+        }
+        Ok(value)
+    }
+
+    /// Pops an element from the first non-empty list of `keys`.
+    fn pop_first(&mut self, keys: &[String], side: Side) -> crate::Result<Option<(String, Bytes)>> {
+        for key in keys {
+            if let Some(value) = self.pop_one(key, side)? {
+                return Ok(Some((key.clone(), value)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn move_element(
+        &mut self,
+        source: &str,
+        destination: &str,
+        from: Side,
+        to: Side,
+    ) -> crate::Result<Option<Bytes>> {
+        // Like Redis, fail before touching `source` if `destination`
+        // cannot receive the element.
+        self.list(destination)?;
+
+        let value = match self.list_mut(source)? {
+            Some(list) => match from {
+                Side::Left => list.pop_front(),
+                Side::Right => list.pop_back(),
+            },
+            None => None,
+        };
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let list = self.list_or_default(destination)?;
+        match to {
+            Side::Left => list.push_front(value.clone()),
+            Side::Right => list.push_back(value.clone()),
+        }
+        self.touch(source);
+        self.touch(destination);
+        self.remove_if_empty(source);
+        self.wake_blocked(destination);
+
+        Ok(Some(value))
+    }
+
+    /// Wakes the clients blocked on `key` so they retry their pop.
+    fn wake_blocked(&self, key: &str) {
+        if let Some(notify) = self.blocked.get(key) {
+            notify.notify_waiters();
+        }
+    }
+
+    fn wake_all_blocked(&self) {
+        for notify in self.blocked.values() {
+            notify.notify_waiters();
+        }
+    }
+
+    fn hash(&self, key: &str) -> crate::Result<Option<&HashMap<String, Bytes>>> {
+        match self.entries.get(key).map(|entry| &*entry.data) {
+            Some(Value::Hash(hash)) => Ok(Some(hash)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn hash_mut(&mut self, key: &str) -> crate::Result<Option<&mut HashMap<String, Bytes>>> {
+        match self
+            .entries
+            .get_mut(key)
+            .map(|entry| Arc::make_mut(&mut entry.data))
+        {
+            Some(Value::Hash(hash)) => Ok(Some(hash)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Like `hash_mut`, but creates an empty hash when the key does not exist.
+    fn hash_or_default(&mut self, key: &str) -> crate::Result<&mut HashMap<String, Bytes>> {
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                data: Arc::new(Value::Hash(HashMap::new())),
+                expires_at: None,
+            });
+
+        match Arc::make_mut(&mut entry.data) {
+            Value::Hash(hash) => Ok(hash),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    fn set(&self, key: &str) -> crate::Result<Option<&HashSet<Bytes>>> {
+        match self.entries.get(key).map(|entry| &*entry.data) {
+            Some(Value::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn set_mut(&mut self, key: &str) -> crate::Result<Option<&mut HashSet<Bytes>>> {
+        match self
+            .entries
+            .get_mut(key)
+            .map(|entry| Arc::make_mut(&mut entry.data))
+        {
+            Some(Value::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Like `set_mut`, but creates an empty set when the key does not exist.
+    fn set_or_default(&mut self, key: &str) -> crate::Result<&mut HashSet<Bytes>> {
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                data: Arc::new(Value::Set(HashSet::new())),
+                expires_at: None,
+            });
+
+        match Arc::make_mut(&mut entry.data) {
+            Value::Set(set) => Ok(set),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    fn zset(&self, key: &str) -> crate::Result<Option<&SortedSet>> {
+        match self.entries.get(key).map(|entry| &*entry.data) {
+            Some(Value::ZSet(zset)) => Ok(Some(zset)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn zset_mut(&mut self, key: &str) -> crate::Result<Option<&mut SortedSet>> {
+        match self
+            .entries
+            .get_mut(key)
+            .map(|entry| Arc::make_mut(&mut entry.data))
+        {
+            Some(Value::ZSet(zset)) => Ok(Some(zset)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Like `zset_mut`, but creates an empty sorted set when the key does not exist.
+    fn zset_or_default(&mut self, key: &str) -> crate::Result<&mut SortedSet> {
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                data: Arc::new(Value::ZSet(SortedSet::default())),
+                expires_at: None,
+            });
+
+        match Arc::make_mut(&mut entry.data) {
+            Value::ZSet(zset) => Ok(zset),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    fn stream(&self, key: &str) -> crate::Result<Option<&Stream>> {
+        match self.entries.get(key).map(|entry| &*entry.data) {
+            Some(Value::Stream(stream)) => Ok(Some(stream)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn stream_mut(&mut self, key: &str) -> crate::Result<Option<&mut Stream>> {
+        match self
+            .entries
+            .get_mut(key)
+            .map(|entry| Arc::make_mut(&mut entry.data))
+        {
+            Some(Value::Stream(stream)) => Ok(Some(stream)),
+            Some(_) => Err(WRONGTYPE.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Like `stream_mut`, but creates an empty stream when the key does not exist.
+    fn stream_or_default(&mut self, key: &str) -> crate::Result<&mut Stream> {
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                data: Arc::new(Value::Stream(Stream::default())),
+                expires_at: None,
+            });
+
+        match Arc::make_mut(&mut entry.data) {
+            Value::Stream(stream) => Ok(stream),
+            _ => Err(WRONGTYPE.into()),
+        }
+    }
+
+    /// The stream a consumer group command operates on, which must exist.
+    fn group_stream(&self, key: &str) -> crate::Result<&Stream> {
+        self.stream(key)?.ok_or_else(|| stream::NOGROUP.into())
+    }
+
+    fn group_stream_mut(&mut self, key: &str) -> crate::Result<&mut Stream> {
+        self.stream_mut(key)?.ok_or_else(|| stream::NOGROUP.into())
+    }
+
+    /// Applies `op` over the sets stored at `keys`, missing keys acting as empty sets.
+    fn combine(&self, op: SetOp, keys: &[String]) -> crate::Result<HashSet<Bytes>> {
+        let sets = keys
+            .iter()
+            .map(|key| self.set(key))
+            .collect::<crate::Result<Vec<_>>>()?;
+        let empty = HashSet::new();
+        let mut sets = sets.into_iter().map(|set| set.unwrap_or(&empty));
+
+        let mut result = sets.next().cloned().unwrap_or_default();
+        for set in sets {
+            match op {
+                SetOp::Inter => result.retain(|member| set.contains(member)),
+                SetOp::Union => result.extend(set.iter().cloned()),
+                SetOp::Diff => result.retain(|member| !set.contains(member)),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Stores a new entry, registering its expiration. Returns whether the
+    /// purge task needs to be woken up for an earlier deadline.
+    fn insert_entry(&mut self, key: String, data: Arc<Value>, expires_at: Option<Instant>) -> bool {
+        let notify = match expires_at {
+            Some(when) => {
+                let notify = self
+                    .next_expiration()
+                    .map(|expiration| expiration > when)
+                    .unwrap_or(true);
+                self.expirations.insert((when, key.clone()));
+                notify
+            }
+            None => false,
+        };
+
+        self.touch(&key);
+        self.entries.insert(key, Entry { data, expires_at });
+        notify
+    }
+
+    /// Removes the entry together with its expiration, keeping both indexes in sync.
+    fn remove_entry(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.discard_entry(key)?;
+        self.touch(key);
+        Some(entry)
+    }
+
+    /// Like `remove_entry`, without recording a modification of `key`: its
+    /// removal is part of a change that was already recorded, or undoes the
+    /// creation of an entry the change did not keep.
+    fn discard_entry(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        if let Some(when) = entry.expires_at {
+            self.expirations.remove(&(when, key.to_string()));
+        }
+        Some(entry)
+    }
+
+    /// Moves the expiration of an existing key to `expires_at`. Returns
+    /// whether the purge task needs to be woken up for an earlier deadline.
+    fn set_expiration(&mut self, key: &str, expires_at: Option<Instant>) -> bool {
+        if !self.entries.contains_key(key) {
+            return false;
+        }
+        self.touch(key);
+
+        let next = self.next_expiration();
+        let entry = self.entries.get_mut(key).unwrap();
+
+        if let Some(when) = std::mem::replace(&mut entry.expires_at, expires_at) {
+            self.expirations.remove(&(when, key.to_string()));
+        }
+        match expires_at {
+            Some(when) => {
+                self.expirations.insert((when, key.to_string()));
+                next.map(|next| next > when).unwrap_or(true)
+            }
+            None => false,
+        }
+    }
+
+    /// Records a modification of `key`, failing the `EXEC` of connections
+    /// watching it. Every change to `entries` goes through here, commands
+    /// that end up changing nothing do not.
+    fn touch(&mut self, key: &str) {
+        self.dirty += 1;
+        if let Some(watched) = self.watched.get_mut(key) {
+            watched.version += 1;
+        }
+    }
+
+    fn touch_all(&mut self) {
+        self.dirty += 1;
+        for watched in self.watched.values_mut() {
+            watched.version += 1;
+        }
+    }
+
+    /// Collections are never stored empty; drop the key once the last element
+    /// is gone. Callers `touch` the key for the change that emptied it.
+    fn remove_if_empty(&mut self, key: &str) {
+        let empty = match self.entries.get(key).map(|entry| &*entry.data) {
+            Some(Value::List(list)) => list.is_empty(),
+            Some(Value::Hash(hash)) => hash.is_empty(),
+            Some(Value::Set(set)) => set.is_empty(),
+            Some(Value::ZSet(zset)) => zset.is_empty(),
+            _ => false,
+        };
+
+        if empty {
+            self.discard_entry(key);
+        }
+    }
+}
+
+/// Converts the Unix time `unix_ms` to an `Instant`, relative to `now`.
+/// Returns `None` when it is too far in the future to be represented.
+pub(crate) fn instant_at(unix_ms: u64, now: Instant) -> Option<Instant> {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let at = Duration::from_millis(unix_ms);
+
+    if at > since_epoch {
+        now.checked_add(at - since_epoch)
+    } else {
+        Some(now.checked_sub(since_epoch - at).unwrap_or(now))
+    }
+}
+
+/// Converts `when` to a Unix time in milliseconds, the inverse of `instant_at`.
+pub(crate) fn unix_ms_at(when: Instant, now: Instant) -> u64 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let unix = if when >= now {
+        since_epoch + (when - now)
+    } else {
+        since_epoch.saturating_sub(now - when)
+    };
+    unix.as_millis() as u64
+}
+
+/// Runs `f`, which keeps the thread busy for long. On a multi-threaded
+/// runtime, the other tasks of the worker are handed over to another one in
+/// the meantime.
+pub(crate) fn block_in_place<T>(f: impl FnOnce() -> T) -> T {
+    let multi_thread = Handle::try_current()
+        .is_ok_and(|handle| handle.runtime_flavor() == RuntimeFlavor::MultiThread);
+    if multi_thread {
+        tokio::task::block_in_place(f)
+    } else {
+        f()
+    }
+}
+
+/// Waits for a lock with `lock`, handing the other tasks of the worker over
+/// to another one when it may be `held_long`.
+fn wait_for<T>(held_long: bool, lock: impl FnOnce() -> T) -> T {
+    if held_long {
+        block_in_place(lock)
+    } else {
+        lock()
+    }
+}
+
+/// Strictly parses a stored value as a base 10 signed integer.
+fn parse_i64(value: &[u8]) -> Option<i64> {
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
+
+/// Resolves a possibly negative index against a collection of length `len`.
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let len = len as i64;
+    let index = if index < 0 { len + index } else { index };
+    (0..len).contains(&index).then_some(index as usize)
+}
+
+/// Resolves an inclusive `start..=stop` range the way `LRANGE` does, clamping
+/// out of bound indexes and returning `None` when the range is empty.
+fn normalize_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+
+    if start > stop || start >= len {
+        None
+    } else {
+        Some((start as usize, stop as usize))
+    }
+}
+
+async fn purge_expired_tasks(shared: Arc<Shared>) {
+    loop {
+        // Held by a script, the keys are purged once it returns, see
+        // `Databases::exclusive`.
+        if shared.is_exclusive() {
+            shared.background_task.notified().await;
+            continue;
+        }
+        if shared.is_shutdown() {
+            break;
+        }
         if let Some(when) = shared.purge_expired_keys() {
             tokio::select! {
                 _ = time::sleep_until(when) => {},
@@ -0,0 +1,275 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Cursor, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bytes::BytesMut;
+use tracing::{error, info, warn};
+
+use super::{propagation::Replay, snapshot::Snapshot, Databases};
+use crate::{frame, server::AppendFsync, Command, Frame};
+
+const BAD_FORMAT: &str = "ERR Bad append only file format";
+
+/// The append only file the commands that change keys are logged to, see
+/// `Databases::propagating`.
+#[derive(Debug)]
+pub(crate) struct Aof {
+    path: Option<PathBuf>,
+    fsync: AppendFsync,
+    log: Mutex<Log>,
+    /// How much of what was logged is known to be on disk, held while
+    /// flushing the file so that flushes don't overlap.
+    synced: tokio::sync::Mutex<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Log {
+    /// Opened once the file is loaded, and closed on shutdown.
+    file: Option<File>,
+    /// What was logged since a rewrite started, to be added to the rewritten
+    /// file once it is written.
+    rewrite: Option<BytesMut>,
+    /// How many times something was logged, see `Aof::synced`.
+    written: u64,
+}
+
+impl Aof {
+    pub(crate) fn new(path: Option<PathBuf>, fsync: AppendFsync) -> Aof {
+        Aof {
+            path,
+            fsync,
+            log: Mutex::new(Log::default()),
+            synced: tokio::sync::Mutex::new(0),
+        }
+    }
+
+    fn path(&self) -> crate::Result<&Path> {
+        self.path.as_deref().ok_or_else(|| {
+            "ERR Append only file is disabled, no append only file is configured".into()
+        })
+    }
+
+    /// Whether an append only file is configured.
+    pub(super) fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Logs the propagated commands `src`, once the file is loaded.
+    pub(super) fn append(&self, src: &[u8]) {
+        if let Err(err) = self.log.lock().unwrap().append(src) {
+            error!(cause = %err, "failed to write to the append only file");
+        }
+    }
+
+    /// Writes `snapshot` followed by what was logged in the meantime to a
+    /// temporary file, which then replaces the file at `path`.
+    fn rewrite(&self, path: &Path, snapshot: Snapshot) -> io::Result<()> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = path.with_file_name(format!("temp-rewrite-{}-{}", std::process::id(), name));
+        let res = (|| {
+            let mut file = File::create(&tmp)?;
+            file.write_all(&snapshot.encode())?;
+            file.sync_data()?;
+
+            // Nothing can be logged from here on until the new file is in
+            // place, so that no command is missed.
+            let mut log = self.log.lock().unwrap();
+            let logged = log.rewrite.take().unwrap_or_default();
+            file.write_all(&logged)?;
+            file.sync_data()?;
+            fs::rename(&tmp, path)?;
+            log.file = Some(file);
+            Ok(())
+        })();
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+            self.log.lock().unwrap().rewrite = None;
+        }
+        res
+    }
+}
+
+impl Log {
+    fn append(&mut self, src: &[u8]) -> io::Result<()> {
+        let Some(file) = &mut self.file else {
+            return Ok(());
+        };
+        if let Some(rewrite) = &mut self.rewrite {
+            rewrite.extend_from_slice(src);
+        }
+
+        file.write_all(src)?;
+        self.written += 1;
+        Ok(())
+    }
+}
+
+impl Databases {
+    /// Replays the configured append only file, if there is one, before the
+    /// server accepts connections. A command cut short at the end of the
+    /// file, like when the server crashed while writing it, is dropped.
+    pub(crate) fn load_aof(&self) -> crate::Result<()> {
+        let path = self.aof.path()?;
+        let src = match fs::read(path) {
+            Ok(src) => src,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err.into()),
+        };
+
+        self.set_loading(true);
+        let res = self.replay(&src);
+        self.set_loading(false);
+        let (len, commands) = res?;
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        if len < src.len() {
+            warn!(
+                path = %path.display(),
+                dropped = src.len() - len,
+                "append only file ends with an incomplete command, truncating it"
+            );
+            file.set_len(len as u64)?;
+        }
+        if !src.is_empty() {
+            info!(path = %path.display(), commands, "loaded append only file");
+        }
+        self.snapshots.loaded(self.dirty());
+        self.aof.log.lock().unwrap().file = Some(file);
+        Ok(())
+    }
+
+    /// Runs the commands of `src`, returning the length of the complete
+    /// ones along with how many there were.
+    fn replay(&self, src: &[u8]) -> crate::Result<(usize, usize)> {
+        let mut rest = src;
+        if Snapshot::is_prefix_of(rest) {
+            let (snapshot, commands) = Snapshot::decode_prefix(rest)?;
+            self.restore(snapshot);
+            rest = commands;
+        }
+        let start = src.len() - rest.len();
+
+        let mut buf = Cursor::new(rest);
+        let mut replay = Replay::new(false);
+        let mut len = 0;
+        let mut commands = 0;
+        while (buf.position() as usize) < rest.len() {
+            let pos = buf.position();
+            match Frame::check(&mut buf) {
+                Ok(()) => {}
+                Err(frame::Error::Incomplete) => break,
+                Err(_) => return Err(BAD_FORMAT.into()),
+            }
+            buf.set_position(pos);
+            let frame = Frame::parse(&mut buf)?;
+
+            let cmd = Command::from_frame(frame).map_err(|_| BAD_FORMAT)?;
+            replay.apply(self, cmd).map_err(|_| BAD_FORMAT)?;
+            commands += 1;
+            // A transaction cut short is dropped as a whole.
+            if !replay.in_transaction() {
+                len = buf.position() as usize;
+            }
+        }
+        Ok((start + len, commands))
+    }
+
+    /// Rewrites the append only file in the background, as a snapshot of the
+    /// data followed by the commands logged while it is written. The
+    /// snapshot is taken right away, so it holds the data as of this call.
+    pub(crate) fn bgrewriteaof(&self) -> crate::Result<()> {
+        let path = self.aof.path()?.to_path_buf();
+        let snapshot = self.between_commands(|| {
+            if self.aof.log.lock().unwrap().rewrite.is_some() {
+                return Err("ERR Background append only file rewriting already in progress");
+            }
+            // Logging for the rewrite starts once the snapshot is taken, as
+            // what `exclusive` propagates while the snapshot waits for the
+            // databases is part of it.
+            let snapshot = self.snapshot().0;
+            self.aof.log.lock().unwrap().rewrite = Some(BytesMut::new());
+            Ok(snapshot)
+        })?;
+
+        let aof = Arc::clone(&self.aof);
+        tokio::spawn(async move {
+            let res = tokio::task::spawn_blocking(move || aof.rewrite(&path, snapshot))
+                .await
+                .unwrap_or_else(|err| Err(io::Error::other(err)));
+            match res {
+                Ok(()) => info!("background append only file rewriting terminated with success"),
+                Err(err) => error!(cause = %err, "background append only file rewriting failed"),
+            }
+        });
+        Ok(())
+    }
+
+    /// Flushes the append only file to disk if its policy is
+    /// `AppendFsync::Everysec`.
+    pub(crate) async fn fsync_aof_if_due(&self) {
+        if self.aof.fsync == AppendFsync::Everysec {
+            self.fsync_aof().await;
+        }
+    }
+
+    /// Flushes the append only file to disk before replying to a command
+    /// that changed keys, if its policy is `AppendFsync::Always`.
+    pub(crate) async fn fsync_aof_if_always(&self) {
+        if self.aof.fsync == AppendFsync::Always {
+            self.fsync_aof().await;
+        }
+    }
+
+    /// Flushes what was logged so far to disk, unless a flush that started
+    /// since did already. The flush blocks, so it runs off the runtime.
+    async fn fsync_aof(&self) {
+        let written = self.aof.log.lock().unwrap().written;
+        let mut synced = self.aof.synced.lock().await;
+        if *synced >= written {
+            return;
+        }
+        let (file, written) = {
+            let log = self.aof.log.lock().unwrap();
+            match log.file.as_ref().map(File::try_clone) {
+                Some(Ok(file)) => (file, log.written),
+                Some(Err(err)) => {
+                    error!(cause = %err, "failed to fsync the append only file");
+                    return;
+                }
+                None => return,
+            }
+        };
+
+        let res = tokio::task::spawn_blocking(move || file.sync_data())
+            .await
+            .unwrap_or_else(|err| Err(io::Error::other(err)));
+        match res {
+            Ok(()) => *synced = written,
+            Err(err) => error!(cause = %err, "failed to fsync the append only file"),
+        }
+    }
+
+    /// Flushes the append only file to disk and closes it, once the server
+    /// is done running commands.
+    pub(crate) fn close_aof(&self) {
+        let mut log = self.aof.log.lock().unwrap();
+        if let Some(file) = log.file.take() {
+            if let Err(err) = file.sync_data() {
+                error!(cause = %err, "failed to fsync the append only file");
+            }
+        }
+    }
+
+    /// Suspends expirations while replaying, see `State::loading`.
+    fn set_loading(&self, loading: bool) {
+        for db in self.dbs.iter() {
+            db.shared.lock().loading = loading;
+            if !loading {
+                db.shared.background_task.notify_one();
+            }
+        }
+    }
+}
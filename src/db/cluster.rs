@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use super::{sha1_hex, Databases};
+use crate::{server::ClusterConfig, Command, Frame};
+
+/// The number of hash slots keys are spread over, like in Redis.
+pub(crate) const SLOTS: u16 = 16384;
+
+/// The nodes of the cluster this server is part of, and the hash slots each
+/// of them owns. The configuration is static, every node is started with
+/// the same one, see `Config::cluster`.
+#[derive(Debug)]
+pub(crate) struct Cluster {
+    nodes: Vec<Node>,
+    myself: usize,
+    /// The node owning each slot, if any.
+    owners: Vec<Option<usize>>,
+    /// The node each slot being migrated is moved to.
+    migrating: HashMap<u16, usize>,
+}
+
+#[derive(Debug)]
+struct Node {
+    /// 40 hexadecimal characters like in Redis, derived from the address so
+    /// that every node agrees on it.
+    id: String,
+    host: String,
+    port: u16,
+}
+
+impl Cluster {
+    pub(crate) fn new(config: &ClusterConfig) -> Cluster {
+        let mut owners = vec![None; SLOTS as usize];
+        for (i, node) in config.nodes.iter().enumerate() {
+            for slot in node.slots.iter().cloned().flatten() {
+                owners[slot as usize] = Some(i);
+            }
+        }
+        let nodes = config
+            .nodes
+            .iter()
+            .map(|node| Node {
+                id: sha1_hex(format!("{}:{}", node.host, node.port).as_bytes()),
+                host: node.host.clone(),
+                port: node.port,
+            })
+            .collect();
+        Cluster {
+            nodes,
+            myself: config.myself,
+            owners,
+            migrating: config.migrating.iter().copied().collect(),
+        }
+    }
+
+    /// The ranges of slots `node` owns, in order.
+    fn ranges(&self, node: usize) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = vec![];
+        for slot in 0..SLOTS {
+            if self.owners[slot as usize] != Some(node) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == slot => *end = slot,
+                _ => ranges.push((slot, slot)),
+            }
+        }
+        ranges
+    }
+
+    fn assigned(&self) -> usize {
+        self.owners.iter().filter(|owner| owner.is_some()).count()
+    }
+
+    /// How `node` is described by `CLUSTER SLOTS` and `CLUSTER SHARDS`.
+    fn address(&self, node: usize) -> (Bytes, i64, Bytes) {
+        let node = &self.nodes[node];
+        (
+            Bytes::from(node.host.clone()),
+            node.port as i64,
+            Bytes::from(node.id.clone()),
+        )
+    }
+}
+
+impl Databases {
+    fn cluster(&self) -> crate::Result<&Cluster> {
+        self.cluster
+            .as_deref()
+            .ok_or_else(|| "ERR This instance has cluster support disabled".into())
+    }
+
+    /// The error redirecting `cmd`, which runs against database `db`, to the
+    /// node serving its keys, or `None` if this node serves them. A slot
+    /// being migrated is served by its owner for the keys it still has, and
+    /// by the node it is moved to for a command that follows `ASKING`.
+    pub(crate) fn redirect(&self, cmd: &Command, db: usize, asking: bool) -> Option<Frame> {
+        let cluster = self.cluster.as_deref()?;
+        let keys = cmd.keys();
+        let slot = key_slot(keys.first()?);
+        if keys.iter().any(|key| key_slot(key) != slot) {
+            return Some(Frame::Error(
+                "CROSSSLOT Keys in request don't hash to the same slot".to_string(),
+            ));
+        }
+
+        let redirect = |kind: &str, node: usize| {
+            let node = &cluster.nodes[node];
+            Some(Frame::Error(format!(
+                "{} {} {}:{}",
+                kind, slot, node.host, node.port
+            )))
+        };
+        let migrating = cluster.migrating.get(&slot).copied();
+        match cluster.owners[slot as usize] {
+            None => Some(Frame::Error("CLUSTERDOWN Hash slot not served".to_string())),
+            Some(owner) if owner == cluster.myself => {
+                let to = migrating?;
+                let db = self.db(db);
+                let missing = keys
+                    .iter()
+                    .filter(|key| db.exists(&[key.to_string()]) == 0)
+                    .count();
+                if missing == 0 {
+                    None
+                } else if missing == keys.len() {
+                    redirect("ASK", to)
+                } else {
+                    Some(Frame::Error(
+                        "TRYAGAIN Multiple keys request during rehashing of slot".to_string(),
+                    ))
+                }
+            }
+            Some(_) if asking && migrating == Some(cluster.myself) => None,
+            Some(owner) => redirect("MOVED", owner),
+        }
+    }
+
+    /// Replies to `CLUSTER INFO`.
+    pub(crate) fn cluster_info(&self) -> crate::Result<String> {
+        let cluster = self.cluster()?;
+        let assigned = cluster.assigned();
+        let state = if assigned == SLOTS as usize {
+            "ok"
+        } else {
+            "fail"
+        };
+        let size = (0..cluster.nodes.len())
+            .filter(|node| cluster.owners.contains(&Some(*node)))
+            .count();
+
+        let mut info = String::new();
+        info.push_str(&format!("cluster_state:{}\r\n", state));
+        info.push_str(&format!("cluster_slots_assigned:{}\r\n", assigned));
+        info.push_str(&format!("cluster_slots_ok:{}\r\n", assigned));
+        info.push_str("cluster_slots_pfail:0\r\n");
+        info.push_str("cluster_slots_fail:0\r\n");
+        info.push_str(&format!("cluster_known_nodes:{}\r\n", cluster.nodes.len()));
+        info.push_str(&format!("cluster_size:{}\r\n", size));
+        info.push_str(&format!(
+            "cluster_current_epoch:{}\r\n",
+            cluster.nodes.len()
+        ));
+        info.push_str(&format!("cluster_my_epoch:{}\r\n", cluster.myself + 1));
+        Ok(info)
+    }
+
+    /// Replies to `CLUSTER KEYSLOT`.
+    pub(crate) fn cluster_keyslot(&self, key: &str) -> crate::Result<u16> {
+        self.cluster()?;
+        Ok(key_slot(key))
+    }
+
+    /// Replies to `CLUSTER MYID`.
+    pub(crate) fn cluster_myid(&self) -> crate::Result<String> {
+        let cluster = self.cluster()?;
+        Ok(cluster.nodes[cluster.myself].id.clone())
+    }
+
+    /// Replies to `CLUSTER NODES`, one line per node. Only this node's line
+    /// tells about the slots it migrates or imports, like in Redis.
+    pub(crate) fn cluster_nodes(&self) -> crate::Result<String> {
+        let cluster = self.cluster()?;
+        let mut lines = String::new();
+        for (i, node) in cluster.nodes.iter().enumerate() {
+            let flags = if i == cluster.myself {
+                "myself,master"
+            } else {
+                "master"
+            };
+            lines.push_str(&format!(
+                "{} {}:{}@{} {} - 0 0 {} connected",
+                node.id,
+                node.host,
+                node.port,
+                node.port as u32 + 10000,
+                flags,
+                i + 1
+            ));
+            for (start, end) in cluster.ranges(i) {
+                if start == end {
+                    lines.push_str(&format!(" {}", start));
+                } else {
+                    lines.push_str(&format!(" {}-{}", start, end));
+                }
+            }
+            if i == cluster.myself {
+                let mut migrating: Vec<_> = cluster.migrating.iter().collect();
+                migrating.sort();
+                for (&slot, &to) in migrating {
+                    let Some(owner) = cluster.owners[slot as usize] else {
+                        continue;
+                    };
+                    if owner == i {
+                        let to = &cluster.nodes[to].id;
+                        lines.push_str(&format!(" [{}->-{}]", slot, to));
+                    } else if to == i {
+                        let from = &cluster.nodes[owner].id;
+                        lines.push_str(&format!(" [{}-<-{}]", slot, from));
+                    }
+                }
+            }
+            lines.push('\n');
+        }
+        Ok(lines)
+    }
+
+    /// Replies to `CLUSTER SLOTS`, with each range of slots and the node
+    /// owning it.
+    pub(crate) fn cluster_slots(&self) -> crate::Result<Frame> {
+        let cluster = self.cluster()?;
+        let mut ranges: Vec<_> = (0..cluster.nodes.len())
+            .flat_map(|node| {
+                cluster
+                    .ranges(node)
+                    .into_iter()
+                    .map(move |range| (range, node))
+            })
+            .collect();
+        ranges.sort();
+
+        let slots = ranges
+            .into_iter()
+            .map(|((start, end), node)| {
+                let (host, port, id) = cluster.address(node);
+                Frame::Array(vec![
+                    Frame::Integer(start as i64),
+                    Frame::Integer(end as i64),
+                    Frame::Array(vec![
+                        Frame::Bulk(host),
+                        Frame::Integer(port),
+                        Frame::Bulk(id),
+                    ]),
+                ])
+            })
+            .collect();
+        Ok(Frame::Array(slots))
+    }
+
+    /// Replies to `CLUSTER SHARDS`, with the slots of each node, as every
+    /// shard is made of a single node.
+    pub(crate) fn cluster_shards(&self) -> crate::Result<Frame> {
+        let cluster = self.cluster()?;
+        let offset = self.repl_offset() as i64;
+        let shards = (0..cluster.nodes.len())
+            .map(|node| {
+                let slots = cluster
+                    .ranges(node)
+                    .into_iter()
+                    .flat_map(|(start, end)| [start, end])
+                    .map(|slot| Frame::Integer(slot as i64))
+                    .collect();
+                let (host, port, id) = cluster.address(node);
+                let offset = if node == cluster.myself { offset } else { 0 };
+                let node = Frame::Array(vec![
+                    Frame::Bulk(Bytes::from_static(b"id")),
+                    Frame::Bulk(id),
+                    Frame::Bulk(Bytes::from_static(b"port")),
+                    Frame::Integer(port),
+                    Frame::Bulk(Bytes::from_static(b"ip")),
+                    Frame::Bulk(host.clone()),
+                    Frame::Bulk(Bytes::from_static(b"endpoint")),
+                    Frame::Bulk(host),
+                    Frame::Bulk(Bytes::from_static(b"role")),
+                    Frame::Bulk(Bytes::from_static(b"master")),
+                    Frame::Bulk(Bytes::from_static(b"replication-offset")),
+                    Frame::Integer(offset),
+                    Frame::Bulk(Bytes::from_static(b"health")),
+                    Frame::Bulk(Bytes::from_static(b"online")),
+                ]);
+                Frame::Array(vec![
+                    Frame::Bulk(Bytes::from_static(b"slots")),
+                    Frame::Array(slots),
+                    Frame::Bulk(Bytes::from_static(b"nodes")),
+                    Frame::Array(vec![node]),
+                ])
+            })
+            .collect();
+        Ok(Frame::Array(shards))
+    }
+
+    /// Whether this server runs in cluster mode.
+    pub(crate) fn cluster_enabled(&self) -> bool {
+        self.cluster.is_some()
+    }
+
+    /// The `cluster` section of `INFO`.
+    pub(crate) fn cluster_enabled_info(&self) -> String {
+        format!(
+            "# Cluster\r\ncluster_enabled:{}\r\n",
+            self.cluster_enabled() as u8
+        )
+    }
+}
+
+/// The hash slot of `key`, the CRC16 of its hash tag if it has one: the part
+/// between the first `{` and the next `}`, unless it is empty. Keys sharing
+/// a hash tag, like `{user:1}:name` and `{user:1}:email`, share a slot.
+fn key_slot(key: &str) -> u16 {
+    let key = key.as_bytes();
+    let tagged = key.iter().position(|&b| b == b'{').and_then(|open| {
+        let tag = &key[open + 1..];
+        let close = tag.iter().position(|&b| b == b'}')?;
+        (close > 0).then_some(&tag[..close])
+    });
+    crc16(tagged.unwrap_or(key)) % SLOTS
+}
+
+/// The CRC16 variant Redis uses, XMODEM.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
@@ -0,0 +1,245 @@
+use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use bytes::{BufMut, BytesMut};
+
+use super::{wait_for, Databases};
+use crate::{
+    cmd::{Exec, Multi, Select},
+    Command, Frame,
+};
+
+/// How the commands that change keys are propagated to the append only file
+/// and to replicas, as they would be sent by a client.
+#[derive(Debug, Default)]
+pub(crate) struct Propagation {
+    /// Held exclusively while running commands that are propagated, so that
+    /// they are propagated in the order they ran. When nothing consumes what
+    /// is propagated, commands only share it, to keep it from starting to.
+    order: RwLock<()>,
+    stream: Mutex<Stream>,
+    /// Whether the changes of databases detached by `Databases::exclusive`
+    /// are propagated, as it was when they were detached. They are
+    /// propagated by `exclusive` once it is done, so that they are not
+    /// propagated in part.
+    detached: Option<bool>,
+}
+
+#[derive(Debug, Default)]
+struct Stream {
+    /// The database the commands propagated last ran against.
+    selected: Option<usize>,
+    /// What the running command propagated, see `Databases::propagating`.
+    pending: Vec<(usize, Frame)>,
+}
+
+/// Runs commands the way they were propagated, where the commands between
+/// `MULTI` and `EXEC` run at once.
+#[derive(Debug)]
+pub(super) struct Replay {
+    selected: usize,
+    transaction: Option<Vec<Command>>,
+    /// Whether the commands are propagated in turn, see `Command::execute`.
+    propagate: bool,
+}
+
+impl Propagation {
+    pub(super) fn detached(propagating: bool) -> Propagation {
+        Propagation {
+            detached: Some(propagating),
+            ..Propagation::default()
+        }
+    }
+}
+
+impl Stream {
+    /// Encodes the changes propagated by one command, selecting their
+    /// databases as needed. Several changes, from `EXEC` or a script, are
+    /// wrapped in a transaction so that they are replayed all or nothing.
+    fn encode(&mut self, changes: Vec<(usize, Frame)>) -> BytesMut {
+        let mut dst = BytesMut::new();
+        let transaction = changes.len() > 1;
+        if transaction {
+            put_command(&mut dst, &Multi::new().into_frame());
+        }
+        for (db, frame) in changes {
+            if self.selected != Some(db) {
+                put_command(&mut dst, &Select::new(db as i64).into_frame());
+                self.selected = Some(db);
+            }
+            put_command(&mut dst, &frame);
+        }
+        if transaction {
+            put_command(&mut dst, &Exec::new().into_frame());
+        }
+        dst
+    }
+}
+
+impl Replay {
+    pub(super) fn new(propagate: bool) -> Replay {
+        Replay {
+            selected: 0,
+            transaction: None,
+            propagate,
+        }
+    }
+
+    /// Whether a transaction was started and not run yet.
+    pub(super) fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Runs, or queues, the propagated command `cmd`.
+    pub(super) fn apply(&mut self, databases: &Databases, cmd: Command) -> crate::Result<()> {
+        const INVALID: &str = "invalid propagated command";
+
+        match cmd {
+            Command::Unknown(_) => return Err(INVALID.into()),
+            Command::Multi(_) => {
+                if self.transaction.replace(vec![]).is_some() {
+                    return Err(INVALID.into());
+                }
+            }
+            Command::Exec(_) => {
+                for cmd in self.transaction.take().ok_or(INVALID)? {
+                    self.run(databases, cmd);
+                }
+            }
+            cmd => match &mut self.transaction {
+                Some(queued) => queued.push(cmd),
+                None => self.run(databases, cmd),
+            },
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, databases: &Databases, cmd: Command) {
+        if self.propagate {
+            cmd.execute(databases, &mut self.selected);
+        } else {
+            cmd.run(databases, &mut self.selected);
+        }
+    }
+}
+
+impl Databases {
+    /// Runs `f`, which runs commands, one at a time with the other commands
+    /// that are propagated, then propagates the changes they made.
+    pub(crate) fn propagating<T>(&self, f: impl FnOnce() -> T) -> T {
+        {
+            let _order = self.shared_order();
+            if !self.is_propagating() {
+                return f();
+            }
+        }
+
+        let _order = self.order();
+        let result = f();
+        self.propagate_all(self.take_pending());
+        result
+    }
+
+    /// Whether changes are propagated, to the append only file or to
+    /// replicas. Only changes while no command runs, see `between_commands`.
+    pub(crate) fn is_propagating(&self) -> bool {
+        self.propagation
+            .detached
+            .unwrap_or_else(|| self.aof.is_enabled() || self.streaming())
+    }
+
+    /// Records that a command changed keys of database `db`, to be
+    /// propagated as `frame` once it is done.
+    pub(crate) fn propagate(&self, db: usize, frame: Frame) {
+        self.propagation
+            .stream
+            .lock()
+            .unwrap()
+            .pending
+            .push((db, frame));
+    }
+
+    /// Adds `frame` to the replication stream, outside of any command and
+    /// without logging it to the append only file.
+    pub(super) fn feed_replicas_command(&self, frame: &Frame) {
+        let _order = self.order();
+        let mut dst = BytesMut::new();
+        put_command(&mut dst, frame);
+        self.feed_replicas(&dst);
+    }
+
+    /// Runs `f` while no command runs that is propagated, so that what it
+    /// sees matches what was propagated so far. What is propagated next
+    /// starts by selecting its database, so that it can follow a snapshot.
+    pub(super) fn between_commands<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _order = self.order();
+        let result = f();
+        // After `f`, as `exclusive` may propagate while `f` waits for the
+        // databases.
+        self.propagation.stream.lock().unwrap().selected = None;
+        result
+    }
+
+    /// The changes propagated by the running command so far.
+    pub(super) fn take_pending(&self) -> Vec<(usize, Frame)> {
+        std::mem::take(&mut self.propagation.stream.lock().unwrap().pending)
+    }
+
+    /// Propagates `changes`, made by one command. Databases detached by
+    /// `exclusive` keep them for it to propagate.
+    pub(super) fn propagate_all(&self, changes: Vec<(usize, Frame)>) {
+        if changes.is_empty() {
+            return;
+        }
+        let mut stream = self.propagation.stream.lock().unwrap();
+        if self.propagation.detached.is_some() {
+            stream.pending.extend(changes);
+            return;
+        }
+        let src = stream.encode(changes);
+        self.aof.append(&src);
+        self.feed_replicas(&src);
+    }
+
+    /// Takes `order` exclusively, which a command may hold while it waits
+    /// for databases held by a script, see `Shared::lock`.
+    pub(super) fn order(&self) -> RwLockWriteGuard<'_, ()> {
+        if let Ok(order) = self.propagation.order.try_write() {
+            return order;
+        }
+        wait_for(self.is_exclusive(), || {
+            self.propagation.order.write().unwrap()
+        })
+    }
+
+    fn shared_order(&self) -> RwLockReadGuard<'_, ()> {
+        if let Ok(order) = self.propagation.order.try_read() {
+            return order;
+        }
+        wait_for(self.is_exclusive(), || {
+            self.propagation.order.read().unwrap()
+        })
+    }
+}
+
+/// Encodes a command as an array of bulk strings, the way clients send
+/// them.
+fn put_command(dst: &mut BytesMut, frame: &Frame) {
+    let Frame::Array(args) = frame else {
+        unreachable!("commands are arrays, not {:?}", frame);
+    };
+    dst.put_slice(format!("*{}\r\n", args.len()).as_bytes());
+    for arg in args {
+        let integer;
+        let arg: &[u8] = match arg {
+            Frame::Bulk(arg) => arg,
+            Frame::Integer(arg) => {
+                integer = arg.to_string();
+                integer.as_bytes()
+            }
+            arg => unreachable!("command arguments are strings, not {:?}", arg),
+        };
+        dst.put_slice(format!("${}\r\n", arg.len()).as_bytes());
+        dst.put_slice(arg);
+        dst.put_slice(b"\r\n");
+    }
+}
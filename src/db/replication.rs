@@ -0,0 +1,608 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use tokio::{
+    net::TcpStream,
+    select,
+    sync::watch,
+    task::JoinHandle,
+    time::{self, Instant},
+};
+use tracing::{info, warn};
+
+use super::{propagation::Replay, sha1_hex, snapshot::Snapshot, Databases};
+use crate::{
+    cmd::{Ping, Psync, Replconf},
+    Command, Connection, Frame,
+};
+
+/// Replicas that lost their connection retry this often.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Replicas acknowledge what they received this often, on top of when their
+/// master asks for it.
+const ACK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The replication of this server's changes to its replicas, and of its
+/// master's changes when it is a replica itself.
+///
+/// Replicas start with a snapshot of the data, then receive the stream of
+/// propagated commands. The end of the stream is kept in a backlog, so that
+/// a replica that lost its connection for a moment can continue where it
+/// left off rather than start over.
+#[derive(Debug)]
+pub(crate) struct Replication {
+    backlog: Mutex<Backlog>,
+    /// Whether a replica ever synchronized, the stream being fed from then
+    /// on. Until then there is no one to resume it, so it is not kept.
+    streaming: AtomicBool,
+    /// The offset of the end of the stream, watched by the connections of
+    /// replicas to know when there is more to send.
+    offset: watch::Sender<u64>,
+    replicas: Mutex<HashMap<u64, Replica>>,
+    /// Notified when a replica acknowledges what it received, see
+    /// `Databases::wait_for_acks`.
+    acks: watch::Sender<()>,
+    next_id: AtomicU64,
+    /// The master this server replicates, if it is a replica.
+    master: Mutex<Option<Master>>,
+    /// The port this server accepts connections on, which its master
+    /// reports to clients.
+    port: AtomicU16,
+    full_syncs: AtomicU64,
+    partial_syncs: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Backlog {
+    /// Identifies the history of the stream, a replica that synchronized
+    /// with another history has to start over.
+    id: String,
+    buf: VecDeque<u8>,
+    capacity: usize,
+    /// How many bytes were written to the stream, the last ones being in
+    /// `buf`.
+    offset: u64,
+}
+
+/// A replica connected to this server.
+#[derive(Debug)]
+struct Replica {
+    ip: IpAddr,
+    port: u16,
+    /// How much of the stream it acknowledged receiving.
+    ack: u64,
+    last_ack: Instant,
+}
+
+#[derive(Debug)]
+struct Master {
+    /// Tells this link apart from later ones, whose task may still be
+    /// winding down.
+    id: u64,
+    host: String,
+    port: u16,
+    link: Link,
+    /// How much of the master's stream was received.
+    offset: u64,
+    task: JoinHandle<()>,
+}
+
+/// The state of the connection to the master, as `ROLE` reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Link {
+    Connecting,
+    Sync,
+    Connected,
+}
+
+/// How a replica starts receiving the stream, see `Databases::psync`.
+#[derive(Debug)]
+pub(crate) enum Resync {
+    /// From where it left off, at `offset`.
+    Partial { id: String, offset: u64 },
+    /// From `offset`, once it loaded the encoded `snapshot` of the data.
+    Full {
+        id: String,
+        offset: u64,
+        snapshot: Bytes,
+    },
+}
+
+/// Registers a connected replica for `ROLE`, `INFO` and `WAIT`, until
+/// dropped.
+pub(crate) struct ReplicaGuard<'a> {
+    replication: &'a Replication,
+    id: u64,
+}
+
+impl Replication {
+    pub(crate) fn new(backlog_size: usize) -> Replication {
+        Replication {
+            backlog: Mutex::new(Backlog {
+                id: new_id(),
+                buf: VecDeque::new(),
+                capacity: backlog_size,
+                offset: 0,
+            }),
+            streaming: AtomicBool::new(false),
+            offset: watch::Sender::new(0),
+            replicas: Mutex::new(HashMap::new()),
+            acks: watch::Sender::new(()),
+            next_id: AtomicU64::new(0),
+            master: Mutex::new(None),
+            port: AtomicU16::new(0),
+            full_syncs: AtomicU64::new(0),
+            partial_syncs: AtomicU64::new(0),
+        }
+    }
+
+    /// Stops replicating the master, if any.
+    pub(super) fn stop(&self) {
+        if let Some(master) = self.master.lock().unwrap().take() {
+            master.task.abort();
+        }
+    }
+
+    /// Updates the link with master `id`, unless it was replaced.
+    fn update_link(&self, id: u64, link: Link, offset: u64) {
+        if let Some(master) = &mut *self.master.lock().unwrap() {
+            if master.id == id {
+                master.link = link;
+                master.offset = offset;
+            }
+        }
+    }
+}
+
+impl ReplicaGuard<'_> {
+    /// Records that the replica acknowledged receiving the stream up to
+    /// `offset`.
+    pub(crate) fn acked(&self, offset: u64) {
+        if let Some(replica) = self.replication.replicas.lock().unwrap().get_mut(&self.id) {
+            replica.ack = offset;
+            replica.last_ack = Instant::now();
+        }
+        self.replication.acks.send_replace(());
+    }
+}
+
+impl Drop for ReplicaGuard<'_> {
+    fn drop(&mut self) {
+        self.replication.replicas.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl Databases {
+    /// Whether this server is a replica, which only changes keys as its
+    /// master tells it to.
+    pub(crate) fn read_only(&self) -> bool {
+        self.replication.master.lock().unwrap().is_some()
+    }
+
+    pub(crate) fn set_listening_port(&self, port: u16) {
+        self.replication.port.store(port, Ordering::Relaxed);
+    }
+
+    /// Adds commands propagated by `Databases::propagating` to the stream.
+    pub(super) fn feed_replicas(&self, src: &[u8]) {
+        let offset = {
+            let mut backlog = self.replication.backlog.lock().unwrap();
+            backlog.buf.extend(src);
+            let excess = backlog.buf.len().saturating_sub(backlog.capacity);
+            backlog.buf.drain(..excess);
+            backlog.offset += src.len() as u64;
+            backlog.offset
+        };
+        self.replication.offset.send_replace(offset);
+    }
+
+    /// Whether the stream is fed to replicas, see `Replication::streaming`.
+    pub(super) fn streaming(&self) -> bool {
+        self.replication.streaming.load(Ordering::Relaxed)
+    }
+
+    /// Watches the offset of the end of the stream.
+    pub(crate) fn stream_offset(&self) -> watch::Receiver<u64> {
+        self.replication.offset.subscribe()
+    }
+
+    /// The stream of history `id` from `offset` on, or `None` if it is no
+    /// longer in the backlog.
+    pub(crate) fn stream_since(&self, id: &str, offset: u64) -> Option<Bytes> {
+        let backlog = self.replication.backlog.lock().unwrap();
+        let start = backlog.offset - backlog.buf.len() as u64;
+        if backlog.id != id || offset < start || offset > backlog.offset {
+            return None;
+        }
+        let skip = (offset - start) as usize;
+        Some(backlog.buf.iter().skip(skip).copied().collect())
+    }
+
+    /// Starts sending the stream to a replica that received the stream of
+    /// history `id` up to `offset`, from there if the backlog still has it,
+    /// or else from a snapshot of the data.
+    pub(crate) async fn psync(&self, id: &str, offset: Option<u64>) -> crate::Result<Resync> {
+        if let Some(offset) = offset {
+            let backlog = self.replication.backlog.lock().unwrap();
+            let start = backlog.offset - backlog.buf.len() as u64;
+            if backlog.id == id && offset >= start && offset <= backlog.offset {
+                self.replication
+                    .partial_syncs
+                    .fetch_add(1, Ordering::Relaxed);
+                return Ok(Resync::Partial {
+                    id: backlog.id.clone(),
+                    offset,
+                });
+            }
+        }
+
+        let (snapshot, id, offset) = self.between_commands(|| {
+            self.replication.streaming.store(true, Ordering::Relaxed);
+            let (snapshot, _) = self.snapshot();
+            let backlog = self.replication.backlog.lock().unwrap();
+            (snapshot, backlog.id.clone(), backlog.offset)
+        });
+        let snapshot = tokio::task::spawn_blocking(move || snapshot.encode()).await?;
+        self.replication.full_syncs.fetch_add(1, Ordering::Relaxed);
+        Ok(Resync::Full {
+            id,
+            offset,
+            snapshot: snapshot.into(),
+        })
+    }
+
+    /// Registers a replica, which acknowledged nothing yet.
+    pub(crate) fn add_replica(&self, ip: IpAddr, port: u16) -> ReplicaGuard<'_> {
+        let id = self.replication.next_id.fetch_add(1, Ordering::Relaxed);
+        let replica = Replica {
+            ip,
+            port,
+            ack: 0,
+            last_ack: Instant::now(),
+        };
+        self.replication
+            .replicas
+            .lock()
+            .unwrap()
+            .insert(id, replica);
+        ReplicaGuard {
+            replication: &self.replication,
+            id,
+        }
+    }
+
+    /// The offset of the end of the stream, which a client's writes are part
+    /// of once they ran.
+    pub(crate) fn repl_offset(&self) -> u64 {
+        self.replication.backlog.lock().unwrap().offset
+    }
+
+    /// How many replicas acknowledged receiving the stream up to `offset`.
+    fn replicas_acked(&self, offset: u64) -> usize {
+        let replicas = self.replication.replicas.lock().unwrap();
+        replicas
+            .values()
+            .filter(|replica| replica.ack >= offset)
+            .count()
+    }
+
+    /// Waits until `numreplicas` replicas acknowledged receiving the stream
+    /// up to `offset`, or `timeout` elapsed if there is one, returning how
+    /// many did. Replicas are asked to acknowledge right away rather than
+    /// when they next would.
+    pub(crate) async fn wait_for_acks(
+        &self,
+        offset: u64,
+        numreplicas: usize,
+        timeout: Option<Duration>,
+    ) -> usize {
+        let mut acks = self.replication.acks.subscribe();
+        let acked = self.replicas_acked(offset);
+        if acked >= numreplicas {
+            return acked;
+        }
+        self.feed_replicas_command(&Replconf::getack().into_frame());
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let changed = async { acks.changed().await.is_ok() };
+            let changed = match deadline {
+                Some(deadline) => time::timeout_at(deadline, changed).await.unwrap_or(false),
+                None => changed.await,
+            };
+            let acked = self.replicas_acked(offset);
+            if acked >= numreplicas || !changed {
+                return acked;
+            }
+        }
+    }
+
+    /// Makes this server a replica of the server at `host` and `port`,
+    /// returning `false` if it already is.
+    pub(crate) fn replicaof(&self, host: String, port: u16) -> bool {
+        let mut master = self.replication.master.lock().unwrap();
+        if let Some(master) = &*master {
+            if master.host == host && master.port == port {
+                return false;
+            }
+            master.task.abort();
+        }
+
+        let id = self.replication.next_id.fetch_add(1, Ordering::Relaxed);
+        info!(%host, port, "replicating master");
+        let task = tokio::spawn(follow_master(self.clone(), id, host.clone(), port));
+        *master = Some(Master {
+            id,
+            host,
+            port,
+            link: Link::Connecting,
+            offset: 0,
+            task,
+        });
+        true
+    }
+
+    /// Makes this server a master again, keeping its data.
+    pub(crate) fn replicaof_no_one(&self) {
+        if self.read_only() {
+            info!("no longer replicating, now a master");
+        }
+        self.replication.stop();
+    }
+
+    /// Synchronizes with the master at `host` and `port`, then runs the
+    /// commands it sends until the connection is lost. `synced` is the
+    /// history and offset received so far, along with the state of the
+    /// stream at that offset.
+    async fn sync_with_master(
+        &self,
+        id: u64,
+        host: &str,
+        port: u16,
+        synced: &mut Option<(String, u64, Replay)>,
+    ) -> crate::Result<()> {
+        let socket = TcpStream::connect((host, port)).await?;
+        let mut master = Connection::new(socket);
+        let offset = synced.as_ref().map_or(0, |(_, offset, _)| *offset);
+        self.replication.update_link(id, Link::Sync, offset);
+
+        request(&mut master, Ping::new(None).into_frame()).await?;
+        let port = self.replication.port.load(Ordering::Relaxed);
+        request(&mut master, Replconf::listening_port(port).into_frame()).await?;
+        let psync = match synced {
+            Some((history, offset, _)) => Psync::new(history.clone(), *offset as i64 + 1),
+            None => Psync::new("?".to_string(), -1),
+        };
+
+        let reply = request(&mut master, psync.into_frame()).await?;
+        let Frame::Simple(reply) = reply else {
+            return Err(format!("unexpected reply to `PSYNC`: {:?}", reply).into());
+        };
+        let mut words = reply.split(' ');
+        match (words.next(), words.next(), words.next()) {
+            (Some("FULLRESYNC"), Some(history), Some(offset)) => {
+                let offset = offset.parse()?;
+                let snapshot = match master.read_frame().await? {
+                    Some(Frame::Bulk(snapshot)) => Snapshot::decode(&snapshot)?,
+                    frame => return Err(format!("unexpected snapshot: {:?}", frame).into()),
+                };
+                self.load_from_master(snapshot);
+                *synced = Some((history.to_string(), offset, Replay::new(true)));
+                info!(host, port, "synchronized with master");
+            }
+            (Some("CONTINUE"), Some(history), None) => match synced {
+                Some((synced, _, _)) => *synced = history.to_string(),
+                None => return Err("unexpected `CONTINUE` without a previous sync".into()),
+            },
+            _ => return Err(format!("unexpected reply to `PSYNC`: {}", reply).into()),
+        }
+        let (_, offset, replay) = synced.as_mut().unwrap();
+        self.replication.update_link(id, Link::Connected, *offset);
+
+        let mut ack = time::interval(ACK_INTERVAL);
+        loop {
+            select! {
+                res = master.read_frame_len() => {
+                    let Some((frame, len)) = res? else {
+                        return Ok(());
+                    };
+                    let cmd = Command::from_frame(frame)
+                        .map_err(|_| "invalid propagated command")?;
+                    *offset += len as u64;
+                    match cmd {
+                        Command::Replconf(cmd) if cmd.is_getack() => {
+                            ack.reset_immediately();
+                        }
+                        cmd => {
+                            self.propagating(|| replay.apply(self, cmd))?;
+                            self.fsync_aof_if_always().await;
+                        }
+                    }
+                    self.replication.update_link(id, Link::Connected, *offset);
+                }
+                _ = ack.tick() => {
+                    master.write_frame(&Replconf::ack(*offset).into_frame()).await?;
+                }
+            }
+        }
+    }
+
+    /// Replaces the data with the snapshot sent by the master. The stream
+    /// this server propagates starts a new history, so that its own replicas
+    /// start over too.
+    fn load_from_master(&self, snapshot: Snapshot) {
+        self.between_commands(|| {
+            self.flush_all(false);
+            self.restore(snapshot);
+
+            let mut backlog = self.replication.backlog.lock().unwrap();
+            backlog.id = new_id();
+            backlog.buf.clear();
+        });
+        self.replication.offset.send_modify(|_| {});
+
+        if self.aof.is_enabled() {
+            if let Err(err) = self.bgrewriteaof() {
+                warn!(cause = %err, "failed to rewrite the append only file after a sync");
+            }
+        }
+    }
+
+    /// Replies to `ROLE`.
+    pub(crate) fn role(&self) -> Frame {
+        let mut frame = Frame::array();
+        if let Some(master) = &*self.replication.master.lock().unwrap() {
+            let link = match master.link {
+                Link::Connecting => "connecting",
+                Link::Sync => "sync",
+                Link::Connected => "connected",
+            };
+            frame.push_bulk(Bytes::from_static(b"slave"));
+            frame.push_bulk(Bytes::from(master.host.clone()));
+            frame.push_int(master.port as i64);
+            frame.push_bulk(Bytes::from_static(link.as_bytes()));
+            frame.push_int(master.offset as i64);
+            return frame;
+        }
+
+        let offset = self.replication.backlog.lock().unwrap().offset;
+        let replicas = self
+            .replication
+            .replicas
+            .lock()
+            .unwrap()
+            .values()
+            .map(|replica| {
+                Frame::Array(vec![
+                    Frame::Bulk(Bytes::from(replica.ip.to_string())),
+                    Frame::Bulk(Bytes::from(replica.port.to_string())),
+                    Frame::Bulk(Bytes::from(replica.ack.to_string())),
+                ])
+            })
+            .collect();
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from_static(b"master")),
+            Frame::Integer(offset as i64),
+            Frame::Array(replicas),
+        ])
+    }
+
+    /// The `replication` section of `INFO`.
+    pub(crate) fn replication_info(&self) -> String {
+        let mut info = String::from("# Replication\r\n");
+        let (history, offset, start, len, capacity) = {
+            let backlog = self.replication.backlog.lock().unwrap();
+            let len = backlog.buf.len() as u64;
+            let id = backlog.id.clone();
+            (
+                id,
+                backlog.offset,
+                backlog.offset - len,
+                len,
+                backlog.capacity,
+            )
+        };
+
+        match &*self.replication.master.lock().unwrap() {
+            Some(master) => {
+                let up = master.link == Link::Connected;
+                info.push_str("role:slave\r\n");
+                info.push_str(&format!("master_host:{}\r\n", master.host));
+                info.push_str(&format!("master_port:{}\r\n", master.port));
+                info.push_str(&format!(
+                    "master_link_status:{}\r\n",
+                    if up { "up" } else { "down" }
+                ));
+                info.push_str(&format!(
+                    "master_sync_in_progress:{}\r\n",
+                    (master.link == Link::Sync) as u8
+                ));
+                info.push_str(&format!("slave_repl_offset:{}\r\n", master.offset));
+                info.push_str("slave_read_only:1\r\n");
+            }
+            None => info.push_str("role:master\r\n"),
+        }
+
+        let replicas = self.replication.replicas.lock().unwrap();
+        info.push_str(&format!("connected_slaves:{}\r\n", replicas.len()));
+        for (i, replica) in replicas.values().enumerate() {
+            info.push_str(&format!(
+                "slave{}:ip={},port={},state=online,offset={},lag={}\r\n",
+                i,
+                replica.ip,
+                replica.port,
+                replica.ack,
+                replica.last_ack.elapsed().as_secs()
+            ));
+        }
+        drop(replicas);
+
+        info.push_str(&format!("master_replid:{}\r\n", history));
+        info.push_str(&format!("master_repl_offset:{}\r\n", offset));
+        info.push_str(&format!("repl_backlog_size:{}\r\n", capacity));
+        info.push_str(&format!("repl_backlog_first_byte_offset:{}\r\n", start + 1));
+        info.push_str(&format!("repl_backlog_histlen:{}\r\n", len));
+        info
+    }
+
+    /// The `stats` section of `INFO`, for now only about replication.
+    pub(crate) fn stats_info(&self) -> String {
+        let mut info = String::from("# Stats\r\n");
+        let full = self.replication.full_syncs.load(Ordering::Relaxed);
+        let partial = self.replication.partial_syncs.load(Ordering::Relaxed);
+        info.push_str(&format!("sync_full:{}\r\n", full));
+        info.push_str(&format!("sync_partial_ok:{}\r\n", partial));
+        info
+    }
+}
+
+/// Replicates the master at `host` and `port`, reconnecting whenever the
+/// connection is lost, until aborted.
+async fn follow_master(databases: Databases, id: u64, host: String, port: u16) {
+    let mut synced = None;
+    loop {
+        match databases
+            .sync_with_master(id, &host, port, &mut synced)
+            .await
+        {
+            Ok(()) => warn!(%host, port, "master closed the connection"),
+            Err(err) => warn!(%host, port, cause = %err, "lost the connection to the master"),
+        }
+        let offset = synced.as_ref().map_or(0, |(_, offset, _)| *offset);
+        databases
+            .replication
+            .update_link(id, Link::Connecting, offset);
+        time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+/// Sends `frame` to the master, failing on an error reply.
+async fn request(master: &mut Connection, frame: Frame) -> crate::Result<Frame> {
+    master.write_frame(&frame).await?;
+    match master.read_frame().await? {
+        Some(Frame::Error(err)) => Err(err.into()),
+        Some(frame) => Ok(frame),
+        None => Err("connection reset by master".into()),
+    }
+}
+
+/// A new history ID, 40 hexadecimal characters like in Redis.
+fn new_id() -> String {
+    static COUNT: AtomicU64 = AtomicU64::new(0);
+    let seed = format!(
+        "{:?}-{}-{}",
+        SystemTime::now(),
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    sha1_hex(seed.as_bytes())
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+};
+
+use bytes::Bytes;
+
+use crate::glob::glob_match;
+
+/// The `COUNT`, `MATCH` and `TYPE` options of `SCAN` and its variants.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub(crate) count: Option<usize>,
+    pub(crate) pattern: Option<Bytes>,
+    pub(crate) key_type: Option<String>,
+}
+
+/// Like Redis, pages hold about 10 elements unless `COUNT` says otherwise.
+const DEFAULT_COUNT: usize = 10;
+
+impl ScanOptions {
+    pub fn new() -> ScanOptions {
+        ScanOptions::default()
+    }
+
+    /// Hint at how many elements each call should return.
+    pub fn count(mut self, count: usize) -> ScanOptions {
+        self.count = Some(count);
+        self
+    }
+
+    /// Only return elements matching the glob `pattern`.
+    pub fn pattern(mut self, pattern: impl Into<Bytes>) -> ScanOptions {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    /// `SCAN` only, only return keys holding the type named `key_type`.
+    pub fn key_type(mut self, key_type: impl ToString) -> ScanOptions {
+        self.key_type = Some(key_type.to_string());
+        self
+    }
+
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        match self.count {
+            Some(0) => Err("ERR syntax error".into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn matches(&self, element: &[u8]) -> bool {
+        match &self.pattern {
+            Some(pattern) => glob_match(pattern, element),
+            None => true,
+        }
+    }
+}
+
+/// Picks the page of a scan starting at `cursor`, returning it along with the
+/// cursor of the next page, `0` once the scan is complete.
+///
+/// There is no state kept between calls. Instead, elements are visited in the
+/// order of a fixed hash of their name and the cursor is the hash to resume
+/// from. Elements present for the whole scan are returned exactly once no
+/// matter how the collection changes in between, at the cost of a walk over
+/// the whole collection for every page.
+pub(crate) fn scan_page<'a, T>(
+    elements: impl Iterator<Item = (&'a [u8], T)>,
+    cursor: u64,
+    options: &ScanOptions,
+) -> (u64, Vec<T>) {
+    let count = options.count.unwrap_or(DEFAULT_COUNT);
+    // The `count` elements with the lowest hashes from `cursor` on. Elements
+    // sharing a hash are never split across pages.
+    let mut page: BTreeMap<u64, Vec<T>> = BTreeMap::new();
+    let mut len = 0;
+    let mut more = false;
+
+    for (name, element) in elements {
+        let hash = position(name);
+        if hash < cursor {
+            continue;
+        }
+        if len >= count && page.last_key_value().is_some_and(|(&last, _)| hash > last) {
+            more = true;
+            continue;
+        }
+
+        page.entry(hash).or_default().push(element);
+        len += 1;
+        while let Some(last) = page.last_entry() {
+            if len - last.get().len() < count {
+                break;
+            }
+            len -= last.remove().len();
+            more = true;
+        }
+    }
+
+    let next = match page.last_key_value() {
+        Some((&last, _)) if more => last.checked_add(1).unwrap_or(0),
+        _ => 0,
+    };
+    (next, page.into_values().flatten().collect())
+}
+
+/// Where `name` falls in the order of a scan. The hasher is created with
+/// fixed keys, so the order is stable for the life of the server.
+fn position(name: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish()
+}
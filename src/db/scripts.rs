@@ -0,0 +1,185 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::{
+    sync::watch,
+    time::{self, Instant},
+};
+
+const BUSY: &str =
+    "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE.";
+
+/// The Lua scripts known to `EVALSHA`, by the SHA1 digest of their body.
+/// Shared by every connection, and not affected by `FLUSHALL` or `SWAPDB`.
+///
+/// Also tracks the script running, if any, which the other clients wait
+/// for, until it runs for longer than the time limit. They are then told
+/// the server is busy, and may kill the script with `SCRIPT KILL`.
+#[derive(Debug, Clone)]
+pub(crate) struct ScriptCache {
+    scripts: Arc<Mutex<HashMap<String, Bytes>>>,
+    running: Arc<watch::Sender<Option<Running>>>,
+    next_id: Arc<AtomicU64>,
+    time_limit: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Running {
+    /// Tells apart the scripts marked as running, see `RunningGuard`.
+    id: u64,
+    /// When the script exceeds the time limit.
+    deadline: Instant,
+    /// Whether the script ran a command that changes keys, which makes it
+    /// unkillable as it would be left half done.
+    wrote: bool,
+    killed: bool,
+}
+
+/// Marks a script as running, see `ScriptCache::start`. Dropping it only
+/// unmarks the script it marked, not one marked since.
+pub(crate) struct RunningGuard<'a> {
+    scripts: &'a ScriptCache,
+    id: u64,
+}
+
+impl ScriptCache {
+    pub(crate) fn new(time_limit: Duration) -> ScriptCache {
+        ScriptCache {
+            scripts: Arc::default(),
+            running: Arc::new(watch::Sender::new(None)),
+            next_id: Arc::default(),
+            time_limit,
+        }
+    }
+
+    /// Caches `body`, returning its digest.
+    pub(crate) fn load(&self, body: Bytes) -> String {
+        let sha = sha1_hex(&body);
+        self.scripts.lock().unwrap().insert(sha.clone(), body);
+        sha
+    }
+
+    pub(crate) fn get(&self, sha: &str) -> Option<Bytes> {
+        let scripts = self.scripts.lock().unwrap();
+        scripts.get(&sha.to_ascii_lowercase()).cloned()
+    }
+
+    pub(crate) fn exists(&self, sha: &str) -> bool {
+        let scripts = self.scripts.lock().unwrap();
+        scripts.contains_key(&sha.to_ascii_lowercase())
+    }
+
+    pub(crate) fn flush(&self) {
+        self.scripts.lock().unwrap().clear();
+    }
+
+    /// Marks a script as about to run, before it waits for the databases,
+    /// so that other clients wait for it rather than for the databases.
+    /// Does nothing if a script is marked as running already.
+    pub(crate) fn announce(&self) -> RunningGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.running.send_if_modified(|running| {
+            if running.is_some() {
+                return false;
+            }
+            *running = Some(self.running_script(id));
+            true
+        });
+        RunningGuard { scripts: self, id }
+    }
+
+    /// Marks a script as running until the returned guard is dropped, in
+    /// place of one that was only announced.
+    pub(crate) fn start(&self) -> RunningGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.running.send_replace(Some(self.running_script(id)));
+        RunningGuard { scripts: self, id }
+    }
+
+    fn running_script(&self, id: u64) -> Running {
+        Running {
+            id,
+            deadline: Instant::now() + self.time_limit,
+            wrote: false,
+            killed: false,
+        }
+    }
+
+    /// Records that the running script changed keys.
+    pub(crate) fn wrote(&self) {
+        self.running.send_modify(|running| {
+            if let Some(running) = running {
+                running.wrote = true;
+            }
+        });
+    }
+
+    /// Whether the running script was killed, and has to stop.
+    pub(crate) fn killed(&self) -> bool {
+        self.running.borrow().is_some_and(|running| running.killed)
+    }
+
+    /// Kills the running script, unless it changed keys.
+    pub(crate) fn kill(&self) -> crate::Result<()> {
+        let mut res = Err("NOTBUSY No scripts in execution right now.".into());
+        self.running.send_if_modified(|running| match running {
+            Some(running) if running.wrote => {
+                res = Err(
+                    "UNKILLABLE Sorry the script already executed write commands \
+                    against the dataset. You can either wait the script termination or kill \
+                    the server in a hard way using the SHUTDOWN NOSAVE command."
+                        .into(),
+                );
+                false
+            }
+            Some(running) => {
+                running.killed = true;
+                res = Ok(());
+                true
+            }
+            None => false,
+        });
+        res
+    }
+
+    /// Waits for the running script, if any, to return, or fails with a
+    /// `BUSY` error once it runs for longer than the time limit.
+    pub(crate) async fn wait_for_script(&self) -> crate::Result<()> {
+        let mut running = self.running.subscribe();
+        loop {
+            let deadline = match *running.borrow_and_update() {
+                None => return Ok(()),
+                Some(running) if running.deadline <= Instant::now() => return Err(BUSY.into()),
+                Some(running) => running.deadline,
+            };
+            tokio::select! {
+                _ = running.changed() => {}
+                _ = time::sleep_until(deadline) => {}
+            }
+        }
+    }
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.scripts.running.send_if_modified(|running| {
+            if running.is_some_and(|running| running.id == self.id) {
+                *running = None;
+                return true;
+            }
+            false
+        });
+    }
+}
+
+/// The lowercase hex SHA1 digest of `data`, which names scripts.
+pub(crate) fn sha1_hex(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
@@ -0,0 +1,548 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::time::{Duration, Instant};
+use tracing::{error, info};
+
+use super::{block_in_place, instant_at, unix_ms_at, Databases, SortedSet, Stream, Value};
+
+/// Snapshot files start with this, followed by the format version.
+const MAGIC: &[u8] = b"MINIREDIS";
+const VERSION: u16 = 1;
+
+/// Opcodes of the records following the header.
+const OP_SELECT_DB: u8 = 0xfe;
+const OP_EOF: u8 = 0xff;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_HASH: u8 = 2;
+const TYPE_SET: u8 = 3;
+const TYPE_ZSET: u8 = 4;
+const TYPE_STREAM: u8 = 5;
+
+const BAD_FORMAT: &str = "ERR Bad snapshot format";
+
+/// The contents of every database at a point in time, with expirations as
+/// Unix times in milliseconds so that they survive a restart.
+#[derive(Debug, Default)]
+pub(crate) struct Snapshot {
+    pub(super) dbs: Vec<Vec<SnapshotEntry>>,
+}
+
+/// A key with its value and expiration, if any.
+pub(super) type SnapshotEntry = (String, Arc<Value>, Option<u64>);
+
+/// Where and when `SAVE`, `BGSAVE` and the save rules write snapshots.
+#[derive(Debug)]
+pub(crate) struct Snapshots {
+    path: Option<PathBuf>,
+    /// Save once at least `.1` changes were made `.0` after the last save.
+    rules: Vec<(Duration, u64)>,
+    progress: Mutex<Progress>,
+}
+
+#[derive(Debug)]
+struct Progress {
+    /// Unix time in seconds of the last successful save, or of startup.
+    last_save: u64,
+    /// `Databases::dirty` as of the last successful save.
+    dirty_at_save: u64,
+    in_progress: bool,
+}
+
+impl Snapshot {
+    /// Serializes the snapshot, followed by the SHA1 digest of its contents.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut dst = BytesMut::new();
+        dst.put_slice(MAGIC);
+        dst.put_u16(VERSION);
+
+        for (index, entries) in self.dbs.iter().enumerate() {
+            if entries.is_empty() {
+                continue;
+            }
+            dst.put_u8(OP_SELECT_DB);
+            dst.put_u32(index as u32);
+
+            for (key, value, expires_at) in entries {
+                put_value_type(&mut dst, value);
+                match expires_at {
+                    Some(unix_ms) => {
+                        dst.put_u8(1);
+                        dst.put_u64(*unix_ms);
+                    }
+                    None => dst.put_u8(0),
+                }
+                put_bytes(&mut dst, key.as_bytes());
+                put_value(&mut dst, value);
+            }
+        }
+        dst.put_u8(OP_EOF);
+
+        let digest = sha1_smol::Sha1::from(&dst[..]).digest().bytes();
+        dst.put_slice(&digest);
+        dst.to_vec()
+    }
+
+    pub(crate) fn decode(src: &[u8]) -> crate::Result<Snapshot> {
+        let (snapshot, rest) = Snapshot::decode_prefix(src)?;
+        if !rest.is_empty() {
+            return Err(BAD_FORMAT.into());
+        }
+        Ok(snapshot)
+    }
+
+    /// Whether `src` starts with a snapshot, like rewritten append only files
+    /// do.
+    pub(crate) fn is_prefix_of(src: &[u8]) -> bool {
+        src.starts_with(MAGIC)
+    }
+
+    /// Decodes the snapshot at the start of `src`, returning what follows it.
+    pub(crate) fn decode_prefix(src: &[u8]) -> crate::Result<(Snapshot, &[u8])> {
+        let start = src;
+        let mut src = src;
+        if !src.starts_with(MAGIC) {
+            return Err(BAD_FORMAT.into());
+        }
+        src.advance(MAGIC.len());
+        let version = get_u16(&mut src)?;
+        if version != VERSION {
+            return Err(format!("ERR Unsupported snapshot version {}", version).into());
+        }
+
+        let mut snapshot = Snapshot::default();
+        let mut db = None;
+        loop {
+            match get_u8(&mut src)? {
+                OP_EOF => break,
+                OP_SELECT_DB => {
+                    let index = get_u32(&mut src)? as usize;
+                    if snapshot.dbs.len() <= index {
+                        snapshot.dbs.resize_with(index + 1, Vec::new);
+                    }
+                    db = Some(index);
+                }
+                value_type => {
+                    let index = db.ok_or(BAD_FORMAT)?;
+                    let expires_at = match get_u8(&mut src)? {
+                        0 => None,
+                        1 => Some(get_u64(&mut src)?),
+                        _ => return Err(BAD_FORMAT.into()),
+                    };
+                    let key = get_string(&mut src)?;
+                    let value = get_value(&mut src, value_type)?;
+                    snapshot.dbs[index].push((key, Arc::new(value), expires_at));
+                }
+            }
+        }
+
+        let contents = &start[..start.len() - src.len()];
+        let (digest, rest) = src.split_first_chunk::<20>().ok_or(BAD_FORMAT)?;
+        if sha1_smol::Sha1::from(contents).digest().bytes() != *digest {
+            return Err("ERR Snapshot checksum mismatch".into());
+        }
+        Ok((snapshot, rest))
+    }
+
+    /// Writes the snapshot to `path` atomically: it is written to a
+    /// temporary file first, which then replaces `path`.
+    pub(crate) fn write(&self, path: &Path) -> io::Result<()> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = path.with_file_name(format!("temp-{}-{}", std::process::id(), name));
+        let res = (|| {
+            let mut file = fs::File::create(&tmp)?;
+            file.write_all(&self.encode())?;
+            file.sync_all()?;
+            fs::rename(&tmp, path)
+        })();
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        res
+    }
+
+    /// Reads the snapshot at `path`, or `None` if there is none.
+    pub(crate) fn read(path: &Path) -> crate::Result<Option<Snapshot>> {
+        match fs::read(path) {
+            Ok(src) => Ok(Some(Snapshot::decode(&src)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl Snapshots {
+    pub(crate) fn new(path: Option<PathBuf>, rules: Vec<(Duration, u64)>) -> Snapshots {
+        Snapshots {
+            path,
+            rules,
+            progress: Mutex::new(Progress {
+                last_save: unix_time(),
+                dirty_at_save: 0,
+                in_progress: false,
+            }),
+        }
+    }
+
+    fn path(&self) -> crate::Result<&Path> {
+        self.path
+            .as_deref()
+            .ok_or_else(|| "ERR Snapshots are disabled, no snapshot file is configured".into())
+    }
+
+    /// Records `dirty` after loading data, which is not a change that needs
+    /// saving.
+    pub(super) fn loaded(&self, dirty: u64) {
+        self.progress.lock().unwrap().dirty_at_save = dirty;
+    }
+
+    /// Marks a save as started, failing if one is already running.
+    fn start(&self) -> crate::Result<()> {
+        let mut progress = self.progress.lock().unwrap();
+        if progress.in_progress {
+            return Err("ERR Background save already in progress".into());
+        }
+        progress.in_progress = true;
+        Ok(())
+    }
+
+    /// Marks the save started by `start` as done, recording `dirty` as of
+    /// when its snapshot was taken if it succeeded.
+    fn finish(&self, dirty: u64, res: &io::Result<()>) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.in_progress = false;
+        if res.is_ok() {
+            progress.last_save = unix_time();
+            progress.dirty_at_save = dirty;
+        }
+    }
+}
+
+impl Databases {
+    /// Loads the configured snapshot, if there is one, before the server
+    /// accepts connections. Keys that expired in the meantime are dropped.
+    pub(crate) fn load_snapshot(&self) -> crate::Result<()> {
+        let Some(path) = self.snapshots.path.as_deref() else {
+            return Ok(());
+        };
+        if let Some(snapshot) = Snapshot::read(path)? {
+            let keys: usize = snapshot.dbs.iter().map(Vec::len).sum();
+            self.restore(snapshot);
+            self.snapshots.loaded(self.dirty());
+            info!(path = %path.display(), keys, "loaded snapshot");
+        }
+        Ok(())
+    }
+
+    /// Writes a snapshot, returning once it is on disk. Encoding and
+    /// writing it block, so they run off the runtime.
+    pub(crate) async fn save(&self) -> crate::Result<()> {
+        let path = self.snapshots.path()?.to_path_buf();
+        self.snapshots.start()?;
+        let (snapshot, dirty) = self.snapshot();
+        let res = write_blocking(snapshot, path).await;
+        self.snapshots.finish(dirty, &res);
+        Ok(res?)
+    }
+
+    /// Like `save`, for callers that can't await, such as a transaction.
+    /// Other tasks are moved off the worker while the snapshot is written.
+    pub(crate) fn save_in_place(&self) -> crate::Result<()> {
+        let path = self.snapshots.path()?;
+        self.snapshots.start()?;
+        let (snapshot, dirty) = self.snapshot();
+        let res = block_in_place(|| snapshot.write(path));
+        self.snapshots.finish(dirty, &res);
+        Ok(res?)
+    }
+
+    /// Writes a snapshot in the background. The snapshot itself is taken
+    /// right away, so it holds the data as of this call.
+    pub(crate) fn bgsave(&self) -> crate::Result<()> {
+        let path = self.snapshots.path()?.to_path_buf();
+        self.snapshots.start()?;
+        let (snapshot, dirty) = self.snapshot();
+        let snapshots = Arc::clone(&self.snapshots);
+
+        tokio::spawn(async move {
+            let res = write_blocking(snapshot, path).await;
+            match &res {
+                Ok(()) => info!("background saving terminated with success"),
+                Err(err) => error!(cause = %err, "background saving failed"),
+            }
+            snapshots.finish(dirty, &res);
+        });
+        Ok(())
+    }
+
+    /// Unix time in seconds of the last successful save.
+    pub(crate) fn last_save(&self) -> u64 {
+        self.snapshots.progress.lock().unwrap().last_save
+    }
+
+    /// Starts a background save if one of the save rules says it is due.
+    pub(crate) fn save_if_due(&self) {
+        if self.snapshots.path.is_none() {
+            return;
+        }
+        let (last_save, changes) = {
+            let progress = self.snapshots.progress.lock().unwrap();
+            if progress.in_progress {
+                return;
+            }
+            (progress.last_save, self.dirty() - progress.dirty_at_save)
+        };
+
+        let elapsed = Duration::from_secs(unix_time().saturating_sub(last_save));
+        let due = self
+            .snapshots
+            .rules
+            .iter()
+            .any(|&(after, min_changes)| changes >= min_changes && elapsed >= after);
+        if due {
+            info!(changes, "saving");
+            let _ = self.bgsave();
+        }
+    }
+
+    /// Saves before shutting down when save rules are configured and there
+    /// is something new to save, like Redis does.
+    pub(crate) async fn save_on_shutdown(&self) {
+        if self.snapshots.path.is_none() || self.snapshots.rules.is_empty() {
+            return;
+        }
+        let dirty_at_save = self.snapshots.progress.lock().unwrap().dirty_at_save;
+        if self.dirty() == dirty_at_save {
+            return;
+        }
+        match self.save().await {
+            Ok(()) => info!("saved on shutdown"),
+            Err(err) => error!(cause = %err, "failed to save on shutdown"),
+        }
+    }
+
+    /// Copies the contents of every database at a single point in time,
+    /// along with `Databases::dirty` at that time. The values are shared
+    /// rather than copied, so the locks are only held to copy the keys.
+    pub(super) fn snapshot(&self) -> (Snapshot, u64) {
+        // Always in index order, like `lock_pair`.
+        let states: Vec<_> = self.dbs.iter().map(|db| db.shared.lock()).collect();
+        let now = Instant::now();
+
+        let dbs = states
+            .iter()
+            .map(|state| {
+                state
+                    .entries
+                    .iter()
+                    .filter(|(_, entry)| entry.expires_at.is_none_or(|when| when > now))
+                    .map(|(key, entry)| {
+                        let expires_at = entry.expires_at.map(|when| unix_ms_at(when, now));
+                        (key.clone(), Arc::clone(&entry.data), expires_at)
+                    })
+                    .collect()
+            })
+            .collect();
+        let dirty = states.iter().map(|state| state.dirty).sum();
+        (Snapshot { dbs }, dirty)
+    }
+
+    /// Adds the entries of `snapshot` to the databases, dropping those that
+    /// expired, unless loading, and those of databases that no longer exist.
+    pub(super) fn restore(&self, snapshot: Snapshot) {
+        let now = Instant::now();
+        for (index, entries) in snapshot.dbs.into_iter().enumerate() {
+            let Some(db) = self.dbs.get(index) else {
+                error!(
+                    index,
+                    "snapshot has more databases than configured, skipping"
+                );
+                continue;
+            };
+
+            let mut state = db.shared.lock();
+            for (key, value, expires_at) in entries {
+                let expires_at = match expires_at {
+                    Some(unix_ms) => match instant_at(unix_ms, now) {
+                        Some(when) if when <= now && !state.loading => continue,
+                        when => when,
+                    },
+                    None => None,
+                };
+                state.insert_entry(key, value, expires_at);
+            }
+            drop(state);
+            db.shared.background_task.notify_one();
+        }
+    }
+}
+
+/// Writes `snapshot` to `path` on a blocking thread.
+async fn write_blocking(snapshot: Snapshot, path: PathBuf) -> io::Result<()> {
+    tokio::task::spawn_blocking(move || snapshot.write(&path))
+        .await
+        .unwrap_or_else(|err| Err(io::Error::other(err)))
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0)
+}
+
+fn put_value_type(dst: &mut BytesMut, value: &Value) {
+    dst.put_u8(match value {
+        Value::String(_) => TYPE_STRING,
+        Value::List(_) => TYPE_LIST,
+        Value::Hash(_) => TYPE_HASH,
+        Value::Set(_) => TYPE_SET,
+        Value::ZSet(_) => TYPE_ZSET,
+        Value::Stream(_) => TYPE_STREAM,
+    });
+}
+
+fn put_value(dst: &mut BytesMut, value: &Value) {
+    match value {
+        Value::String(value) => put_bytes(dst, value),
+        Value::List(list) => {
+            dst.put_u64(list.len() as u64);
+            for item in list {
+                put_bytes(dst, item);
+            }
+        }
+        Value::Hash(hash) => {
+            dst.put_u64(hash.len() as u64);
+            for (field, value) in hash {
+                put_bytes(dst, field.as_bytes());
+                put_bytes(dst, value);
+            }
+        }
+        Value::Set(set) => {
+            dst.put_u64(set.len() as u64);
+            for member in set {
+                put_bytes(dst, member);
+            }
+        }
+        Value::ZSet(zset) => {
+            dst.put_u64(zset.len() as u64);
+            for (member, score) in zset.iter() {
+                put_bytes(dst, member);
+                dst.put_f64(score);
+            }
+        }
+        Value::Stream(stream) => stream.encode(dst),
+    }
+}
+
+fn get_value(src: &mut &[u8], value_type: u8) -> crate::Result<Value> {
+    Ok(match value_type {
+        TYPE_STRING => Value::String(get_bytes(src)?),
+        TYPE_LIST => {
+            let len = get_len(src)?;
+            let mut list = VecDeque::with_capacity(len);
+            for _ in 0..len {
+                list.push_back(get_bytes(src)?);
+            }
+            Value::List(list)
+        }
+        TYPE_HASH => {
+            let len = get_len(src)?;
+            let mut hash = HashMap::with_capacity(len);
+            for _ in 0..len {
+                hash.insert(get_string(src)?, get_bytes(src)?);
+            }
+            Value::Hash(hash)
+        }
+        TYPE_SET => {
+            let len = get_len(src)?;
+            let mut set = HashSet::with_capacity(len);
+            for _ in 0..len {
+                set.insert(get_bytes(src)?);
+            }
+            Value::Set(set)
+        }
+        TYPE_ZSET => {
+            let mut zset = SortedSet::default();
+            for _ in 0..get_len(src)? {
+                let member = get_bytes(src)?;
+                let score = get_f64(src)?;
+                if score.is_nan() {
+                    return Err(BAD_FORMAT.into());
+                }
+                zset.insert(member, score);
+            }
+            Value::ZSet(zset)
+        }
+        TYPE_STREAM => Value::Stream(Stream::decode(src)?),
+        _ => return Err(BAD_FORMAT.into()),
+    })
+}
+
+pub(super) fn put_bytes(dst: &mut BytesMut, src: &[u8]) {
+    dst.put_u64(src.len() as u64);
+    dst.put_slice(src);
+}
+
+pub(super) fn get_u8(src: &mut &[u8]) -> crate::Result<u8> {
+    if src.remaining() < 1 {
+        return Err(BAD_FORMAT.into());
+    }
+    Ok(src.get_u8())
+}
+
+fn get_u16(src: &mut &[u8]) -> crate::Result<u16> {
+    if src.remaining() < 2 {
+        return Err(BAD_FORMAT.into());
+    }
+    Ok(src.get_u16())
+}
+
+fn get_u32(src: &mut &[u8]) -> crate::Result<u32> {
+    if src.remaining() < 4 {
+        return Err(BAD_FORMAT.into());
+    }
+    Ok(src.get_u32())
+}
+
+pub(super) fn get_u64(src: &mut &[u8]) -> crate::Result<u64> {
+    if src.remaining() < 8 {
+        return Err(BAD_FORMAT.into());
+    }
+    Ok(src.get_u64())
+}
+
+fn get_f64(src: &mut &[u8]) -> crate::Result<f64> {
+    if src.remaining() < 8 {
+        return Err(BAD_FORMAT.into());
+    }
+    Ok(src.get_f64())
+}
+
+/// Reads the length of a collection, which can't be more than the bytes
+/// left since every element takes at least one.
+pub(super) fn get_len(src: &mut &[u8]) -> crate::Result<usize> {
+    let len = get_u64(src)?;
+    if len > src.remaining() as u64 {
+        return Err(BAD_FORMAT.into());
+    }
+    Ok(len as usize)
+}
+
+pub(super) fn get_bytes(src: &mut &[u8]) -> crate::Result<Bytes> {
+    let len = get_len(src)?;
+    Ok(src.copy_to_bytes(len))
+}
+
+pub(super) fn get_string(src: &mut &[u8]) -> crate::Result<String> {
+    Ok(String::from_utf8(get_bytes(src)?.to_vec()).map_err(|_| BAD_FORMAT)?)
+}
@@ -0,0 +1,589 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::time::{Duration, Instant};
+
+use super::snapshot::{get_bytes, get_len, get_string, get_u64, put_bytes};
+
+/// A stream entry ID, `<milliseconds>-<sequence>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct StreamId {
+    pub(crate) ms: u64,
+    pub(crate) seq: u64,
+}
+
+pub(crate) type Fields = Vec<(Bytes, Bytes)>;
+
+/// Records delivered to a consumer. Records that were deleted while still
+/// pending are reported without their fields.
+pub(crate) type Delivered = Vec<(StreamId, Option<Fields>)>;
+
+/// What `XCLAIM` and `XAUTOCLAIM` did to the PEL.
+#[derive(Debug, Default)]
+pub(crate) struct Claimed {
+    pub(crate) records: Vec<(StreamId, Fields)>,
+    /// The IDs of deleted records, dropped from the PEL instead.
+    pub(crate) deleted: Vec<StreamId>,
+    /// The PEL entries of the claimed records as they were left, and of the
+    /// deleted ones as they were, in the order they were visited.
+    pub(crate) changes: Vec<(StreamId, Pending)>,
+}
+
+/// The cursor `XAUTOCLAIM` continues from, and what it claimed.
+pub(crate) type AutoClaimed = (StreamId, Claimed);
+
+/// How `XCLAIM` updates the PEL entries of the records it claims.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ClaimOptions {
+    /// When the records count as delivered, now unless set with `IDLE` or
+    /// `TIME`.
+    pub(crate) delivered_at: Option<Instant>,
+    /// The delivery count set with `RETRYCOUNT`, otherwise incremented
+    /// unless `justid` is set.
+    pub(crate) deliveries: Option<u64>,
+    /// Whether records missing from the PEL are added to it.
+    pub(crate) force: bool,
+    pub(crate) justid: bool,
+}
+
+/// An append-only log of field/value records keyed by monotonically
+/// increasing IDs, plus the consumer groups reading from it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Stream {
+    records: BTreeMap<StreamId, Fields>,
+    last_id: StreamId,
+    groups: HashMap<String, ConsumerGroup>,
+}
+
+#[derive(Debug, Clone)]
+struct ConsumerGroup {
+    last_delivered: StreamId,
+    /// Delivered but not yet acknowledged records, the group's PEL.
+    pending: BTreeMap<StreamId, Pending>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Pending {
+    pub(crate) consumer: String,
+    pub(crate) delivered_at: Instant,
+    pub(crate) deliveries: u64,
+}
+
+/// How `XADD` picks the ID of the new record.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum XaddId {
+    /// `*`
+    Auto,
+    /// `<ms>-*`
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+/// Where `XREADGROUP` starts reading for a stream.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ReadFrom {
+    /// `>`, records never delivered to any consumer of the group.
+    New,
+    /// The consumer's own pending records after the given ID.
+    Pending(StreamId),
+}
+
+/// Outcome of `XPENDING` without a range.
+#[derive(Debug, Default)]
+pub(crate) struct PendingSummary {
+    pub(crate) count: usize,
+    pub(crate) bounds: Option<(StreamId, StreamId)>,
+    pub(crate) consumers: Vec<(String, usize)>,
+}
+
+pub(crate) const NOGROUP: &str = "NOGROUP No such key or consumer group";
+const INVALID_ID: &str = "ERR Invalid stream ID specified as stream command argument";
+
+impl StreamId {
+    fn encode(&self, dst: &mut BytesMut) {
+        dst.put_u64(self.ms);
+        dst.put_u64(self.seq);
+    }
+
+    fn decode(src: &mut &[u8]) -> crate::Result<StreamId> {
+        Ok(StreamId {
+            ms: get_u64(src)?,
+            seq: get_u64(src)?,
+        })
+    }
+
+    pub(crate) const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub(crate) const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    /// Parses `<ms>-<seq>`, or `<ms>` with `default_seq` as the sequence.
+    fn parse_with(src: &str, default_seq: u64) -> crate::Result<StreamId> {
+        let (ms, seq) = match src.split_once('-') {
+            Some((ms, seq)) => (ms, seq.parse().map_err(|_| INVALID_ID)?),
+            None => (src, default_seq),
+        };
+        let ms = ms.parse().map_err(|_| INVALID_ID)?;
+        Ok(StreamId { ms, seq })
+    }
+
+    pub(crate) fn parse(src: &str) -> crate::Result<StreamId> {
+        StreamId::parse_with(src, 0)
+    }
+
+    /// The lower end of an `XRANGE`, accepting `-` and exclusive `(` IDs.
+    pub(crate) fn parse_start(src: &str) -> crate::Result<StreamId> {
+        match src {
+            "-" => Ok(StreamId::MIN),
+            "+" => Ok(StreamId::MAX),
+            _ => match src.strip_prefix('(') {
+                Some(id) => StreamId::parse_with(id, 0)?
+                    .next()
+                    .ok_or_else(|| INVALID_ID.into()),
+                None => StreamId::parse_with(src, 0),
+            },
+        }
+    }
+
+    /// The upper end of an `XRANGE`, accepting `+` and exclusive `(` IDs.
+    pub(crate) fn parse_end(src: &str) -> crate::Result<StreamId> {
+        match src {
+            "-" => Ok(StreamId::MIN),
+            "+" => Ok(StreamId::MAX),
+            _ => match src.strip_prefix('(') {
+                Some(id) => StreamId::parse_with(id, u64::MAX)?
+                    .prev()
+                    .ok_or_else(|| INVALID_ID.into()),
+                None => StreamId::parse_with(src, u64::MAX),
+            },
+        }
+    }
+
+    fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId {
+                ms: self.ms.checked_add(1)?,
+                seq: 0,
+            }),
+        }
+    }
+
+    fn prev(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId {
+                ms: self.ms.checked_sub(1)?,
+                seq: u64::MAX,
+            }),
+        }
+    }
+}
+
+impl Display for StreamId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl XaddId {
+    pub(crate) fn parse(src: &str) -> crate::Result<XaddId> {
+        if src == "*" {
+            return Ok(XaddId::Auto);
+        }
+        match src.strip_suffix("-*") {
+            Some(ms) => Ok(XaddId::AutoSeq(ms.parse().map_err(|_| INVALID_ID)?)),
+            None => Ok(XaddId::Explicit(StreamId::parse(src)?)),
+        }
+    }
+}
+
+impl Stream {
+    /// Serializes the stream with its consumer groups for a snapshot.
+    pub(super) fn encode(&self, dst: &mut BytesMut) {
+        let now = Instant::now();
+        dst.put_u64(self.records.len() as u64);
+        for (id, fields) in &self.records {
+            id.encode(dst);
+            dst.put_u64(fields.len() as u64);
+            for (field, value) in fields {
+                put_bytes(dst, field);
+                put_bytes(dst, value);
+            }
+        }
+        self.last_id.encode(dst);
+
+        dst.put_u64(self.groups.len() as u64);
+        for (name, group) in &self.groups {
+            put_bytes(dst, name.as_bytes());
+            group.last_delivered.encode(dst);
+            dst.put_u64(group.pending.len() as u64);
+            for (id, pending) in &group.pending {
+                id.encode(dst);
+                put_bytes(dst, pending.consumer.as_bytes());
+                dst.put_u64(super::unix_ms_at(pending.delivered_at, now));
+                dst.put_u64(pending.deliveries);
+            }
+        }
+    }
+
+    /// The inverse of `Stream::encode`.
+    pub(super) fn decode(src: &mut &[u8]) -> crate::Result<Stream> {
+        let now = Instant::now();
+        let mut stream = Stream::default();
+        for _ in 0..get_len(src)? {
+            let id = StreamId::decode(src)?;
+            let len = get_len(src)?;
+            let mut fields = Vec::with_capacity(len);
+            for _ in 0..len {
+                fields.push((get_bytes(src)?, get_bytes(src)?));
+            }
+            stream.records.insert(id, fields);
+        }
+        stream.last_id = StreamId::decode(src)?;
+
+        for _ in 0..get_len(src)? {
+            let name = get_string(src)?;
+            let last_delivered = StreamId::decode(src)?;
+            let mut pending = BTreeMap::new();
+            for _ in 0..get_len(src)? {
+                let id = StreamId::decode(src)?;
+                let consumer = get_string(src)?;
+                let delivered_at = super::instant_at(get_u64(src)?, now).unwrap_or(now);
+                let deliveries = get_u64(src)?;
+                pending.insert(
+                    id,
+                    Pending {
+                        consumer,
+                        delivered_at,
+                        deliveries,
+                    },
+                );
+            }
+            stream.groups.insert(
+                name,
+                ConsumerGroup {
+                    last_delivered,
+                    pending,
+                },
+            );
+        }
+        Ok(stream)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub(crate) fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    /// Appends a record, failing when the ID would not be greater than the
+    /// last one ever added.
+    pub(crate) fn add(&mut self, id: XaddId, fields: Fields) -> crate::Result<StreamId> {
+        let last = self.last_id;
+        let id = match id {
+            XaddId::Auto => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|now| now.as_millis() as u64)
+                    .unwrap_or(0);
+                if now > last.ms {
+                    StreamId { ms: now, seq: 0 }
+                } else {
+                    last.next()
+                        .ok_or("ERR The stream has exhausted the last possible ID")?
+                }
+            }
+            XaddId::AutoSeq(ms) if ms == last.ms => last.next().filter(|id| id.ms == ms).ok_or(
+                "ERR The ID specified in XADD is equal or smaller than the target stream top item",
+            )?,
+            XaddId::AutoSeq(ms) => StreamId { ms, seq: 0 },
+            XaddId::Explicit(id) => id,
+        };
+
+        if id == StreamId::MIN {
+            return Err("ERR The ID specified in XADD must be greater than 0-0".into());
+        }
+        if id <= last {
+            return Err(
+                "ERR The ID specified in XADD is equal or smaller than the target stream top item"
+                    .into(),
+            );
+        }
+
+        self.records.insert(id, fields);
+        self.last_id = id;
+        Ok(id)
+    }
+
+    /// Evicts the oldest records until at most `maxlen` remain.
+    pub(crate) fn trim(&mut self, maxlen: usize) -> usize {
+        let mut removed = 0;
+        while self.records.len() > maxlen {
+            self.records.pop_first();
+            removed += 1;
+        }
+        removed
+    }
+
+    pub(crate) fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> Vec<(StreamId, Fields)> {
+        if start > end {
+            return vec![];
+        }
+
+        let records = self
+            .records
+            .range(start..=end)
+            .map(|(id, fields)| (*id, fields.clone()));
+        let count = count.unwrap_or(usize::MAX);
+
+        if rev {
+            records.rev().take(count).collect()
+        } else {
+            records.take(count).collect()
+        }
+    }
+
+    pub(crate) fn create_group(&mut self, name: &str, id: StreamId) -> crate::Result<()> {
+        if self.groups.contains_key(name) {
+            return Err("BUSYGROUP Consumer Group name already exists".into());
+        }
+
+        self.groups.insert(
+            name.to_string(),
+            ConsumerGroup {
+                last_delivered: id,
+                pending: BTreeMap::new(),
+            },
+        );
+        Ok(())
+    }
+
+    pub(crate) fn destroy_group(&mut self, name: &str) -> bool {
+        self.groups.remove(name).is_some()
+    }
+
+    fn group_mut(&mut self, name: &str) -> crate::Result<&mut ConsumerGroup> {
+        self.groups.get_mut(name).ok_or_else(|| NOGROUP.into())
+    }
+
+    /// Delivers records to `consumer`. New records are added to the group's
+    /// PEL unless `noack` is set. Reading pending history yields `None` for
+    /// records that were deleted since their delivery.
+    pub(crate) fn read_group(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        from: ReadFrom,
+        count: Option<usize>,
+        noack: bool,
+    ) -> crate::Result<Delivered> {
+        let records = &self.records;
+        let group = self.groups.get_mut(group).ok_or(NOGROUP)?;
+        let count = count.unwrap_or(usize::MAX);
+        let now = Instant::now();
+
+        match from {
+            ReadFrom::New => {
+                let start = match group.last_delivered.next() {
+                    Some(start) => start,
+                    None => return Ok(vec![]),
+                };
+                let delivered: Vec<_> = records
+                    .range(start..)
+                    .take(count)
+                    .map(|(id, fields)| (*id, Some(fields.clone())))
+                    .collect();
+
+                for (id, _) in &delivered {
+                    group.last_delivered = *id;
+                    if !noack {
+                        group.pending.insert(
+                            *id,
+                            Pending {
+                                consumer: consumer.to_string(),
+                                delivered_at: now,
+                                deliveries: 1,
+                            },
+                        );
+                    }
+                }
+                Ok(delivered)
+            }
+            ReadFrom::Pending(after) => Ok(group
+                .pending
+                .range(after..)
+                .filter(|(id, pending)| **id > after && pending.consumer == consumer)
+                .take(count)
+                .map(|(id, _)| (*id, records.get(id).cloned()))
+                .collect()),
+        }
+    }
+
+    pub(crate) fn ack(&mut self, group: &str, ids: &[StreamId]) -> crate::Result<usize> {
+        let group = self.group_mut(group)?;
+        Ok(ids
+            .iter()
+            .filter(|id| group.pending.remove(id).is_some())
+            .count())
+    }
+
+    pub(crate) fn pending_summary(&self, group: &str) -> crate::Result<PendingSummary> {
+        let group = self.groups.get(group).ok_or(NOGROUP)?;
+
+        let mut consumers: BTreeMap<&str, usize> = BTreeMap::new();
+        for pending in group.pending.values() {
+            *consumers.entry(&pending.consumer).or_default() += 1;
+        }
+
+        Ok(PendingSummary {
+            count: group.pending.len(),
+            bounds: group
+                .pending
+                .keys()
+                .next()
+                .zip(group.pending.keys().next_back())
+                .map(|(min, max)| (*min, *max)),
+            consumers: consumers
+                .into_iter()
+                .map(|(consumer, count)| (consumer.to_string(), count))
+                .collect(),
+        })
+    }
+
+    pub(crate) fn pending_range(
+        &self,
+        group: &str,
+        min_idle: Duration,
+        start: StreamId,
+        end: StreamId,
+        count: usize,
+        consumer: Option<&str>,
+    ) -> crate::Result<Vec<(StreamId, Pending)>> {
+        let group = self.groups.get(group).ok_or(NOGROUP)?;
+        if start > end {
+            return Ok(vec![]);
+        }
+
+        let now = Instant::now();
+        Ok(group
+            .pending
+            .range(start..=end)
+            .filter(|(_, pending)| consumer.is_none_or(|c| pending.consumer == c))
+            .filter(|(_, pending)| now - pending.delivered_at >= min_idle)
+            .take(count)
+            .map(|(id, pending)| (*id, pending.clone()))
+            .collect())
+    }
+
+    /// Transfers ownership of idle pending records to `consumer`. Records that
+    /// no longer exist in the stream are dropped from the PEL instead.
+    pub(crate) fn claim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        min_idle: Duration,
+        ids: &[StreamId],
+        options: ClaimOptions,
+    ) -> crate::Result<Claimed> {
+        let records = &self.records;
+        let group = self.groups.get_mut(group).ok_or(NOGROUP)?;
+        let now = Instant::now();
+
+        let mut claimed = Claimed::default();
+        for id in ids {
+            if options.force && records.contains_key(id) && !group.pending.contains_key(id) {
+                let pending = Pending {
+                    consumer: consumer.to_string(),
+                    delivered_at: now,
+                    deliveries: 0,
+                };
+                group.pending.insert(*id, pending);
+            }
+            let idle = match group.pending.get(id) {
+                Some(pending) => now.saturating_duration_since(pending.delivered_at),
+                None => continue,
+            };
+            if idle < min_idle {
+                continue;
+            }
+
+            match records.get(id) {
+                Some(fields) => {
+                    let pending = group.pending.get_mut(id).unwrap();
+                    pending.consumer = consumer.to_string();
+                    pending.delivered_at = options.delivered_at.unwrap_or(now);
+                    match options.deliveries {
+                        Some(deliveries) => pending.deliveries = deliveries,
+                        None if !options.justid => pending.deliveries += 1,
+                        None => {}
+                    }
+                    claimed.changes.push((*id, pending.clone()));
+                    claimed.records.push((*id, fields.clone()));
+                }
+                None => {
+                    let pending = group.pending.remove(id).unwrap();
+                    claimed.changes.push((*id, pending));
+                    claimed.deleted.push(*id);
+                }
+            }
+        }
+
+        Ok(claimed)
+    }
+
+    /// Scans the PEL from `start`, claiming up to `count` idle records.
+    ///
+    /// Returns the ID to continue the scan from (`0-0` once the whole PEL was
+    /// visited), the claimed records and the IDs of deleted records that were
+    /// removed from the PEL.
+    pub(crate) fn autoclaim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        min_idle: Duration,
+        start: StreamId,
+        count: usize,
+        justid: bool,
+    ) -> crate::Result<AutoClaimed> {
+        let now = Instant::now();
+        let candidates: Vec<StreamId> = {
+            let group = self.groups.get(group).ok_or(NOGROUP)?;
+            group
+                .pending
+                .range(start..)
+                .filter(|(_, pending)| now - pending.delivered_at >= min_idle)
+                .map(|(id, _)| *id)
+                .take(count + 1)
+                .collect()
+        };
+
+        let next = if candidates.len() > count {
+            candidates[count]
+        } else {
+            StreamId::MIN
+        };
+        let candidates = &candidates[..candidates.len().min(count)];
+
+        let options = ClaimOptions {
+            justid,
+            ..ClaimOptions::default()
+        };
+        let claimed = self.claim(group, consumer, Duration::ZERO, candidates, options)?;
+
+        Ok((next, claimed))
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    ops::Bound,
+};
+
+use bytes::Bytes;
+
+/// Members ordered by `(score, member)`, with a hash index for score lookups.
+///
+/// The `BTreeSet` keeps the ordering Redis exposes through `ZRANGE`, so range
+/// queries are a walk over the tree instead of a sort of every member.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SortedSet {
+    scores: HashMap<Bytes, f64>,
+    ordered: BTreeSet<(Score, Bytes)>,
+}
+
+/// `f64` with a total order, NaN never makes it into a sorted set.
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+/// One end of a `BYSCORE` range, e.g. `1.5`, `(1.5` or `-inf`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ScoreBound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+/// One end of a `BYLEX` range, e.g. `[a`, `(a`, `-` or `+`.
+#[derive(Debug, Clone)]
+pub(crate) enum LexBound {
+    Inclusive(Bytes),
+    Exclusive(Bytes),
+    Min,
+    Max,
+}
+
+/// The `NX`, `XX`, `GT`, `LT` and `CH` flags of `ZADD`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZaddOptions {
+    pub(crate) nx: bool,
+    pub(crate) xx: bool,
+    pub(crate) gt: bool,
+    pub(crate) lt: bool,
+    pub(crate) ch: bool,
+}
+
+/// Which index a `ZRANGE` query runs against.
+#[derive(Debug, Clone)]
+pub(crate) enum RangeBy {
+    Rank(i64, i64),
+    Score(ScoreBound, ScoreBound),
+    Lex(LexBound, LexBound),
+}
+
+impl SortedSet {
+    pub(crate) fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub(crate) fn score(&self, member: &Bytes) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Inserts or updates `member`, returning its previous score.
+    pub(crate) fn insert(&mut self, member: Bytes, score: f64) -> Option<f64> {
+        // `-0` and `0` are the same score but not the same `total_cmp` key.
+        let score = if score == 0.0 { 0.0 } else { score };
+        let prev = self.scores.insert(member.clone(), score);
+        if let Some(prev) = prev {
+            self.ordered.remove(&(Score(prev), member.clone()));
+        }
+        self.ordered.insert((Score(score), member));
+        prev
+    }
+
+    pub(crate) fn remove(&mut self, member: &Bytes) -> Option<f64> {
+        let score = self.scores.remove(member)?;
+        self.ordered.remove(&(Score(score), member.clone()));
+        Some(score)
+    }
+
+    /// The 0-based position of `member` in ascending score order.
+    pub(crate) fn rank(&self, member: &Bytes) -> Option<usize> {
+        let score = self.score(member)?;
+        Some(self.ordered.range(..(Score(score), member.clone())).count())
+    }
+
+    pub(crate) fn pop_min(&mut self, count: usize) -> Vec<(Bytes, f64)> {
+        let mut popped = Vec::with_capacity(count.min(self.len()));
+        while popped.len() < count {
+            match self.ordered.pop_first() {
+                Some((Score(score), member)) => {
+                    self.scores.remove(&member);
+                    popped.push((member, score));
+                }
+                None => break,
+            }
+        }
+        popped
+    }
+
+    pub(crate) fn iter(&self) -> impl DoubleEndedIterator<Item = (&Bytes, f64)> {
+        self.ordered.iter().map(|(score, member)| (member, score.0))
+    }
+
+    /// Members with a score inside `min..max`, in ascending order.
+    pub(crate) fn range_by_score(
+        &self,
+        min: ScoreBound,
+        max: ScoreBound,
+    ) -> impl DoubleEndedIterator<Item = (&Bytes, f64)> {
+        let start = Bound::Included((Score(min.value()), Bytes::new()));
+        self.ordered
+            .range((start, Bound::Unbounded))
+            .map(|(score, member)| (member, score.0))
+            .filter(move |(_, score)| min.below(*score) && max.above(*score))
+    }
+
+    /// Members inside the lexicographical range, assuming all scores are equal.
+    pub(crate) fn range_by_lex<'a>(
+        &'a self,
+        min: &'a LexBound,
+        max: &'a LexBound,
+    ) -> impl DoubleEndedIterator<Item = (&'a Bytes, f64)> {
+        self.iter()
+            .filter(move |(member, _)| min.below(member) && max.above(member))
+    }
+}
+
+impl ZaddOptions {
+    pub fn new() -> ZaddOptions {
+        ZaddOptions::default()
+    }
+
+    /// Only add new members, never update existing ones.
+    pub fn nx(mut self) -> ZaddOptions {
+        self.nx = true;
+        self
+    }
+
+    /// Only update existing members, never add new ones.
+    pub fn xx(mut self) -> ZaddOptions {
+        self.xx = true;
+        self
+    }
+
+    /// Only update existing members when the new score is greater.
+    pub fn gt(mut self) -> ZaddOptions {
+        self.gt = true;
+        self
+    }
+
+    /// Only update existing members when the new score is less.
+    pub fn lt(mut self) -> ZaddOptions {
+        self.lt = true;
+        self
+    }
+
+    /// Count changed members in the reply, not only added ones.
+    pub fn ch(mut self) -> ZaddOptions {
+        self.ch = true;
+        self
+    }
+
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        if self.nx && self.xx {
+            return Err("ERR XX and NX options at the same time are not compatible".into());
+        }
+        if (self.gt && self.lt) || (self.nx && (self.gt || self.lt)) {
+            return Err("ERR GT, LT, and/or NX options at the same time are not compatible".into());
+        }
+        Ok(())
+    }
+
+    /// Whether an existing member currently at `old` may be moved to `new`.
+    pub(crate) fn allows_update(&self, old: f64, new: f64) -> bool {
+        !self.nx && (!self.gt || new > old) && (!self.lt || new < old)
+    }
+}
+
+impl ScoreBound {
+    pub(crate) fn parse(src: &str) -> crate::Result<ScoreBound> {
+        const MSG: &str = "ERR min or max is not a float";
+        match src.strip_prefix('(') {
+            Some(score) => Ok(ScoreBound::Exclusive(parse_score(score).ok_or(MSG)?)),
+            None => Ok(ScoreBound::Inclusive(parse_score(src).ok_or(MSG)?)),
+        }
+    }
+
+    fn value(&self) -> f64 {
+        match self {
+            ScoreBound::Inclusive(score) | ScoreBound::Exclusive(score) => *score,
+        }
+    }
+
+    /// Whether `score` is on the upper side of this bound.
+    fn below(&self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(min) => score >= *min,
+            ScoreBound::Exclusive(min) => score > *min,
+        }
+    }
+
+    /// Whether `score` is on the lower side of this bound.
+    fn above(&self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(max) => score <= *max,
+            ScoreBound::Exclusive(max) => score < *max,
+        }
+    }
+}
+
+impl LexBound {
+    pub(crate) fn parse(src: &Bytes) -> crate::Result<LexBound> {
+        match src.first() {
+            Some(b'-') if src.len() == 1 => Ok(LexBound::Min),
+            Some(b'+') if src.len() == 1 => Ok(LexBound::Max),
+            Some(b'[') => Ok(LexBound::Inclusive(src.slice(1..))),
+            Some(b'(') => Ok(LexBound::Exclusive(src.slice(1..))),
+            _ => Err("ERR min or max not valid string range item".into()),
+        }
+    }
+
+    fn below(&self, member: &Bytes) -> bool {
+        match self {
+            LexBound::Inclusive(min) => member >= min,
+            LexBound::Exclusive(min) => member > min,
+            LexBound::Min => true,
+            LexBound::Max => false,
+        }
+    }
+
+    fn above(&self, member: &Bytes) -> bool {
+        match self {
+            LexBound::Inclusive(max) => member <= max,
+            LexBound::Exclusive(max) => member < max,
+            LexBound::Min => false,
+            LexBound::Max => true,
+        }
+    }
+}
+
+/// Parses a score the way Redis does, accepting `inf`, `+inf` and `-inf`
+/// but never NaN.
+pub(crate) fn parse_score(src: &str) -> Option<f64> {
+    src.parse::<f64>().ok().filter(|score| !score.is_nan())
+}
+
+/// Formats a score the way Redis replies with it, e.g. `1`, `1.5` or `inf`.
+pub(crate) fn format_score(score: f64) -> String {
+    score.to_string()
+}
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
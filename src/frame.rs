@@ -10,14 +10,34 @@ use std::{fmt::Display, io::Cursor, num::TryFromIntError, string::FromUtf8Error}
 
 use bytes::{Buf, Bytes};
 
-#[derive(Clone, Debug)]
+/// A RESP frame. The variants after `Array` were added by RESP3, they are
+/// only sent as is to connections that switched to it with `HELLO 3`, see
+/// `Connection::write_frame`.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Frame {
     Simple(String),
     Error(String),
-    Integer(u64),
+    Integer(i64),
     Bulk(Bytes),
     Null,
     Array(Vec<Frame>),
+    Map(Vec<(Frame, Frame)>),
+    Set(Vec<Frame>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    /// A string along with its three letters format, such as `txt`.
+    Verbatim {
+        format: String,
+        text: Bytes,
+    },
+    /// Out of band information about `frame`, which RESP2 clients don't get.
+    Attribute {
+        attributes: Vec<(Frame, Frame)>,
+        frame: Box<Frame>,
+    },
+    /// Data the server sends on its own, such as pub/sub messages.
+    Push(Vec<Frame>),
 }
 
 #[derive(Debug)]
@@ -33,33 +53,53 @@ impl Frame {
 
     pub(crate) fn push_bulk(&mut self, bytes: Bytes) {
         match self {
-            Frame::Array(vec) => {
+            Frame::Array(vec) | Frame::Push(vec) => {
                 vec.push(Frame::Bulk(bytes));
             }
             _ => panic!("not an array frame"),
         }
     }
 
-    pub(crate) fn push_int(&mut self, value: u64) {
+    pub(crate) fn push_int(&mut self, value: i64) {
         match self {
-            Frame::Array(vec) => {
+            Frame::Array(vec) | Frame::Push(vec) => {
                 vec.push(Frame::Integer(value));
             }
             _ => panic!("not an array frame"),
         }
     }
 
+    /// Whether `byte` starts a frame, anything else starts an inline command.
+    pub(crate) fn is_type_byte(byte: u8) -> bool {
+        matches!(
+            byte,
+            b'+' | b'-'
+                | b':'
+                | b'$'
+                | b'*'
+                | b'_'
+                | b'%'
+                | b'~'
+                | b'>'
+                | b','
+                | b'#'
+                | b'('
+                | b'='
+                | b'|'
+        )
+    }
+
     pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
         match get_u8(src)? {
-            b'+' | b'-' => {
+            b'+' | b'-' | b',' | b'#' | b'(' | b'_' => {
                 get_line(src)?;
                 Ok(())
             }
             b':' => {
-                let _ = get_decimal(src)?;
+                let _ = get_signed_decimal(src)?;
                 Ok(())
             }
-            b'$' => {
+            b'$' | b'=' => {
                 if b'-' == peek_u8(src)? {
                     skip(src, 4)
                 } else {
@@ -67,13 +107,28 @@ impl Frame {
                     skip(src, len + 2)
                 }
             }
-            b'*' => {
+            b'*' | b'~' | b'>' => {
                 let len = get_decimal(src)?;
                 for _ in 0..len {
                     Frame::check(src)?;
                 }
                 Ok(())
             }
+            b'%' => {
+                let len = get_decimal(src)?;
+                for _ in 0..len * 2 {
+                    Frame::check(src)?;
+                }
+                Ok(())
+            }
+            b'|' => {
+                let len = get_decimal(src)?;
+                for _ in 0..len * 2 {
+                    Frame::check(src)?;
+                }
+                // The attributes are followed by the frame they describe.
+                Frame::check(src)
+            }
             actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
         }
     }
@@ -91,8 +146,8 @@ impl Frame {
                 Ok(Frame::Error(string))
             }
             b':' => {
-                let len = get_decimal(src)?;
-                Ok(Frame::Integer(len))
+                let num = get_signed_decimal(src)?;
+                Ok(Frame::Integer(num))
             }
             b'$' => {
                 if b'-' == peek_u8(src)? {
@@ -116,15 +171,52 @@ impl Frame {
                     Ok(Frame::Bulk(data))
                 }
             }
-            b'*' => {
-                let len = get_decimal(src)?.try_into()?;
-                let mut out = Vec::with_capacity(len);
-                for _ in 0..len {
-                    out.push(Frame::parse(src)?);
+            b'*' => Ok(Frame::Array(parse_frames(src)?)),
+            b'_' => {
+                get_line(src)?;
+                Ok(Frame::Null)
+            }
+            b'%' => Ok(Frame::Map(parse_pairs(src)?)),
+            b'~' => Ok(Frame::Set(parse_frames(src)?)),
+            b'>' => Ok(Frame::Push(parse_frames(src)?)),
+            b',' => {
+                let line = get_line(src)?;
+                let double = std::str::from_utf8(line)
+                    .ok()
+                    .and_then(|line| line.parse().ok())
+                    .ok_or("protocol error; invalid frame format")?;
+                Ok(Frame::Double(double))
+            }
+            b'#' => match get_line(src)? {
+                b"t" => Ok(Frame::Boolean(true)),
+                b"f" => Ok(Frame::Boolean(false)),
+                _ => Err("protocol error; invalid frame format".into()),
+            },
+            b'(' => {
+                let line = get_line(src)?.to_vec();
+                Ok(Frame::BigNumber(String::from_utf8(line)?))
+            }
+            b'=' => {
+                let len: usize = get_decimal(src)?.try_into()?;
+                if src.remaining() < len + 2 {
+                    return Err(Error::Incomplete);
                 }
-                Ok(Frame::Array(out))
+                let data = &src.chunk()[..len];
+                if len < 4 || data[3] != b':' {
+                    return Err("protocol error; invalid frame format".into());
+                }
+                let format = String::from_utf8(data[..3].to_vec())?;
+                let text = Bytes::copy_from_slice(&data[4..]);
+                skip(src, len + 2)?;
+
+                Ok(Frame::Verbatim { format, text })
+            }
+            b'|' => {
+                let attributes = parse_pairs(src)?;
+                let frame = Box::new(Frame::parse(src)?);
+                Ok(Frame::Attribute { attributes, frame })
             }
-            _ => unimplemented!(),
+            b => Err(format!("protocol error; invalid frame type byte `{}`", b).into()),
         }
     }
 
@@ -133,11 +225,32 @@ impl Frame {
     }
 }
 
+/// Parses the elements of an array, set or push frame.
+fn parse_frames(src: &mut Cursor<&[u8]>) -> Result<Vec<Frame>, Error> {
+    let len = get_decimal(src)?.try_into()?;
+    let mut out = Vec::with_capacity(len);
+    for _ in 0..len {
+        out.push(Frame::parse(src)?);
+    }
+    Ok(out)
+}
+
+/// Parses the entries of a map or attribute frame.
+fn parse_pairs(src: &mut Cursor<&[u8]>) -> Result<Vec<(Frame, Frame)>, Error> {
+    let len = get_decimal(src)?.try_into()?;
+    let mut out = Vec::with_capacity(len);
+    for _ in 0..len {
+        let key = Frame::parse(src)?;
+        out.push((key, Frame::parse(src)?));
+    }
+    Ok(out)
+}
+
 impl PartialEq<&str> for Frame {
     fn eq(&self, other: &&str) -> bool {
         match self {
-            Frame::Simple(s) => s.eq(other),
-            Frame::Bulk(s) => s.eq(other),
+            Frame::Simple(s) | Frame::BigNumber(s) => s.eq(other),
+            Frame::Bulk(s) | Frame::Verbatim { text: s, .. } => s.eq(other),
             _ => false,
         }
     }
@@ -154,7 +267,7 @@ impl Display for Frame {
                 Err(_) => write!(f, "{:?}", msg),
             },
             Frame::Null => "(nil)".fmt(f),
-            Frame::Array(parts) => {
+            Frame::Array(parts) | Frame::Set(parts) | Frame::Push(parts) => {
                 for (i, part) in parts.iter().enumerate() {
                     if i > 0 {
                         write!(f, " ")?;
@@ -163,6 +276,20 @@ impl Display for Frame {
                 }
                 Ok(())
             }
+            Frame::Map(pairs) => {
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{} => {}", key, value)?;
+                }
+                Ok(())
+            }
+            Frame::Double(num) => num.fmt(f),
+            Frame::Boolean(value) => value.fmt(f),
+            Frame::BigNumber(num) => num.fmt(f),
+            Frame::Verbatim { text, .. } => Frame::Bulk(text.clone()).fmt(f),
+            Frame::Attribute { frame, .. } => frame.fmt(f),
         }
     }
 }
@@ -195,6 +322,12 @@ fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
     atoi::<u64>(line).ok_or_else(|| "protocol error; invalid frame format".into())
 }
 
+fn get_signed_decimal(src: &mut Cursor<&[u8]>) -> Result<i64, Error> {
+    use atoi::atoi;
+    let line = get_line(src)?;
+    atoi::<i64>(line).ok_or_else(|| "protocol error; invalid frame format".into())
+}
+
 fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
     let start = src.position() as usize;
     let end = src.get_ref().len() - 1;
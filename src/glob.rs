@@ -0,0 +1,72 @@
+//! Redis style glob patterns, as used by `KEYS`, `SCAN ... MATCH` and
+//! `PSUBSCRIBE`.
+//!
+//! `*` matches any run of bytes, `?` any single byte, `[abc]`, `[a-z]` and
+//! `[^abc]` a class of bytes, and `\` escapes the next byte.
+
+/// Whether `text` matches `pattern` as a whole.
+pub(crate) fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*` when the rest of the pattern fails
+    // to match: the `*` swallows one more byte each time.
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            backtrack = Some((p, t));
+            continue;
+        }
+        if p < pattern.len() {
+            if let Some(len) = match_one(&pattern[p..], text[t]) {
+                p += len;
+                t += 1;
+                continue;
+            }
+        }
+        match backtrack {
+            Some((star_p, star_t)) => {
+                p = star_p;
+                t = star_t + 1;
+                backtrack = Some((star_p, t));
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Matches `c` against the element at the start of `pattern`, which is
+/// anything but `*`. Returns the length of the element if it matches.
+fn match_one(pattern: &[u8], c: u8) -> Option<usize> {
+    match pattern[0] {
+        b'?' => Some(1),
+        b'\\' if pattern.len() > 1 => (pattern[1] == c).then_some(2),
+        b'[' => {
+            let negate = pattern.get(1) == Some(&b'^');
+            let mut i = if negate { 2 } else { 1 };
+            let mut matched = false;
+            // Like Redis, an unterminated class runs to the end of the pattern.
+            while let Some(&x) = pattern.get(i) {
+                if x == b']' {
+                    i += 1;
+                    break;
+                }
+                if x == b'\\' && i + 1 < pattern.len() {
+                    matched |= pattern[i + 1] == c;
+                    i += 2;
+                } else if pattern.get(i + 1) == Some(&b'-') && i + 2 < pattern.len() {
+                    let (lo, hi) = (x.min(pattern[i + 2]), x.max(pattern[i + 2]));
+                    matched |= (lo..=hi).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= x == c;
+                    i += 1;
+                }
+            }
+            (matched != negate).then_some(i)
+        }
+        x => (x == c).then_some(1),
+    }
+}
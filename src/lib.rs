@@ -7,12 +7,17 @@ mod connection;
 pub use connection::Connection;
 
 mod db;
+pub use db::{ExpireOptions, ScanOptions, SetOptions, Side, ZaddOptions};
 
 mod frame;
 pub use frame::Frame;
 
+mod glob;
+
 mod parse;
 
+mod scripting;
+
 mod shutdown;
 
 pub mod server;
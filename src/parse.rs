@@ -4,6 +4,8 @@ use bytes::Bytes;
 
 use crate::Frame;
 
+const NOT_INTEGER: &str = "ERR value is not an integer or out of range";
+
 #[derive(Debug)]
 pub(crate) struct Parse {
     parts: vec::IntoIter<Frame>,
@@ -12,6 +14,8 @@ pub(crate) struct Parse {
 #[derive(Debug)]
 pub(crate) enum ParseError {
     EndOfStream,
+    /// There were more arguments than expected, see `Parse::finish`.
+    Extra,
     Other(crate::Error),
 }
 
@@ -56,14 +60,71 @@ impl Parse {
         }
     }
 
+    /// Parses a non-negative integer argument, which has to be a number as a
+    /// whole.
     pub(crate) fn next_int(&mut self) -> Result<u64, ParseError> {
-        use atoi::atoi;
-        const MSG: &str = "protocol error; invalid number";
+        u64::try_from(self.next_signed_int()?).map_err(|_| NOT_INTEGER.into())
+    }
+
+    /// Parses an integer argument, which has to be a number as a whole.
+    pub(crate) fn next_signed_int(&mut self) -> Result<i64, ParseError> {
+        let parsed = match self.next()? {
+            Frame::Integer(v) => Some(v),
+            Frame::Simple(data) => data.parse::<i64>().ok(),
+            Frame::Bulk(data) => std::str::from_utf8(&data)
+                .ok()
+                .and_then(|data| data.parse::<i64>().ok()),
+            frame => {
+                return Err(
+                    format!("protocol error; expected int frame but got {:?}", frame).into(),
+                )
+            }
+        };
+        parsed.ok_or_else(|| NOT_INTEGER.into())
+    }
+
+    /// Parses a float argument, accepting `inf`, `+inf` and `-inf` but not NaN.
+    pub(crate) fn next_double(&mut self) -> Result<f64, ParseError> {
+        const MSG: &str = "ERR value is not a valid float";
         match self.next()? {
-            Frame::Integer(v) => Ok(v),
-            Frame::Simple(data) => atoi::<u64>(data.as_bytes()).ok_or_else(|| MSG.into()),
-            Frame::Bulk(data) => atoi::<u64>(&data).ok_or_else(|| MSG.into()),
-            frame => Err(format!("protocol error; expected int frame but got {:?}", frame).into()),
+            Frame::Integer(v) => Ok(v as f64),
+            Frame::Simple(data) => data
+                .parse::<f64>()
+                .ok()
+                .filter(|v| !v.is_nan())
+                .ok_or_else(|| MSG.into()),
+            Frame::Bulk(data) => std::str::from_utf8(&data)
+                .ok()
+                .and_then(|data| data.parse::<f64>().ok())
+                .filter(|v| !v.is_nan())
+                .ok_or_else(|| MSG.into()),
+            frame => {
+                Err(format!("protocol error; expected float frame but got {:?}", frame).into())
+            }
+        }
+    }
+
+    /// Collects every remaining part as a string, for variadic arguments.
+    pub(crate) fn remaining_strings(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut strings = vec![];
+        loop {
+            match self.next_string() {
+                Ok(s) => strings.push(s),
+                Err(ParseError::EndOfStream) => return Ok(strings),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Collects every remaining part as raw bytes, for variadic arguments.
+    pub(crate) fn remaining_bytes(&mut self) -> Result<Vec<Bytes>, ParseError> {
+        let mut values = vec![];
+        loop {
+            match self.next_bytes() {
+                Ok(value) => values.push(value),
+                Err(ParseError::EndOfStream) => return Ok(values),
+                Err(err) => return Err(err),
+            }
         }
     }
 
@@ -71,7 +132,7 @@ impl Parse {
         if self.parts.next().is_none() {
             Ok(())
         } else {
-            Err("protocol error; expected end of frame, but there was more".into())
+            Err(ParseError::Extra)
         }
     }
 }
@@ -92,6 +153,7 @@ impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParseError::EndOfStream => "protocol error; unexpected end of stream".fmt(f),
+            ParseError::Extra => "protocol error; expected end of frame, but there was more".fmt(f),
             ParseError::Other(err) => err.fmt(f),
         }
     }
@@ -0,0 +1,263 @@
+//! Runs the Lua scripts of `EVAL` and `EVALSHA`, with `redis.call` bridged
+//! to `Command::execute`.
+
+use std::{cell::Cell, error::Error, fmt, rc::Rc};
+
+use bytes::Bytes;
+use mlua::{Function, HookTriggers, Lua, LuaOptions, MultiValue, StdLib, Table, Value};
+
+use crate::{
+    cmd::READONLY,
+    db::{block_in_place, format_score, sha1_hex, Databases},
+    Command, Frame,
+};
+
+/// How many Lua instructions run between checks for `SCRIPT KILL`, like in
+/// Redis.
+const HOOK_INSTRUCTIONS: u32 = 100_000;
+
+/// An error reply of a command run by `redis.call`, raised as a Lua error
+/// and replied as is if the script doesn't catch it.
+#[derive(Debug)]
+struct ReplyError(String);
+
+/// Runs `body` against `databases`, which the caller holds exclusively so
+/// that the script is atomic. The script starts in the database at
+/// `selected`, a `SELECT` in it doesn't outlive it. Other clients wait for
+/// it to return, see `ScriptCache::wait_for_script`.
+pub(crate) fn run(
+    databases: &Databases,
+    selected: usize,
+    body: &[u8],
+    keys: Vec<String>,
+    args: Vec<Bytes>,
+) -> Frame {
+    let run = || {
+        let _running = databases.scripts().start();
+        match try_run(databases, selected, body, keys, args) {
+            Ok(frame) => frame,
+            Err(err) => Frame::Error(error_message(&err)),
+        }
+    };
+
+    // The script may keep the thread busy for long.
+    block_in_place(run)
+}
+
+fn try_run(
+    databases: &Databases,
+    selected: usize,
+    body: &[u8],
+    keys: Vec<String>,
+    args: Vec<Bytes>,
+) -> mlua::Result<Frame> {
+    let lua = Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+        LuaOptions::new(),
+    )?;
+    let scripts = databases.scripts().clone();
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS),
+        move |_, _| {
+            if scripts.killed() {
+                let msg = "ERR Script killed by user with SCRIPT KILL...";
+                return Err(mlua::Error::external(ReplyError(msg.to_string())));
+            }
+            Ok(())
+        },
+    );
+
+    let globals = lua.globals();
+    globals.set("KEYS", lua.create_sequence_from(keys)?)?;
+    let args = args
+        .iter()
+        .map(|arg| lua.create_string(arg))
+        .collect::<mlua::Result<Vec<_>>>()?;
+    globals.set("ARGV", lua.create_sequence_from(args)?)?;
+
+    let selected = Rc::new(Cell::new(selected));
+    let redis = lua.create_table()?;
+    redis.set(
+        "call",
+        command_fn(&lua, databases.clone(), selected.clone(), false)?,
+    )?;
+    redis.set(
+        "pcall",
+        command_fn(&lua, databases.clone(), selected, true)?,
+    )?;
+    redis.set(
+        "sha1hex",
+        lua.create_function(|_, data: mlua::String| Ok(sha1_hex(data.as_bytes())))?,
+    )?;
+    redis.set(
+        "status_reply",
+        lua.create_function(|lua, msg: mlua::String| reply_table(lua, "ok", msg))?,
+    )?;
+    redis.set(
+        "error_reply",
+        lua.create_function(|lua, msg: mlua::String| reply_table(lua, "err", msg))?,
+    )?;
+    globals.set("redis", redis)?;
+
+    let value = lua.load(body).set_name("=user_script").eval::<Value>()?;
+    Ok(to_frame(value))
+}
+
+/// `redis.call`, or `redis.pcall` when `protected`, which returns error
+/// replies as `{err = ...}` tables instead of raising them.
+fn command_fn(
+    lua: &Lua,
+    databases: Databases,
+    selected: Rc<Cell<usize>>,
+    protected: bool,
+) -> mlua::Result<Function<'_>> {
+    lua.create_function(move |lua, args: MultiValue| {
+        match execute(lua, args, &databases, &selected) {
+            Frame::Error(msg) if !protected => Err(mlua::Error::external(ReplyError(msg))),
+            frame => from_frame(lua, frame),
+        }
+    })
+}
+
+fn execute(lua: &Lua, args: MultiValue, databases: &Databases, selected: &Cell<usize>) -> Frame {
+    if args.is_empty() {
+        return Frame::Error(
+            "ERR Please specify at least one argument for this redis lib call".to_string(),
+        );
+    }
+
+    let mut frame = Frame::array();
+    for arg in args {
+        match lua.coerce_string(arg) {
+            Ok(Some(arg)) => frame.push_bulk(Bytes::copy_from_slice(arg.as_bytes())),
+            _ => {
+                return Frame::Error(
+                    "ERR Lua redis lib command arguments must be strings or integers".to_string(),
+                )
+            }
+        }
+    }
+
+    let cmd = match Command::from_frame(frame) {
+        Ok(cmd) => cmd,
+        Err(err) => return Frame::Error(err.to_string()),
+    };
+    match cmd {
+        Command::Eval(_)
+        | Command::Evalsha(_)
+        | Command::Script(_)
+        | Command::Multi(_)
+        | Command::Exec(_)
+        | Command::Discard(_)
+        | Command::Watch(_)
+        | Command::Unwatch(_)
+        | Command::Subscribe(_)
+        | Command::Unsubscribe(_)
+        | Command::Psubscribe(_)
+        | Command::Punsubscribe(_)
+        | Command::Save(_)
+        | Command::Bgsave(_)
+        | Command::Bgrewriteaof(_)
+        | Command::Replicaof(_)
+        | Command::Slaveof(_)
+        | Command::Psync(_)
+        | Command::Replconf(_)
+        | Command::Wait(_)
+        | Command::Asking(_)
+        | Command::Hello(_) => {
+            Frame::Error("ERR This Redis command is not allowed from script".to_string())
+        }
+        cmd if cmd.is_write() && databases.read_only() => Frame::Error(READONLY.to_string()),
+        cmd => {
+            if cmd.is_write() {
+                databases.scripts().wrote();
+            }
+            let mut index = selected.get();
+            let frame = cmd.execute(databases, &mut index);
+            selected.set(index);
+            frame
+        }
+    }
+}
+
+fn reply_table<'lua>(lua: &'lua Lua, field: &str, msg: mlua::String) -> mlua::Result<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.set(field, msg)?;
+    Ok(table)
+}
+
+/// Converts a command reply to Lua, like Redis does: status and error
+/// replies become `{ok = ...}` and `{err = ...}` tables, and nulls `false`.
+/// Scripts get the RESP2 form of replies, so maps become flat arrays.
+fn from_frame(lua: &Lua, frame: Frame) -> mlua::Result<Value<'_>> {
+    Ok(match frame {
+        Frame::Simple(msg) => Value::Table(reply_table(lua, "ok", lua.create_string(msg)?)?),
+        Frame::Error(msg) => Value::Table(reply_table(lua, "err", lua.create_string(msg)?)?),
+        Frame::Integer(value) => Value::Integer(value),
+        Frame::Bulk(value) => Value::String(lua.create_string(&value)?),
+        Frame::Null => Value::Boolean(false),
+        Frame::Array(frames) | Frame::Set(frames) | Frame::Push(frames) => {
+            let values = frames
+                .into_iter()
+                .map(|frame| from_frame(lua, frame))
+                .collect::<mlua::Result<Vec<_>>>()?;
+            Value::Table(lua.create_sequence_from(values)?)
+        }
+        Frame::Map(pairs) => {
+            let frames = pairs
+                .into_iter()
+                .flat_map(|(key, value)| [key, value])
+                .collect();
+            from_frame(lua, Frame::Array(frames))?
+        }
+        Frame::Double(value) => Value::String(lua.create_string(format_score(value))?),
+        Frame::Boolean(value) => Value::Integer(value as i64),
+        Frame::BigNumber(value) => Value::String(lua.create_string(value)?),
+        Frame::Verbatim { text, .. } => Value::String(lua.create_string(&text)?),
+        Frame::Attribute { frame, .. } => from_frame(lua, *frame)?,
+    })
+}
+
+/// Converts the value returned by a script to its reply. Numbers are
+/// truncated to integers, and arrays stop at their first `nil`.
+fn to_frame(value: Value) -> Frame {
+    match value {
+        Value::Boolean(true) => Frame::Integer(1),
+        Value::Integer(value) => Frame::Integer(value),
+        Value::Number(value) => Frame::Integer(value as i64),
+        Value::String(value) => Frame::Bulk(Bytes::copy_from_slice(value.as_bytes())),
+        Value::Table(table) => {
+            if let Ok(Value::String(msg)) = table.raw_get("err") {
+                return Frame::Error(msg.to_string_lossy().into_owned());
+            }
+            if let Ok(Value::String(msg)) = table.raw_get("ok") {
+                return Frame::Simple(msg.to_string_lossy().into_owned());
+            }
+            let values = table.sequence_values::<Value>();
+            Frame::Array(values.map_while(Result::ok).map(to_frame).collect())
+        }
+        _ => Frame::Null,
+    }
+}
+
+fn error_message(err: &mlua::Error) -> String {
+    match err {
+        mlua::Error::CallbackError { cause, .. } => error_message(cause),
+        mlua::Error::SyntaxError { message, .. } => {
+            format!("ERR Error compiling script: {}", message)
+        }
+        mlua::Error::RuntimeError(message) => format!("ERR Error running script: {}", message),
+        err => match err.downcast_ref::<ReplyError>() {
+            Some(ReplyError(msg)) => msg.clone(),
+            None => format!("ERR Error running script: {}", err),
+        },
+    }
+}
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for ReplyError {}
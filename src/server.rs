@@ -1,4 +1,4 @@
-use std::{future::Future, sync::Arc};
+use std::{future::Future, ops::RangeInclusive, path::PathBuf, sync::Arc};
 
 use tokio::{
     net::{TcpListener, TcpStream},
@@ -9,7 +9,8 @@ use tokio::{
 use tracing::{debug, error, info, instrument};
 
 use crate::{
-    db::{Db, DbDropGuard},
+    cmd::MultiState,
+    db::{Databases, DbDropGuard, SLOTS},
     shutdown::Shutdown,
     Command, Connection,
 };
@@ -32,21 +33,286 @@ struct Listener {
 
 #[derive(Debug)]
 struct Handler {
-    db: Db,
+    databases: Databases,
+    /// The database selected with `SELECT`, `0` for new connections.
+    db: usize,
+    /// Commands queued by `MULTI` and keys under `WATCH`.
+    transaction: MultiState,
+    /// The offset of the replication stream once the last command of this
+    /// client ran, which `WAIT` waits for replicas to acknowledge.
+    write_offset: u64,
     connection: Connection,
     shutdown: Shutdown,
     _shutdown_complete: mpsc::Sender<()>,
 }
 
+/// Server settings for `run_with_config`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub(crate) databases: usize,
+    pub(crate) pubsub_capacity: usize,
+    pub(crate) slow_subscribers: SlowSubscribers,
+    pub(crate) snapshot_path: Option<PathBuf>,
+    pub(crate) save_rules: Vec<(Duration, u64)>,
+    pub(crate) aof_path: Option<PathBuf>,
+    pub(crate) appendfsync: AppendFsync,
+    pub(crate) replicaof: Option<(String, u16)>,
+    pub(crate) repl_backlog_size: usize,
+    pub(crate) cluster: Option<ClusterConfig>,
+    pub(crate) lua_time_limit: Duration,
+}
+
+/// The nodes of a cluster, which every one of them is configured with, see
+/// `Config::cluster`.
+#[derive(Debug, Clone)]
+pub(crate) struct ClusterConfig {
+    pub(crate) nodes: Vec<ClusterNode>,
+    /// The index of this server in `nodes`.
+    pub(crate) myself: usize,
+    /// Slots being moved from their owner to another node, by index.
+    pub(crate) migrating: Vec<(u16, usize)>,
+}
+
+/// A node of a cluster and the hash slots it owns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterNode {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) slots: Vec<RangeInclusive<u16>>,
+}
+
+/// What happens to a subscriber that falls so far behind that messages
+/// published to it are dropped, see `Config::pubsub_capacity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlowSubscribers {
+    /// Close its connection, like Redis does once a client exceeds its
+    /// `client-output-buffer-limit`.
+    Disconnect,
+    /// Send it a `lagged` notification, or `plagged` for a pattern, with
+    /// the number of messages it missed.
+    Notify,
+}
+
+/// When the append only file is flushed to disk, like Redis' `appendfsync`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendFsync {
+    /// After every write, before replying to the client.
+    Always,
+    /// Once a second, so that at most a second of writes is lost.
+    Everysec,
+    /// Whenever the operating system sees fit.
+    No,
+}
+
 const MAX_CONNECTIONS: usize = 250;
 
+/// Like Redis, servers start with 16 databases.
+const DEFAULT_DATABASES: usize = 16;
+
+const DEFAULT_PUBSUB_CAPACITY: usize = 1024;
+
+/// Like Redis, replicas can catch up on the last megabyte of changes.
+const DEFAULT_REPL_BACKLOG_SIZE: usize = 1024 * 1024;
+
+const DEFAULT_LUA_TIME_LIMIT: Duration = Duration::from_secs(5);
+
+impl ClusterNode {
+    /// The node accepting connections at `host` and `port`, which owns no
+    /// slots yet.
+    pub fn new(host: impl ToString, port: u16) -> ClusterNode {
+        ClusterNode {
+            host: host.to_string(),
+            port,
+            slots: vec![],
+        }
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Makes the node own the hash `slots`. Ranges add up.
+    pub fn slots(mut self, slots: RangeInclusive<u16>) -> ClusterNode {
+        self.slots.push(slots);
+        self
+    }
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    /// The number of databases clients can `SELECT`, at least one.
+    pub fn databases(mut self, databases: usize) -> Config {
+        self.databases = databases.max(1);
+        self
+    }
+
+    /// How many published messages may wait for a subscriber before it is
+    /// considered too slow, at least one. `1024` by default.
+    pub fn pubsub_capacity(mut self, capacity: usize) -> Config {
+        self.pubsub_capacity = capacity.max(1);
+        self
+    }
+
+    /// What to do about slow subscribers, they are notified by default.
+    pub fn slow_subscribers(mut self, policy: SlowSubscribers) -> Config {
+        self.slow_subscribers = policy;
+        self
+    }
+
+    /// The file `SAVE` and `BGSAVE` write snapshots to, which is loaded when
+    /// the server starts. Without one, data only lives in memory.
+    pub fn snapshot_path(mut self, path: impl Into<PathBuf>) -> Config {
+        self.snapshot_path = Some(path.into());
+        self
+    }
+
+    /// Saves a snapshot in the background once at least `changes` were made
+    /// `after` the last save, like Redis' `save 900 1`. Rules add up.
+    pub fn save_rule(mut self, after: Duration, changes: u64) -> Config {
+        self.save_rules.push((after, changes));
+        self
+    }
+
+    /// Logs every change to `path`, which is replayed instead of the
+    /// snapshot when the server starts, like Redis' `appendonly yes`.
+    pub fn aof_path(mut self, path: impl Into<PathBuf>) -> Config {
+        self.aof_path = Some(path.into());
+        self
+    }
+
+    /// How often the append only file is flushed to disk, every second by
+    /// default.
+    pub fn appendfsync(mut self, policy: AppendFsync) -> Config {
+        self.appendfsync = policy;
+        self
+    }
+
+    /// Starts as a replica of the server at `host` and `port`, like Redis'
+    /// `replicaof` directive.
+    pub fn replicaof(mut self, host: impl ToString, port: u16) -> Config {
+        self.replicaof = Some((host.to_string(), port));
+        self
+    }
+
+    /// How many bytes of the latest changes are kept for replicas that lost
+    /// their connection to catch up on, at least one. 1MB by default.
+    pub fn repl_backlog_size(mut self, size: usize) -> Config {
+        self.repl_backlog_size = size.max(1);
+        self
+    }
+
+    /// How long a script runs before other clients are told the server is
+    /// busy instead of waiting for it, and may kill it with `SCRIPT KILL`,
+    /// like Redis' `lua-time-limit`. 5 seconds by default.
+    pub fn lua_time_limit(mut self, limit: Duration) -> Config {
+        self.lua_time_limit = limit;
+        self
+    }
+
+    /// Runs as node `myself` of a cluster of `nodes`, like Redis'
+    /// `cluster-enabled yes` with a static configuration. Keys are then only
+    /// served by the node owning their hash slot, the others redirect
+    /// clients to it.
+    ///
+    /// Fails if `myself` is not a node, or if slots are out of range or
+    /// owned by several nodes.
+    pub fn cluster(mut self, nodes: Vec<ClusterNode>, myself: usize) -> crate::Result<Config> {
+        if myself >= nodes.len() {
+            return Err(format!("node {} is not in the cluster", myself).into());
+        }
+        let mut owned = vec![false; SLOTS as usize];
+        for slot in nodes
+            .iter()
+            .flat_map(|node| node.slots.iter().cloned().flatten())
+        {
+            if slot >= SLOTS {
+                return Err(format!("slot {} is out of range", slot).into());
+            }
+            if owned[slot as usize] {
+                return Err(format!("slot {} is owned twice", slot).into());
+            }
+            owned[slot as usize] = true;
+        }
+        self.cluster = Some(ClusterConfig {
+            nodes,
+            myself,
+            migrating: vec![],
+        });
+        Ok(self)
+    }
+
+    /// Marks `slot` as being moved from its owner to node `to`, the owner
+    /// then asks clients to try the keys it no longer has on `to`, with an
+    /// `ASK` redirection.
+    ///
+    /// Fails if the cluster is not configured yet, if `slot` is out of range
+    /// or if `to` is not a node.
+    pub fn cluster_migrating(mut self, slot: u16, to: usize) -> crate::Result<Config> {
+        let Some(cluster) = self.cluster.as_mut() else {
+            return Err("the cluster is not configured".into());
+        };
+        if slot >= SLOTS {
+            return Err(format!("slot {} is out of range", slot).into());
+        }
+        if to >= cluster.nodes.len() {
+            return Err(format!("node {} is not in the cluster", to).into());
+        }
+        cluster.migrating.push((slot, to));
+        Ok(self)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            databases: DEFAULT_DATABASES,
+            pubsub_capacity: DEFAULT_PUBSUB_CAPACITY,
+            slow_subscribers: SlowSubscribers::Notify,
+            snapshot_path: None,
+            save_rules: vec![],
+            aof_path: None,
+            appendfsync: AppendFsync::Everysec,
+            replicaof: None,
+            repl_backlog_size: DEFAULT_REPL_BACKLOG_SIZE,
+            cluster: None,
+            lua_time_limit: DEFAULT_LUA_TIME_LIMIT,
+        }
+    }
+}
+
 pub async fn run(listener: TcpListener, shutdown: impl Future) {
+    run_with_config(listener, shutdown, Config::default()).await
+}
+
+pub async fn run_with_config(listener: TcpListener, shutdown: impl Future, config: Config) {
     let (notify_shutdown, _) = broadcast::channel(1);
     let (shutdown_complete_tx, mut shutdown_complete_rx) = mpsc::channel(1);
 
+    let db_holder = DbDropGuard::new(&config);
+    let databases = db_holder.databases();
+    if let Err(err) = databases.load() {
+        error!(cause = %err, "failed to load data");
+        return;
+    }
+    if let Ok(addr) = listener.local_addr() {
+        databases.set_listening_port(addr.port());
+    }
+    if let Some((host, port)) = config.replicaof.clone() {
+        databases.replicaof(host, port);
+    }
+    let save_rules = tokio::spawn(check_save_rules(databases.clone()));
+    let fsync = tokio::spawn(fsync_aof(databases.clone()));
+
     let mut server = Listener {
         listener,
-        db_holder: DbDropGuard::new(),
+        db_holder,
         limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
         notify_shutdown,
         shutdown_complete_tx,
@@ -73,6 +339,30 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) {
     drop(shutdown_complete_tx);
 
     let _ = shutdown_complete_rx.recv().await;
+
+    save_rules.abort();
+    fsync.abort();
+    databases.save_on_shutdown().await;
+    databases.close_aof();
+}
+
+/// Checks the save rules every second, like Redis' `serverCron`.
+async fn check_save_rules(databases: Databases) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        databases.save_if_due();
+    }
+}
+
+/// Flushes the append only file to disk every second, when its policy is
+/// `AppendFsync::Everysec`.
+async fn fsync_aof(databases: Databases) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        databases.fsync_aof_if_due().await;
+    }
 }
 
 impl Listener {
@@ -87,7 +377,10 @@ impl Listener {
                 .unwrap();
             let socket = self.accept().await?;
             let mut handler = Handler {
-                db: self.db_holder.db(),
+                databases: self.db_holder.databases(),
+                db: 0,
+                transaction: MultiState::default(),
+                write_offset: 0,
                 connection: Connection::new(socket),
                 shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
                 _shutdown_complete: self.shutdown_complete_tx.clone(),
@@ -126,7 +419,7 @@ impl Handler {
     async fn run(&mut self) -> crate::Result<()> {
         while !self.shutdown.is_shutdown() {
             let maybe_frame = tokio::select! {
-                res = self.connection.read_frame() => res?,
+                res = self.connection.read_request() => res?,
                 _ = self.shutdown.recv() => {
                     return Ok(());
                 }
@@ -136,11 +429,44 @@ impl Handler {
                 None => return Ok(()),
             };
 
-            let cmd = Command::from_frame(frame)?;
+            // Invalid arguments are refused with an error reply, like Redis
+            // does, rather than by closing the connection.
+            let cmd = match Command::from_frame(frame) {
+                Ok(cmd) => cmd,
+                Err(err) => {
+                    let response = Command::parse_error_reply(err);
+                    self.transaction
+                        .refuse(response, &mut self.connection)
+                        .await?;
+                    continue;
+                }
+            };
             debug!(?cmd);
 
-            cmd.apply(&self.db, &mut self.connection, &mut self.shutdown)
-                .await?;
+            // `WAIT` blocks this connection only, outside of a transaction
+            // where it is refused.
+            match cmd {
+                Command::Wait(cmd) if !self.transaction.is_queuing() => {
+                    cmd.apply(
+                        &self.databases,
+                        self.write_offset,
+                        &mut self.connection,
+                        &mut self.shutdown,
+                    )
+                    .await?
+                }
+                cmd => {
+                    cmd.apply(
+                        &self.databases,
+                        &mut self.db,
+                        &mut self.transaction,
+                        &mut self.connection,
+                        &mut self.shutdown,
+                    )
+                    .await?;
+                    self.write_offset = self.databases.repl_offset();
+                }
+            }
         }
 
         Ok(())
@@ -15,6 +15,33 @@ async fn pool_key_value_get_set() {
     assert_eq!(b"world", &value[..]);
 }
 
+#[tokio::test]
+async fn pool_counters() {
+    let (addr, _) = start_server().await;
+
+    let client = Client::connect(addr).await.unwrap();
+    let client = BufferedClient::buffer(client);
+
+    // Concurrent increments through clones of the buffered client never race.
+    let tasks: Vec<_> = (0..10)
+        .map(|_| {
+            let mut client = client.clone();
+            tokio::spawn(async move { client.incr("counter").await.unwrap() })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut client = client;
+    assert_eq!(10, client.incrby("counter", 0).await.unwrap());
+    assert_eq!(2.5, client.incrbyfloat("float", 2.5).await.unwrap());
+    assert_eq!(3, client.append("s", "abc".into()).await.unwrap());
+    assert_eq!(3, client.setrange("s", 1, "xy".into()).await.unwrap());
+    assert_eq!("axy", client.getrange("s", 0, -1).await.unwrap());
+    assert_eq!(3, client.strlen("s").await.unwrap());
+}
+
 async fn start_server() -> (SocketAddr, JoinHandle<()>) {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
@@ -1,7 +1,16 @@
-use std::net::SocketAddr;
+use std::{collections::HashSet, net::SocketAddr, ops::RangeInclusive, sync::Arc, time::Duration};
 
-use mini_redis::{server, Client};
-use tokio::{net::TcpListener, task::JoinHandle};
+use bytes::Bytes;
+use mini_redis::{
+    clients::ReplicationRole, server, Client, ExpireOptions, Frame, ScanOptions, SetOptions, Side,
+    ZaddOptions,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{oneshot, Notify},
+    task::JoinHandle,
+    time,
+};
 
 #[tokio::test]
 async fn ping_pong_without_message() {
@@ -82,6 +91,2710 @@ async fn unsubscribes_from_channels() {
     assert_eq!(subscriber.get_subscribed().len(), 0);
 }
 
+#[tokio::test]
+async fn receive_message_subscribed_pattern() {
+    let (addr, _) = start_server().await;
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.psubscribe(vec!["orders.*".into()]).await.unwrap();
+    subscriber.subscribe(&["orders.new".into()]).await.unwrap();
+
+    let mut publisher = Client::connect(addr).await.unwrap();
+    assert_eq!(
+        2,
+        publisher.publish("orders.new", "1".into()).await.unwrap()
+    );
+    assert_eq!(0, publisher.publish("users.new", "2".into()).await.unwrap());
+    assert_eq!(
+        1,
+        publisher.publish("orders.paid", "3".into()).await.unwrap()
+    );
+
+    let mut messages = vec![];
+    for _ in 0..3 {
+        let message = subscriber.next_message().await.unwrap().unwrap();
+        messages.push((message.pattern, message.channel, message.content));
+    }
+    // Messages from different subscriptions may arrive in any order.
+    messages.sort();
+    assert_eq!(
+        vec![
+            (None, "orders.new".to_string(), "1".into()),
+            (
+                Some("orders.*".to_string()),
+                "orders.new".to_string(),
+                "1".into()
+            ),
+            (
+                Some("orders.*".to_string()),
+                "orders.paid".to_string(),
+                "3".into()
+            ),
+        ],
+        messages
+    );
+
+    subscriber.punsubscribe(&[]).await.unwrap();
+    assert!(subscriber.get_subscribed_patterns().is_empty());
+    assert_eq!(1, subscriber.get_subscribed().len());
+    assert_eq!(
+        1,
+        publisher.publish("orders.new", "4".into()).await.unwrap()
+    );
+    assert_eq!(
+        0,
+        publisher.publish("orders.paid", "5".into()).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn list_push_pop_range() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let len = client
+        .rpush("queue", vec!["b".into(), "c".into()])
+        .await
+        .unwrap();
+    assert_eq!(2, len);
+    let len = client.lpush("queue", vec!["a".into()]).await.unwrap();
+    assert_eq!(3, len);
+
+    let values = client.lrange("queue", 0, -1).await.unwrap();
+    assert_eq!(vec!["a", "b", "c"], values);
+    assert_eq!(Some("c".into()), client.lindex("queue", -1).await.unwrap());
+
+    client.lset("queue", 1, "B".into()).await.unwrap();
+    assert_eq!(Some("a".into()), client.lpop("queue").await.unwrap());
+    assert_eq!(Some("c".into()), client.rpop("queue").await.unwrap());
+    assert_eq!(1, client.llen("queue").await.unwrap());
+
+    assert_eq!(Some("B".into()), client.lpop("queue").await.unwrap());
+    assert_eq!(None, client.lpop("queue").await.unwrap());
+    assert_eq!(0, client.llen("queue").await.unwrap());
+}
+
+#[tokio::test]
+async fn list_trim_and_remove() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let values = vec!["x".into(), "y".into(), "x".into(), "z".into(), "x".into()];
+    client.rpush("list", values).await.unwrap();
+
+    assert_eq!(2, client.lrem("list", -2, "x".into()).await.unwrap());
+    assert_eq!(
+        vec!["x", "y", "z"],
+        client.lrange("list", 0, -1).await.unwrap()
+    );
+
+    client.ltrim("list", 1, 5).await.unwrap();
+    assert_eq!(vec!["y", "z"], client.lrange("list", 0, 100).await.unwrap());
+}
+
+#[tokio::test]
+async fn list_command_against_string_key() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    let err = client.lpush("hello", vec!["x".into()]).await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"));
+
+    client.rpush("list", vec!["x".into()]).await.unwrap();
+    let err = client.get("list").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"));
+}
+
+#[tokio::test]
+async fn hash_field_operations() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let fields = vec![
+        ("name".to_string(), "ferris".into()),
+        ("lang".to_string(), "rust".into()),
+    ];
+    assert_eq!(2, client.hset("user:1", fields).await.unwrap());
+    let fields = vec![("lang".to_string(), "Rust".into())];
+    assert_eq!(0, client.hset("user:1", fields).await.unwrap());
+
+    assert_eq!(
+        Some("Rust".into()),
+        client.hget("user:1", "lang").await.unwrap()
+    );
+    assert_eq!(
+        vec![Some("ferris".into()), None],
+        client.hmget("user:1", &["name", "age"]).await.unwrap()
+    );
+    assert!(client.hexists("user:1", "name").await.unwrap());
+    assert_eq!(2, client.hlen("user:1").await.unwrap());
+
+    let all = client.hgetall("user:1").await.unwrap();
+    assert_eq!(2, all.len());
+    assert_eq!(b"ferris", &all["name"][..]);
+
+    assert_eq!(-3, client.hincrby("user:1", "age", -3).await.unwrap());
+    assert_eq!(7, client.hincrby("user:1", "age", 10).await.unwrap());
+    let err = client.hincrby("user:1", "name", 1).await.unwrap_err();
+    assert_eq!("ERR hash value is not an integer", err.to_string());
+
+    assert_eq!(
+        3,
+        client
+            .hdel("user:1", &["name", "lang", "age"])
+            .await
+            .unwrap()
+    );
+    assert!(client.hkeys("user:1").await.unwrap().is_empty());
+
+    client.set("hello", "world".into()).await.unwrap();
+    let err = client.hget("hello", "field").await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"));
+}
+
+#[tokio::test]
+async fn set_membership_and_algebra() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let added = client
+        .sadd("a", vec!["1".into(), "2".into(), "3".into(), "2".into()])
+        .await
+        .unwrap();
+    assert_eq!(3, added);
+    client
+        .sadd("b", vec!["2".into(), "3".into(), "4".into()])
+        .await
+        .unwrap();
+
+    assert!(client.sismember("a", "1".into()).await.unwrap());
+    assert!(!client.sismember("a", "4".into()).await.unwrap());
+    assert_eq!(3, client.scard("a").await.unwrap());
+
+    let inter: HashSet<Bytes> = ["2", "3"].into_iter().map(Bytes::from).collect();
+    assert_eq!(inter, client.sinter(&["a", "b"]).await.unwrap());
+    assert_eq!(4, client.sunion(&["a", "b"]).await.unwrap().len());
+    let diff: HashSet<Bytes> = ["1"].into_iter().map(Bytes::from).collect();
+    assert_eq!(diff, client.sdiff(&["a", "b"]).await.unwrap());
+    assert!(client.sinter(&["a", "missing"]).await.unwrap().is_empty());
+
+    assert_eq!(2, client.sinterstore("c", &["a", "b"]).await.unwrap());
+    assert_eq!(inter, client.smembers("c").await.unwrap());
+    assert_eq!(0, client.sdiffstore("c", &["c", "a"]).await.unwrap());
+    assert_eq!(0, client.scard("c").await.unwrap());
+
+    assert_eq!(
+        1,
+        client
+            .srem("a", vec!["1".into(), "9".into()])
+            .await
+            .unwrap()
+    );
+
+    client.set("hello", "world".into()).await.unwrap();
+    let err = client.sunion(&["a", "hello"]).await.unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"));
+}
+
+#[tokio::test]
+async fn sorted_set_leaderboard() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let members = vec![
+        (10.0, "alice".into()),
+        (20.0, "bob".into()),
+        (15.0, "carol".into()),
+    ];
+    assert_eq!(3, client.zadd("board", members).await.unwrap());
+
+    assert_eq!(
+        vec!["alice", "carol", "bob"],
+        client.zrange("board", 0, -1).await.unwrap()
+    );
+    assert_eq!(vec!["bob"], client.zrevrange("board", 0, 0).await.unwrap());
+    assert_eq!(
+        Some(1),
+        client.zrank("board", "carol".into()).await.unwrap()
+    );
+    assert_eq!(
+        Some(20.0),
+        client.zscore("board", "bob".into()).await.unwrap()
+    );
+    assert_eq!(None, client.zscore("board", "dave".into()).await.unwrap());
+
+    assert_eq!(
+        25.5,
+        client.zincrby("board", 10.5, "carol".into()).await.unwrap()
+    );
+    assert_eq!(
+        vec![(Bytes::from("bob"), 20.0), (Bytes::from("carol"), 25.5)],
+        client
+            .zrangebyscore("board", "(10", "+inf", None)
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        vec![(Bytes::from("carol"), 25.5)],
+        client
+            .zrangebyscore("board", "-inf", "inf", Some((2, 5)))
+            .await
+            .unwrap()
+    );
+
+    assert_eq!(
+        vec![(Bytes::from("alice"), 10.0)],
+        client.zpopmin("board", 1).await.unwrap()
+    );
+    assert_eq!(1, client.zrem("board", vec!["bob".into()]).await.unwrap());
+    assert_eq!(
+        vec![(Bytes::from("carol"), 25.5)],
+        client.zrange_withscores("board", 0, -1).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn sorted_set_zadd_flags() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.zadd("z", vec![(1.0, "a".into())]).await.unwrap();
+
+    let nx = ZaddOptions::new().nx();
+    let members = vec![(5.0, "a".into()), (2.0, "b".into())];
+    assert_eq!(1, client.zadd_options("z", nx, members).await.unwrap());
+    assert_eq!(Some(1.0), client.zscore("z", "a".into()).await.unwrap());
+
+    let xx_ch = ZaddOptions::new().xx().ch();
+    let members = vec![(3.0, "a".into()), (9.0, "c".into())];
+    assert_eq!(1, client.zadd_options("z", xx_ch, members).await.unwrap());
+    assert_eq!(None, client.zscore("z", "c".into()).await.unwrap());
+
+    let gt = ZaddOptions::new().gt();
+    assert_eq!(
+        None,
+        client.zadd_incr("z", gt, -1.0, "a".into()).await.unwrap()
+    );
+    assert_eq!(
+        Some(4.0),
+        client.zadd_incr("z", gt, 1.0, "a".into()).await.unwrap()
+    );
+
+    client
+        .zadd(
+            "lex",
+            vec![(0.0, "a".into()), (0.0, "b".into()), (0.0, "c".into())],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        vec!["b", "c"],
+        client.zrangebylex("lex", "(a", "+").await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn stream_add_range_trim() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let field = |value: &str| vec![(Bytes::from("f"), Bytes::from(value.to_string()))];
+    assert_eq!(
+        "1-1",
+        client.xadd("s", "1-1", field("a"), None).await.unwrap()
+    );
+    assert_eq!(
+        "1-2",
+        client.xadd("s", "1-*", field("b"), None).await.unwrap()
+    );
+    assert!(client.xadd("s", "1-1", field("c"), None).await.is_err());
+    client.xadd("s", "*", field("c"), None).await.unwrap();
+    assert_eq!(3, client.xlen("s").await.unwrap());
+
+    let records = client.xrange("s", "-", "+", Some(2)).await.unwrap();
+    let ids: Vec<_> = records.iter().map(|record| &record.id[..]).collect();
+    assert_eq!(vec!["1-1", "1-2"], ids);
+    assert_eq!(field("a"), records[0].fields);
+
+    let records = client.xrevrange("s", "+", "(1-1", None).await.unwrap();
+    assert_eq!(2, records.len());
+    assert_eq!("1-2", records[1].id);
+
+    assert_eq!(1, client.xtrim("s", 2).await.unwrap());
+    client.xadd("s", "*", field("d"), Some(2)).await.unwrap();
+    assert_eq!(2, client.xlen("s").await.unwrap());
+}
+
+#[tokio::test]
+async fn stream_consumer_groups() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client
+        .xgroup_create("jobs", "workers", "$", true)
+        .await
+        .unwrap();
+    for job in ["a", "b", "c"] {
+        let fields = vec![(Bytes::from("job"), Bytes::from(job))];
+        client.xadd("jobs", "*", fields, None).await.unwrap();
+    }
+
+    // Each new record is delivered to a single consumer.
+    let read = client
+        .xreadgroup("workers", "alice", Some(2), &[("jobs", ">")])
+        .await
+        .unwrap();
+    assert_eq!(1, read.len());
+    let alice: Vec<_> = read[0].1.iter().map(|record| record.id.clone()).collect();
+    assert_eq!(2, alice.len());
+
+    let read = client
+        .xreadgroup("workers", "bob", None, &[("jobs", ">")])
+        .await
+        .unwrap();
+    let bob = read[0].1[0].id.clone();
+    assert!(client
+        .xreadgroup("workers", "bob", None, &[("jobs", ">")])
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Unacknowledged records stay pending and can be read again.
+    assert_eq!(1, client.xack("jobs", "workers", &[&bob]).await.unwrap());
+    assert_eq!(2, client.xpending("jobs", "workers").await.unwrap());
+    let pending = client
+        .xpending_range("jobs", "workers", "-", "+", 10)
+        .await
+        .unwrap();
+    assert_eq!("alice", pending[0].consumer);
+    assert_eq!(1, pending[0].deliveries);
+    let read = client
+        .xreadgroup("workers", "alice", None, &[("jobs", "0")])
+        .await
+        .unwrap();
+    assert_eq!(2, read[0].1.len());
+
+    // Another consumer takes over the pending records of a dead one.
+    let claimed = client
+        .xclaim("jobs", "workers", "bob", 0, &[&alice[0]])
+        .await
+        .unwrap();
+    assert_eq!(alice[0], claimed[0].id);
+    let (next, claimed) = client
+        .xautoclaim("jobs", "workers", "bob", 0, "0", None)
+        .await
+        .unwrap();
+    assert_eq!("0-0", next);
+    assert_eq!(2, claimed.len());
+    let pending = client
+        .xpending_range("jobs", "workers", "-", "+", 10)
+        .await
+        .unwrap();
+    assert!(pending.iter().all(|entry| entry.consumer == "bob"));
+
+    assert!(client
+        .xreadgroup("nobody", "alice", None, &[("jobs", ">")])
+        .await
+        .is_err());
+    assert!(client.xgroup_destroy("jobs", "workers").await.unwrap());
+}
+
+#[tokio::test]
+async fn blocking_pop_waits_for_push() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let waiter = tokio::spawn(async move {
+        let mut client = Client::connect(addr).await.unwrap();
+        client.blpop(&["empty", "jobs"], None).await.unwrap()
+    });
+
+    // Give the waiter time to block before pushing.
+    time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiter.is_finished());
+    client.rpush("jobs", vec!["a".into()]).await.unwrap();
+
+    let popped = time::timeout(Duration::from_secs(1), waiter)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Some(("jobs".to_string(), Bytes::from("a"))), popped);
+    assert_eq!(0, client.llen("jobs").await.unwrap());
+
+    // Elements already present are popped without blocking.
+    client
+        .rpush("jobs", vec!["b".into(), "c".into()])
+        .await
+        .unwrap();
+    assert_eq!(
+        Some(("jobs".to_string(), Bytes::from("c"))),
+        client.brpop(&["jobs"], None).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn blocking_pop_times_out() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let timeout = Some(Duration::from_millis(50));
+    assert_eq!(None, client.blpop(&["empty"], timeout).await.unwrap());
+    assert_eq!(
+        None,
+        client
+            .blmove("empty", "dst", Side::Left, Side::Right, timeout)
+            .await
+            .unwrap()
+    );
+
+    client.set("string", "value".into()).await.unwrap();
+    assert!(client.blpop(&["string"], timeout).await.is_err());
+}
+
+#[tokio::test]
+async fn blocking_move_waits_for_source() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let waiter = tokio::spawn(async move {
+        let mut client = Client::connect(addr).await.unwrap();
+        client
+            .blmove("src", "dst", Side::Right, Side::Left, None)
+            .await
+            .unwrap()
+    });
+
+    time::sleep(Duration::from_millis(50)).await;
+    client
+        .rpush("src", vec!["a".into(), "b".into()])
+        .await
+        .unwrap();
+    assert_eq!(Some(Bytes::from("b")), waiter.await.unwrap());
+    assert_eq!(vec!["a"], client.lrange("src", 0, -1).await.unwrap());
+    assert_eq!(vec!["b"], client.lrange("dst", 0, -1).await.unwrap());
+}
+
+#[tokio::test]
+async fn blocking_pop_released_on_shutdown() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = tokio::spawn(server::run(listener, shutdown_rx));
+
+    let waiter = tokio::spawn(async move {
+        let mut client = Client::connect(addr).await.unwrap();
+        client.blpop(&["jobs"], None).await
+    });
+    time::sleep(Duration::from_millis(50)).await;
+
+    shutdown_tx.send(()).unwrap();
+    time::timeout(Duration::from_secs(1), server)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(waiter.await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn string_counters() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert_eq!(1, client.incr("counter").await.unwrap());
+    assert_eq!(11, client.incrby("counter", 10).await.unwrap());
+    assert_eq!(10, client.decr("counter").await.unwrap());
+    assert_eq!(-5, client.decrby("counter", 15).await.unwrap());
+    assert_eq!(b"-5", &client.get("counter").await.unwrap().unwrap()[..]);
+
+    client
+        .set("max", i64::MAX.to_string().into())
+        .await
+        .unwrap();
+    let err = client.incr("max").await.unwrap_err();
+    assert!(err.to_string().contains("overflow"), "{}", err);
+
+    client.set("word", "hello".into()).await.unwrap();
+    let err = client.incr("word").await.unwrap_err();
+    assert!(err.to_string().contains("not an integer"), "{}", err);
+
+    client.lpush("list", vec!["a".into()]).await.unwrap();
+    assert!(client.incr("list").await.is_err());
+
+    assert_eq!(10.5, client.incrbyfloat("float", 10.5).await.unwrap());
+    assert_eq!(5.0, client.incrbyfloat("float", -5.5).await.unwrap());
+    assert_eq!(b"5", &client.get("float").await.unwrap().unwrap()[..]);
+}
+
+#[tokio::test]
+async fn string_byte_ranges() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert_eq!(5, client.append("s", "Hello".into()).await.unwrap());
+    assert_eq!(11, client.append("s", " World".into()).await.unwrap());
+    assert_eq!(11, client.strlen("s").await.unwrap());
+    assert_eq!(0, client.strlen("missing").await.unwrap());
+
+    assert_eq!("Hello", client.getrange("s", 0, 4).await.unwrap());
+    assert_eq!("World", client.getrange("s", -5, -1).await.unwrap());
+    assert_eq!("Hello World", client.getrange("s", 0, 100).await.unwrap());
+    assert_eq!("", client.getrange("s", 5, 2).await.unwrap());
+
+    assert_eq!(11, client.setrange("s", 6, "Redis".into()).await.unwrap());
+    assert_eq!("Hello Redis", client.get("s").await.unwrap().unwrap());
+
+    assert_eq!(5, client.setrange("padded", 2, "abc".into()).await.unwrap());
+    assert_eq!(
+        &b"\0\0abc"[..],
+        &client.get("padded").await.unwrap().unwrap()[..]
+    );
+    assert_eq!(0, client.setrange("empty", 3, Bytes::new()).await.unwrap());
+    assert_eq!(None, client.get("empty").await.unwrap());
+}
+
+#[tokio::test]
+async fn set_options() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    // `SET lock token NX PX 30000` only succeeds for the first owner.
+    let lock = SetOptions::new().nx().px(30000);
+    assert!(client.set_options("lock", "a".into(), lock).await.unwrap());
+    assert!(!client.set_options("lock", "b".into(), lock).await.unwrap());
+    assert_eq!("a", client.get("lock").await.unwrap().unwrap());
+
+    let xx = SetOptions::new().xx();
+    assert!(!client.set_options("missing", "v".into(), xx).await.unwrap());
+    assert_eq!(None, client.get("missing").await.unwrap());
+    assert!(client.set_options("lock", "c".into(), xx).await.unwrap());
+
+    let get = SetOptions::new();
+    assert_eq!(
+        Some("c".into()),
+        client.set_get("lock", "d".into(), get).await.unwrap()
+    );
+    assert_eq!(
+        None,
+        client.set_get("fresh", "v".into(), get).await.unwrap()
+    );
+    client.lpush("list", vec!["a".into()]).await.unwrap();
+    assert!(client.set_get("list", "v".into(), get).await.is_err());
+
+    // A timestamp in the past deletes the key.
+    assert!(client
+        .set_options("lock", "e".into(), SetOptions::new().exat(1))
+        .await
+        .unwrap());
+    assert_eq!(None, client.get("lock").await.unwrap());
+}
+
+#[tokio::test]
+async fn set_keepttl() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let ttl = SetOptions::new().px(100);
+    client.set_options("k", "a".into(), ttl).await.unwrap();
+    let keepttl = SetOptions::new().keepttl();
+    client.set_options("k", "b".into(), keepttl).await.unwrap();
+    assert_eq!("b", client.get("k").await.unwrap().unwrap());
+
+    time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(None, client.get("k").await.unwrap());
+}
+
+#[tokio::test]
+async fn set_rejects_conflicting_options() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    // The errors are replied to, the connection stays open.
+    let nx_xx = SetOptions::new().nx().xx();
+    let err = client
+        .set_options("k", "v".into(), nx_xx)
+        .await
+        .unwrap_err();
+    assert_eq!("ERR syntax error", err.to_string());
+
+    let zero = SetOptions::new().ex(0);
+    let err = client.set_options("k", "v".into(), zero).await.unwrap_err();
+    assert_eq!("ERR invalid expire time in 'set' command", err.to_string());
+
+    client.set("k", "v".into()).await.unwrap();
+    assert_eq!(Some(Bytes::from("v")), client.get("k").await.unwrap());
+}
+
+#[tokio::test]
+async fn multi_key_strings() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client
+        .mset(&[("a", "1".into()), ("b", "2".into())])
+        .await
+        .unwrap();
+    client.lpush("list", vec!["x".into()]).await.unwrap();
+    assert_eq!(
+        vec![Some("1".into()), None, Some("2".into()), None],
+        client.mget(&["a", "missing", "b", "list"]).await.unwrap()
+    );
+
+    // `MSETNX` writes nothing when any key exists.
+    assert!(!client
+        .msetnx(&[("c", "3".into()), ("a", "x".into())])
+        .await
+        .unwrap());
+    assert_eq!(None, client.get("c").await.unwrap());
+    assert!(client
+        .msetnx(&[("c", "3".into()), ("d", "4".into())])
+        .await
+        .unwrap());
+    assert_eq!(Some("3".into()), client.get("c").await.unwrap());
+
+    assert_eq!(Some("1".into()), client.getdel("a").await.unwrap());
+    assert_eq!(None, client.getdel("a").await.unwrap());
+    assert!(client.getdel("list").await.is_err());
+
+    assert!(client.setnx("e", "5".into()).await.unwrap());
+    assert!(!client.setnx("e", "6".into()).await.unwrap());
+    assert_eq!(Some("5".into()), client.get("e").await.unwrap());
+}
+
+#[tokio::test]
+async fn string_expirations() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.setex("a", 1, "1".into()).await.unwrap();
+    client.set("b", "2".into()).await.unwrap();
+    assert_eq!(
+        Some("2".into()),
+        client.getex("b", Duration::from_millis(100)).await.unwrap()
+    );
+    // `PERSIST` cancels the one second expiration of `SETEX`.
+    assert_eq!(Some("1".into()), client.getex_persist("a").await.unwrap());
+
+    time::sleep(Duration::from_millis(1200)).await;
+    assert_eq!(None, client.get("b").await.unwrap());
+    assert_eq!(Some("1".into()), client.get("a").await.unwrap());
+
+    // `MSET` clears existing expirations.
+    client
+        .set_expirse("c", "3".into(), Duration::from_millis(100))
+        .await
+        .unwrap();
+    client.mset(&[("c", "4".into())]).await.unwrap();
+    time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(Some("4".into()), client.get("c").await.unwrap());
+}
+
+#[tokio::test]
+async fn key_space_commands() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("s", "v".into()).await.unwrap();
+    client.rpush("l", vec!["a".into()]).await.unwrap();
+    client.sadd("set", vec!["m".into()]).await.unwrap();
+    assert_eq!("string", client.key_type("s").await.unwrap());
+    assert_eq!("list", client.key_type("l").await.unwrap());
+    assert_eq!("set", client.key_type("set").await.unwrap());
+    assert_eq!("none", client.key_type("missing").await.unwrap());
+
+    assert_eq!(3, client.exists(&["s", "s", "l", "missing"]).await.unwrap());
+    assert_eq!(2, client.touch(&["s", "l", "missing"]).await.unwrap());
+    assert_eq!(2, client.del(&["s", "l", "missing"]).await.unwrap());
+    assert_eq!(0, client.exists(&["s", "l"]).await.unwrap());
+
+    // UNLINK frees large values off the lock.
+    let values = (0..1000).map(|i| Bytes::from(i.to_string())).collect();
+    client.rpush("big", values).await.unwrap();
+    assert_eq!(2, client.unlink(&["big", "set"]).await.unwrap());
+    assert_eq!(0, client.exists(&["big", "set"]).await.unwrap());
+}
+
+#[tokio::test]
+async fn rename_and_copy() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert!(client.rename("missing", "b").await.is_err());
+
+    client.set("a", "1".into()).await.unwrap();
+    client.set("b", "2".into()).await.unwrap();
+    assert!(!client.renamenx("a", "b").await.unwrap());
+    client.rename("a", "b").await.unwrap();
+    assert_eq!(None, client.get("a").await.unwrap());
+    assert_eq!(Some("1".into()), client.get("b").await.unwrap());
+    assert!(client.renamenx("b", "c").await.unwrap());
+
+    // Copies are independent of the original.
+    client
+        .rpush("list", vec!["x".into(), "y".into()])
+        .await
+        .unwrap();
+    assert!(client.copy("list", "copy", false).await.unwrap());
+    client.rpop("copy").await.unwrap();
+    assert_eq!(vec!["x", "y"], client.lrange("list", 0, -1).await.unwrap());
+    assert_eq!(vec!["x"], client.lrange("copy", 0, -1).await.unwrap());
+    assert!(!client.copy("list", "copy", false).await.unwrap());
+    assert!(client.copy("list", "copy", true).await.unwrap());
+    assert_eq!(2, client.llen("copy").await.unwrap());
+    assert!(!client.copy("missing", "copy", true).await.unwrap());
+    assert!(client.copy("list", "list", true).await.is_err());
+
+    // Expirations follow renamed and copied keys.
+    client
+        .set_expirse("ttl", "v".into(), Duration::from_millis(100))
+        .await
+        .unwrap();
+    client.copy("ttl", "ttl-copy", false).await.unwrap();
+    client.rename("ttl", "ttl-renamed").await.unwrap();
+    time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(
+        0,
+        client
+            .exists(&["ttl", "ttl-copy", "ttl-renamed"])
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn expire_ttl_and_persist() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    assert_eq!(-2, client.ttl("missing").await.unwrap());
+    assert!(!client.expire("missing", 10).await.unwrap());
+
+    client.set("key", "v".into()).await.unwrap();
+    assert_eq!(-1, client.ttl("key").await.unwrap());
+    assert_eq!(-1, client.pexpiretime("key").await.unwrap());
+    assert!(!client.persist("key").await.unwrap());
+
+    assert!(client.expire("key", 100).await.unwrap());
+    assert_eq!(100, client.ttl("key").await.unwrap());
+    let pttl = client.pttl("key").await.unwrap();
+    assert!(pttl > 99_000 && pttl <= 100_000);
+
+    let unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert!(client.expireat("key", unix + 1000).await.unwrap());
+    let expiretime = client.expiretime("key").await.unwrap() as u64;
+    assert!((unix + 999..=unix + 1000).contains(&expiretime));
+
+    assert!(client.persist("key").await.unwrap());
+    assert_eq!(-1, client.ttl("key").await.unwrap());
+
+    // A deadline in the past deletes the key.
+    assert!(client.pexpireat("key", 1).await.unwrap());
+    assert_eq!(None, client.get("key").await.unwrap());
+}
+
+#[tokio::test]
+async fn expire_options() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    client.set("key", "v".into()).await.unwrap();
+
+    let (nx, xx) = (ExpireOptions::new().nx(), ExpireOptions::new().xx());
+    let (gt, lt) = (ExpireOptions::new().gt(), ExpireOptions::new().lt());
+
+    // Without an expiration, GT never applies and LT always does.
+    assert!(!client.expire_options("key", 100, xx).await.unwrap());
+    assert!(!client.expire_options("key", 100, gt).await.unwrap());
+    assert!(client.expire_options("key", 100, lt).await.unwrap());
+    assert!(!client.expire_options("key", 50, nx).await.unwrap());
+
+    assert!(!client.expire_options("key", 50, gt).await.unwrap());
+    assert!(client.expire_options("key", 200, gt).await.unwrap());
+    assert!(!client.expire_options("key", 300, lt).await.unwrap());
+    assert!(client.expire_options("key", 150, xx).await.unwrap());
+    assert_eq!(150, client.ttl("key").await.unwrap());
+
+    // Incompatible options are refused, the connection stays open.
+    let err = client
+        .expire_options("key", 10, ExpireOptions::new().nx().xx())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        "ERR NX and XX, GT or LT options at the same time are not compatible",
+        err.to_string()
+    );
+    assert!(client
+        .expire_options("key", 10, ExpireOptions::new().nx().gt())
+        .await
+        .is_err());
+    assert_eq!(150, client.ttl("key").await.unwrap());
+}
+
+#[tokio::test]
+async fn expire_wakes_purge_task() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    // The purge task sleeps until the later deadline unless it is woken up
+    // for the earlier one.
+    client
+        .set_expirse("late", "v".into(), Duration::from_secs(100))
+        .await
+        .unwrap();
+    client.set("early", "v".into()).await.unwrap();
+    assert!(client.pexpire("early", 100).await.unwrap());
+
+    time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(0, client.exists(&["early"]).await.unwrap());
+    assert_eq!(1, client.exists(&["late"]).await.unwrap());
+}
+
+#[tokio::test]
+async fn keys_glob_patterns() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    for key in ["hello", "hallo", "hxllo", "hllo", "heeeello", "h*llo"] {
+        client.set(key, "v".into()).await.unwrap();
+    }
+
+    let keys = sorted_keys(&mut client, "h?llo").await;
+    assert_eq!(vec!["h*llo", "hallo", "hello", "hxllo"], keys);
+    assert_eq!(6, sorted_keys(&mut client, "h*llo").await.len());
+    let keys = sorted_keys(&mut client, "h[ae]llo").await;
+    assert_eq!(vec!["hallo", "hello"], keys);
+    let keys = sorted_keys(&mut client, "h[^e]llo").await;
+    assert_eq!(vec!["h*llo", "hallo", "hxllo"], keys);
+    assert_eq!(vec!["hallo"], sorted_keys(&mut client, "h[a-b]llo").await);
+    assert_eq!(vec!["h*llo"], sorted_keys(&mut client, "h\\*llo").await);
+    assert!(sorted_keys(&mut client, "hello?").await.is_empty());
+}
+
+async fn sorted_keys(client: &mut Client, pattern: &str) -> Vec<String> {
+    let mut keys = client.keys(pattern).await.unwrap();
+    keys.sort();
+    keys
+}
+
+#[tokio::test]
+async fn scan_returns_every_key_once() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    for i in 0..100 {
+        client.set(&format!("key:{}", i), "v".into()).await.unwrap();
+    }
+    client.rpush("list:0", vec!["a".into()]).await.unwrap();
+
+    let mut seen = vec![];
+    let mut cursor = 0;
+    let mut added = 0;
+    loop {
+        let options = ScanOptions::new().count(7).pattern("key:*");
+        let (next, keys) = client.scan(cursor, options).await.unwrap();
+        assert!(keys.len() <= 7);
+        seen.extend(keys);
+
+        // Keys written in the middle of the scan don't disturb the cursor.
+        client
+            .set(&format!("key:new:{}", added), "v".into())
+            .await
+            .unwrap();
+        added += 1;
+
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+
+    let unique: HashSet<_> = seen.iter().cloned().collect();
+    assert_eq!(seen.len(), unique.len());
+    for i in 0..100 {
+        assert!(unique.contains(&format!("key:{}", i)));
+    }
+    assert!(!unique.contains("list:0"));
+
+    let (_, lists) = client
+        .scan(0, ScanOptions::new().count(1000).key_type("list"))
+        .await
+        .unwrap();
+    assert_eq!(vec!["list:0"], lists);
+    assert!(client.scan(0, ScanOptions::new().count(0)).await.is_err());
+}
+
+#[tokio::test]
+async fn scan_iter_streams_keys() {
+    use tokio_stream::StreamExt;
+
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    for i in 0..25 {
+        client.set(&format!("key:{}", i), "v".into()).await.unwrap();
+    }
+
+    let keys: Vec<String> = client
+        .scan_iter(ScanOptions::new().count(4))
+        .collect::<mini_redis::Result<_>>()
+        .await
+        .unwrap();
+    let keys: HashSet<String> = keys.into_iter().collect();
+    assert_eq!(25, keys.len());
+}
+
+#[tokio::test]
+async fn collection_scans() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let fields = (0..20)
+        .map(|i| (format!("f{}", i), Bytes::from(i.to_string())))
+        .collect();
+    client.hset("hash", fields).await.unwrap();
+    let members = (0..20).map(|i| Bytes::from(format!("m{}", i))).collect();
+    client.sadd("set", members).await.unwrap();
+    let members = (0..20)
+        .map(|i| (i as f64, Bytes::from(format!("m{}", i))))
+        .collect();
+    client.zadd("zset", members).await.unwrap();
+
+    let mut cursor = 0;
+    let mut hash = vec![];
+    loop {
+        let (next, page) = client
+            .hscan("hash", cursor, ScanOptions::new().count(3))
+            .await
+            .unwrap();
+        hash.extend(page);
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    assert_eq!(20, hash.len());
+    assert!(hash.contains(&("f7".to_string(), Bytes::from("7"))));
+
+    let (cursor, set) = client
+        .sscan("set", 0, ScanOptions::new().count(100).pattern("m1*"))
+        .await
+        .unwrap();
+    assert_eq!(0, cursor);
+    assert_eq!(11, set.len());
+
+    let (_, zset) = client
+        .zscan("zset", 0, ScanOptions::new().count(100).pattern("m5"))
+        .await
+        .unwrap();
+    assert_eq!(vec![(Bytes::from("m5"), 5.0)], zset);
+
+    assert_eq!(
+        (0, vec![]),
+        client
+            .sscan("missing", 0, ScanOptions::new())
+            .await
+            .unwrap()
+    );
+    assert!(client.zscan("hash", 0, ScanOptions::new()).await.is_err());
+}
+
+#[tokio::test]
+async fn select_isolates_databases() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let mut other = Client::connect(addr).await.unwrap();
+
+    client.set("key", "zero".into()).await.unwrap();
+    client.select(1).await.unwrap();
+    assert_eq!(None, client.get("key").await.unwrap());
+    client.set("key", "one".into()).await.unwrap();
+    client.set("only-one", "v".into()).await.unwrap();
+    assert_eq!(2, client.dbsize().await.unwrap());
+
+    // The selection is per connection.
+    assert_eq!(Some("zero".into()), other.get("key").await.unwrap());
+    assert_eq!(1, other.dbsize().await.unwrap());
+
+    assert!(client.select(16).await.is_err());
+    assert_eq!(Some("one".into()), client.get("key").await.unwrap());
+}
+
+#[tokio::test]
+async fn move_and_swap_databases() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let mut other = Client::connect(addr).await.unwrap();
+
+    client
+        .set_expirse("ttl", "v".into(), Duration::from_secs(100))
+        .await
+        .unwrap();
+    client.set("taken", "zero".into()).await.unwrap();
+    assert!(client.move_key("ttl", 2).await.unwrap());
+    assert!(!client.move_key("ttl", 2).await.unwrap());
+    assert!(client.move_key("taken", 0).await.is_err());
+    assert!(client.move_key("taken", 99).await.is_err());
+
+    other.select(2).await.unwrap();
+    other.set("taken", "two".into()).await.unwrap();
+    assert!(!client.move_key("taken", 2).await.unwrap());
+    assert_eq!(100, other.ttl("ttl").await.unwrap());
+
+    // Connections keep their index and see the swapped contents.
+    client.swapdb(0, 2).await.unwrap();
+    assert_eq!(Some("two".into()), client.get("taken").await.unwrap());
+    assert_eq!(100, client.ttl("ttl").await.unwrap());
+    assert_eq!(Some("zero".into()), other.get("taken").await.unwrap());
+    assert_eq!(1, other.dbsize().await.unwrap());
+}
+
+#[tokio::test]
+async fn swapdb_wakes_blocked_clients() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    client.select(1).await.unwrap();
+    client.rpush("list", vec!["a".into()]).await.unwrap();
+
+    let popped = tokio::spawn(async move {
+        let mut blocked = Client::connect(addr).await.unwrap();
+        blocked.blpop(&["list"], None).await.unwrap()
+    });
+    time::sleep(Duration::from_millis(50)).await;
+    client.swapdb(0, 1).await.unwrap();
+
+    let popped = time::timeout(Duration::from_secs(1), popped).await.unwrap();
+    assert_eq!(Some(("list".into(), "a".into())), popped.unwrap());
+}
+
+#[tokio::test]
+async fn flushdb_and_flushall() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("a", "v".into()).await.unwrap();
+    client.select(1).await.unwrap();
+    client.set("b", "v".into()).await.unwrap();
+    client.set("c", "v".into()).await.unwrap();
+
+    client.flushdb().await.unwrap();
+    assert_eq!(0, client.dbsize().await.unwrap());
+    client.select(0).await.unwrap();
+    assert_eq!(1, client.dbsize().await.unwrap());
+
+    client.select(1).await.unwrap();
+    client.set("b", "v".into()).await.unwrap();
+    client.flushall().await.unwrap();
+    assert_eq!(0, client.dbsize().await.unwrap());
+    client.select(0).await.unwrap();
+    assert_eq!(0, client.dbsize().await.unwrap());
+}
+
+#[tokio::test]
+async fn pub_sub_is_server_wide() {
+    let (addr, _) = start_server().await;
+    let client = Client::connect(addr).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["hello".into()]).await.unwrap();
+
+    let mut publisher = Client::connect(addr).await.unwrap();
+    publisher.select(3).await.unwrap();
+    assert_eq!(1, publisher.publish("hello", "world".into()).await.unwrap());
+
+    let message = subscriber.next_message().await.unwrap().unwrap();
+    assert_eq!(b"world", &message.content[..]);
+}
+
+#[tokio::test]
+async fn transaction_runs_queued_commands() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("counter", "10".into()).await.unwrap();
+    let replies = client
+        .transaction()
+        .incr("counter")
+        .incrby("counter", 5)
+        .get("counter")
+        .rpush("list", vec!["a".into(), "b".into()])
+        .exec()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        vec![
+            Frame::Integer(11),
+            Frame::Integer(16),
+            Frame::Bulk("16".into()),
+            Frame::Integer(2),
+        ],
+        replies
+    );
+
+    // Errors of a queued command are replied in place, the others still run.
+    client.set("text", "hello".into()).await.unwrap();
+    let replies = client
+        .transaction()
+        .incr("text")
+        .set("text", "world".into())
+        .exec()
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(replies[0], Frame::Error(_)));
+    assert_eq!(replies[1], "OK");
+    assert_eq!(Some("world".into()), client.get("text").await.unwrap());
+}
+
+#[tokio::test]
+async fn transaction_aborts_on_watched_key_change() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let mut other = Client::connect(addr).await.unwrap();
+
+    client.set("balance", "100".into()).await.unwrap();
+    client.watch(&["balance"]).await.unwrap();
+    other.set("balance", "50".into()).await.unwrap();
+
+    let replies = client
+        .transaction()
+        .incrby("balance", 10)
+        .exec()
+        .await
+        .unwrap();
+    assert_eq!(None, replies);
+    assert_eq!(Some("50".into()), client.get("balance").await.unwrap());
+
+    // EXEC unwatched the key.
+    other.set("balance", "60".into()).await.unwrap();
+    let replies = client
+        .transaction()
+        .incrby("balance", 10)
+        .exec()
+        .await
+        .unwrap();
+    assert_eq!(Some(vec![Frame::Integer(70)]), replies);
+
+    // Modifying another key, or unwatching, lets the transaction run.
+    client.watch(&["balance"]).await.unwrap();
+    other.set("other", "1".into()).await.unwrap();
+    let replies = client.transaction().incr("balance").exec().await.unwrap();
+    assert_eq!(Some(vec![Frame::Integer(71)]), replies);
+
+    client.watch(&["balance"]).await.unwrap();
+    client.unwatch().await.unwrap();
+    other.del(&["balance"]).await.unwrap();
+    let replies = client.transaction().incr("balance").exec().await.unwrap();
+    assert_eq!(Some(vec![Frame::Integer(1)]), replies);
+}
+
+#[tokio::test]
+async fn transaction_watch_sees_expiration_and_flush() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let mut other = Client::connect(addr).await.unwrap();
+
+    client.set("key", "value".into()).await.unwrap();
+    client.watch(&["key"]).await.unwrap();
+    other.expire("key", 100).await.unwrap();
+    let replies = client.transaction().get("key").exec().await.unwrap();
+    assert_eq!(None, replies);
+
+    client.watch(&["key"]).await.unwrap();
+    other.flushall().await.unwrap();
+    let replies = client.transaction().get("key").exec().await.unwrap();
+    assert_eq!(None, replies);
+}
+
+#[tokio::test]
+async fn transaction_watch_ignores_writes_changing_nothing() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let mut other = Client::connect(addr).await.unwrap();
+
+    // Popping a missing list, deleting a missing field or failing with
+    // WRONGTYPE leaves the watched keys alone.
+    client
+        .hset("hash", vec![("f".to_string(), "v".into())])
+        .await
+        .unwrap();
+    client.watch(&["list", "hash"]).await.unwrap();
+    assert_eq!(None, other.lpop("list").await.unwrap());
+    assert_eq!(0, other.hdel("hash", &["missing"]).await.unwrap());
+    assert!(other.lpop("hash").await.is_err());
+
+    let replies = client.transaction().get("list").exec().await.unwrap();
+    assert_eq!(Some(vec![Frame::Null]), replies);
+}
+
+#[tokio::test]
+async fn transaction_refuses_unknown_commands() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let unknown = Frame::Array(vec![Frame::Bulk("nosuchcommand".into())]);
+    let err = client
+        .transaction()
+        .set("key", "value".into())
+        .command(unknown)
+        .exec()
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("unknown command"), "{}", err);
+
+    // The transaction was discarded and the connection is usable.
+    assert_eq!(None, client.get("key").await.unwrap());
+    let replies = client.transaction().get("key").exec().await.unwrap();
+    assert_eq!(Some(vec![Frame::Null]), replies);
+}
+
+#[tokio::test]
+async fn transaction_select_applies_to_later_commands() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let select = Frame::Array(vec![Frame::Bulk("select".into()), Frame::Bulk("1".into())]);
+    let replies = client
+        .transaction()
+        .command(select)
+        .set("key", "one".into())
+        .exec()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(2, replies.len());
+
+    // The selection outlives the transaction.
+    assert_eq!(Some("one".into()), client.get("key").await.unwrap());
+    client.select(0).await.unwrap();
+    assert_eq!(None, client.get("key").await.unwrap());
+}
+
+#[tokio::test]
+async fn transaction_is_atomic() {
+    let (addr, _) = start_server().await;
+
+    let mut tasks = vec![];
+    for _ in 0..4 {
+        tasks.push(tokio::spawn(async move {
+            let mut client = Client::connect(addr).await.unwrap();
+            for _ in 0..25 {
+                let replies = client
+                    .transaction()
+                    .incr("a")
+                    .incr("b")
+                    .exec()
+                    .await
+                    .unwrap()
+                    .unwrap();
+                // No other transaction ran between the two increments.
+                assert_eq!(replies[0], replies[1]);
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn eval_runs_scripts() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let reply = client
+        .eval(
+            "return {KEYS[1], ARGV[1], 3, 4.7, true, false}",
+            &["key"],
+            vec!["arg".into()],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk("key".into()),
+            Frame::Bulk("arg".into()),
+            Frame::Integer(3),
+            Frame::Integer(4),
+            Frame::Integer(1),
+            Frame::Null,
+        ]),
+        reply
+    );
+
+    let script = "redis.call('SET', KEYS[1], ARGV[1]); return redis.call('INCRBY', KEYS[1], 5)";
+    let reply = client
+        .eval(script, &["counter"], vec!["10".into()])
+        .await
+        .unwrap();
+    assert_eq!(Frame::Integer(15), reply);
+    assert_eq!(Some("15".into()), client.get("counter").await.unwrap());
+
+    // Replies convert back and forth like in Redis.
+    let reply = client
+        .eval("return redis.call('SET', KEYS[1], 'x')", &["key"], vec![])
+        .await
+        .unwrap();
+    assert_eq!(Frame::Simple("OK".into()), reply);
+    let reply = client
+        .eval("return redis.call('GET', 'missing') == false", &[], vec![])
+        .await
+        .unwrap();
+    assert_eq!(Frame::Integer(1), reply);
+    let reply = client
+        .eval("return redis.status_reply('FINE')", &[], vec![])
+        .await
+        .unwrap();
+    assert_eq!(Frame::Simple("FINE".into()), reply);
+}
+
+#[tokio::test]
+async fn eval_errors() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.lpush("list", vec!["a".into()]).await.unwrap();
+    let err = client
+        .eval("return redis.call('GET', KEYS[1])", &["list"], vec![])
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGTYPE"), "{}", err);
+
+    // pcall hands the error to the script instead.
+    let reply = client
+        .eval(
+            "return redis.pcall('GET', KEYS[1])['err']",
+            &["list"],
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert!(matches!(reply, Frame::Bulk(msg) if msg.starts_with(b"WRONGTYPE")));
+
+    let err = client.eval("return +", &[], vec![]).await.unwrap_err();
+    assert!(
+        err.to_string().contains("Error compiling script"),
+        "{}",
+        err
+    );
+    let err = client.eval("error('boom')", &[], vec![]).await.unwrap_err();
+    assert!(err.to_string().contains("boom"), "{}", err);
+    let err = client
+        .eval("return redis.error_reply('MY failure')", &[], vec![])
+        .await
+        .unwrap_err();
+    assert_eq!("MY failure", err.to_string());
+    let err = client
+        .eval("return redis.call('EVAL', 'return 1', 0)", &[], vec![])
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("not allowed from script"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn evalsha_and_script_cache() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let script = "return redis.call('INCR', KEYS[1])";
+    let sha = client.script_load(script).await.unwrap();
+    assert_eq!(40, sha.len());
+    let reply = client.eval("return redis.sha1hex(ARGV[1])", &[], vec![script.into()]);
+    assert_eq!(Frame::Bulk(sha.clone().into()), reply.await.unwrap());
+
+    assert_eq!(
+        Frame::Integer(1),
+        client.evalsha(&sha, &["n"], vec![]).await.unwrap()
+    );
+    let upper = sha.to_uppercase();
+    assert_eq!(
+        Frame::Integer(2),
+        client.evalsha(&upper, &["n"], vec![]).await.unwrap()
+    );
+
+    // EVAL caches the scripts it runs.
+    client.eval("return 7", &[], vec![]).await.unwrap();
+    let other = "e54c2a6b8a2df7e1a1a6cf2e4cd7d9b1f6d2a6c3";
+    let exists = client.script_exists(&[&sha, other]).await.unwrap();
+    assert_eq!(vec![true, false], exists);
+
+    client.script_flush().await.unwrap();
+    assert_eq!(vec![false], client.script_exists(&[&sha]).await.unwrap());
+    let err = client.evalsha(&sha, &["n"], vec![]).await.unwrap_err();
+    assert!(err.to_string().starts_with("NOSCRIPT"), "{}", err);
+}
+
+#[tokio::test]
+async fn eval_is_atomic() {
+    let (addr, _) = start_server().await;
+
+    // Check-and-set: only works if no other script runs in between.
+    let script = "local value = tonumber(redis.call('GET', KEYS[1]) or '0') \
+        redis.call('SET', KEYS[1], value + 1) \
+        return value + 1";
+    let mut tasks = vec![];
+    for _ in 0..4 {
+        tasks.push(tokio::spawn(async move {
+            let mut client = Client::connect(addr).await.unwrap();
+            for _ in 0..25 {
+                client.eval(script, &["counter"], vec![]).await.unwrap();
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!(Some("100".into()), client.get("counter").await.unwrap());
+}
+
+// The script keeps a worker thread busy, the other clients are served by
+// another one.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn script_kill_stops_slow_scripts() {
+    let config = server::Config::new().lua_time_limit(Duration::from_millis(50));
+    let (addr, _) = start_server_with_config(config).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let mut other = Client::connect(addr).await.unwrap();
+
+    let err = client.script_kill().await.unwrap_err();
+    assert!(err.to_string().starts_with("NOTBUSY"), "{}", err);
+
+    let script = tokio::spawn(async move { client.eval("while true do end", &[], vec![]).await });
+
+    // Other clients wait for the script until it runs for too long.
+    let err = loop {
+        match other.ping(None).await {
+            Ok(_) => time::sleep(Duration::from_millis(10)).await,
+            Err(err) => break err,
+        }
+    };
+    assert!(err.to_string().starts_with("BUSY"), "{}", err);
+    let err = other.get("key").await.unwrap_err();
+    assert!(err.to_string().starts_with("BUSY"), "{}", err);
+
+    other.script_kill().await.unwrap();
+    let err = script.await.unwrap().unwrap_err();
+    assert!(err.to_string().contains("SCRIPT KILL"), "{}", err);
+    assert_eq!(None, other.get("key").await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn slow_scripts_do_not_stall_other_clients() {
+    let path = aof_path("slow-script");
+    let _ = std::fs::remove_file(&path);
+    let config = server::Config::new()
+        .lua_time_limit(Duration::from_millis(50))
+        .aof_path(&path);
+    let (addr, _) = start_server_with_config(config).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let mut other = Client::connect(addr).await.unwrap();
+    let mut publisher = Client::connect(addr).await.unwrap();
+    let mut subscriber = Client::connect(addr)
+        .await
+        .unwrap()
+        .subscribe(vec!["news".into()])
+        .await
+        .unwrap();
+
+    // One blocked client gives up while the script runs, the other is
+    // served once it returns.
+    let mut timed = Client::connect(addr).await.unwrap();
+    let timed = tokio::spawn(async move {
+        timed
+            .blpop(&["list"], Some(Duration::from_millis(100)))
+            .await
+    });
+    let mut blocked = Client::connect(addr).await.unwrap();
+    let blocked = tokio::spawn(async move { blocked.blpop(&["list"], None).await });
+    time::sleep(Duration::from_millis(20)).await;
+
+    let script = tokio::spawn(async move { client.eval("while true do end", &[], vec![]).await });
+    let mut published = 0;
+    let err = loop {
+        match publisher.publish("news", "early".into()).await {
+            Ok(_) => published += 1,
+            Err(err) => break err,
+        }
+        time::sleep(Duration::from_millis(10)).await;
+    };
+    assert!(err.to_string().starts_with("BUSY"), "{}", err);
+    for _ in 0..published {
+        let message = subscriber.next_message().await.unwrap().unwrap();
+        assert_eq!(Bytes::from("early"), message.content);
+    }
+
+    // Clients are still told the server is busy after the timed out client
+    // gave up.
+    time::sleep(Duration::from_millis(150)).await;
+    let err = time::timeout(Duration::from_secs(1), other.ping(None))
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert!(err.to_string().starts_with("BUSY"), "{}", err);
+
+    other.script_kill().await.unwrap();
+    assert!(script.await.unwrap().is_err());
+    assert_eq!(None, timed.await.unwrap().unwrap());
+
+    other.rpush("list", vec!["item".into()]).await.unwrap();
+    assert_eq!(
+        Some(("list".to_string(), Bytes::from("item"))),
+        blocked.await.unwrap().unwrap()
+    );
+    assert_eq!(1, publisher.publish("news", "late".into()).await.unwrap());
+    assert_eq!(
+        Some(Bytes::from("late")),
+        subscriber
+            .next_message()
+            .await
+            .unwrap()
+            .map(|msg| msg.content)
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn eval_select_is_local_to_the_script() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let script = "redis.call('SELECT', 1) return redis.call('SET', KEYS[1], 'one')";
+    client.eval(script, &["key"], vec![]).await.unwrap();
+    assert_eq!(None, client.get("key").await.unwrap());
+    client.select(1).await.unwrap();
+    assert_eq!(Some("one".into()), client.get("key").await.unwrap());
+}
+
+#[tokio::test]
+async fn hello_switches_to_resp3() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let info = client.hello(3).await.unwrap();
+    assert_eq!(Some(&Frame::Integer(3)), info.get("proto"));
+    assert_eq!(Some(&Frame::Bulk("redis".into())), info.get("server"));
+    assert_eq!(Some(&Frame::Bulk("standalone".into())), info.get("mode"));
+    assert_eq!(Some(&Frame::Bulk("master".into())), info.get("role"));
+
+    client
+        .hset("hash", vec![("field".into(), "value".into())])
+        .await
+        .unwrap();
+    client.sadd("set", vec!["a".into()]).await.unwrap();
+    client.zadd("zset", vec![(1.5, "m".into())]).await.unwrap();
+
+    // Replies use the native RESP3 types...
+    let hgetall = Frame::Array(vec![
+        Frame::Bulk("hgetall".into()),
+        Frame::Bulk("hash".into()),
+    ]);
+    let smembers = Frame::Array(vec![
+        Frame::Bulk("smembers".into()),
+        Frame::Bulk("set".into()),
+    ]);
+    let zscore = Frame::Array(vec![
+        Frame::Bulk("zscore".into()),
+        Frame::Bulk("zset".into()),
+        Frame::Bulk("m".into()),
+    ]);
+    let zrange = Frame::Array(vec![
+        Frame::Bulk("zrange".into()),
+        Frame::Bulk("zset".into()),
+        Frame::Bulk("0".into()),
+        Frame::Bulk("-1".into()),
+        Frame::Bulk("withscores".into()),
+    ]);
+    let replies = client
+        .transaction()
+        .command(hgetall)
+        .command(smembers)
+        .command(zscore)
+        .command(zrange)
+        .get("missing")
+        .exec()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        vec![
+            Frame::Map(vec![(
+                Frame::Bulk("field".into()),
+                Frame::Bulk("value".into())
+            )]),
+            Frame::Set(vec![Frame::Bulk("a".into())]),
+            Frame::Double(1.5),
+            Frame::Array(vec![Frame::Bulk("m".into()), Frame::Double(1.5)]),
+            Frame::Null,
+        ],
+        replies
+    );
+
+    // ...which the client methods understand.
+    let fields = client.hgetall("hash").await.unwrap();
+    assert_eq!(Some(&Bytes::from("value")), fields.get("field"));
+    let members = client.smembers("set").await.unwrap();
+    assert!(members.contains(&Bytes::from("a")));
+    assert_eq!(Some(1.5), client.zscore("zset", "m".into()).await.unwrap());
+    assert_eq!(
+        vec![(Bytes::from("m"), 1.5)],
+        client.zrange_withscores("zset", 0, -1).await.unwrap()
+    );
+    assert_eq!(4.0, client.zincrby("zset", 2.5, "m".into()).await.unwrap());
+
+    // And back to RESP2.
+    let info = client.hello(2).await.unwrap();
+    assert_eq!(Some(&Frame::Integer(2)), info.get("proto"));
+    let replies = client
+        .transaction()
+        .command(Frame::Array(vec![
+            Frame::Bulk("hgetall".into()),
+            Frame::Bulk("hash".into()),
+        ]))
+        .exec()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        vec![Frame::Array(vec![
+            Frame::Bulk("field".into()),
+            Frame::Bulk("value".into())
+        ])],
+        replies
+    );
+
+    let err = client.hello(4).await.unwrap_err();
+    assert!(err.to_string().starts_with("NOPROTO"), "{}", err);
+}
+
+#[tokio::test]
+async fn resp3_pub_sub() {
+    let (addr, _) = start_server().await;
+    let mut publisher = Client::connect(addr).await.unwrap();
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.hello(3).await.unwrap();
+    let mut subscriber = client.subscribe(vec!["news".into()]).await.unwrap();
+    publisher.publish("news", "hello".into()).await.unwrap();
+
+    let message = subscriber.next_message().await.unwrap().unwrap();
+    assert_eq!("news", message.channel);
+    assert_eq!(&b"hello"[..], &message.content[..]);
+    subscriber.unsubscribe(&[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn pubsub_introspection() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let mut subscriber = Client::connect(addr)
+        .await
+        .unwrap()
+        .subscribe(vec!["orders.new".into(), "users.new".into()])
+        .await
+        .unwrap();
+    subscriber
+        .psubscribe(&["orders.*".into(), "users.*".into()])
+        .await
+        .unwrap();
+    let other = Client::connect(addr)
+        .await
+        .unwrap()
+        .subscribe(vec!["orders.new".into()])
+        .await
+        .unwrap();
+
+    let mut channels = client.pubsub_channels(None).await.unwrap();
+    channels.sort();
+    assert_eq!(vec!["orders.new", "users.new"], channels);
+    assert_eq!(
+        vec!["orders.new"],
+        client.pubsub_channels(Some("orders.*")).await.unwrap()
+    );
+    assert_eq!(
+        vec![("orders.new".to_string(), 2), ("nobody".to_string(), 0)],
+        client
+            .pubsub_numsub(&["orders.new", "nobody"])
+            .await
+            .unwrap()
+    );
+    assert_eq!(2, client.pubsub_numpat().await.unwrap());
+
+    subscriber.punsubscribe(&["users.*".into()]).await.unwrap();
+    assert_eq!(1, client.pubsub_numpat().await.unwrap());
+
+    // Channels are forgotten along with their last subscriber, even when it
+    // just disconnects.
+    drop(other);
+    drop(subscriber);
+    while !client.pubsub_channels(None).await.unwrap().is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(0, client.pubsub_numpat().await.unwrap());
+}
+
+#[tokio::test]
+async fn pubsub_forgets_unsubscribed_channels() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let mut subscriber = Client::connect(addr)
+        .await
+        .unwrap()
+        .subscribe(vec!["keep".into()])
+        .await
+        .unwrap();
+
+    for round in 0..5 {
+        let channels: Vec<String> = (0..1000)
+            .map(|i| format!("channel:{}:{}", round, i))
+            .collect();
+        let patterns: Vec<String> = channels.iter().map(|c| format!("{}.*", c)).collect();
+        subscriber.subscribe(&channels).await.unwrap();
+        subscriber.psubscribe(&patterns).await.unwrap();
+        assert_eq!(1001, client.pubsub_channels(None).await.unwrap().len());
+        assert_eq!(1000, client.pubsub_numpat().await.unwrap());
+
+        subscriber.unsubscribe(&channels).await.unwrap();
+        subscriber.punsubscribe(&[]).await.unwrap();
+        assert_eq!(vec!["keep"], client.pubsub_channels(None).await.unwrap());
+        assert_eq!(0, client.pubsub_numpat().await.unwrap());
+    }
+}
+
+#[tokio::test]
+async fn pubsub_forgets_disconnected_subscribers() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    for round in 0..3 {
+        let mut subscribers = vec![];
+        for i in 0..10 {
+            // Every subscriber shares a channel and a pattern with the
+            // others, and has some of its own.
+            let channels = vec!["shared".to_string(), format!("own:{}:{}", round, i)];
+            let patterns = vec!["shared.*".to_string(), format!("own:{}:{}.*", round, i)];
+            let mut subscriber = Client::connect(addr)
+                .await
+                .unwrap()
+                .subscribe(channels)
+                .await
+                .unwrap();
+            subscriber.psubscribe(&patterns).await.unwrap();
+            subscribers.push(subscriber);
+        }
+        assert_eq!(11, client.pubsub_channels(None).await.unwrap().len());
+        assert_eq!(11, client.pubsub_numpat().await.unwrap());
+
+        // The subscribers disconnect without unsubscribing.
+        drop(subscribers);
+        let mut forgotten = false;
+        for _ in 0..100 {
+            let channels = client.pubsub_channels(None).await.unwrap();
+            if channels.is_empty() && client.pubsub_numpat().await.unwrap() == 0 {
+                forgotten = true;
+                break;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(forgotten);
+        assert_eq!(
+            vec![("shared".to_string(), 0)],
+            client.pubsub_numsub(&["shared"]).await.unwrap()
+        );
+    }
+}
+
+#[tokio::test]
+async fn slow_subscribers_are_notified() {
+    let config = server::Config::new().pubsub_capacity(2);
+    let (addr, _) = start_server_with_config(config).await;
+    let mut subscriber = Client::connect(addr)
+        .await
+        .unwrap()
+        .subscribe(vec!["news".into()])
+        .await
+        .unwrap();
+    subscriber.psubscribe(&["n*".into()]).await.unwrap();
+
+    // The script runs without letting the subscriber catch up.
+    let mut client = Client::connect(addr).await.unwrap();
+    client.eval(PUBLISH_TEN, &["news"], vec![]).await.unwrap();
+
+    let mut contents = vec![];
+    for _ in 0..4 {
+        let message = subscriber.next_message().await.unwrap().unwrap();
+        contents.push((message.pattern, message.content));
+    }
+    contents.sort();
+    assert_eq!(
+        vec![
+            (None, Bytes::from("10")),
+            (None, Bytes::from("9")),
+            (Some("n*".to_string()), Bytes::from("10")),
+            (Some("n*".to_string()), Bytes::from("9")),
+        ],
+        contents
+    );
+    assert_eq!(16, subscriber.dropped_messages());
+    assert_eq!(
+        vec![("news".to_string(), 8), ("n*".to_string(), 8)],
+        client.pubsub_dropped(&["news", "n*"]).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn slow_subscribers_are_disconnected() {
+    let config = server::Config::new()
+        .pubsub_capacity(2)
+        .slow_subscribers(server::SlowSubscribers::Disconnect);
+    let (addr, _) = start_server_with_config(config).await;
+    let mut subscriber = Client::connect(addr)
+        .await
+        .unwrap()
+        .subscribe(vec!["news".into()])
+        .await
+        .unwrap();
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client.eval(PUBLISH_TEN, &["news"], vec![]).await.unwrap();
+
+    assert!(subscriber.next_message().await.unwrap().is_none());
+    assert_eq!(
+        vec![("news".to_string(), 0)],
+        client.pubsub_numsub(&["news"]).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn snapshot_survives_restart() {
+    let path = snapshot_path("restart");
+    let (addr, stop) = start_stoppable_server(server::Config::new().snapshot_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.set("string", "value".into()).await.unwrap();
+    client.expire("string", 3600).await.unwrap();
+    client.set("short", "lived".into()).await.unwrap();
+    client.pexpire("short", 50).await.unwrap();
+    client
+        .lpush("list", vec!["a".into(), "b".into()])
+        .await
+        .unwrap();
+    client
+        .hset("hash", vec![("field".into(), "value".into())])
+        .await
+        .unwrap();
+    client
+        .zadd("zset", vec![(1.0, "one".into()), (2.0, "two".into())])
+        .await
+        .unwrap();
+    client
+        .xadd("stream", "1-1", vec![("k".into(), "v".into())], None)
+        .await
+        .unwrap();
+    client
+        .xgroup_create("stream", "group", "0", false)
+        .await
+        .unwrap();
+    client
+        .xreadgroup("group", "alice", None, &[("stream", ">")])
+        .await
+        .unwrap();
+    client.select(3).await.unwrap();
+    client.sadd("set", vec!["member".into()]).await.unwrap();
+    client.save().await.unwrap();
+    assert!(client.lastsave().await.unwrap() > 0);
+
+    stop_server(stop).await;
+    time::sleep(Duration::from_millis(100)).await;
+
+    let (addr, _stop) = start_stoppable_server(server::Config::new().snapshot_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!(
+        Some(Bytes::from("value")),
+        client.get("string").await.unwrap()
+    );
+    let ttl = client.pttl("string").await.unwrap();
+    assert!(ttl > 3_500_000 && ttl <= 3_600_000);
+    assert_eq!(None, client.get("short").await.unwrap());
+    assert_eq!(vec!["b", "a"], client.lrange("list", 0, -1).await.unwrap());
+    assert_eq!(
+        Some(&Bytes::from("value")),
+        client.hgetall("hash").await.unwrap().get("field")
+    );
+    assert_eq!(
+        vec!["one", "two"],
+        client.zrange("zset", 0, -1).await.unwrap()
+    );
+    assert_eq!(1, client.xlen("stream").await.unwrap());
+    assert_eq!(1, client.xpending("stream", "group").await.unwrap());
+    assert!(client.smembers("set").await.unwrap().is_empty());
+    client.select(3).await.unwrap();
+    assert!(client
+        .smembers("set")
+        .await
+        .unwrap()
+        .contains("member".as_bytes()));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn snapshot_bgsave_and_save_rules() {
+    let path = snapshot_path("rules");
+    let config = server::Config::new()
+        .snapshot_path(&path)
+        .save_rule(Duration::from_secs(1), 1);
+    let (addr, _stop) = start_stoppable_server(config).await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client.bgsave().await.unwrap();
+    time::sleep(Duration::from_millis(100)).await;
+    assert!(path.exists());
+    let saved = client.lastsave().await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    client.set("hello", "world".into()).await.unwrap();
+    time::sleep(Duration::from_millis(2500)).await;
+    assert!(path.exists());
+    assert!(client.lastsave().await.unwrap() >= saved);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn snapshot_save_in_transaction_and_on_shutdown() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let path = snapshot_path("transaction");
+    let config = || {
+        server::Config::new()
+            .snapshot_path(&path)
+            .save_rule(Duration::from_secs(3600), 1)
+    };
+    let (addr, stop) = start_stoppable_server(config()).await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"MULTI\r\nSET in transaction\r\nSAVE\r\nEXEC\r\n")
+        .await
+        .unwrap();
+    let expected = b"+OK\r\n+QUEUED\r\n+QUEUED\r\n*2\r\n+OK\r\n+OK\r\n";
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(&expected[..], &response[..]);
+    assert!(path.exists());
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client.set("before", "shutdown".into()).await.unwrap();
+    stop_server(stop).await;
+
+    let (addr, _stop) = start_stoppable_server(config()).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!(
+        Some(Bytes::from("transaction")),
+        client.get("in").await.unwrap()
+    );
+    assert_eq!(
+        Some(Bytes::from("shutdown")),
+        client.get("before").await.unwrap()
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn snapshot_errors() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    assert!(client.save().await.is_err());
+    assert!(client.bgsave().await.is_err());
+
+    let path = snapshot_path("corrupt");
+    std::fs::write(&path, b"MINIREDIS garbage").unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = server::Config::new().snapshot_path(&path);
+    let server = tokio::spawn(server::run_with_config(
+        listener,
+        std::future::pending::<()>(),
+        config,
+    ));
+    time::timeout(Duration::from_secs(1), server)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn aof_survives_restart() {
+    let path = aof_path("restart");
+    let _ = std::fs::remove_file(&path);
+    let (addr, stop) = start_stoppable_server(server::Config::new().aof_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    client
+        .set_options("string", "value".into(), SetOptions::new().px(3_600_000))
+        .await
+        .unwrap();
+    client.set("short", "lived".into()).await.unwrap();
+    client.pexpire("short", 50).await.unwrap();
+    client
+        .transaction()
+        .incr("counter")
+        .incrby("counter", 5)
+        .exec()
+        .await
+        .unwrap()
+        .unwrap();
+    client
+        .eval(
+            "redis.call('RPUSH', KEYS[1], 'a') redis.call('RPUSH', KEYS[1], 'b')",
+            &["list"],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let mut blocked = Client::connect(addr).await.unwrap();
+    let popped = tokio::spawn(async move { blocked.blpop(&["jobs"], None).await.unwrap() });
+    time::sleep(Duration::from_millis(50)).await;
+    client.rpush("jobs", vec!["job".into()]).await.unwrap();
+    assert!(popped.await.unwrap().is_some());
+
+    client.select(2).await.unwrap();
+    let id = client
+        .xadd("stream", "*", vec![("k".into(), "v".into())], None)
+        .await
+        .unwrap();
+    // A relative expiration is logged as a Unix time.
+    let logged = std::fs::read(&path).unwrap();
+    assert!(String::from_utf8_lossy(&logged).contains("pxat"));
+
+    stop_server(stop).await;
+    time::sleep(Duration::from_millis(100)).await;
+
+    let (addr, _stop) = start_stoppable_server(server::Config::new().aof_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!(
+        Some(Bytes::from("value")),
+        client.get("string").await.unwrap()
+    );
+    let ttl = client.pttl("string").await.unwrap();
+    assert!(ttl > 3_500_000 && ttl <= 3_600_000);
+    assert_eq!(None, client.get("short").await.unwrap());
+    assert_eq!(Some(Bytes::from("6")), client.get("counter").await.unwrap());
+    assert_eq!(vec!["a", "b"], client.lrange("list", 0, -1).await.unwrap());
+    assert_eq!(0, client.llen("jobs").await.unwrap());
+    client.select(2).await.unwrap();
+    let entries = client.xrange("stream", "-", "+", None).await.unwrap();
+    assert_eq!(1, entries.len());
+    assert_eq!(id, entries[0].id);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn aof_replays_claims() {
+    let path = aof_path("claims");
+    let _ = std::fs::remove_file(&path);
+    let (addr, stop) = start_stoppable_server(server::Config::new().aof_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let fields = || vec![("k".into(), "v".into())];
+    let first = client.xadd("stream", "*", fields(), None).await.unwrap();
+    let second = client.xadd("stream", "*", fields(), None).await.unwrap();
+    client
+        .xgroup_create("stream", "group", "0", false)
+        .await
+        .unwrap();
+    client
+        .xreadgroup("group", "alice", None, &[("stream", ">")])
+        .await
+        .unwrap();
+    time::sleep(Duration::from_millis(50)).await;
+    client
+        .xclaim("stream", "group", "bob", 20, &[&first])
+        .await
+        .unwrap();
+    let (_, claimed) = client
+        .xautoclaim("stream", "group", "carol", 20, "0", None)
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![second.clone()],
+        claimed.into_iter().map(|e| e.id).collect::<Vec<_>>()
+    );
+
+    stop_server(stop).await;
+    time::sleep(Duration::from_millis(100)).await;
+
+    // The records were not idle for long when replayed, the claims are
+    // applied all the same.
+    let (addr, _stop) = start_stoppable_server(server::Config::new().aof_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    let pending = client
+        .xpending_range("stream", "group", "-", "+", 10)
+        .await
+        .unwrap();
+    let owners: Vec<_> = pending
+        .iter()
+        .map(|entry| (&entry.id[..], &entry.consumer[..], entry.deliveries))
+        .collect();
+    assert_eq!(
+        vec![(&first[..], "bob", 2), (&second[..], "carol", 2)],
+        owners
+    );
+    assert!(pending[0].idle_ms >= 100);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn aof_truncated_tail() {
+    let path = aof_path("truncated");
+    let _ = std::fs::remove_file(&path);
+    let config = || {
+        server::Config::new()
+            .aof_path(&path)
+            .appendfsync(server::AppendFsync::Always)
+    };
+    let (addr, stop) = start_stoppable_server(config()).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    client.set("complete", "yes".into()).await.unwrap();
+    stop_server(stop).await;
+
+    // As if the server crashed while logging a command.
+    let len = std::fs::metadata(&path).unwrap().len();
+    let mut logged = std::fs::read(&path).unwrap();
+    logged.extend_from_slice(b"*3\r\n$3\r\nset\r\n$7\r\npartial\r\n$2\r\nn");
+    std::fs::write(&path, logged).unwrap();
+
+    let (addr, stop) = start_stoppable_server(config()).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!(
+        Some(Bytes::from("yes")),
+        client.get("complete").await.unwrap()
+    );
+    assert_eq!(None, client.get("partial").await.unwrap());
+    assert_eq!(len, std::fs::metadata(&path).unwrap().len());
+
+    client.set("after", "crash".into()).await.unwrap();
+    stop_server(stop).await;
+
+    let (addr, _stop) = start_stoppable_server(config()).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!(
+        Some(Bytes::from("yes")),
+        client.get("complete").await.unwrap()
+    );
+    assert_eq!(
+        Some(Bytes::from("crash")),
+        client.get("after").await.unwrap()
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn aof_rewrite() {
+    let path = aof_path("rewrite");
+    let _ = std::fs::remove_file(&path);
+    let (addr, stop) = start_stoppable_server(server::Config::new().aof_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    for _ in 0..100 {
+        client.incr("counter").await.unwrap();
+    }
+    client.select(1).await.unwrap();
+    client.set("other", "db".into()).await.unwrap();
+    let len = std::fs::metadata(&path).unwrap().len();
+
+    client.bgrewriteaof().await.unwrap();
+    client.set("during", "rewrite".into()).await.unwrap();
+    time::sleep(Duration::from_millis(100)).await;
+    let rewritten = std::fs::read(&path).unwrap();
+    assert!(rewritten.starts_with(b"MINIREDIS"));
+    assert!((rewritten.len() as u64) < len);
+
+    client.select(0).await.unwrap();
+    client.set("after", "rewrite".into()).await.unwrap();
+    stop_server(stop).await;
+    time::sleep(Duration::from_millis(100)).await;
+
+    let (addr, _stop) = start_stoppable_server(server::Config::new().aof_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!(
+        Some(Bytes::from("100")),
+        client.get("counter").await.unwrap()
+    );
+    assert_eq!(
+        Some(Bytes::from("rewrite")),
+        client.get("after").await.unwrap()
+    );
+    client.select(1).await.unwrap();
+    assert_eq!(Some(Bytes::from("db")), client.get("other").await.unwrap());
+    assert_eq!(
+        Some(Bytes::from("rewrite")),
+        client.get("during").await.unwrap()
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn aof_rewrite_snapshot_is_not_changed_by_later_commands() {
+    let path = aof_path("rewrite-changes");
+    let _ = std::fs::remove_file(&path);
+    let (addr, stop) = start_stoppable_server(server::Config::new().aof_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+
+    let values: Vec<Bytes> = (0..10_000).map(|i| Bytes::from(i.to_string())).collect();
+    client.rpush("list", values).await.unwrap();
+
+    // Changed in place while the snapshot of the rewrite may still be
+    // written, they must be in the new file exactly once.
+    client.bgrewriteaof().await.unwrap();
+    client.rpush("list", vec!["last".into()]).await.unwrap();
+    client.lpop("list").await.unwrap();
+    time::sleep(Duration::from_millis(200)).await;
+    stop_server(stop).await;
+    time::sleep(Duration::from_millis(100)).await;
+
+    let (addr, _stop) = start_stoppable_server(server::Config::new().aof_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    assert_eq!(10_000, client.llen("list").await.unwrap());
+    assert_eq!(
+        vec![Bytes::from("1")],
+        client.lrange("list", 0, 0).await.unwrap()
+    );
+    assert_eq!(
+        vec![Bytes::from("last")],
+        client.lrange("list", -1, -1).await.unwrap()
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn aof_errors() {
+    let (addr, _) = start_server().await;
+    let mut client = Client::connect(addr).await.unwrap();
+    assert!(client.bgrewriteaof().await.is_err());
+
+    let path = aof_path("corrupt");
+    std::fs::write(&path, b"*1\r\n$4\r\nnope\r\n").unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = server::Config::new().aof_path(&path);
+    let server = tokio::spawn(server::run_with_config(
+        listener,
+        std::future::pending::<()>(),
+        config,
+    ));
+    time::timeout(Duration::from_secs(1), server)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn replication_full_sync_and_stream() {
+    let (master_addr, _) = start_server().await;
+    let mut master = Client::connect(master_addr).await.unwrap();
+    master.set("before", "sync".into()).await.unwrap();
+    master
+        .set_options("session", "abc".into(), SetOptions::new().px(3_600_000))
+        .await
+        .unwrap();
+    master.select(2).await.unwrap();
+    master.rpush("list", vec!["a".into()]).await.unwrap();
+
+    let (replica_addr, _) = start_server().await;
+    let mut replica = Client::connect(replica_addr).await.unwrap();
+    replica
+        .replicaof("127.0.0.1", master_addr.port())
+        .await
+        .unwrap();
+    wait_for_link(&mut replica).await;
+    assert_eq!(
+        Some(Bytes::from("sync")),
+        replica.get("before").await.unwrap()
+    );
+    let ttl = replica.pttl("session").await.unwrap();
+    assert!(ttl > 3_500_000 && ttl <= 3_600_000);
+
+    // What follows is streamed, transactions and scripts included.
+    master.rpush("list", vec!["b".into()]).await.unwrap();
+    master.select(0).await.unwrap();
+    master
+        .transaction()
+        .incr("counter")
+        .incr("counter")
+        .exec()
+        .await
+        .unwrap()
+        .unwrap();
+    master
+        .eval("redis.call('SET', KEYS[1], 'done')", &["script"], vec![])
+        .await
+        .unwrap();
+    wait_for_value(&mut replica, "script", "done").await;
+    assert_eq!(
+        Some(Bytes::from("2")),
+        replica.get("counter").await.unwrap()
+    );
+    replica.select(2).await.unwrap();
+    assert_eq!(vec!["a", "b"], replica.lrange("list", 0, -1).await.unwrap());
+    replica.select(0).await.unwrap();
+
+    // Replicas are read only.
+    let err = replica.set("write", "me".into()).await.unwrap_err();
+    assert!(err.to_string().starts_with("READONLY"));
+    let err = replica
+        .eval("return redis.call('SET', KEYS[1], 'x')", &["write"], vec![])
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("READONLY"));
+
+    let ReplicationRole::Master { offset, replicas } = master.role().await.unwrap() else {
+        panic!("not a master");
+    };
+    assert_eq!(1, replicas.len());
+    assert_eq!(replica_addr.port(), replicas[0].1);
+    assert_eq!(
+        ReplicationRole::Replica {
+            host: "127.0.0.1".to_string(),
+            port: master_addr.port(),
+            state: "connected".to_string(),
+            offset,
+        },
+        replica.role().await.unwrap()
+    );
+    let info = replica.info(&["replication"]).await.unwrap();
+    assert!(info.contains("role:slave\r\n"));
+    let hello = replica.hello(2).await.unwrap();
+    assert_eq!(Some(&Frame::Bulk("replica".into())), hello.get("role"));
+    assert!(info.contains("master_link_status:up\r\n"));
+    assert!(master
+        .info(&[])
+        .await
+        .unwrap()
+        .contains("connected_slaves:1\r\n"));
+
+    // Promoted replicas keep their data and take writes.
+    replica.replicaof_no_one().await.unwrap();
+    replica.set("write", "me".into()).await.unwrap();
+    assert_eq!(
+        Some(Bytes::from("2")),
+        replica.get("counter").await.unwrap()
+    );
+    assert!(matches!(
+        replica.role().await.unwrap(),
+        ReplicationRole::Master { .. }
+    ));
+}
+
+#[tokio::test]
+async fn replication_partial_resync() {
+    let (master_addr, _) = start_server().await;
+    let (proxy_addr, cut) = start_proxy(master_addr).await;
+    let mut master = Client::connect(master_addr).await.unwrap();
+
+    let (replica_addr, _) = start_server().await;
+    let mut replica = Client::connect(replica_addr).await.unwrap();
+    replica
+        .replicaof("127.0.0.1", proxy_addr.port())
+        .await
+        .unwrap();
+    wait_for_link(&mut replica).await;
+    master.set("first", "streamed".into()).await.unwrap();
+    wait_for_value(&mut replica, "first", "streamed").await;
+
+    // Changes made while the replica is disconnected are caught up on.
+    cut.notify_waiters();
+    master.set("second", "missed".into()).await.unwrap();
+    wait_for_value(&mut replica, "second", "missed").await;
+
+    let stats = master.info(&["stats"]).await.unwrap();
+    assert!(stats.contains("sync_full:1\r\n"));
+    assert!(stats.contains("sync_partial_ok:1\r\n"));
+}
+
+#[tokio::test]
+async fn replication_full_resync_past_backlog() {
+    let (master_addr, _) =
+        start_server_with_config(server::Config::new().repl_backlog_size(16)).await;
+    let (proxy_addr, cut) = start_proxy(master_addr).await;
+    let mut master = Client::connect(master_addr).await.unwrap();
+
+    let (replica_addr, _) = start_server().await;
+    let mut replica = Client::connect(replica_addr).await.unwrap();
+    replica
+        .replicaof("127.0.0.1", proxy_addr.port())
+        .await
+        .unwrap();
+    wait_for_link(&mut replica).await;
+
+    // Too much changes for the backlog, the replica has to start over.
+    cut.notify_waiters();
+    master
+        .set("long", "enough to overflow".into())
+        .await
+        .unwrap();
+    master.set("and", "some more".into()).await.unwrap();
+    wait_for_value(&mut replica, "and", "some more").await;
+    assert_eq!(
+        Some(Bytes::from("enough to overflow")),
+        replica.get("long").await.unwrap()
+    );
+
+    let stats = master.info(&["stats"]).await.unwrap();
+    assert!(stats.contains("sync_full:2\r\n"));
+    assert!(stats.contains("sync_partial_ok:0\r\n"));
+}
+
+#[tokio::test]
+async fn wait_for_replica_acks() {
+    let (master_addr, _) = start_server().await;
+    let mut master = Client::connect(master_addr).await.unwrap();
+    master.set("alone", "here".into()).await.unwrap();
+    assert_eq!(0, master.wait(0, None).await.unwrap());
+    assert_eq!(
+        0,
+        master
+            .wait(1, Some(Duration::from_millis(50)))
+            .await
+            .unwrap()
+    );
+
+    let (replica_addr, _) = start_server().await;
+    let mut replica = Client::connect(replica_addr).await.unwrap();
+    replica
+        .replicaof("127.0.0.1", master_addr.port())
+        .await
+        .unwrap();
+    wait_for_link(&mut replica).await;
+
+    // Replicas are asked to acknowledge right away, rather than when they
+    // next would.
+    master.set("key", "value".into()).await.unwrap();
+    let acked = time::timeout(Duration::from_millis(500), master.wait(1, None))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(1, acked);
+    assert_eq!(
+        Some(Bytes::from("value")),
+        replica.get("key").await.unwrap()
+    );
+    assert_eq!(
+        1,
+        master
+            .wait(2, Some(Duration::from_millis(50)))
+            .await
+            .unwrap()
+    );
+
+    let ReplicationRole::Master { offset, replicas } = master.role().await.unwrap() else {
+        panic!("not a master");
+    };
+    assert!(replicas[0].2 > 0 && replicas[0].2 <= offset);
+
+    let err = replica.wait(0, None).await.unwrap_err();
+    assert!(err.to_string().contains("WAIT cannot be used with replica"));
+}
+
+#[tokio::test]
+async fn cluster_redirections() {
+    let addrs = start_cluster(&[0..=5460, 5461..=10922, 10923..=16383], |_, config| config).await;
+    let mut first = Client::connect(addrs[0]).await.unwrap();
+    let mut last = Client::connect(addrs[2]).await.unwrap();
+
+    assert_eq!(12182, first.cluster_keyslot("foo").await.unwrap());
+    assert_eq!(12739, first.cluster_keyslot("123456789").await.unwrap());
+    // Only the hash tag counts, unless it is empty.
+    assert_eq!(
+        first.cluster_keyslot("{user1000}.following").await.unwrap(),
+        first.cluster_keyslot("{user1000}.followers").await.unwrap()
+    );
+    assert_eq!(
+        first.cluster_keyslot("foo").await.unwrap(),
+        first.cluster_keyslot("{foo}{bar}").await.unwrap()
+    );
+    assert_ne!(
+        first.cluster_keyslot("foo").await.unwrap(),
+        first.cluster_keyslot("{}foo").await.unwrap()
+    );
+
+    let err = first.set("foo", "bar".into()).await.unwrap_err();
+    assert_eq!(format!("MOVED 12182 {}", addrs[2]), err.to_string());
+    last.set("foo", "bar".into()).await.unwrap();
+    assert_eq!(Some(Bytes::from("bar")), last.get("foo").await.unwrap());
+
+    // Commands on several keys need them in the same slot.
+    let err = last.mget(&["foo", "bar"]).await.unwrap_err();
+    assert!(err.to_string().starts_with("CROSSSLOT"));
+    last.mset(&[("{foo}:a", "1".into()), ("{foo}:b", "2".into())])
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![Some(Bytes::from("1")), Some(Bytes::from("2"))],
+        last.mget(&["{foo}:a", "{foo}:b"]).await.unwrap()
+    );
+
+    // Redirections apply to the commands of transactions too.
+    let err = first.transaction().incr("foo").exec().await.unwrap_err();
+    assert!(err.to_string().starts_with("MOVED"));
+
+    // Commands without keys are served by any node.
+    assert_eq!(0, first.dbsize().await.unwrap());
+
+    let (addr, _) = start_server().await;
+    let mut standalone = Client::connect(addr).await.unwrap();
+    let err = standalone.cluster_info().await.unwrap_err();
+    assert!(err.to_string().contains("cluster support disabled"));
+    standalone.mget(&["foo", "bar"]).await.unwrap();
+}
+
+#[test]
+fn cluster_config_is_validated() {
+    let node = |port, slots| server::ClusterNode::new("127.0.0.1", port).slots(slots);
+
+    let err = server::Config::new()
+        .cluster(vec![node(7000, 0..=16384)], 0)
+        .unwrap_err();
+    assert_eq!("slot 16384 is out of range", err.to_string());
+    let err = server::Config::new()
+        .cluster(vec![node(7000, 0..=100), node(7001, 100..=200)], 0)
+        .unwrap_err();
+    assert_eq!("slot 100 is owned twice", err.to_string());
+    let err = server::Config::new()
+        .cluster(vec![node(7000, 0..=100)], 1)
+        .unwrap_err();
+    assert_eq!("node 1 is not in the cluster", err.to_string());
+
+    let config = server::Config::new()
+        .cluster(vec![node(7000, 0..=100)], 0)
+        .unwrap();
+    let err = config.cluster_migrating(50, 1).unwrap_err();
+    assert_eq!("node 1 is not in the cluster", err.to_string());
+    let err = server::Config::new().cluster_migrating(50, 0).unwrap_err();
+    assert_eq!("the cluster is not configured", err.to_string());
+}
+
+#[tokio::test]
+async fn cluster_topology() {
+    let addrs = start_cluster(&[0..=5460, 5461..=10922, 10923..=16383], |_, config| config).await;
+    let mut client = Client::connect(addrs[1]).await.unwrap();
+
+    let info = client.cluster_info().await.unwrap();
+    assert!(info.contains("cluster_state:ok\r\n"));
+    assert!(info.contains("cluster_slots_assigned:16384\r\n"));
+    assert!(info.contains("cluster_known_nodes:3\r\n"));
+    assert!(info.contains("cluster_size:3\r\n"));
+    assert!(client
+        .info(&["cluster"])
+        .await
+        .unwrap()
+        .contains("cluster_enabled:1\r\n"));
+    let hello = client.hello(2).await.unwrap();
+    assert_eq!(Some(&Frame::Bulk("cluster".into())), hello.get("mode"));
+
+    let slots = client.cluster_slots().await.unwrap();
+    assert_eq!(3, slots.len());
+    assert_eq!(5461..=10922, slots[1].slots);
+    assert_eq!(addrs[1].port(), slots[1].port);
+    assert_eq!("127.0.0.1", slots[1].host);
+
+    let myid = client.cluster_myid().await.unwrap();
+    assert_eq!(40, myid.len());
+    assert_eq!(myid, slots[1].id);
+
+    let nodes = client.cluster_nodes().await.unwrap();
+    let lines: Vec<_> = nodes.lines().collect();
+    assert_eq!(3, lines.len());
+    assert!(lines[1].starts_with(&format!("{} {}@", myid, addrs[1])));
+    assert!(lines[1].contains(" myself,master "));
+    assert!(lines[1].ends_with(" connected 5461-10922"));
+    assert!(lines[0].ends_with(" connected 0-5460"));
+
+    let shards = client.cluster_shards().await.unwrap();
+    assert_eq!(3, shards.len());
+    assert_eq!(
+        Frame::Array(vec![Frame::Integer(10923), Frame::Integer(16383)]),
+        shards[2]["slots"]
+    );
+}
+
+#[tokio::test]
+async fn cluster_ask_redirection() {
+    // The source of the migration starts with a key of the slot.
+    let path = snapshot_path("cluster-ask");
+    let (addr, (stop, handle)) =
+        start_stoppable_server(server::Config::new().snapshot_path(&path)).await;
+    let mut client = Client::connect(addr).await.unwrap();
+    client.set("123456789", "here".into()).await.unwrap();
+    client.save().await.unwrap();
+    stop.send(()).unwrap();
+    handle.await.unwrap();
+
+    // The slot of `123456789` is moved from the second node to the first.
+    let addrs = start_cluster(&[0..=8191, 8192..=16383], |myself, config| {
+        let config = config.cluster_migrating(12739, 0).unwrap();
+        match myself {
+            1 => config.snapshot_path(&path),
+            _ => config,
+        }
+    })
+    .await;
+    let mut source = Client::connect(addrs[1]).await.unwrap();
+    let mut target = Client::connect(addrs[0]).await.unwrap();
+
+    // The keys the source still has are served there, the others are asked
+    // for on the target, even to be created.
+    let err = source
+        .set("{123456789}moved", "there".into())
+        .await
+        .unwrap_err();
+    assert_eq!(format!("ASK 12739 {}", addrs[0]), err.to_string());
+    assert_eq!(
+        Some(Bytes::from("here")),
+        source.get("123456789").await.unwrap()
+    );
+    let err = source.get("{123456789}moved").await.unwrap_err();
+    assert_eq!(format!("ASK 12739 {}", addrs[0]), err.to_string());
+    let err = source
+        .mget(&["123456789", "{123456789}moved"])
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("TRYAGAIN"));
+
+    // The target only serves the slot for commands that follow `ASKING`.
+    let err = target.get("{123456789}moved").await.unwrap_err();
+    assert_eq!(format!("MOVED 12739 {}", addrs[1]), err.to_string());
+    target.asking().await.unwrap();
+    target
+        .set("{123456789}moved", "there".into())
+        .await
+        .unwrap();
+    assert!(target.get("{123456789}moved").await.is_err());
+
+    std::fs::remove_file(&path).unwrap();
+
+    let source_id = source.cluster_myid().await.unwrap();
+    let target_id = target.cluster_myid().await.unwrap();
+    let nodes = source.cluster_nodes().await.unwrap();
+    assert!(nodes.contains(&format!("[12739->-{}]", target_id)));
+    let nodes = target.cluster_nodes().await.unwrap();
+    assert!(nodes.contains(&format!("[12739-<-{}]", source_id)));
+}
+
+const PUBLISH_TEN: &str = "for i = 1, 10 do redis.call('PUBLISH', KEYS[1], i) end";
+
 async fn start_server() -> (SocketAddr, JoinHandle<()>) {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
@@ -89,3 +2802,124 @@ async fn start_server() -> (SocketAddr, JoinHandle<()>) {
     let handle = tokio::spawn(async move { server::run(listener, tokio::signal::ctrl_c()).await });
     (addr, handle)
 }
+
+async fn start_server_with_config(config: server::Config) -> (SocketAddr, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handle = tokio::spawn(async move {
+        server::run_with_config(listener, tokio::signal::ctrl_c(), config).await
+    });
+    (addr, handle)
+}
+
+/// Starts a cluster with a node per range of `slots`, each of them with the
+/// configuration `configure` returns given its index.
+async fn start_cluster(
+    slots: &[RangeInclusive<u16>],
+    configure: impl Fn(usize, server::Config) -> server::Config,
+) -> Vec<SocketAddr> {
+    let mut listeners = vec![];
+    for _ in slots {
+        listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    }
+    let addrs: Vec<_> = listeners
+        .iter()
+        .map(|listener| listener.local_addr().unwrap())
+        .collect();
+    let nodes: Vec<_> = addrs
+        .iter()
+        .zip(slots)
+        .map(|(addr, slots)| {
+            server::ClusterNode::new("127.0.0.1", addr.port()).slots(slots.clone())
+        })
+        .collect();
+
+    for (myself, listener) in listeners.into_iter().enumerate() {
+        let config = server::Config::new()
+            .cluster(nodes.clone(), myself)
+            .unwrap();
+        let config = configure(myself, config);
+        tokio::spawn(async move {
+            server::run_with_config(listener, std::future::pending::<()>(), config).await
+        });
+    }
+    addrs
+}
+
+/// A snapshot file of its own for the test `name`.
+fn snapshot_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("mini-redis-{}-{}.rdb", name, std::process::id()))
+}
+
+/// An append only file of its own for the test `name`.
+fn aof_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("mini-redis-{}-{}.aof", name, std::process::id()))
+}
+
+async fn start_stoppable_server(
+    config: server::Config,
+) -> (SocketAddr, (oneshot::Sender<()>, JoinHandle<()>)) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+    let handle = tokio::spawn(server::run_with_config(listener, shutdown_rx, config));
+    (addr, (shutdown_tx, handle))
+}
+
+async fn stop_server((shutdown_tx, handle): (oneshot::Sender<()>, JoinHandle<()>)) {
+    shutdown_tx.send(()).unwrap();
+    time::timeout(Duration::from_secs(1), handle)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+/// Waits for `replica` to be connected to its master.
+async fn wait_for_link(replica: &mut Client) {
+    for _ in 0..100 {
+        if let ReplicationRole::Replica { state, .. } = replica.role().await.unwrap() {
+            if state == "connected" {
+                return;
+            }
+        }
+        time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the replica did not connect to its master");
+}
+
+/// Waits for `key` to be set to `value`, as a replica eventually does.
+async fn wait_for_value(client: &mut Client, key: &str, value: &str) {
+    for _ in 0..100 {
+        if client.get(key).await.unwrap() == Some(Bytes::from(value.to_string())) {
+            return;
+        }
+        time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("`{}` was not set to `{}`", key, value);
+}
+
+/// Forwards connections to `target` until `cut` is notified, which drops
+/// them like a network failure would.
+async fn start_proxy(target: SocketAddr) -> (SocketAddr, Arc<Notify>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let cut = Arc::new(Notify::new());
+
+    let notify = cut.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut inbound, _) = listener.accept().await.unwrap();
+            let cut = notify.clone();
+            tokio::spawn(async move {
+                let mut outbound = TcpStream::connect(target).await.unwrap();
+                tokio::select! {
+                    _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound) => {}
+                    _ = cut.notified() => {}
+                }
+            });
+        }
+    });
+    (addr, cut)
+}
@@ -263,6 +263,268 @@ async fn manage_subscription() {
     );
 }
 
+#[tokio::test]
+async fn transaction_errors() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream.write_all(b"*1\r\n$4\r\nEXEC\r\n").await.unwrap();
+    let mut response = [0; 25];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"-ERR EXEC without MULTI\r\n", &response);
+
+    stream
+        .write_all(b"*1\r\n$5\r\nMULTI\r\n*1\r\n$5\r\nMULTI\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 41];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n-ERR MULTI calls can not be nested\r\n", &response);
+
+    stream
+        .write_all(b"*1\r\n$3\r\nFOO\r\n*1\r\n$4\r\nEXEC\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 88];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"-ERR unknown command foo\r\n\
+        -EXECABORT Transaction discarded because of previous errors.\r\n"[..],
+        &response[..]
+    );
+
+    stream.write_all(b"*1\r\n$7\r\nDISCARD\r\n").await.unwrap();
+    let mut response = [0; 28];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"-ERR DISCARD without MULTI\r\n", &response);
+}
+
+#[tokio::test]
+async fn hello_resp3_encoding() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(b"*4\r\n$4\r\nHSET\r\n$1\r\nh\r\n$1\r\nf\r\n$1\r\nv\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 4];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b":1\r\n", &response);
+
+    // RESP2 gets maps as flat arrays.
+    stream
+        .write_all(b"*2\r\n$7\r\nHGETALL\r\n$1\r\nh\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 18];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"*2\r\n$1\r\nf\r\n$1\r\nv\r\n", &response);
+
+    stream
+        .write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n")
+        .await
+        .unwrap();
+    let mut response = vec![];
+    while !response.ends_with(b"$7\r\nmodules\r\n*0\r\n") {
+        stream.read_buf(&mut response).await.unwrap();
+    }
+    assert!(response.starts_with(b"%6\r\n$6\r\nserver\r\n$5\r\nredis\r\n"));
+
+    stream
+        .write_all(b"*2\r\n$7\r\nHGETALL\r\n$1\r\nh\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 18];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"%1\r\n$1\r\nf\r\n$1\r\nv\r\n", &response);
+
+    stream
+        .write_all(b"*2\r\n$3\r\nGET\r\n$1\r\nx\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 3];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"_\r\n", &response);
+}
+
+#[tokio::test]
+async fn inline_commands() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream.write_all(b"PING\r\n").await.unwrap();
+    let mut response = [0; 7];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+PONG\r\n", &response);
+
+    // Quoted arguments, bare newlines and empty lines, as typed in `nc`.
+    stream
+        .write_all(b"\r\n  SET a \"b c\\x21\"\nSET 'it\\'s' x\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 10];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n+OK\r\n", &response);
+
+    // Inline commands mix with regular ones.
+    stream
+        .write_all(b"GET a\r\n*2\r\n$3\r\nGET\r\n$4\r\nit's\r\n")
+        .await
+        .unwrap();
+    let mut response = [0; 17];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"$4\r\nb c!\r\n$1\r\nx\r\n", &response);
+
+    // Unbalanced quotes are replied to before the connection is closed.
+    stream.write_all(b"GET \"a\r\n").await.unwrap();
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await.unwrap();
+    assert_eq!(
+        &b"-ERR Protocol error: unbalanced quotes in request\r\n"[..],
+        &response[..]
+    );
+}
+
+#[tokio::test]
+async fn invalid_options_fail_transactions() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(b"MULTI\r\nSET k v NX XX\r\nSET k v EX 0\r\nEXEC\r\nGET k\r\n")
+        .await
+        .unwrap();
+    let expected = &b"+OK\r\n\
+        -ERR syntax error\r\n\
+        -ERR invalid expire time in 'set' command\r\n\
+        -EXECABORT Transaction discarded because of previous errors.\r\n\
+        $-1\r\n"[..];
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(expected, &response[..]);
+}
+
+#[tokio::test]
+async fn string_invalid_arguments() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // The error is replied to, the connection stays open.
+    stream
+        .write_all(b"SETRANGE k abc x\r\nSETRANGE k 1 x\r\n")
+        .await
+        .unwrap();
+    let expected = &b"-ERR offset is out of range\r\n:2\r\n"[..];
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(expected, &response[..]);
+}
+
+#[tokio::test]
+async fn integer_arguments_must_be_whole_numbers() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(
+            b"INCRBY k 5abc\r\n\
+            *3\r\n$6\r\nINCRBY\r\n$1\r\nk\r\n$0\r\n\r\n\
+            INCRBY k 9223372036854775808\r\n\
+            EXPIRE k 10xyz\r\n\
+            LRANGE nolist 0 1x\r\n\
+            INCRBY k 5\r\n",
+        )
+        .await
+        .unwrap();
+    let expected = &b"-ERR value is not an integer or out of range\r\n\
+        -ERR value is not an integer or out of range\r\n\
+        -ERR value is not an integer or out of range\r\n\
+        -ERR value is not an integer or out of range\r\n\
+        -ERR value is not an integer or out of range\r\n\
+        :5\r\n"[..];
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(expected, &response[..]);
+}
+
+#[tokio::test]
+async fn set_invalid_expire_times() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(
+            b"SET k v EX 10xyz\r\n\
+            SET k v EX -5\r\n\
+            SET k v PX 0\r\n\
+            SETEX k -5 v\r\n\
+            GETEX k EX -5\r\n\
+            EXISTS k\r\n",
+        )
+        .await
+        .unwrap();
+    let expected = &b"-ERR value is not an integer or out of range\r\n\
+        -ERR invalid expire time in 'set' command\r\n\
+        -ERR invalid expire time in 'set' command\r\n\
+        -ERR invalid expire time in 'setex' command\r\n\
+        -ERR invalid expire time in 'getex' command\r\n\
+        :0\r\n"[..];
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(expected, &response[..]);
+}
+
+#[tokio::test]
+async fn wrong_number_of_arguments() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(b"GET\r\nGET a b\r\nBLPOP 0\r\nGET a\r\n")
+        .await
+        .unwrap();
+    let expected = &b"-ERR wrong number of arguments for 'get' command\r\n\
+        -ERR wrong number of arguments for 'get' command\r\n\
+        -ERR wrong number of arguments for 'blpop' command\r\n\
+        $-1\r\n"[..];
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(expected, &response[..]);
+}
+
+#[tokio::test]
+async fn sorted_set_invalid_arguments() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // The error is replied to, the connection stays open.
+    stream
+        .write_all(b"ZADD z abc m\r\nZADD z 1 m\r\n")
+        .await
+        .unwrap();
+    let expected = &b"-ERR value is not a valid float\r\n:1\r\n"[..];
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(expected, &response[..]);
+}
+
+#[tokio::test]
+async fn list_invalid_arguments() {
+    let (addr, _) = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // The error is replied to, the connection stays open.
+    stream
+        .write_all(b"RPUSH k a\r\nLPOP k abc\r\nLPOP k\r\n")
+        .await
+        .unwrap();
+    let expected = &b":1\r\n-ERR value is out of range, must be positive\r\n$1\r\na\r\n"[..];
+    let mut response = vec![0; expected.len()];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(expected, &response[..]);
+}
+
 async fn start_server() -> (SocketAddr, JoinHandle<()>) {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();